@@ -1,5 +1,6 @@
+use alloc::{string::String, vec::Vec};
 use chumsky::prelude::*;
-use std::{fmt, ops::Range};
+use core::{fmt, ops::Range};
 
 pub type Span = Range<usize>;
 
@@ -15,6 +16,7 @@ pub enum Token {
     Discard,
     ReadSeq,
     FixedSeq,
+    Header,
     Self_,
     Reverse,
     ReverseComp,
@@ -33,7 +35,16 @@ pub enum Token {
     FilterWithinDist,
     Hamming,
     TransformTo,
+    Repeat,
+    Optional,
+    Alternatives,
+    Pre,
+    QualTrim,
+    Adapters,
+    AssertLen,
+    Times(usize),
     Arg(usize),
+    SpecVersion,
     U,
     G,
     T,
@@ -76,9 +87,19 @@ impl fmt::Display for Token {
             Discard => write!(f, "Discard"),
             ReadSeq => write!(f, "ReadSeq"),
             FixedSeq => write!(f, "FixedSeq"),
+            Header => write!(f, "Header"),
             TransformTo => write!(f, "Transform into"),
+            Repeat => write!(f, "Repeat"),
+            Optional => write!(f, "Optional"),
+            Alternatives => write!(f, "Alternatives"),
+            Pre => write!(f, "Pre"),
+            QualTrim => write!(f, "QualTrim"),
+            Adapters => write!(f, "Adapters"),
+            AssertLen => write!(f, "AssertLen"),
+            Times(n) => write!(f, "x{n}"),
             Self_ => write!(f, "Self"),
             Arg(n) => write!(f, "argument {n}"),
+            SpecVersion => write!(f, "--spec-version"),
         }
     }
 }
@@ -86,14 +107,14 @@ impl fmt::Display for Token {
 pub fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
     let int = text::int(10).from_str().unwrapped().map(Token::Num);
 
-    let ctrl = one_of("()[]{},").map(Token::Ctrl);
+    let ctrl = one_of("()[]{},;|").map(Token::Ctrl);
 
     let label = just('<')
         .ignore_then(text::ident())
         .then_ignore(just('>'))
         .map(Token::Label);
 
-    let special = one_of(":-=").map(Token::Special);
+    let special = one_of(":-=@.").map(Token::Special);
 
     let file = just('"')
         .ignored()
@@ -103,10 +124,18 @@ pub fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
 
     let transformto = just('-').then(just('>')).to(Token::TransformTo);
 
+    // `--spec-version 2` at the top of a geometry file
+    let spec_version = just("--spec-version").to(Token::SpecVersion);
+
     let argument = just('$')
         .then(text::int(10).from_str().unwrapped())
         .map(|(_, n)| Token::Arg(n));
 
+    // repeat count after a group, `x` directly followed by a number
+    let times = just('x')
+        .ignore_then(text::int(10).from_str().unwrapped())
+        .map(Token::Times);
+
     let nucs = choice((
         just('A').to(Token::A),
         just('T').to(Token::T),
@@ -133,18 +162,28 @@ pub fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
         "map" => Token::Map,
         "hamming" => Token::Hamming,
         "self" => Token::Self_,
+        "repeat" => Token::Repeat,
+        "opt" => Token::Optional,
+        "alt" => Token::Alternatives,
+        "pre" => Token::Pre,
+        "qualtrim" => Token::QualTrim,
+        "adapters" => Token::Adapters,
+        "assert_len" => Token::AssertLen,
         "b" => Token::Barcode,
         "u" => Token::Umi,
         "r" => Token::ReadSeq,
         "x" => Token::Discard,
         "f" => Token::FixedSeq,
+        "h" => Token::Header,
         _ => Token::Label(s),
     });
 
     let token = nucs
         .or(argument)
+        .or(times)
         .or(ident)
         .or(label)
+        .or(spec_version)
         .or(transformto)
         .or(int)
         .or(ctrl)
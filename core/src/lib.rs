@@ -0,0 +1,14 @@
+/*
+   The FGDL lexer, parser and syntax tree, without the standard library
+   so geometries can be parsed where there is no filesystem or threads,
+   such as wasm or the config validation of another tool. Only `alloc` is
+   needed. `seqproc` re-exports both modules as `seqproc::lexer` and
+   `seqproc::parser`.
+*/
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod lexer;
+pub mod parser;
@@ -0,0 +1,1094 @@
+// the `select!` parsers return chumsky's `Simple<Token>` as their error, the
+// error type every parser of the grammar shares and seqproc reports from, so
+// it is left unboxed
+#![allow(clippy::result_large_err)]
+
+use crate::lexer::{Span, Token};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use chumsky::{prelude::*, recovery::NestedDelimiters};
+use core::{fmt, ops::Deref};
+
+pub type Spanned<T> = (T, Span);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Size {
+    // searched for `start..end` bases after the piece before it, if set
+    FixedSeq(Spanned<String>, Miss, Option<(usize, usize)>),
+    FixedLen(Spanned<usize>),
+    RangedLen(Spanned<(usize, usize)>),
+    UnboundedLen,
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Size::*;
+        match self {
+            FixedLen((n, _)) => write!(f, "[{}]", n),
+            FixedSeq((s, _), miss, window) => {
+                if *miss == Miss::Drop {
+                    write!(f, "[{}]", s)?;
+                } else {
+                    write!(f, "[{};miss={}]", s, miss)?;
+                }
+                if let Some((start, end)) = window {
+                    write!(f, "@{}..{}", start, end)?;
+                }
+                Ok(())
+            }
+            RangedLen(((a, b), _)) => write!(f, "[{}-{}]", a, b),
+            UnboundedLen => write!(f, ":"),
+        }
+    }
+}
+
+// what happens to a read where a fixed sequence is not found, `f[SEQ;miss=keep-raw]`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Miss {
+    // the read is dropped
+    #[default]
+    Drop,
+    // the read is kept as it was read, without the transformation
+    KeepRaw,
+    // the sequence is assumed to follow the piece before it at its maximum length
+    Fallback,
+}
+
+impl fmt::Display for Miss {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Miss::Drop => write!(f, "drop"),
+            Miss::KeepRaw => write!(f, "keep-raw"),
+            Miss::Fallback => write!(f, "fallback-fixed"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Function {
+    Reverse,
+    ReverseComp,
+    Truncate(usize),
+    TruncateLeft(usize),
+    TruncateTo(usize),
+    TruncateToLeft(usize),
+    // `[16;slack=3]`, trimmed from the left to its length as `TruncateToLeft`
+    Slack(usize),
+    // `v<label>[18-22]`, a ranged read segment between two fixed sequences
+    Anchored,
+    Remove,
+    Pad(usize, char),
+    PadLeft(usize, char),
+    PadTo(usize, char),
+    PadToLeft(usize, char),
+    Normalize,
+    Map(String, Box<Spanned<Expr>>),
+    MapWithMismatch(String, Box<Spanned<Expr>>, usize),
+    FilterWithinDist(String, usize),
+    Hamming(usize),
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Function::*;
+        match self {
+            Reverse => write!(f, "rev"),
+            ReverseComp => write!(f, "revcomp"),
+            Truncate(n) => write!(f, "trunc({}", n),
+            TruncateLeft(n) => write!(f, "trunc_left({}", n),
+            TruncateTo(n) => write!(f, "trunc_to({}", n),
+            TruncateToLeft(n) => write!(f, "trunc_to_left({}", n),
+            Slack(n) => write!(f, "slack({}", n),
+            Anchored => write!(f, "anchored"),
+            Remove => write!(f, "remove"),
+            Pad(n, nuc) => write!(f, "pad({}, {}", n, nuc),
+            PadLeft(n, nuc) => write!(f, "pad_left({}, {}", n, nuc),
+            PadTo(n, nuc) => write!(f, "pad_to({}, {}", n, nuc),
+            PadToLeft(n, nuc) => write!(f, "pad_to_left({}, {}", n, nuc),
+            Normalize => write!(f, "norm"),
+            Map(p, b) => {
+                let (s, _) = b.deref();
+                write!(f, "map({}, {}", p, s)
+            }
+            MapWithMismatch(p, b, n) => {
+                let (s, _) = b.deref();
+                write!(f, "map_with_mismatch({}, {}, {}", p, s, n)
+            }
+            FilterWithinDist(p, n) => write!(f, "filter_within_dist({}, {}", p, n),
+            Hamming(n) => write!(f, "hamming({}", n),
+        }
+    }
+}
+
+// a step applied to whole reads before their geometry, `pre{qualtrim(20) adapters(truseq)}`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PreStep {
+    // trim the 3' end of each read below this quality
+    QualTrim(usize),
+    // trim a built in set of adapters from the 3' end of each read
+    Adapters(String),
+    // trim the adapters of a fasta file
+    AdapterFile(String),
+    // stop the run at a read of another length, or shorter with `at_least`,
+    // than the geometry was written for. every read if none is given
+    AssertLen {
+        read: Option<usize>,
+        len: usize,
+        at_least: bool,
+    },
+}
+
+impl fmt::Display for PreStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreStep::QualTrim(q) => write!(f, "qualtrim({q})"),
+            PreStep::Adapters(name) => write!(f, "adapters({name})"),
+            PreStep::AdapterFile(path) => write!(f, "adapters(\"{path}\")"),
+            PreStep::AssertLen {
+                read,
+                len,
+                at_least,
+            } => {
+                write!(f, "assert_len(")?;
+                if let Some(read) = read {
+                    write!(f, "{read}, ")?;
+                }
+                write!(f, "{len}{})", if *at_least { ":" } else { "" })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Type {
+    Barcode,
+    Umi,
+    Discard,
+    ReadSeq,
+    FixedSeq,
+    Header,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Type::*;
+        match self {
+            Barcode => write!(f, "Barcode"),
+            Umi => write!(f, "Umi"),
+            Discard => write!(f, "Discard"),
+            ReadSeq => write!(f, "ReadSeq"),
+            FixedSeq => write!(f, "FixedSeq"),
+            Header => write!(f, "Header"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Error,
+    Self_,
+    Argument(usize),
+    Label(Spanned<String>),
+    Type(Spanned<Type>),
+    GeomPiece(Type, Size),
+    LabeledGeomPiece(Spanned<String>, Box<Spanned<Self>>),
+    Function(Spanned<Function>, Box<Spanned<Self>>),
+    Read(Spanned<usize>, Vec<Spanned<Self>>),
+    Repeat(Vec<Spanned<Self>>),
+    Pre(Vec<Spanned<PreStep>>),
+    Group(Vec<Spanned<Self>>),
+    Repeated(Vec<Spanned<Self>>, usize),
+    Optional(Vec<Spanned<Self>>),
+    Alternatives(Vec<Vec<Spanned<Self>>>),
+    Definitions(Vec<Spanned<Self>>),
+    Transform(Vec<Self>),
+    Description(
+        Box<Option<Spanned<Self>>>,
+        Spanned<Vec<Self>>,
+        Box<Spanned<Option<Self>>>,
+    ),
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Expr::*;
+        match self {
+            Error => write!(f, "Error"),
+            Argument(n) => write!(f, "argument {n}"),
+            Self_ => write!(f, "self"),
+            Label((s, _)) => write!(f, "{}", s),
+            Type((t, _)) => write!(f, "{}", t),
+            GeomPiece(t, s) => write!(f, "{}{}", t, s),
+            LabeledGeomPiece((l, _), box_) => {
+                let (expr, _) = box_.deref();
+
+                write!(f, "{}={}", l, expr)
+            }
+            Function((fn_, _), box_) => {
+                let (expr, _) = box_.deref();
+                write!(f, "{}({}))", fn_, expr)
+            }
+            Read((n, _), exprs) => write!(
+                f,
+                "{n}{{{}}}",
+                exprs
+                    .iter()
+                    .map(|(x, _)| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Repeat(exprs) => write!(
+                f,
+                "repeat{{{}}}",
+                exprs
+                    .iter()
+                    .map(|(x, _)| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Pre(steps) => write!(
+                f,
+                "pre{{{}}}",
+                steps
+                    .iter()
+                    .map(|(x, _)| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Group(exprs) => write!(
+                f,
+                "({})",
+                exprs
+                    .iter()
+                    .map(|(x, _)| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Repeated(exprs, n) => write!(
+                f,
+                "({})x{n}",
+                exprs
+                    .iter()
+                    .map(|(x, _)| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Optional(exprs) => write!(
+                f,
+                "opt({})",
+                exprs
+                    .iter()
+                    .map(|(x, _)| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Alternatives(branches) => write!(
+                f,
+                "alt{{{}}}",
+                branches
+                    .iter()
+                    .map(|exprs| exprs
+                        .iter()
+                        .map(|(x, _)| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" "))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            ),
+            Definitions(exprs) => write!(
+                f,
+                "def(\n{}\n)",
+                exprs
+                    .iter()
+                    .map(|(x, _)| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            Transform(exprs) => write!(
+                f,
+                " -> {}",
+                exprs
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Description(d, r, t) => {
+                let d_w = if let Some((d, _)) = d.deref() {
+                    Some(format!("{}", d))
+                } else {
+                    None
+                };
+
+                let t_w = if let (Some(t), _) = t.deref() {
+                    Some(format!("{}", t))
+                } else {
+                    None
+                };
+
+                let (r, _) = r;
+
+                let r_w = r
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                if let Some(d_s) = d_w {
+                    if let Some(t_s) = t_w {
+                        write!(f, "{}\n{}\n{}", d_s, r_w, t_s)
+                    } else {
+                        write!(f, "{}\n{}", d_s, r_w)
+                    }
+                } else {
+                    write!(f, "{}", r_w)
+                }
+            }
+        }
+    }
+}
+
+// the version of the grammar this parser reads, declared in a geometry with `--spec-version 2`
+pub const SPEC_VERSION: usize = 6;
+
+// grammar features and the spec version that introduced them
+pub const FEATURES: &[(&str, usize)] = &[
+    ("repeat", 2),
+    ("groups", 2),
+    ("repeated groups", 2),
+    ("optional groups", 2),
+    ("passthrough reads", 2),
+    ("header pieces", 2),
+    ("miss policies", 2),
+    ("search windows", 2),
+    ("preprocessing", 3),
+    ("alternatives", 4),
+    ("slack", 5),
+    ("anchored segments", 6),
+];
+
+// the features used by an expression, with where they are used
+fn features(expr: &Expr, span: &Span, found: &mut Vec<(&'static str, Span)>) {
+    let mut all = |exprs: &[Spanned<Expr>]| {
+        exprs
+            .iter()
+            .for_each(|(expr, span)| features(expr, span, found))
+    };
+
+    match expr {
+        Expr::GeomPiece(Type::Header, _) => found.push(("header pieces", span.clone())),
+        Expr::GeomPiece(_, Size::FixedSeq(_, miss, window)) => {
+            if *miss != Miss::Drop {
+                found.push(("miss policies", span.clone()));
+            }
+            if window.is_some() {
+                found.push(("search windows", span.clone()));
+            }
+        }
+        Expr::Function((Function::Slack(_), _), gp) => {
+            features(&gp.0, &gp.1, found);
+            found.push(("slack", span.clone()));
+        }
+        Expr::Function((Function::Anchored, _), gp) => {
+            features(&gp.0, &gp.1, found);
+            found.push(("anchored segments", span.clone()));
+        }
+        Expr::LabeledGeomPiece(_, gp) | Expr::Function(_, gp) => features(&gp.0, &gp.1, found),
+        Expr::Read((0, n_span), exprs) if exprs.is_empty() => {
+            found.push(("passthrough reads", n_span.clone()))
+        }
+        Expr::Read(_, exprs) | Expr::Definitions(exprs) => all(exprs),
+        Expr::Repeat(exprs) => {
+            all(exprs);
+            found.push(("repeat", span.clone()));
+        }
+        Expr::Pre(_) => found.push(("preprocessing", span.clone())),
+        Expr::Group(exprs) => {
+            all(exprs);
+            found.push(("groups", span.clone()));
+        }
+        Expr::Repeated(exprs, _) => {
+            all(exprs);
+            found.push(("repeated groups", span.clone()));
+        }
+        Expr::Optional(exprs) => {
+            all(exprs);
+            found.push(("optional groups", span.clone()));
+        }
+        Expr::Alternatives(branches) => {
+            branches.iter().for_each(|exprs| all(exprs));
+            found.push(("alternatives", span.clone()));
+        }
+        Expr::Transform(exprs) => exprs.iter().for_each(|expr| features(expr, span, found)),
+        Expr::Description(d, (r, r_span), t) => {
+            if let Some((d, d_span)) = d.deref() {
+                features(d, d_span, found);
+            }
+            r.iter().for_each(|expr| features(expr, r_span, found));
+            if let (Some(t), t_span) = t.deref() {
+                features(t, t_span, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+// a geometry declaring an older spec version may not use anything newer,
+// so it fails here instead of being read differently than intended
+fn check_spec_version(
+    (version, span): Spanned<usize>,
+    description: &Spanned<Expr>,
+) -> Vec<Simple<Token>> {
+    if version == 0 || version > SPEC_VERSION {
+        return vec![Simple::custom(
+            span,
+            format!("Spec version {version} is not supported, the latest is {SPEC_VERSION}"),
+        )];
+    }
+
+    let mut found = Vec::new();
+    features(&description.0, &description.1, &mut found);
+
+    found
+        .into_iter()
+        .filter_map(|(feature, feature_span)| {
+            let (_, since) = FEATURES.iter().find(|(f, _)| *f == feature)?;
+
+            (*since > version).then(|| {
+                Simple::custom(
+                    feature_span,
+                    format!(
+                        "Using {feature} needs spec version {since}, the geometry declares spec version {version}"
+                    ),
+                )
+            })
+        })
+        .collect()
+}
+
+type ReadRecovery = NestedDelimiters<Token, fn(Span) -> Vec<Spanned<Expr>>, 2>;
+
+// skip over a read with an error in it so the following reads and the
+// transformation are still parsed and their errors reported
+fn read_recovery() -> ReadRecovery {
+    nested_delimiters(
+        Token::Ctrl('{'),
+        Token::Ctrl('}'),
+        [
+            (Token::Ctrl('['), Token::Ctrl(']')),
+            (Token::Ctrl('('), Token::Ctrl(')')),
+        ],
+        |span| vec![(Expr::Error, span)],
+    )
+}
+
+pub fn parser() -> impl Parser<Token, Spanned<Expr>, Error = Simple<Token>> + Clone {
+    /*
+       Start with creating combinators and
+       a recursive definition of a geom_piece
+
+       At execution time we will check if it is a valid
+       geometry without any ambiguity. Here we will
+       restruct some invalid definitions
+    */
+
+    let ident = select! { Token::Label(ident) => ident }.labelled("Piece Label");
+
+    let num = select! { Token::Num(n) => n }.labelled("Size Label");
+
+    let file = select! { Token::File(f) => f }.labelled("File Path");
+
+    let argument = select! { Token::Arg(n) => n.to_string() }.labelled("Argument");
+
+    let piece_type = select! {
+        Token::Barcode => Type::Barcode,
+        Token::Umi => Type::Umi,
+        Token::Discard => Type::Discard,
+        Token::ReadSeq => Type::ReadSeq,
+    }
+    .labelled("Piece Type");
+
+    let nuc = select! {
+        Token::U => 'U',
+        Token::A => 'A',
+        Token::T => 'T',
+        Token::G => 'G',
+        Token::C => 'C',
+    };
+
+    // the name given to a piece or group, and a reference to one
+    let name = ident.map_with_span(|l, span| (l, span)).labelled("Label");
+    let label = name.map(Expr::Label);
+
+    let self_ = just(Token::Self_).to(Expr::Self_).labelled("Self");
+
+    let range = just(Token::Ctrl('['))
+        .ignored()
+        .then(
+            num.then_ignore(just(Token::Special('-')))
+                .then(num)
+                .map_with_span(|(a, b), span| Size::RangedLen(((a, b), span)))
+                .then_ignore(just(Token::Ctrl(']'))),
+        )
+        .labelled("Range");
+
+    let fixed_len = just(Token::Ctrl('['))
+        .ignore_then(num.map_with_span(|n, span| Size::FixedLen((n, span))))
+        .then_ignore(just(Token::Ctrl(']')))
+        .labelled("Fixed Length");
+
+    let seq = nuc.repeated().collect::<String>();
+
+    // plain words are lexed as labels
+    let word = |w: &str| just(Token::Label(w.to_string()));
+
+    let miss = just(Token::Ctrl(';'))
+        .ignore_then(word("miss"))
+        .ignore_then(just(Token::Special('=')))
+        .ignore_then(choice((
+            word("drop").to(Miss::Drop),
+            word("keep")
+                .then(just(Token::Special('-')))
+                .then(word("raw"))
+                .to(Miss::KeepRaw),
+            word("fallback")
+                .then(just(Token::Special('-')).then(word("fixed")).or_not())
+                .to(Miss::Fallback),
+        )))
+        .labelled("Miss Policy");
+
+    // `[16;slack=3]`, a length that may start up to 3 bases late
+    let slack_len = just(Token::Ctrl('['))
+        .ignore_then(num.map_with_span(|n, span| (n, span)))
+        .then_ignore(just(Token::Ctrl(';')))
+        .then_ignore(word("slack"))
+        .then_ignore(just(Token::Special('=')))
+        .then(num)
+        .then_ignore(just(Token::Ctrl(']')))
+        .labelled("Slack Length");
+
+    let nucstr = just(Token::Ctrl('['))
+        .ignore_then(seq.map_with_span(|nucstr, span| (nucstr, span)))
+        .then(miss.or_not())
+        .then_ignore(just(Token::Ctrl(']')))
+        .labelled("Nucleotide String");
+
+    // `@10..20`, where the sequence may start
+    let window = just(Token::Special('@'))
+        .ignore_then(num)
+        .then_ignore(just(Token::Special('.')))
+        .then_ignore(just(Token::Special('.')))
+        .then(num)
+        .labelled("Search Window");
+
+    let unbounded = piece_type
+        .then(name.or_not())
+        .then_ignore(just(Token::Special(':')))
+        .map_with_span(|(type_, label), span| {
+            let expr = Expr::GeomPiece(type_, Size::UnboundedLen);
+            if let Some(label) = label {
+                Expr::LabeledGeomPiece(label, Box::new((expr, span)))
+            } else {
+                expr
+            }
+        })
+        .labelled("Unbounded Segment");
+
+    let ranged = piece_type
+        .then(name.or_not())
+        .then(range.clone())
+        .map_with_span(|((type_, label), (_, range)), span| {
+            let expr = Expr::GeomPiece(type_, range);
+            if let Some(label) = label {
+                Expr::LabeledGeomPiece(label, Box::new((expr, span)))
+            } else {
+                expr
+            }
+        })
+        .labelled("Ranged Segment");
+
+    // a piece with slack is cut as a ranged one, so it ends where the
+    // sequence after it is found, then trimmed from the left to its length
+    let slack = piece_type
+        .then(name.or_not())
+        .then(slack_len)
+        .map_with_span(|((type_, label), ((len, len_span), slack)), span: Span| {
+            let size = Size::RangedLen(((len, len + slack), len_span));
+            let expr = Expr::GeomPiece(type_, size);
+            let expr = if let Some(label) = label {
+                Expr::LabeledGeomPiece(label, Box::new((expr, span.clone())))
+            } else {
+                expr
+            };
+            Expr::Function((Function::Slack(len), span.clone()), Box::new((expr, span)))
+        })
+        .labelled("Slack Segment");
+
+    // `v<guide>[18-22]`, a read segment cut exactly between the fixed
+    // sequences on either side of it. `v` is only read as a word here, so it
+    // is still free as the name of a definition
+    let anchored = word("v")
+        .ignore_then(name.or_not())
+        .then(range)
+        .map_with_span(|(label, (_, range)), span: Span| {
+            let expr = Expr::GeomPiece(Type::ReadSeq, range);
+            let expr = if let Some(label) = label {
+                Expr::LabeledGeomPiece(label, Box::new((expr, span.clone())))
+            } else {
+                expr
+            };
+            Expr::Function((Function::Anchored, span.clone()), Box::new((expr, span)))
+        })
+        .labelled("Anchored Segment");
+
+    let fixed = piece_type
+        .then(name.or_not())
+        .then(fixed_len.clone())
+        .map_with_span(|((type_, label), len), span| {
+            let expr = Expr::GeomPiece(type_, len);
+            if let Some(label) = label {
+                Expr::LabeledGeomPiece(label, Box::new((expr, span)))
+            } else {
+                expr
+            }
+        })
+        .labelled("Fixed Length Segment");
+
+    let fixed_seq = just(Token::FixedSeq)
+        .to(Type::FixedSeq)
+        .then(name.or_not())
+        .then(nucstr)
+        .then(window.or_not())
+        .map_with_span(|(((type_, label), (nucs, miss)), window), span| {
+            let size = Size::FixedSeq(nucs, miss.unwrap_or_default(), window);
+            let expr = Expr::GeomPiece(type_, size);
+            if let Some(label) = label {
+                Expr::LabeledGeomPiece(label, Box::new((expr, span)))
+            } else {
+                expr
+            }
+        })
+        .labelled("Fixed Sequence Segment");
+
+    // an index taken from the read header, always labeled and of fixed length
+    let header = just(Token::Header)
+        .to(Type::Header)
+        .then(name)
+        .then(fixed_len)
+        .map_with_span(|((type_, label), len), span| {
+            Expr::LabeledGeomPiece(label, Box::new((Expr::GeomPiece(type_, len), span)))
+        })
+        .labelled("Header Segment");
+
+    let geom_piece = choice((
+        unbounded.clone(),
+        ranged.clone(),
+        slack,
+        anchored,
+        fixed.clone(),
+        fixed_seq.clone(),
+        header,
+        label,
+        self_,
+    ));
+
+    let transformed_pieces = recursive(|transformed_pieces| {
+        let recursive_num_arg = transformed_pieces
+            .clone()
+            .then_ignore(just(Token::Ctrl(',')))
+            .then(num)
+            .map_with_span(|tok, span| (tok, span))
+            .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
+        let recursive_num_nuc_args = transformed_pieces
+            .clone()
+            .then_ignore(just(Token::Ctrl(',')))
+            .then(num)
+            .then_ignore(just(Token::Ctrl(',')))
+            .then(nuc)
+            .map_with_span(|tok, span| (tok, span))
+            .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
+        let num_arg = geom_piece
+            .clone()
+            .then_ignore(just(Token::Ctrl(',')))
+            .then(num)
+            .map_with_span(|tok, span| (tok, span))
+            .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
+        let recursive_no_arg = transformed_pieces
+            .clone()
+            .map_with_span(|tok, span| (tok, span))
+            .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
+        choice((
+            geom_piece.clone(),
+            just(Token::Remove)
+                .map_with_span(|_, span| (Function::Remove, span))
+                .then(recursive_no_arg.clone())
+                .map(|(fn_, tok)| Expr::Function(fn_, Box::new(tok)))
+                .labelled("Remove function"),
+            just(Token::Normalize)
+                .map_with_span(|_, span| (Function::Normalize, span))
+                .then(recursive_no_arg.clone())
+                .map(|(fn_, tok)| Expr::Function(fn_, Box::new(tok)))
+                .labelled("Normalize function"),
+            just(Token::Hamming)
+                .map_with_span(|_, span| span)
+                .then(num_arg)
+                .map(|(fn_span, ((geom_p, num), span))| {
+                    Expr::Function((Function::Hamming(num), fn_span), Box::new((geom_p, span)))
+                })
+                .labelled("Hamming function"),
+            just(Token::Truncate)
+                .map_with_span(|_, span| span)
+                .then(recursive_num_arg.clone())
+                .map(|(fn_span, ((geom_p, num), span))| {
+                    Expr::Function((Function::Truncate(num), fn_span), Box::new((geom_p, span)))
+                })
+                .labelled("Truncate function"),
+            just(Token::TruncateLeft)
+                .map_with_span(|_, span| span)
+                .then(recursive_num_arg.clone())
+                .map(|(fn_span, ((geom_p, num), span))| {
+                    Expr::Function(
+                        (Function::TruncateLeft(num), fn_span),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Truncate Left function"),
+            just(Token::TruncateTo)
+                .map_with_span(|_, span| span)
+                .then(recursive_num_arg.clone())
+                .map(|(fn_span, ((geom_p, num), span))| {
+                    Expr::Function(
+                        (Function::TruncateTo(num), fn_span),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Truncate To function"),
+            just(Token::TruncateToLeft)
+                .map_with_span(|_, span| span)
+                .then(recursive_num_arg.clone())
+                .map(|(fn_span, ((geom_p, num), span))| {
+                    Expr::Function(
+                        (Function::TruncateToLeft(num), fn_span),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Truncate To Left function"),
+            just(Token::Pad)
+                .map_with_span(|_, span| span)
+                .then(recursive_num_nuc_args.clone())
+                .map(|(fn_span, (((geom_p, num), nuc), span))| {
+                    Expr::Function((Function::Pad(num, nuc), fn_span), Box::new((geom_p, span)))
+                })
+                .labelled("Pad function"),
+            just(Token::PadLeft)
+                .map_with_span(|_, span| span)
+                .then(recursive_num_nuc_args.clone())
+                .map(|(fn_span, (((geom_p, num), nuc), span))| {
+                    Expr::Function(
+                        (Function::PadLeft(num, nuc), fn_span),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Pad Left function"),
+            just(Token::PadTo)
+                .map_with_span(|_, span| span)
+                .then(recursive_num_nuc_args.clone())
+                .map(|(fn_span, (((geom_p, num), nuc), span))| {
+                    Expr::Function(
+                        (Function::PadTo(num, nuc), fn_span),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Pad To function"),
+            just(Token::PadToLeft)
+                .map_with_span(|_, span| span)
+                .then(recursive_num_nuc_args)
+                .map(|(fn_span, (((geom_p, num), nuc), span))| {
+                    Expr::Function(
+                        (Function::PadToLeft(num, nuc), fn_span),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Pad To Left function"),
+            just(Token::Reverse)
+                .map_with_span(|_, span| (Function::Reverse, span))
+                .then(recursive_no_arg.clone())
+                .map(|(fn_, tok)| Expr::Function(fn_, Box::new(tok)))
+                .labelled("Reverse function"),
+            just(Token::ReverseComp)
+                .map_with_span(|_, span| (Function::ReverseComp, span))
+                .then(recursive_no_arg.clone())
+                .map(|(fn_, tok)| Expr::Function(fn_, Box::new(tok)))
+                .labelled("Reverse Compliment function"),
+            just(Token::Map)
+                .map_with_span(|_, span| span)
+                .then(
+                    geom_piece
+                        .clone()
+                        .then_ignore(just(Token::Ctrl(',')))
+                        .then(file.or(argument))
+                        .then_ignore(just(Token::Ctrl(',')))
+                        .then(
+                            transformed_pieces
+                                .clone()
+                                .map_with_span(|tok, span| (tok, span)),
+                        )
+                        .map_with_span(|tok, span| (tok, span))
+                        .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))),
+                )
+                .map(|(fn_span, (((geom_p, path), self_expr), span))| {
+                    Expr::Function(
+                        (Function::Map(path, Box::new(self_expr)), fn_span),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Map function"),
+            just(Token::MapWithMismatch)
+                .map_with_span(|_, span| span)
+                .then(
+                    geom_piece
+                        .clone()
+                        .then_ignore(just(Token::Ctrl(',')))
+                        .then(file.or(argument))
+                        .then_ignore(just(Token::Ctrl(',')))
+                        .then(
+                            transformed_pieces
+                                .clone()
+                                .map_with_span(|tok, span| (tok, span)),
+                        )
+                        .then_ignore(just(Token::Ctrl(',')))
+                        .then(num)
+                        .map_with_span(|tok, span| (tok, span))
+                        .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))),
+                )
+                .map(|(fn_span, ((((geom_p, path), self_expr), num), span))| {
+                    Expr::Function(
+                        (
+                            Function::MapWithMismatch(path, Box::new(self_expr), num),
+                            fn_span,
+                        ),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Map with mismatch function"),
+            just(Token::FilterWithinDist)
+                .map_with_span(|_, span| span)
+                .then(
+                    geom_piece
+                        .then_ignore(just(Token::Ctrl(',')))
+                        .then(file.or(argument))
+                        .then_ignore(just(Token::Ctrl(',')))
+                        .then(num)
+                        .map_with_span(|tok, span| (tok, span))
+                        .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))),
+                )
+                .map(|(fn_span, (((geom_p, path), num), span))| {
+                    Expr::Function(
+                        (Function::FilterWithinDist(path, num), fn_span),
+                        Box::new((geom_p, span)),
+                    )
+                })
+                .labelled("Filter within dist function"),
+        ))
+    })
+    .map_with_span(|tok, span| (tok, span));
+
+    let definitions = name
+        .then_ignore(just(Token::Special('=')))
+        .then(transformed_pieces.clone())
+        .map_with_span(|(label, geom_p), span| {
+            (Expr::LabeledGeomPiece(label, Box::new(geom_p)), span)
+        })
+        .repeated()
+        .at_least(1);
+
+    let times = select! { Token::Times(n) => n }.labelled("Repeat Count");
+
+    // groups of pieces, repeated a fixed number of times with `(b[8] f[ACGT])x3`,
+    // made optional with `opt(f[ACGT] b[8])` or labeled as a whole with `bc=(..)`.
+    // `alt{f[ACGT] b[8] | f[TTTT] b[10]}` reads the first branch found in the read
+    let read_pieces = recursive(|read_pieces| {
+        let group = read_pieces
+            .clone()
+            .repeated()
+            .at_least(1)
+            .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
+        let repeated = group
+            .clone()
+            .then(times.or_not())
+            .map_with_span(|(exprs, n), span| {
+                if let Some(n) = n {
+                    (Expr::Repeated(exprs, n), span)
+                } else {
+                    (Expr::Group(exprs), span)
+                }
+            })
+            .labelled("Group");
+
+        let optional = just(Token::Optional)
+            .ignore_then(group)
+            .map_with_span(|exprs, span| (Expr::Optional(exprs), span))
+            .labelled("Optional Group");
+
+        let groups = repeated.or(optional);
+
+        let alternatives = just(Token::Alternatives)
+            .ignore_then(
+                read_pieces
+                    .repeated()
+                    .at_least(1)
+                    .separated_by(just(Token::Ctrl('|')))
+                    .at_least(2)
+                    .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}'))),
+            )
+            .map_with_span(|branches, span| (Expr::Alternatives(branches), span))
+            .labelled("Alternatives");
+
+        name.then_ignore(just(Token::Special('=')))
+            .then(groups.clone())
+            .map_with_span(|(label, group), span| {
+                (Expr::LabeledGeomPiece(label, Box::new(group)), span)
+            })
+            .labelled("Labeled Group")
+            .or(groups)
+            .or(alternatives)
+            .or(transformed_pieces.clone())
+    });
+
+    // a read passed through unchanged, no pieces are cut from it
+    let passthrough = just(Token::Num(0))
+        .map_with_span(|_, span| (0, span))
+        .then_ignore(just(Token::Ctrl('{')))
+        .then_ignore(just(Token::Ctrl('}')))
+        .map(|n| Expr::Read(n, Vec::new()))
+        .labelled("Passthrough Read");
+
+    let reads = passthrough
+        .clone()
+        .or(num
+            .map_with_span(|tok, span| (tok, span))
+            .then(
+                read_pieces
+                    .clone()
+                    .repeated()
+                    .at_least(1)
+                    .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}')))
+                    .recover_with(read_recovery()),
+            )
+            .map(|(n, read)| Expr::Read(n, read)))
+        .repeated()
+        .at_least(1)
+        .at_most(2)
+        .collect::<Vec<_>>();
+
+    // a single read made of repeated copies of the same geometry
+    let repeat = just(Token::Repeat)
+        .ignore_then(
+            read_pieces
+                .repeated()
+                .at_least(1)
+                .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}'))),
+        )
+        .map(|read| vec![Expr::Repeat(read)])
+        .labelled("Repeat");
+
+    let pre_step = choice((
+        just(Token::QualTrim)
+            .ignore_then(num.delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))))
+            .map(PreStep::QualTrim)
+            .labelled("Quality Trim"),
+        just(Token::Adapters)
+            .ignore_then(
+                ident
+                    .map(PreStep::Adapters)
+                    .or(file.map(PreStep::AdapterFile))
+                    .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))),
+            )
+            .labelled("Adapter Trim"),
+        just(Token::AssertLen)
+            .ignore_then(
+                num.then_ignore(just(Token::Ctrl(',')))
+                    .or_not()
+                    .then(num)
+                    .then(just(Token::Special(':')).or_not())
+                    .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))),
+            )
+            .map(|((read, len), at_least)| PreStep::AssertLen {
+                read,
+                len,
+                at_least: at_least.is_some(),
+            })
+            .labelled("Assert Length"),
+    ))
+    .map_with_span(|step, span| (step, span));
+
+    // steps on whole reads before the geometry of each read
+    let pre = just(Token::Pre)
+        .ignore_then(
+            pre_step
+                .repeated()
+                .at_least(1)
+                .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}'))),
+        )
+        .map(Expr::Pre)
+        .labelled("Preprocessing");
+
+    let transform_read = passthrough.or(num
+        .map_with_span(|tok, span| (tok, span))
+        .then(
+            transformed_pieces
+                .clone()
+                .repeated()
+                .at_least(1)
+                .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}')))
+                .recover_with(read_recovery()),
+        )
+        .map(|(n, read)| Expr::Read(n, read)));
+
+    let transformation = choice((
+        end().map_with_span(|_, span| (None, span)),
+        just(Token::TransformTo)
+            .then(transform_read.repeated().at_least(1).at_most(2))
+            .map_with_span(|(_, val), span| (Some(Expr::Transform(val)), span)),
+    ));
+
+    let spec_version = just(Token::SpecVersion)
+        .ignore_then(num.map_with_span(|n, span| (n, span)))
+        .labelled("Spec Version");
+
+    let description = definitions
+        .map_with_span(|tok, span| (Expr::Definitions(tok), span))
+        .or_not()
+        .then(
+            pre.or_not()
+                .then(repeat.or(reads))
+                .map(|(pre, reads)| pre.into_iter().chain(reads).collect::<Vec<_>>())
+                .map_with_span(|tok, span| (tok, span)),
+        )
+        .then(transformation)
+        .map(|((d, r), t)| Expr::Description(Box::new(d), r, Box::new(t)))
+        .map_with_span(|tok, span| (tok, span));
+
+    spec_version
+        .or_not()
+        .then(description)
+        .validate(|(version, description), _, emit| {
+            if let Some(version) = version {
+                check_spec_version(version, &description)
+                    .into_iter()
+                    .for_each(&mut *emit);
+            }
+            description
+        })
+        .recover_with(skip_then_retry_until([]))
+}
@@ -0,0 +1,43 @@
+/* C bindings of seqproc, see ffi/src/lib.rs */
+
+#ifndef SEQPROC_H
+#define SEQPROC_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SEQPROC_OK 0
+#define SEQPROC_INVALID_ARGUMENT 1
+#define SEQPROC_GEOMETRY_ERROR 2
+#define SEQPROC_RUN_FAILED 3
+#define SEQPROC_CANCELLED 4
+
+/* a panic inside seqproc never unwinds into the caller, the function
+   returns SEQPROC_RUN_FAILED with the panic's message as the last error */
+
+typedef struct {
+    /* reads that entered the pipeline */
+    uint64_t reads;
+} SeqprocStats;
+
+/* the message of the last error on this thread, null if there was none */
+const char *seqproc_last_error(void);
+
+int seqproc_parse(const char *geometry);
+
+int seqproc_validate(const char *geometry);
+
+/* file2, out1, out2 and stats may be null */
+int seqproc_run(const char *geometry, const char *file1, const char *file2,
+                const char *out1, const char *out2, size_t threads,
+                SeqprocStats *stats);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
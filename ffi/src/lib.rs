@@ -0,0 +1,173 @@
+/*
+   C bindings of seqproc, declared in `seqproc.h`.
+   Every function returns one of the `SEQPROC_*` codes. The message of
+   the last error on the calling thread is kept until the next call
+   and can be read with `seqproc_last_error`. Strings are nul terminated
+   UTF-8, optional ones may be null. A panic never unwinds into the
+   caller, it is returned as `SEQPROC_RUN_FAILED` with its message.
+*/
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use seqproc::{
+    failure::Failure,
+    interpret::InterpretOptions,
+    run,
+    runner::{compile_geometry, parse_geometry, CancellationToken, RunConfig, RunError},
+};
+
+pub const SEQPROC_OK: c_int = 0;
+pub const SEQPROC_INVALID_ARGUMENT: c_int = 1;
+pub const SEQPROC_GEOMETRY_ERROR: c_int = 2;
+pub const SEQPROC_RUN_FAILED: c_int = 3;
+pub const SEQPROC_CANCELLED: c_int = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SeqprocStats {
+    // reads that entered the pipeline
+    pub reads: u64,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(msg: String) {
+    // a message with a nul in it is cut there
+    let msg = CString::new(msg).unwrap_or_else(|e| {
+        let end = e.nul_position();
+        CString::new(&e.into_vec()[..end]).unwrap()
+    });
+
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+}
+
+fn code(err: RunError) -> c_int {
+    let code = match err {
+        RunError::Geometry(_) => SEQPROC_GEOMETRY_ERROR,
+        RunError::Cancelled => SEQPROC_CANCELLED,
+        RunError::Failed(_) => SEQPROC_RUN_FAILED,
+    };
+
+    set_error(err.to_string());
+    code
+}
+
+// the code `body` returns, unwinding across the C ABI is undefined
+fn guarded(body: impl FnOnce() -> c_int) -> c_int {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        set_error(Failure::from_panic(&*payload).message);
+        SEQPROC_RUN_FAILED
+    })
+}
+
+// a string argument, `None` if it is null
+unsafe fn string(s: *const c_char, name: &str) -> Result<Option<String>, c_int> {
+    if s.is_null() {
+        return Ok(None);
+    }
+
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Ok(Some(s.to_string())),
+        Err(_) => {
+            set_error(format!("{name} is not valid UTF-8"));
+            Err(SEQPROC_INVALID_ARGUMENT)
+        }
+    }
+}
+
+unsafe fn required(s: *const c_char, name: &str) -> Result<String, c_int> {
+    string(s, name)?.ok_or_else(|| {
+        set_error(format!("{name} is null"));
+        SEQPROC_INVALID_ARGUMENT
+    })
+}
+
+/// The message of the last error on this thread, null if there was none.
+/// It is owned by seqproc and valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn seqproc_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |msg| msg.as_ptr())
+    })
+}
+
+/// Check that a geometry parses, without checking what it means.
+///
+/// # Safety
+/// `geometry` must be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn seqproc_parse(geometry: *const c_char) -> c_int {
+    guarded(|| match required(geometry, "geometry") {
+        Ok(geometry) => parse_geometry(&geometry).map_or_else(code, |_| SEQPROC_OK),
+        Err(code) => code,
+    })
+}
+
+/// Check that a geometry parses and compiles.
+///
+/// # Safety
+/// `geometry` must be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn seqproc_validate(geometry: *const c_char) -> c_int {
+    guarded(|| match required(geometry, "geometry") {
+        Ok(geometry) => compile_geometry(&geometry).map_or_else(code, |_| SEQPROC_OK),
+        Err(code) => code,
+    })
+}
+
+/// Process the reads of `file1`, and `file2` if not null, with a geometry.
+/// Null outputs are not written, `stats` is filled in if not null.
+///
+/// # Safety
+/// The strings must be null or nul terminated, `stats` must be null or
+/// point to a `SeqprocStats`.
+#[no_mangle]
+pub unsafe extern "C" fn seqproc_run(
+    geometry: *const c_char,
+    file1: *const c_char,
+    file2: *const c_char,
+    out1: *const c_char,
+    out2: *const c_char,
+    threads: usize,
+    stats: *mut SeqprocStats,
+) -> c_int {
+    guarded(|| {
+        let config = (|| {
+            Ok(RunConfig {
+                geometry: required(geometry, "geometry")?,
+                file1: required(file1, "file1")?,
+                file2: string(file2, "file2")?,
+                out1: string(out1, "out1")?.unwrap_or_default(),
+                out2: string(out2, "out2")?.unwrap_or_default(),
+                threads: threads.max(1),
+                options: InterpretOptions::default(),
+            })
+        })();
+
+        let config = match config {
+            Ok(config) => config,
+            Err(code) => return code,
+        };
+
+        match run(config, |_| (), CancellationToken::new()) {
+            Ok(progress) => {
+                if !stats.is_null() {
+                    *stats = SeqprocStats {
+                        reads: progress.reads as u64,
+                    };
+                }
+                SEQPROC_OK
+            }
+            Err(err) => code(err),
+        }
+    })
+}
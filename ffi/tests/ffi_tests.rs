@@ -0,0 +1,82 @@
+use std::{
+    env,
+    ffi::{CStr, CString},
+    fs, process, ptr,
+};
+
+use seqproc_ffi::{
+    seqproc_last_error, seqproc_parse, seqproc_run, seqproc_validate, SEQPROC_GEOMETRY_ERROR,
+    SEQPROC_INVALID_ARGUMENT, SEQPROC_OK, SEQPROC_RUN_FAILED,
+};
+
+#[test]
+fn validate() {
+    let valid = CString::new("1{b[16]u[12]r:}").unwrap();
+    let unknown_label = CString::new("1{b<bc>[16]} -> 1{<umi>}").unwrap();
+
+    unsafe {
+        assert_eq!(SEQPROC_OK, seqproc_parse(valid.as_ptr()));
+        assert_eq!(SEQPROC_OK, seqproc_validate(valid.as_ptr()));
+
+        // parses but does not compile
+        assert_eq!(SEQPROC_OK, seqproc_parse(unknown_label.as_ptr()));
+        assert_eq!(
+            SEQPROC_GEOMETRY_ERROR,
+            seqproc_validate(unknown_label.as_ptr())
+        );
+        assert!(!seqproc_last_error().is_null());
+    }
+}
+
+#[test]
+fn null_arguments() {
+    let geometry = CString::new("1{r:}").unwrap();
+
+    unsafe {
+        assert_eq!(SEQPROC_INVALID_ARGUMENT, seqproc_validate(ptr::null()));
+        assert_eq!(
+            SEQPROC_INVALID_ARGUMENT,
+            seqproc_run(
+                geometry.as_ptr(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                1,
+                ptr::null_mut()
+            )
+        );
+        assert_eq!(
+            "file1 is null",
+            CStr::from_ptr(seqproc_last_error()).to_str().unwrap()
+        );
+    }
+}
+
+#[test]
+fn failed_run() {
+    let r1 = env::temp_dir().join(format!("seqproc-ffi-{}.fastq", process::id()));
+    fs::write(&r1, "@read0\nACGTACGT\n+\nIIIIIIII\n").unwrap();
+
+    let geometry = CString::new("1{r:}").unwrap();
+    let file1 = CString::new(r1.display().to_string()).unwrap();
+    let out1 = CString::new("/nonexistent/seqproc/out.fastq").unwrap();
+
+    unsafe {
+        assert_eq!(
+            SEQPROC_RUN_FAILED,
+            seqproc_run(
+                geometry.as_ptr(),
+                file1.as_ptr(),
+                ptr::null(),
+                out1.as_ptr(),
+                ptr::null(),
+                1,
+                ptr::null_mut()
+            )
+        );
+        assert!(!seqproc_last_error().is_null());
+    }
+
+    fs::remove_file(r1).unwrap();
+}
@@ -0,0 +1,51 @@
+/*
+   Adapter trimming for the `pre{..}` block of a geometry.
+   Reads shorter than their library insert run into the adapter at
+   their 3' end. An adapter is looked for from the start of the read
+   on, a hit may run off the end of the read as long as enough of the
+   adapter is left to tell it apart from chance. The read is cut
+   where the first adapter of a set starts.
+*/
+
+// the shortest end of a read taken as the start of an adapter
+pub const MIN_OVERLAP: usize = 3;
+
+// one mismatch is allowed every this many bases of the overlap
+const BASES_PER_MISMATCH: usize = 10;
+
+// common adapter sets by name
+pub fn builtin(name: &str) -> Option<Vec<Vec<u8>>> {
+    let adapters: &[&[u8]] = match name {
+        // shared start of the TruSeq read 1 and read 2 adapters
+        "truseq" => &[b"AGATCGGAAGAGC"],
+        "nextera" => &[b"CTGTCTCTTATACACATCT"],
+        "smallrna" => &[b"TGGAATTCTCGG"],
+        _ => return None,
+    };
+
+    Some(adapters.iter().map(|a| a.to_vec()).collect())
+}
+
+// where `adapter` starts in `seq`, it may run off the end of the read
+pub fn adapter_start(seq: &[u8], adapter: &[u8]) -> Option<usize> {
+    (0..seq.len()).find(|&start| {
+        let overlap = adapter.len().min(seq.len() - start);
+
+        let mismatches = seq[start..start + overlap]
+            .iter()
+            .zip(adapter)
+            .filter(|(a, b)| !a.eq_ignore_ascii_case(b))
+            .count();
+
+        overlap >= MIN_OVERLAP && mismatches <= overlap / BASES_PER_MISMATCH
+    })
+}
+
+// the length `seq` is cut to, before the first of `adapters`
+pub fn trimmed_len(seq: &[u8], adapters: &[Vec<u8>]) -> usize {
+    adapters
+        .iter()
+        .filter_map(|adapter| adapter_start(seq, adapter))
+        .min()
+        .unwrap_or(seq.len())
+}
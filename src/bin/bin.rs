@@ -1,161 +1,1803 @@
-use antisequence::{iter_fastq2, Reads};
+use std::{
+    fs::{self, File},
+    io::{self, IsTerminal},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    process,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use antisequence::{iter_fastq1, iter_fastq2, Reads};
 use ariadne::{Color, Fmt, Label, Report, ReportKind, Source};
 use chumsky::{prelude::*, Stream};
-use clap::{arg, Parser as cParser};
+use clap::{arg, builder::ValueHint, CommandFactory, Parser as cParser, Subcommand};
+use regex::bytes::Regex;
 
 use seqproc::{
-    compile::{compile, CompiledData},
+    checksum::{checksum_algorithm, Algorithm, Checksums},
+    compile::{compile, lint::lint, CompiledData},
+    convert::{
+        convert_format,
+        seqspec::{read_seqspec, write_seqspec},
+        umitools, Format,
+    },
+    describe::{describe, ToolFormat},
+    explain::{
+        graph::{graph, graph_format, GraphFormat},
+        json, plan, text,
+    },
+    failure::{fail, io_failed, Failure, FailureKind},
+    features::{modality_output, out_template, template_outputs, Features},
+    filters::{
+        complexity::ComplexityFilter,
+        dedup::{DedupConfig, DedupMode},
+        name::{name_pattern, tile, NameFilter, Tile},
+        umi::UmiFilter,
+        whitelist::WhitelistLengths,
+        OnFail,
+    },
+    golden::Case,
+    interpret::InterpretOptions,
     lexer,
-    parser::parser,
+    matchers::{matcher_choice, MatcherChoice},
+    merge::MergeConfig,
+    monitor::{
+        metrics::{metrics_target, Emitter, Metrics, MetricsTarget},
+        Timings,
+    },
+    parser::{parser, Type},
+    presets::{
+        chemistries, config_dir,
+        fetch::{cache_dir, fetch_whitelist},
+        find_chemistry, read_geometry_of, Chemistry,
+    },
+    preview::{sample, Preview, DEFAULT_READS},
+    primers::PrimerConfig,
+    quality::{parse_qual, qual_offset, QualOffset},
+    repair::{verify_pairs as verify, RepairedFiles},
+    report::{self, schema, StageTiming},
+    rng::DEFAULT_SEED,
+    runner::compile_geometry,
+    sink::{
+        atomic::AtomicOutputs,
+        cram::CramWriter,
+        fastq::{segment_output, tech_read, TechRead},
+        output_dirs,
+        provenance::Provenance,
+        route::{route, Route},
+        shard::{shard_size, ShardSize, Sharding},
+        tags::RawTags,
+        tsv::Capture,
+        well::WellMap,
+    },
+    source::{
+        crlf,
+        decompress::{is_compressed, Decompressed},
+        fastq::FastqReader,
+        lengths::{LengthSample, SAMPLE_SIZE},
+        pooled::{geom_by, GeomBy, KeyOn, PooledFiles},
+        prescan, quality_offset, regular,
+        remote::{remote, RemoteInputs},
+        salvage::Salvaged,
+    },
+    summary::{
+        composition::Composition, hopping::IndexHopping, BaseCounts, DuplicationEstimate, UmiCounts,
+    },
 };
 
+#[cfg(feature = "parquet")]
+use seqproc::sink::table::SegmentWriter;
+#[cfg(feature = "async-io")]
+use seqproc::source::prefetch::Prefetched;
+
 /// General puprose sequence preprocessor
 #[derive(Debug, cParser)]
+#[command(subcommand_negates_reqs = true, args_override_self = true)]
 pub struct Args {
-    /// FGDL string
-    #[arg(short, long)]
-    geom: String,
+    #[command(subcommand)]
+    command: Option<Cmd>,
+
+    /// FGDL file, or a built-in geometry as `preset:<name>`, e.g. `preset:crispr`
+    #[arg(short, long, required = true, value_hint = ValueHint::FilePath)]
+    geom: Option<String>,
+
+    /// file of chemistries of your own given as `preset:<name>`, read after those of the config directory
+    #[arg(long, global = true, value_hint = ValueHint::FilePath)]
+    preset_file: Vec<String>,
 
-    /// r1 fastq file
-    #[arg(short = '1', long)]
-    file1: String,
+    /// process reads whose header matches a pattern with its own geometry, e.g. `SAMPLEA:a.fgdl;SAMPLEB:b.fgdl`, or whose r1 segment `<label>`, as cut by `--geom`, does with `seg:<label>=<pattern>`, other reads with `--geom`. Each part is run on its own, so this cannot be combined with --explain, --checksum, --cram, --merge, --shard-size, --split, --segment-out, --tech-read, --capture-tsv, --well-tsv, --raw-tags-tsv, --whitelist, --skip-bad-records, --features, --parquet or --async-io
+    #[arg(long, value_parser = geom_by, conflicts_with_all = ["explain", "checksum", "cram", "merge", "shard_size", "split", "segment_out", "tech_read", "capture_tsv", "well_tsv", "raw_tags_tsv", "whitelist"])]
+    geom_by: Option<GeomBy>,
 
-    /// r2 fastq file
-    #[arg(short = '2', long)]
-    file2: String,
+    /// r1 fastq file, or an s3://, gs:// or http(s):// url
+    #[arg(short = '1', long, required_unless_present = "explain", value_hint = ValueHint::FilePath)]
+    file1: Option<String>,
+
+    /// r2 fastq file or url, omit for single end reads
+    #[arg(short = '2', long, value_hint = ValueHint::FilePath)]
+    file2: Option<String>,
 
     /// r1 out fastq file
-    #[arg(short = 'o', long, default_value = "")]
+    #[arg(short = 'o', long, default_value = "", value_hint = ValueHint::AnyPath)]
     out1: String,
 
     /// r2 out fastq file
-    #[arg(short = 'w', long, default_value = "")]
+    #[arg(short = 'w', long, default_value = "", value_hint = ValueHint::AnyPath)]
     out2: String,
 
+    /// create the missing directories of the output files
+    #[arg(long)]
+    mkdir: bool,
+
+    /// check that the input files are complete and well formed before processing
+    #[arg(long)]
+    prescan: bool,
+
+    /// skip up to this many malformed records, logging each and dropping their mates, rather than failing
+    #[arg(long, conflicts_with_all = ["prescan", "repair", "geom_by"])]
+    skip_bad_records: Option<usize>,
+
+    /// print the lengths of the first 10000 reads of each input and warn if the geometry does not fit them
+    #[arg(long)]
+    sample_lengths: bool,
+
+    /// re-pair r1 and r2 by read name before processing, dropping reads without a mate
+    #[arg(long, requires = "file2")]
+    repair: bool,
+
+    /// write reads left without a mate by `--repair` to this fastq file
+    #[arg(long, requires = "repair", value_hint = ValueHint::AnyPath)]
+    repair_singletons: Option<String>,
+
+    /// read the outputs back once the run is done and fail it if the mates of a pair differ in name
+    #[arg(long, requires = "file2", conflicts_with_all = ["shard_size", "split", "cram"])]
+    verify_pairs: bool,
+
+    /// split each output into numbered shards of this many reads, e.g. `50M`, or bytes, e.g. `2GB`
+    #[arg(long, value_parser = shard_size)]
+    shard_size: Option<ShardSize>,
+
+    /// distribute reads round-robin across this many numbered outputs, keeping mates together
+    #[arg(long, conflicts_with = "shard_size", value_parser = clap::value_parser!(u64).range(1..))]
+    split: Option<u64>,
+
+    /// seed of every random choice, runs with the same seed give the same output
+    #[arg(long, default_value_t = DEFAULT_SEED)]
+    seed: u64,
+
     /// number of threads to use
     #[arg(short, long, default_value = "1")]
     threads: usize,
 
     #[arg(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
     additional: Vec<String>,
+
+    /// keep only reads whose header matches this regex, e.g. `^@A00.*:1:` for a single lane
+    #[arg(long, value_parser = name_pattern)]
+    filter_name: Option<Regex>,
+
+    /// drop reads whose header matches this regex
+    #[arg(long, value_parser = name_pattern)]
+    exclude_name: Option<Regex>,
+
+    /// drop reads from these tiles by the coordinates in their names, as `<lane>:<tile>` or `<tile>` for every lane
+    #[arg(long, value_delimiter = ',', value_parser = tile)]
+    exclude_tiles: Vec<Tile>,
+
+    /// drop reads whose UMI is a homopolymer
+    #[arg(long)]
+    umi_homopolymer: bool,
+
+    /// drop reads whose UMI contains an ambiguous base
+    #[arg(long)]
+    umi_ambiguous: bool,
+
+    /// drop reads whose UMI has a mean quality below this value
+    #[arg(long)]
+    umi_min_qual: Option<f64>,
+
+    /// trim runs of Gs from the end of biological reads, left by dark cycles of two colour chemistry
+    #[arg(long)]
+    trim_poly_g: bool,
+
+    /// drop reads whose biological read has a DUST score above this, e.g. `7`
+    #[arg(long)]
+    dust: Option<f64>,
+
+    /// flag failing UMIs in the read header instead of dropping the read
+    #[arg(long)]
+    umi_flag: bool,
+
+    /// drop duplicates sharing barcodes, UMIs and read prefix
+    #[arg(long)]
+    dedup: bool,
+
+    /// number of read bases used in the duplicate key
+    #[arg(long, default_value = "8")]
+    dedup_prefix: usize,
+
+    /// compare full duplicate keys using an on-disk table at this path
+    #[arg(long)]
+    dedup_exact: Option<String>,
+
+    /// flag duplicates in the read header instead of dropping them
+    #[arg(long)]
+    dedup_flag: bool,
+
+    /// estimate the fraction of duplicates sharing barcodes, UMIs and read prefix without dropping them
+    #[arg(long)]
+    estimate_duplication: bool,
+
+    /// write the number of distinct UMIs of each barcode to this tsv
+    #[arg(long, value_hint = ValueHint::AnyPath)]
+    umi_counts: Option<String>,
+
+    /// correct labeled UMIs of a defined set to the UMIs of a file, e.g. `umi=umis.txt`
+    #[arg(long, value_parser = segment_output, num_args = 1.., value_delimiter = ' ')]
+    whitelist: Vec<(String, String)>,
+
+    /// mismatches corrected by --whitelist
+    #[arg(long, requires = "whitelist", default_value = "1")]
+    whitelist_mismatch: usize,
+
+    /// cut ranged barcodes at the length closest to their whitelist, allowing the mismatches of `map_with_mismatch`, and print the lengths found
+    #[arg(long)]
+    len_from_whitelist: bool,
+
+    /// download the whitelist of a `preset:<name>` into the cache directory unless it is there, and give it to the geometry as `$0`
+    #[arg(long)]
+    fetch_whitelist: bool,
+
+    /// print the bases cut into barcodes, UMIs, anchors, discards and reads, and the bases written
+    #[arg(long, conflicts_with = "cram")]
+    count_bases: bool,
+
+    /// write the bases at each position of every named segment to this json file, warning about constant barcode or UMI positions
+    #[arg(long, conflicts_with = "cram", value_hint = ValueHint::AnyPath)]
+    composition: Option<String>,
+
+    /// write the reads of each combination of the barcodes labeled i7 and i5 to this tsv, reporting the reads whose pair is not in --sample-sheet
+    #[arg(long, requires = "sample_sheet", conflicts_with = "cram", value_hint = ValueHint::AnyPath)]
+    index_hopping: Option<String>,
+
+    /// csv of `<sample>,<i7>,<i5>` lines, the index pairs expected by --index-hopping
+    #[arg(long, requires = "index_hopping", value_hint = ValueHint::FilePath)]
+    sample_sheet: Option<String>,
+
+    /// detect the strand of single end long reads from the anchors of the geometry, allowing an edit in every 8 bases of an anchor. reads whose strand cannot be told fail, see --strict and --flag-failed
+    #[arg(long)]
+    long_read: bool,
+
+    /// trim the best matching primer of this fasta from the start of the read
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    primers: Option<String>,
+
+    /// mismatches allowed when matching a primer
+    #[arg(long, default_value = "2")]
+    primer_mismatch: usize,
+
+    /// write the primer of each read to this tsv instead of the read header
+    #[arg(long, value_hint = ValueHint::AnyPath)]
+    primer_tsv: Option<String>,
+
+    /// write named segments to their own fastq files, e.g. `cb=cb.fastq.gz umi=umi.fastq.gz`
+    #[arg(long, value_parser = segment_output, num_args = 1.., value_delimiter = ' ')]
+    segment_out: Vec<(String, String)>,
+
+    /// write a read built from segments and constant bases, e.g. `r3.fastq=<cb1><cb2>TT<umi>`
+    #[arg(long, value_parser = tech_read)]
+    tech_read: Option<TechRead>,
+
+    /// add named segments such as matched anchors to the read header as `name:bases`
+    #[arg(long, num_args = 1.., value_delimiter = ' ')]
+    capture: Vec<String>,
+
+    /// write the segments of `--capture` to this tsv instead of the read header
+    #[arg(long, requires = "capture", value_hint = ValueHint::AnyPath)]
+    capture_tsv: Option<String>,
+
+    /// add the plate well of each read's barcodes to the read header as `well:A01`, from a tsv of `<barcode>..\t<well>` lines
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    well_map: Option<String>,
+
+    /// write the well of each read to this tsv instead of the read header
+    #[arg(long, requires = "well_map", value_hint = ValueHint::AnyPath)]
+    well_tsv: Option<String>,
+
+    /// add each read's barcode and UMI to the read header both as read and corrected by a map, as `CR:Z:` and `CB:Z:`, `UR:Z:` and `UB:Z:`
+    #[arg(long, conflicts_with = "cram")]
+    raw_tags: bool,
+
+    /// write the tags of `--raw-tags` to this tsv instead of the read header
+    #[arg(long, requires = "raw_tags", value_hint = ValueHint::AnyPath)]
+    raw_tags_tsv: Option<String>,
+
+    /// csv of `<name>,<sequence>,<modality>` feature barcodes, such as the ADT and HTO tags of CITE-seq, sending the reads starting with one to the outputs of its modality
+    #[arg(long, requires_all = ["feature_segment", "modality_out"], conflicts_with_all = ["merge", "cram", "geom_by"], value_hint = ValueHint::FilePath)]
+    features: Option<String>,
+
+    /// the named segment of the geometry the feature barcodes of `--features` are read from
+    #[arg(long, requires = "features")]
+    feature_segment: Option<String>,
+
+    /// mismatches allowed when matching a feature barcode
+    #[arg(long, default_value = "1")]
+    feature_mismatch: usize,
+
+    /// the outputs of a modality of `--features`, e.g. `ADT=adt_1.fq,adt_2.fq`, other reads go to -o and -w
+    #[arg(long, requires = "features", value_parser = modality_output, num_args = 1.., value_delimiter = ' ')]
+    modality_out: Vec<(String, [String; 2])>,
+
+    /// name the outputs of a modality given to --modality-out without any, e.g. `{sample}_S{idx}_R{read}_001.fastq.gz` as bcl2fastq does, with the modality as `{sample}` and its number from 1 as `{idx}`
+    #[arg(long, requires = "features", value_parser = out_template)]
+    out_template: Option<String>,
+
+    /// send a kind of segment to the read, the header, a sidecar fastq or nowhere, or replace it by its hash in the header, e.g. `umi=header`
+    #[arg(long, value_parser = route)]
+    route: Vec<(Type, Route)>,
+
+    /// quality character given to padded bases
+    #[arg(long, value_parser = parse_qual, default_value = "!")]
+    pad_qual: u8,
+
+    /// check every record after each stage, e.g. that bases and qualities match in length
+    #[arg(long)]
+    check_invariants: bool,
+
+    /// check the segments of the first n reads against the geometry, failing at the first that does not follow it
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    verify_sample: usize,
+
+    /// abort at the first dropped read, naming it and the stage that dropped it
+    #[arg(long)]
+    strict: bool,
+
+    /// keep reads failing a check as they were read, tagged `QC:fail:<check>`, instead of dropping them
+    #[arg(long, conflicts_with = "strict")]
+    flag_failed: bool,
+
+    /// abort if fewer than this fraction of reads pass over a sliding window
+    #[arg(long)]
+    min_pass_rate: Option<f64>,
+
+    /// print the time spent in each stage when the run is done
+    #[arg(long)]
+    timings: bool,
+
+    /// search fixed sequences with this backend, antisequence, kmer or myers, or `label=backend` for one
+    #[arg(long, value_parser = matcher_choice, num_args = 1.., value_delimiter = ' ')]
+    matcher: Vec<MatcherChoice>,
+
+    /// uppercase soft-masked bases in the output, they are kept as they are otherwise
+    #[arg(long)]
+    uppercase: bool,
+
+    /// offset of the input qualities, `auto` tells it from the first reads of inputs that are regular files and refuses those that could be either
+    #[arg(long, value_parser = qual_offset, default_value = "auto")]
+    qual_offset: QualOffset,
+
+    /// merge overlapping pairs into this fastq file, unmerged pairs go to the r1 and r2 outputs
+    #[arg(long, value_hint = ValueHint::AnyPath)]
+    merge: Option<String>,
+
+    /// minimum overlap of merged pairs
+    #[arg(long, default_value = "10")]
+    merge_min_overlap: usize,
+
+    /// fraction of the overlap of merged pairs allowed to mismatch
+    #[arg(long, default_value = "0.2")]
+    merge_max_diff: f64,
+
+    /// print the stages the geometry and options compile to without reading any reads
+    #[arg(long)]
+    explain: bool,
+
+    /// print the plan of `--explain` as json
+    #[arg(long, requires = "explain")]
+    json: bool,
+
+    /// write the kind, exit code and message of a failure to this json file
+    #[arg(long, value_hint = ValueHint::AnyPath)]
+    error_json: Option<String>,
+
+    /// write how each fastq output was made to `<out>.provenance.json` next to it
+    #[arg(long, conflicts_with = "cram")]
+    provenance: bool,
+
+    /// send counts of the run to statsd://host:port or a pushgateway at http://host:port/path
+    #[arg(long, value_parser = metrics_target)]
+    metrics: Option<MetricsTarget>,
+
+    /// seconds between the metrics sent by `--metrics`
+    #[arg(long, requires = "metrics", default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    metrics_interval: u64,
+
+    /// draw live counts of the run in the terminal every second, with the reads reaching each stage
+    #[arg(long, conflicts_with = "metrics")]
+    tui: bool,
+
+    /// hash the input and output fastq files as they are read and written, md5 or sha256
+    #[arg(long, requires = "report", conflicts_with_all = ["repair", "shard_size", "split", "cram"], value_parser = checksum_algorithm)]
+    checksum: Option<Algorithm>,
+
+    /// write a json report of the run to this file, with the reads passed and failed, timings, QC metrics and the digests of `--checksum`, see `seqproc schema`
+    #[arg(long, conflicts_with = "cram", value_hint = ValueHint::AnyPath)]
+    report: Option<String>,
+
+    /// report only the first error in the geometry
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// write unaligned CRAM with CB and UB tags instead of fastq, needs samtools
+    #[arg(long, value_hint = ValueHint::AnyPath)]
+    cram: Option<String>,
+
+    /// reference fasta for the CRAM output, omit to write it reference free
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    cram_reference: Option<String>,
+
+    /// write the segments of each read to a parquet table instead of fastq
+    #[cfg(feature = "parquet")]
+    #[arg(long, conflicts_with_all = ["checksum", "provenance", "count_bases", "composition", "index_hopping", "geom_by"], value_hint = ValueHint::AnyPath)]
+    parquet: Option<String>,
+
+    /// read the input files ahead of processing with async io, for network filesystems
+    #[cfg(feature = "async-io")]
+    #[arg(long, conflicts_with = "geom_by")]
+    async_io: bool,
 }
 
-pub fn interpret(args: Args, compiled_data: CompiledData) {
+#[derive(Debug, Subcommand)]
+enum Cmd {
+    /// print a CWL or Galaxy descriptor of this command line
+    DescribeTool {
+        #[arg(long, value_enum)]
+        format: ToolFormat,
+    },
+    /// flag likely mistakes in a geometry and suggest rewrites, exiting with 1 if any is found
+    Lint {
+        #[arg(value_hint = ValueHint::FilePath)]
+        geom: String,
+    },
+    /// print the stages of a geometry as a Graphviz DOT or Mermaid diagram, each read's in a box of its own
+    Graph {
+        /// FGDL file, or a built-in geometry as `preset:<name>`
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        geom: String,
+
+        /// `dot` or `mermaid`
+        #[arg(long, value_parser = graph_format, default_value = "dot")]
+        format: GraphFormat,
+    },
+    /// print the JSON Schema of the `--report` file
+    Schema,
+    /// list the chemistries given as `preset:<name>`, built in and of your own
+    Chemistries,
+    /// print the geometry of reads described for another tool as FGDL
+    Convert {
+        /// `umitools` or `seqspec`, the format read
+        #[arg(long, value_parser = convert_format, required_unless_present = "to", conflicts_with = "to")]
+        from: Option<Format>,
+
+        /// `seqspec`, the format a geometry is written as
+        #[arg(long, value_parser = convert_format, requires = "geom")]
+        to: Option<Format>,
+
+        /// FGDL file written with --to, or a built-in geometry as `preset:<name>`
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        geom: Option<String>,
+
+        /// barcode pattern of read 1, as given to `umi_tools extract --bc-pattern`
+        #[arg(long, required_if_eq("from", "umitools"))]
+        pattern: Option<String>,
+
+        /// barcode pattern of read 2, as given to `--bc-pattern2`
+        #[arg(long)]
+        pattern2: Option<String>,
+
+        /// the reads are paired, read 2 is kept whole unless it has a pattern
+        #[arg(long)]
+        paired: bool,
+
+        /// seqspec YAML file read with --from seqspec
+        #[arg(long, required_if_eq("from", "seqspec"), value_hint = ValueHint::FilePath)]
+        spec: Option<String>,
+
+        /// modality of the seqspec read or written
+        #[arg(long, default_value = "rna")]
+        modality: String,
+
+        /// length of the reads of a seqspec written, unbounded pieces take what is left of it
+        #[arg(long, default_value = "150")]
+        read_len: usize,
+    },
+    /// run regression cases and compare their outputs with those expected, exiting with 1 if any differs
+    Test {
+        /// directory of a case, holding geom.fgdl, r1.fastq, expected_r1.fastq and their mates
+        #[arg(long, required = true, value_hint = ValueHint::DirPath)]
+        case: Vec<String>,
+
+        /// number of threads to use
+        #[arg(short, long, default_value = "1")]
+        threads: usize,
+    },
+    /// print a few reads sampled at random as the geometry cuts them, explaining those dropped
+    Preview {
+        /// FGDL file, or a built-in geometry as `preset:<name>`
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        geom: String,
+
+        /// r1 fastq file
+        #[arg(short = '1', long, value_hint = ValueHint::FilePath)]
+        file1: String,
+
+        /// r2 fastq file, omit for single end reads
+        #[arg(short = '2', long, value_hint = ValueHint::FilePath)]
+        file2: Option<String>,
+
+        /// number of reads sampled
+        #[arg(short, default_value_t = DEFAULT_READS)]
+        n: usize,
+
+        /// seed of the sample
+        #[arg(long, default_value_t = DEFAULT_SEED)]
+        seed: u64,
+
+        /// print without colors, as when not printing to a terminal or with NO_COLOR set
+        #[arg(long)]
+        no_color: bool,
+    },
+}
+
+// the geometry of a subcommand compiled, exiting if it cannot be
+fn load_geometry(geom: &str, chemistries: &[Chemistry]) -> (String, CompiledData) {
+    let compiled = read_geometry_of(geom, chemistries)
+        .map_err(|e| Failure::new(FailureKind::Io, format!("{geom}: {e}")))
+        .and_then(|text| {
+            let compiled = compile_geometry(&text)
+                .map_err(|e| Failure::new(FailureKind::Geometry, format!("{geom}: {e}")))?;
+            Ok((text, compiled))
+        });
+
+    match compiled {
+        Ok(compiled) => compiled,
+        Err(failure) => {
+            eprintln!("Error: {failure}");
+            exit_with(failure, None)
+        }
+    }
+}
+
+pub fn interpret(
+    args: Args,
+    mut compiled_data: CompiledData,
+    // the counts reported by the caller once the run is done
+    shared: InterpretOptions,
+    geometry: &str,
+) {
     let Args {
+        command: _,
         geom: _,
+        preset_file: _,
+        geom_by,
         file1,
         file2,
         out1,
         out2,
+        mkdir,
+        prescan: scan_first,
+        skip_bad_records,
+        sample_lengths,
+        repair,
+        repair_singletons,
+        verify_pairs,
+        shard_size,
+        split,
+        seed,
         threads,
         additional,
+        filter_name,
+        exclude_name,
+        exclude_tiles,
+        umi_homopolymer,
+        umi_ambiguous,
+        umi_min_qual,
+        trim_poly_g,
+        dust,
+        umi_flag,
+        dedup,
+        dedup_prefix,
+        dedup_exact,
+        dedup_flag,
+        estimate_duplication: _,
+        umi_counts: _,
+        whitelist,
+        whitelist_mismatch,
+        len_from_whitelist: _,
+        fetch_whitelist: _,
+        count_bases,
+        composition,
+        index_hopping,
+        sample_sheet,
+        long_read,
+        primers,
+        primer_mismatch,
+        primer_tsv,
+        segment_out,
+        tech_read,
+        capture,
+        capture_tsv,
+        well_map,
+        well_tsv,
+        raw_tags,
+        raw_tags_tsv,
+        features,
+        feature_segment,
+        feature_mismatch,
+        modality_out,
+        out_template,
+        route,
+        pad_qual,
+        check_invariants,
+        verify_sample,
+        strict,
+        flag_failed,
+        min_pass_rate,
+        timings: _,
+        matcher,
+        uppercase,
+        qual_offset,
+        merge,
+        merge_min_overlap,
+        merge_max_diff,
+        explain,
+        json: as_json,
+        error_json: _,
+        provenance,
+        metrics,
+        metrics_interval,
+        tui,
+        checksum,
+        report,
+        fail_fast: _,
+        cram,
+        cram_reference,
+        #[cfg(feature = "parquet")]
+        parquet,
+        #[cfg(feature = "async-io")]
+        async_io,
     } = args;
 
-    let read = iter_fastq2(file1, file2, 256)
-        .unwrap_or_else(|e| panic!("{e}"))
-        .boxed();
+    // the wall time of the run in the `--report`
+    let started = Instant::now();
+
+    // the labels of the other options are checked with them by `check_options`
+    let whitelisted = whitelist
+        .iter()
+        .map(|(label, _)| ("--whitelist", label.as_str()))
+        .collect::<Vec<_>>();
+    compiled_data
+        .check_labels(&whitelisted)
+        .unwrap_or_else(|e| fail(FailureKind::Geometry, e));
+
+    for (label, file) in whitelist {
+        compiled_data
+            .whitelist_umi(&label, file, whitelist_mismatch)
+            .unwrap_or_else(|e| fail(FailureKind::Geometry, e));
+    }
+
+    let counts = (metrics.is_some() || tui || report.is_some()).then(|| {
+        Arc::new(match &shared.timings {
+            Some(timings) => Metrics::with_timings(timings.clone()),
+            None => Metrics::default(),
+        })
+    });
+    let base_counts = count_bases.then(|| Arc::new(BaseCounts::default()));
+    let complexity =
+        (trim_poly_g || dust.is_some()).then(|| Arc::new(ComplexityFilter::new(trim_poly_g, dust)));
+    let composition = composition.map(|out| Arc::new(Composition::new(out)));
+    // modalities given without outputs are named by the template
+    let mut modality_out = modality_out;
+    if let Some(template) = &out_template {
+        let reads = compiled_data.output_reads();
+        for (i, (modality, outs)) in modality_out.iter_mut().enumerate() {
+            if outs[0].is_empty() {
+                *outs = template_outputs(template, modality, i + 1, reads)
+                    .unwrap_or_else(|e| fail(FailureKind::Geometry, e));
+            }
+        }
+    }
+    let features = features.zip(feature_segment).map(|(path, segment)| {
+        Arc::new(
+            Features::open(&path, segment, feature_mismatch, modality_out)
+                .unwrap_or_else(|e| io_failed(format!("{path}: {e}"))),
+        )
+    });
+    let index_hopping = index_hopping.zip(sample_sheet).map(|(out, sheet)| {
+        Arc::new(
+            IndexHopping::from_file(out, &sheet)
+                .unwrap_or_else(|e| io_failed(format!("{sheet}: {e}"))),
+        )
+    });
+
+    let mut options = InterpretOptions {
+        additional_args: additional,
+        umi_filter: UmiFilter {
+            homopolymer: umi_homopolymer,
+            ambiguous: umi_ambiguous,
+            min_mean_qual: umi_min_qual,
+            flag: umi_flag,
+        },
+        name_filter: NameFilter {
+            include: filter_name,
+            exclude: exclude_name,
+            tiles: exclude_tiles,
+        },
+        complexity: complexity.clone(),
+        dedup: if dedup || dedup_exact.is_some() {
+            Some(DedupConfig {
+                prefix_len: dedup_prefix,
+                mode: dedup_exact
+                    .map_or(DedupMode::Approximate, |path| DedupMode::Exact(path.into())),
+                flag: dedup_flag,
+            })
+        } else {
+            None
+        },
+        orient: long_read,
+        primers: primers.map(|fasta| PrimerConfig {
+            fasta,
+            max_mismatch: primer_mismatch,
+            tsv: primer_tsv,
+        }),
+        segment_out,
+        tech_read,
+        capture: (!capture.is_empty()).then_some(Capture {
+            names: capture,
+            tsv: capture_tsv,
+        }),
+        wells: well_map.map(|path| {
+            Arc::new(
+                WellMap::open(&path, well_tsv)
+                    .unwrap_or_else(|e| io_failed(format!("{path}: {e}"))),
+            )
+        }),
+        raw_tags: raw_tags.then_some(RawTags { tsv: raw_tags_tsv }),
+        features: features.clone(),
+        routes: route,
+        pad_qual: Some(pad_qual),
+        check_invariants,
+        verify_sample,
+        on_fail: if strict {
+            OnFail::Abort
+        } else if flag_failed {
+            OnFail::Flag
+        } else {
+            OnFail::Drop
+        },
+        min_pass_rate,
+        metrics: counts.clone(),
+        matchers: matcher,
+        uppercase,
+        phred64: qual_offset == QualOffset::Phred64,
+        shards: shard_size
+            .map(Sharding::Size)
+            .or(split.map(|n| Sharding::RoundRobin(n as usize))),
+        seed,
+        base_counts: base_counts.clone(),
+        composition: composition.clone(),
+        index_hopping: index_hopping.clone(),
+        merge: merge.map(|out| MergeConfig {
+            min_overlap: merge_min_overlap,
+            max_diff: merge_max_diff,
+            out,
+        }),
+        ..shared.clone()
+    };
 
-    let read = compiled_data.interpret(read, out1, out2, additional);
+    compiled_data
+        .check_options(&options)
+        .unwrap_or_else(|e| fail(FailureKind::Geometry, e));
 
-    read.run_with_threads(threads)
+    if verify_pairs && compiled_data.output_reads() != 2 {
+        fail(
+            FailureKind::Geometry,
+            "Only pairs of output reads can be verified",
+        );
+    }
+
+    // the outputs are read back, which stdout, pipes and discarded ones cannot be
+    let readable =
+        |out: &str| !out.is_empty() && !std::fs::metadata(out).is_ok_and(|meta| !meta.is_file());
+    if verify_pairs && !(readable(&out1) && readable(&out2)) {
+        io_failed("--verify-pairs reads the outputs back, give --out1 and --out2 as files");
+    }
+
+    if let Some(by) = &geom_by {
+        by.check(&compiled_data)
+            .unwrap_or_else(|e| fail(FailureKind::Geometry, format!("--geom-by: {e}")));
+    }
+
+    // compiled before any read is split, in the order of the patterns
+    let pooled_geometries = geom_by.iter().flat_map(|by| &by.keys).map(|(_, path, _)| {
+        let text = fs::read_to_string(path).unwrap_or_else(|e| io_failed(format!("{path}: {e}")));
+        let compiled = compile_geometry(&text)
+            .unwrap_or_else(|e| fail(FailureKind::Geometry, format!("{path}: {e}")));
+
+        if compiled.output_reads() != compiled_data.output_reads() {
+            fail(
+                FailureKind::Geometry,
+                format!(
+                    "{path} writes {} reads, the geometry of --geom writes {}",
+                    compiled.output_reads(),
+                    compiled_data.output_reads()
+                ),
+            );
+        }
+
+        compiled
+    });
+    let pooled_geometries = pooled_geometries.collect::<Vec<_>>();
+
+    if explain {
+        let stages = plan(&compiled_data, &options);
+        let plan = if as_json {
+            json(&stages)
+        } else {
+            text(&stages)
+        };
+
+        return print!("{plan}");
+    }
+
+    let file1 = file1.unwrap();
+    let (file1_arg, file2_arg) = (file1.clone(), file2.clone());
+
+    // streamed inputs can only be read once, by the pipeline
+    let remote_inputs = remote(&file1).is_some() || file2.as_deref().and_then(remote).is_some();
+    if remote_inputs && (scan_first || repair || sample_lengths || geom_by.is_some()) {
+        io_failed("--prescan, --repair, --sample-lengths and --geom-by need local input files");
+    }
+
+    // told before the inputs are read through pipes, streamed ones and those
+    // which are not regular files are taken to end their lines in `\n`
+    let crlf_inputs = std::iter::once(&file1)
+        .chain(&file2)
+        .map(|file| {
+            !is_compressed(file) && crlf(file).unwrap_or_else(|e| io_failed(format!("{file}: {e}")))
+        })
+        .collect::<Vec<_>>();
+
+    let mut outputs = vec![out1.as_str(), out2.as_str()];
+    outputs.extend(options.merge.as_ref().map(|config| config.out.as_str()));
+    outputs.extend(cram.as_deref());
+    for (_, outs) in options.features.iter().flat_map(|f| &f.modalities) {
+        outputs.extend(outs.iter().map(String::as_str));
+    }
+    output_dirs(&outputs, mkdir).unwrap_or_else(|e| io_failed(e));
+
+    // mates out of sync are expected when they are about to be repaired
+    if scan_first {
+        let records = prescan(&file1, file2.as_deref(), repair).unwrap_or_else(|e| io_failed(e));
+
+        eprintln!("Prescan found {records} well formed records");
+    }
+
+    if sample_lengths {
+        for (i, file) in std::iter::once(&file1).chain(&file2).enumerate() {
+            let sample = LengthSample::read(file, SAMPLE_SIZE).unwrap_or_else(|e| io_failed(e));
+            let (min, max) = compiled_data.read_lengths(i);
+
+            eprintln!("{}", sample.report());
+            if let Some(warning) = sample.check(min, max) {
+                eprintln!("Warning: {warning}");
+            }
+        }
+    }
+
+    // streamed inputs and pipes can only be read once, their qualities are
+    // taken as phred+33
+    let regular_inputs = std::iter::once(&file1)
+        .chain(&file2)
+        .all(|file| regular(file));
+    if qual_offset == QualOffset::Auto && regular_inputs {
+        let offsets = std::iter::once(&file1)
+            .chain(&file2)
+            .map(|file| quality_offset(file).unwrap_or_else(|e| io_failed(e)))
+            .collect::<Vec<_>>();
+
+        if offsets.iter().any(|offset| *offset != offsets[0]) {
+            io_failed("the input files have qualities of different offsets");
+        }
+
+        options.phred64 = offsets[0] == QualOffset::Phred64;
+        if options.phred64 {
+            eprintln!("Qualities are phred+64, converting them to phred+33");
+        }
+    }
+
+    // the repaired copies are removed when this is dropped at the end of the run
+    let repaired = repair.then(|| {
+        let repaired = RepairedFiles::create(
+            &file1,
+            file2.as_deref().unwrap(),
+            repair_singletons.as_deref(),
+        )
+        .unwrap_or_else(|e| io_failed(e));
+
+        eprintln!(
+            "Repaired {} pairs, {} reads had no mate",
+            repaired.stats.pairs, repaired.stats.singletons
+        );
+
+        repaired
+    });
+
+    let (file1, file2) = match &repaired {
+        Some(repaired) => (
+            repaired.file1.display().to_string(),
+            Some(repaired.file2.display().to_string()),
+        ),
+        None => (file1, file2),
+    };
+
+    // the parts are removed when this is dropped at the end of the run
+    let pooled = geom_by.map(|by| {
+        let pooled = PooledFiles::create(&file1, file2.as_deref(), &by, &compiled_data)
+            .unwrap_or_else(|e| io_failed(e));
+
+        for ((pattern, _, on), records) in by.keys.iter().zip(&pooled.records) {
+            match on {
+                KeyOn::Header => eprintln!("{records} reads matching `{pattern}`"),
+                KeyOn::Segment(label) => {
+                    eprintln!("{records} reads matching `seg:{label}={pattern}`")
+                }
+            }
+        }
+        eprintln!(
+            "{} reads matching no pattern",
+            pooled.records[by.keys.len()]
+        );
+
+        pooled
+    });
+
+    // the urls are streamed through pipes, which are removed when this is dropped
+    let streamed = remote_inputs.then(|| {
+        let files = std::iter::once(file1.clone()).chain(file2.clone());
+        RemoteInputs::open(files.collect()).unwrap_or_else(|e| io_failed(e))
+    });
+
+    let (file1, file2) = match &streamed {
+        Some(streamed) => (streamed.paths[0].clone(), streamed.paths.get(1).cloned()),
+        None => (file1, file2),
+    };
+
+    let outputs = match compiled_data.output_reads() {
+        1 => [out1, String::new()],
+        _ => [out1, out2],
+    };
+    let mut provenance = provenance.then(|| Provenance::new(geometry, std::env::args().collect()));
+    let sidecars = provenance.as_ref().map(|_| outputs.clone());
+
+    // written beside the outputs and renamed into place once the run is done,
+    // see `sink::atomic`. shards are written in place
+    let atomic = options
+        .shards
+        .is_none()
+        .then(|| AtomicOutputs::new(&outputs));
+    let (outputs, final_outputs) = match &atomic {
+        Some(atomic) => ([atomic.paths[0].clone(), atomic.paths[1].clone()], outputs),
+        None => (outputs.clone(), outputs),
+    };
+    let written = outputs.clone();
+
+    // a last sample is sent when this is dropped at the end of the run
+    let (target, interval) = if tui {
+        (Some(MetricsTarget::Terminal), 1)
+    } else {
+        (metrics, metrics_interval)
+    };
+    let _emitter = target.zip(counts.clone()).map(|(target, counts)| {
+        let files = outputs.iter().filter(|out| !out.is_empty()).cloned();
+        Emitter::start(
+            target,
+            counts,
+            files.collect(),
+            Duration::from_secs(interval),
+        )
+    });
+
+    // the files are hashed through pipes as the pipeline reads and writes them
+    let inputs = std::iter::once(file1.clone()).chain(file2.clone());
+    let checksums = checksum.map(|algorithm| {
+        Checksums::start(algorithm, &inputs.collect::<Vec<_>>(), &outputs)
+            .unwrap_or_else(|e| io_failed(e))
+    });
+
+    let (file1, file2, out1, out2) = match &checksums {
+        Some(checksums) => (
+            checksums.inputs[0].clone(),
+            checksums.inputs.get(1).cloned(),
+            checksums.outputs[0].clone(),
+            checksums.outputs[1].clone(),
+        ),
+        None => {
+            let [out1, out2] = outputs;
+            (file1, file2, out1, out2)
+        }
+    };
+
+    // the pipes are read in place of the files, and removed when this is dropped
+    #[cfg(feature = "async-io")]
+    let prefetched = async_io.then(|| {
+        let files = std::iter::once(file1.clone()).chain(file2.clone());
+        Prefetched::start(&files.collect::<Vec<_>>()).unwrap_or_else(|e| io_failed(e))
+    });
+
+    #[cfg(feature = "async-io")]
+    let (file1, file2) = match &prefetched {
+        Some(prefetched) => (
+            prefetched.paths[0].clone(),
+            prefetched.paths.get(1).cloned(),
+        ),
+        None => (file1, file2),
+    };
+
+    // the well formed records reach the pipeline through pipes, removed when
+    // this is dropped
+    let salvaged = skip_bad_records.map(|max_bad| {
+        let files = std::iter::once(file1.clone()).chain(file2.clone());
+        Salvaged::open(files.collect(), max_bad).unwrap_or_else(|e| io_failed(e))
+    });
+
+    let (file1, file2) = match &salvaged {
+        Some(salvaged) => (salvaged.paths[0].clone(), salvaged.paths.get(1).cloned()),
+        None => (file1, file2),
+    };
+
+    // compressed inputs reach the pipeline decompressed and checked through
+    // pipes, removed when this is dropped. the pooled parts are not compressed
+    let decompressed = (pooled.is_none() && salvaged.is_none()).then(|| {
+        let files = std::iter::once(file1.clone()).chain(file2.clone());
+        Decompressed::open(files.collect(), &crlf_inputs).unwrap_or_else(|e| io_failed(e))
+    });
+
+    let (file1, file2) = match &decompressed {
+        Some(decompressed) => (
+            decompressed.paths[0].clone(),
+            decompressed.paths.get(1).cloned(),
+        ),
+        None => (file1, file2),
+    };
+
+    // the inputs end early when their reader fails, which the pipeline only
+    // learns once they are joined, before anything is kept of the run
+    let finish_inputs = move || {
+        let finished = decompressed
+            .map_or(Ok(()), Decompressed::finish)
+            .and(salvaged.map_or(Ok(()), Salvaged::finish));
+        #[cfg(feature = "async-io")]
+        let finished = finished.and(prefetched.map_or(Ok(()), Prefetched::finish));
+        finished.and(streamed.map_or(Ok(()), RemoteInputs::finish))
+    };
+
+    let read = if let Some(file2) = file2 {
+        iter_fastq2(file1, file2, 256)
+            .unwrap_or_else(|e| io_failed(e))
+            .boxed()
+    } else {
+        iter_fastq1(file1, 256)
+            .unwrap_or_else(|e| io_failed(e))
+            .boxed()
+    };
+
+    if let Some(path) = cram {
+        let writer = Arc::new(
+            CramWriter::create(path, cram_reference.as_deref().map(Path::new))
+                .unwrap_or_else(|e| io_failed(e)),
+        );
+        let cram = writer.clone();
+
+        compiled_data
+            .for_each_record(read, options, move |record| {
+                cram.push(record).unwrap_or_else(|e| io_failed(e))
+            })
+            .run_with_threads(threads);
+        finish_inputs().unwrap_or_else(|e| io_failed(e));
+
+        return Arc::into_inner(writer)
+            .unwrap()
+            .finish()
+            .unwrap_or_else(|e| io_failed(e));
+    }
+
+    #[cfg(feature = "parquet")]
+    if let Some(path) = parquet {
+        let writer = Arc::new(SegmentWriter::create(path).unwrap_or_else(|e| io_failed(e)));
+        let table = writer.clone();
+
+        compiled_data
+            .for_each_record(read, options, move |record| {
+                table.push(record).unwrap_or_else(|e| io_failed(e))
+            })
+            .run_with_threads(threads);
+        finish_inputs().unwrap_or_else(|e| io_failed(e));
+
+        return Arc::into_inner(writer)
+            .unwrap()
+            .finish()
+            .unwrap_or_else(|e| io_failed(e));
+    }
+
+    match &pooled {
+        Some(pooled) => {
+            let geometries = pooled_geometries
+                .iter()
+                .chain(std::iter::once(&compiled_data));
+
+            run_pooled(pooled, geometries.collect(), [out1, out2], options, threads);
+        }
+        None => compiled_data
+            .interpret(read, out1, out2, options)
+            .run_with_threads(threads),
+    }
+
+    // the outputs of a run which lost part of its inputs are removed
+    if let Err(e) = finish_inputs() {
+        drop(atomic);
+        io_failed(e);
+    }
+
+    // the metrics of the run go to the `--report` if there is one
+    let mut qc = Vec::new();
+    let mut summary = |metrics: Vec<(String, f64)>, text: String| {
+        if report.is_some() {
+            qc.extend(metrics);
+        } else {
+            eprint!("{text}");
+        }
+    };
+
+    if let Some(counts) = base_counts {
+        summary(counts.metrics(), counts.report());
+    }
+
+    if let Some(filter) = complexity {
+        summary(filter.metrics(), filter.report());
+    }
+
+    if let Some(composition) = composition {
+        for warning in composition.warnings() {
+            eprintln!("Warning: {warning}");
+        }
+        composition.write().unwrap_or_else(|e| io_failed(e));
+    }
+
+    if let Some(features) = features {
+        summary(features.metrics(), features.report());
+    }
+
+    if let Some(hopping) = index_hopping {
+        summary(hopping.metrics(), hopping.report());
+        hopping.write().unwrap_or_else(|e| io_failed(e));
+    }
+
+    // before the outputs are renamed into place, a failed check leaves none
+    if verify_pairs {
+        let open = |out: &String| {
+            FastqReader::open(out).unwrap_or_else(|e| io_failed(format!("{out}: {e}")))
+        };
+        let (mut r1, mut r2) = (open(&written[0]), open(&written[1]));
+
+        match verify(&mut r1, &mut r2) {
+            Ok(pairs) => summary(
+                vec![("verified_pairs".to_string(), pairs as f64)],
+                format!("Verified the mates of {pairs} pairs\n"),
+            ),
+            Err(e) => io_failed(format!("the outputs are out of step, {e}")),
+        }
+    }
+
+    if let Some(path) = report {
+        let (reads, passed) = counts.map_or((0, 0), |counts| counts.counts());
+        let mut report = report::Report {
+            reads,
+            passed,
+            seconds: started.elapsed().as_secs_f64(),
+            ..report::Report::default()
+        };
+
+        if let Some(timings) = &shared.timings {
+            report.stages = timings
+                .stages()
+                .into_iter()
+                .map(|(stage, time, reads)| StageTiming {
+                    stage: stage.to_string(),
+                    seconds: time.as_secs_f64(),
+                    reads,
+                })
+                .collect();
+        }
+
+        if reads > 0 {
+            report
+                .qc
+                .push(("pass_rate".to_string(), passed as f64 / reads as f64));
+        }
+        report.qc.extend(qc);
+        // printed once the run is done when there is no report
+        report.qc.extend(
+            shared
+                .duplication
+                .iter()
+                .flat_map(|estimate| estimate.metrics()),
+        );
+        report.qc.extend(
+            shared
+                .whitelist_lengths
+                .iter()
+                .flat_map(|lengths| lengths.metrics()),
+        );
+
+        if let Some(checksums) = checksums {
+            let mut digests = checksums.finish().unwrap_or_else(|e| io_failed(e));
+
+            // streamed inputs are reported by the names they were given
+            let names = std::iter::once(file1_arg).chain(file2_arg);
+            for ((file, _), name) in digests.inputs.iter_mut().zip(names) {
+                *file = name;
+            }
+
+            // and outputs by theirs, rather than those of their temporary files
+            let names = final_outputs.iter().filter(|out| !out.is_empty());
+            for ((file, _), name) in digests.outputs.iter_mut().zip(names) {
+                *file = name.clone();
+            }
+
+            report.digests = Some(digests);
+        }
+
+        std::fs::write(&path, report.json()).unwrap_or_else(|e| io_failed(e));
+    }
+
+    if let Some(atomic) = atomic {
+        atomic.commit().unwrap_or_else(|e| io_failed(e));
+    }
+
+    if let (Some(provenance), Some(outputs)) = (provenance.as_mut(), sidecars) {
+        provenance.finish(&outputs).unwrap_or_else(|e| io_failed(e));
+    }
+}
+
+// each part of a pooled run through its own geometry, appending its outputs
+// to those given
+fn run_pooled(
+    pooled: &PooledFiles,
+    geometries: Vec<&CompiledData>,
+    outputs: [String; 2],
+    options: InterpretOptions,
+    threads: usize,
+) {
+    let dir = std::env::temp_dir();
+    let id = process::id();
+
+    let mut files = outputs
+        .iter()
+        .map(|out| {
+            let create =
+                |out| File::create(out).unwrap_or_else(|e| io_failed(format!("{out}: {e}")));
+            (!out.is_empty()).then(|| create(out))
+        })
+        .collect::<Vec<_>>();
+
+    for (i, (compiled, (part1, part2))) in geometries.into_iter().zip(&pooled.parts).enumerate() {
+        if pooled.records[i] == 0 {
+            continue;
+        }
+
+        // named after the outputs, so compressed outputs are compressed alike
+        let [part_out1, part_out2] = outputs
+            .clone()
+            .map(|out| match Path::new(&out).file_name() {
+                Some(name) => dir
+                    .join(format!("seqproc-{id}-out{i}-{}", name.to_string_lossy()))
+                    .display()
+                    .to_string(),
+                None => String::new(),
+            });
+
+        let (part1, part2) = (
+            part1.display().to_string(),
+            part2.as_ref().map(|p| p.display().to_string()),
+        );
+        let read = if let Some(part2) = part2 {
+            iter_fastq2(part1, part2, 256)
+                .unwrap_or_else(|e| io_failed(e))
+                .boxed()
+        } else {
+            iter_fastq1(part1, 256)
+                .unwrap_or_else(|e| io_failed(e))
+                .boxed()
+        };
+
+        compiled
+            .interpret(read, part_out1.clone(), part_out2.clone(), options.clone())
+            .run_with_threads(threads);
+
+        for (file, part_out) in files.iter_mut().zip([part_out1, part_out2]) {
+            if let Some(file) = file {
+                let mut part = File::open(&part_out).unwrap_or_else(|e| io_failed(e));
+                io::copy(&mut part, file).unwrap_or_else(|e| io_failed(e));
+                fs::remove_file(&part_out).unwrap_or_else(|e| io_failed(e));
+            }
+        }
+    }
+}
+
+// exit with the code of `failure`, also written to `error_json` if given
+fn exit_with(failure: Failure, error_json: Option<&str>) -> ! {
+    if let Some(path) = error_json {
+        if let Err(e) = std::fs::write(path, failure.json()) {
+            eprintln!("Could not write {path}: {e}");
+        }
+    }
+
+    process::exit(failure.kind.exit_code())
+}
+
+// the chemistries of the config directory and `--preset-file`, exiting if
+// one cannot be read
+fn load_chemistries(files: &[String]) -> Vec<Chemistry> {
+    chemistries(config_dir().as_deref(), files).unwrap_or_else(|e| {
+        let failure = Failure::new(FailureKind::Io, e);
+        eprintln!("Error: {failure}");
+        exit_with(failure, None)
+    })
 }
 
 fn main() {
-    let args: Args = Args::parse();
+    let mut args: Args = Args::parse();
+    let chemistries = load_chemistries(&args.preset_file);
+
+    // the options of a preset come first, those given take precedence
+    if let (None, Some(geom)) = (&args.command, &args.geom) {
+        if let Ok(Some(chemistry)) = find_chemistry(geom, &chemistries) {
+            if !chemistry.options.is_empty() {
+                let mut argv = std::env::args_os().collect::<Vec<_>>();
+                let options = chemistry.options.iter().map(Into::into);
+                argv.splice(1..1, options);
+
+                args = Args::parse_from(argv);
+            }
+        }
+    }
+
+    if let (true, Some(geom)) = (args.fetch_whitelist, &args.geom) {
+        let whitelist = match (find_chemistry(geom, &chemistries), cache_dir()) {
+            (Ok(Some(chemistry)), Some(dir)) => fetch_whitelist(chemistry, &dir),
+            (Ok(Some(_)), None) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no cache directory, set XDG_CACHE_HOME or HOME",
+            )),
+            (Ok(None), _) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--fetch-whitelist needs a geometry given as `preset:<name>`",
+            )),
+            (Err(e), _) => Err(e),
+        };
+
+        match whitelist {
+            Ok(path) => args
+                .additional
+                .insert(0, path.to_string_lossy().into_owned()),
+            Err(e) => {
+                let failure = Failure::new(FailureKind::Io, e);
+                eprintln!("Error: {failure}");
+                exit_with(failure, None)
+            }
+        }
+    }
+
+    if let Some(Cmd::Chemistries) = args.command {
+        for chemistry in &chemistries {
+            println!("{}: {}", chemistry.name, chemistry.description);
+            if let Some(whitelist) = &chemistry.whitelist {
+                println!("  whitelist: {whitelist}");
+            }
+            if let Some(sha256) = &chemistry.whitelist_sha256 {
+                println!("  whitelist sha256: {sha256}");
+            }
+            if !chemistry.options.is_empty() {
+                println!("  options: {}", chemistry.options.join(" "));
+            }
+            if let Some(file) = &chemistry.file {
+                println!("  from {}", file.display());
+            }
+        }
+        return;
+    }
+
+    if let Some(Cmd::DescribeTool { format }) = args.command {
+        return print!("{}", describe(&Args::command(), format));
+    }
+
+    if let Some(Cmd::Schema) = args.command {
+        return print!("{}", schema());
+    }
+
+    if let Some(Cmd::Convert {
+        from,
+        to,
+        geom,
+        pattern,
+        pattern2,
+        paired,
+        spec,
+        modality,
+        read_len,
+    }) = &args.command
+    {
+        if let Some(to) = to {
+            let geom = geom.as_deref().unwrap_or_default();
+            let (_, compiled) = load_geometry(geom, &chemistries);
+
+            match to {
+                Format::Seqspec => {
+                    let name = Path::new(geom)
+                        .file_stem()
+                        .map_or(geom.into(), |stem| stem.to_string_lossy());
+
+                    return print!("{}", write_seqspec(&compiled, &name, modality, *read_len));
+                }
+                Format::UmiTools => {
+                    let failure = Failure::new(
+                        FailureKind::Geometry,
+                        "a geometry cannot be written as a umi_tools pattern",
+                    );
+                    eprintln!("Error: {failure}");
+                    exit_with(failure, None)
+                }
+            }
+        }
+
+        let (source, converted) = match from {
+            Some(Format::Seqspec) => {
+                let spec = spec.as_deref().unwrap_or_default();
+                let converted = fs::read_to_string(spec)
+                    .map_err(|e| e.to_string())
+                    .and_then(|yaml| read_seqspec(&yaml, modality));
+                (spec, converted)
+            }
+            _ => {
+                let pattern = pattern.as_deref().unwrap_or_default();
+                let converted = umitools::geometry(pattern, pattern2.as_deref(), *paired);
+                (pattern, converted)
+            }
+        };
+        let geometry = converted
+            .and_then(|geometry| match compile_geometry(&geometry) {
+                Ok(_) => Ok(geometry),
+                Err(e) => Err(format!("{}does not compile: {e}", geometry)),
+            })
+            .unwrap_or_else(|e| {
+                let failure = Failure::new(FailureKind::Geometry, format!("{source}: {e}"));
+                eprintln!("Error: {failure}");
+                exit_with(failure, None)
+            });
+
+        return print!("{geometry}");
+    }
+
+    if let Some(Cmd::Graph { geom, format }) = &args.command {
+        let (_, compiled) = load_geometry(geom, &chemistries);
+        let stages = plan(&compiled, &InterpretOptions::default());
+
+        return print!("{}", graph(&stages, *format));
+    }
+
+    if let Some(Cmd::Test { case, threads }) = &args.command {
+        let mut failed = 0;
+        for dir in case {
+            let res = Case::open(Path::new(dir))
+                .map_err(|e| e.to_string())
+                .and_then(|case| case.run(*threads));
+
+            match res {
+                Ok(diffs) if diffs.is_empty() => println!("case {dir} ... ok"),
+                Ok(diffs) => {
+                    failed += 1;
+                    println!("case {dir} ... FAILED");
+                    for d in diffs {
+                        print!("{d}");
+                    }
+                }
+                Err(e) => {
+                    failed += 1;
+                    println!("case {dir} ... FAILED\n{e}");
+                }
+            }
+        }
+        println!("{} passed, {failed} failed", case.len() - failed);
+
+        process::exit(if failed == 0 { 0 } else { 1 });
+    }
+
+    if let Some(Cmd::Preview {
+        geom,
+        file1,
+        file2,
+        n,
+        seed,
+        no_color,
+    }) = &args.command
+    {
+        let (_, compiled) = load_geometry(geom, &chemistries);
+        let color = !no_color
+            && io::stdout().is_terminal()
+            && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+
+        let res = std::iter::once(file1)
+            .chain(file2)
+            .map(FastqReader::open)
+            .collect::<io::Result<Vec<_>>>()
+            .and_then(|mut readers| sample(&mut readers, *n, *seed))
+            .map_err(|e| Failure::new(FailureKind::Io, e))
+            .and_then(|(records, total)| {
+                Preview::new(&compiled, color)
+                    .show(&records, total)
+                    .map_err(|e| Failure::new(FailureKind::Geometry, format!("{geom}: {e}")))
+            });
+
+        match res {
+            Ok(preview) => return print!("{preview}"),
+            Err(failure) => {
+                eprintln!("Error: {failure}");
+                exit_with(failure, None)
+            }
+        }
+    }
+
+    if let Some(Cmd::Lint { geom }) = &args.command {
+        let (text, compiled) = load_geometry(geom, &chemistries);
+
+        let lints = lint(&compiled);
+        for l in &lints {
+            let report = Report::build(ReportKind::Warning, (), l.span.start)
+                .with_message(&l.msg)
+                .with_label(
+                    Label::new(l.span.clone())
+                        .with_message(format!("{}", (&l.msg).fg(Color::Yellow)))
+                        .with_color(Color::Yellow),
+                );
+
+            match &l.fix {
+                Some(fix) => report.with_help(fix),
+                None => report,
+            }
+            .finish()
+            .eprint(Source::from(&text))
+            .unwrap();
+        }
 
-    let geom = std::fs::read_to_string(args.geom.clone()).unwrap();
+        process::exit(if lints.is_empty() { 0 } else { 1 });
+    }
 
-    let (tokens, mut errs) = lexer::lexer().parse_recovery(geom.clone());
+    let error_json = args.error_json.clone();
 
-    let parse_errs = if let Some(tokens) = &tokens {
+    // the first failure on any thread, a worker thread panicking only
+    // reaches this one as a generic message
+    let failure: Arc<Mutex<Option<Failure>>> = Arc::default();
+    let first = failure.clone();
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let failure = Failure::from_panic(info.payload());
+
+        if failure.kind == FailureKind::Internal {
+            default_hook(info);
+        } else {
+            eprintln!("Error: {failure}");
+        }
+
+        first.lock().unwrap().get_or_insert(failure);
+    }));
+
+    let geom_path = args.geom.clone().unwrap();
+
+    let geom = read_geometry_of(&geom_path, &chemistries).unwrap_or_else(|e| {
+        exit_with(
+            Failure::new(FailureKind::Io, format!("{geom_path}: {e}")),
+            error_json.as_deref(),
+        )
+    });
+
+    let fail_fast = args.fail_fast;
+
+    // the stages of `--tui` are those timed
+    let timings =
+        (args.timings || args.tui || args.report.is_some()).then(|| Arc::new(Timings::default()));
+    let print_timings = args.timings;
+
+    let explain = args.explain;
+    let reporting = args.report.is_some();
+
+    let umi_counts = args
+        .umi_counts
+        .clone()
+        .map(|out| Arc::new(UmiCounts::new(out)));
+
+    let duplication = args
+        .estimate_duplication
+        .then(|| Arc::new(DuplicationEstimate::new(args.dedup_prefix)));
+
+    let whitelist_lengths = args
+        .len_from_whitelist
+        .then(|| Arc::new(WhitelistLengths::default()));
+
+    let (tokens, lex_errs) = lexer::lexer().parse_recovery(geom.clone());
+
+    let mut errs = lex_errs
+        .into_iter()
+        .map(|e| e.map(|c| c.to_string()))
+        .collect::<Vec<_>>();
+
+    if let Some(tokens) = &tokens {
         let (ast, parse_errs) = parser().parse_recovery(Stream::from_iter(
             tokens.len()..tokens.len() + 1,
             tokens.clone().into_iter(),
         ));
 
-        if let Some((ast, _)) = &ast {
+        errs.extend(parse_errs.into_iter().map(|e| e.map(|tok| tok.to_string())));
+
+        // a recovered geometry is only compiled if nothing had to be skipped
+        if let (Some((ast, _)), true) = (&ast, errs.is_empty()) {
             let res = compile(ast.clone());
 
             if let Err(e) = res {
                 errs.push(Simple::custom(e.span, e.msg));
             } else {
-                interpret(args, res.ok().unwrap());
+                let compiled = res.ok().unwrap();
+
+                // warnings go to stderr and the run goes on
+                for warning in &compiled.warnings {
+                    Report::build(ReportKind::Warning, (), warning.span.start)
+                        .with_message(&warning.msg)
+                        .with_label(
+                            Label::new(warning.span.clone())
+                                .with_message(format!("{}", (&warning.msg).fg(Color::Yellow)))
+                                .with_color(Color::Yellow),
+                        )
+                        .finish()
+                        .eprint(Source::from(&geom))
+                        .unwrap();
+                }
+
+                let run = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let shared = InterpretOptions {
+                        timings: timings.clone(),
+                        umi_counts: umi_counts.clone(),
+                        duplication: duplication.clone(),
+                        whitelist_lengths: whitelist_lengths.clone(),
+                        ..InterpretOptions::default()
+                    };
+
+                    interpret(args, compiled, shared, &geom)
+                }));
+
+                if let Err(payload) = run {
+                    let failure = failure.lock().unwrap().take();
+                    exit_with(
+                        failure.unwrap_or_else(|| Failure::from_panic(&*payload)),
+                        error_json.as_deref(),
+                    );
+                }
+
+                if let Some(timings) = timings.filter(|_| print_timings) {
+                    eprint!("{}", timings.report());
+                }
+
+                // nothing was counted if the plan was only explained
+                if let Some(counts) = umi_counts.filter(|_| !explain) {
+                    counts.write().unwrap_or_else(|e| io_failed(e));
+                }
+
+                // the estimates are in the `--report` if there is one
+                if let Some(estimate) = duplication.filter(|_| !explain && !reporting) {
+                    eprintln!(
+                        "Estimated duplication rate {:.1}% of {} reads",
+                        estimate.rate() * 100.0,
+                        estimate.reads()
+                    );
+                }
+
+                if let Some(lengths) = whitelist_lengths.filter(|_| !explain && !reporting) {
+                    eprint!("{}", lengths.report());
+                }
             }
-        };
+        }
+    }
 
-        parse_errs
-    } else {
-        Vec::new()
-    };
+    // error recovery, report every error in the order they appear in the geometry
+    errs.sort_by_key(|e| e.span().start);
 
-    // error recovery
-    errs.into_iter()
-        .map(|e| e.map(|c| c.to_string()))
-        .chain(parse_errs.into_iter().map(|e| e.map(|tok| tok.to_string())))
-        .for_each(|e| {
-            let report = Report::build(ReportKind::Error, (), e.span().start);
+    if fail_fast {
+        errs.truncate(1);
+    }
 
-            let report = match e.reason() {
-                chumsky::error::SimpleReason::Custom(msg) => report.with_message(msg).with_label(
+    let geometry_failure = (!errs.is_empty()).then(|| {
+        let messages = errs.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        Failure::new(FailureKind::Geometry, messages.join("; "))
+    });
+
+    errs.into_iter().for_each(|e| {
+        let report = Report::build(ReportKind::Error, (), e.span().start);
+
+        let report = match e.reason() {
+            chumsky::error::SimpleReason::Custom(msg) => report.with_message(msg).with_label(
+                Label::new(e.span())
+                    .with_message(format!("{}", msg.fg(Color::Red)))
+                    .with_color(Color::Red),
+            ),
+            chumsky::error::SimpleReason::Unclosed { span, delimiter } => report
+                .with_message(format!(
+                    "Unclosed delimiter {}",
+                    delimiter.fg(Color::Yellow)
+                ))
+                .with_label(
+                    Label::new(span.clone())
+                        .with_message(format!(
+                            "Unclosed delimiter {}",
+                            delimiter.fg(Color::Yellow)
+                        ))
+                        .with_color(Color::Yellow),
+                )
+                .with_label(
                     Label::new(e.span())
-                        .with_message(format!("{}", msg.fg(Color::Red)))
+                        .with_message(format!(
+                            "Must be closed before this {}",
+                            e.found()
+                                .unwrap_or(&"end of file".to_string())
+                                .fg(Color::Red)
+                        ))
                         .with_color(Color::Red),
                 ),
-                chumsky::error::SimpleReason::Unclosed { span, delimiter } => report
-                    .with_message(format!(
-                        "Unclosed delimiter {}",
-                        delimiter.fg(Color::Yellow)
-                    ))
-                    .with_label(
-                        Label::new(span.clone())
-                            .with_message(format!(
-                                "Unclosed delimiter {}",
-                                delimiter.fg(Color::Yellow)
-                            ))
-                            .with_color(Color::Yellow),
-                    )
-                    .with_label(
-                        Label::new(e.span())
-                            .with_message(format!(
-                                "Must be closed before this {}",
-                                e.found()
-                                    .unwrap_or(&"end of file".to_string())
-                                    .fg(Color::Red)
-                            ))
-                            .with_color(Color::Red),
-                    ),
-                chumsky::error::SimpleReason::Unexpected => report
-                    .with_message(format!(
-                        "{}, expected {}",
-                        if e.found().is_some() {
-                            "Unexpected token in input"
-                        } else {
-                            "Unexpected end of input"
-                        },
-                        if e.expected().len() == 0 {
-                            "something else".to_string()
-                        } else {
-                            e.expected()
-                                .map(|expected| match expected {
-                                    Some(expected) => expected.to_string(),
-                                    None => "end of input".to_string(),
-                                })
-                                .collect::<Vec<_>>()
-                                .join(", ")
-                        }
-                    ))
-                    .with_label(
-                        Label::new(e.span())
-                            .with_message(format!(
-                                "Unexpected token {}",
-                                e.found()
-                                    .unwrap_or(&"end of file".to_string())
-                                    .fg(Color::Red)
-                            ))
-                            .with_color(Color::Red),
-                    ),
-            };
-
-            report.finish().print(Source::from(&geom)).unwrap();
-        })
+            chumsky::error::SimpleReason::Unexpected => report
+                .with_message(format!(
+                    "{}, expected {}",
+                    if e.found().is_some() {
+                        "Unexpected token in input"
+                    } else {
+                        "Unexpected end of input"
+                    },
+                    if e.expected().len() == 0 {
+                        "something else".to_string()
+                    } else {
+                        e.expected()
+                            .map(|expected| match expected {
+                                Some(expected) => expected.to_string(),
+                                None => "end of input".to_string(),
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    }
+                ))
+                .with_label(
+                    Label::new(e.span())
+                        .with_message(format!(
+                            "Unexpected token {}",
+                            e.found()
+                                .unwrap_or(&"end of file".to_string())
+                                .fg(Color::Red)
+                        ))
+                        .with_color(Color::Red),
+                ),
+        };
+
+        report.finish().print(Source::from(&geom)).unwrap();
+    });
+
+    if let Some(failure) = geometry_failure {
+        exit_with(failure, error_json.as_deref());
+    }
 }
@@ -0,0 +1,253 @@
+/*
+   `--checksum` digests of the input and output fastq files, for tracing
+   an output back to the data it was made from. The files are hashed as
+   they stream into and out of the pipeline rather than read again once
+   the run is done: each one is passed through a named pipe, read by
+   antisequence in place of the file, with a thread hashing the bytes
+   going through it. The digests are written to the `--report`.
+*/
+
+use std::{
+    env, fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use md5::Md5;
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{digest::DynDigest, Sha256};
+
+use crate::source::make_pipe;
+
+// bytes hashed at a time
+const BUF: usize = 1 << 16;
+
+// written to the report by its name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Algorithm::Md5 => write!(f, "md5"),
+            Algorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+pub fn checksum_algorithm(s: &str) -> Result<Algorithm, String> {
+    match s {
+        "md5" => Ok(Algorithm::Md5),
+        "sha256" => Ok(Algorithm::Sha256),
+        _ => Err(format!("Unknown checksum `{s}`, expected md5 or sha256")),
+    }
+}
+
+impl Algorithm {
+    fn hasher(self) -> Box<dyn DynDigest + Send> {
+        match self {
+            Algorithm::Md5 => Box::new(Md5::default()),
+            Algorithm::Sha256 => Box::new(Sha256::default()),
+        }
+    }
+}
+
+// `from` copied into `to`, returning the hex digest of the bytes. once `to`
+// is closed the rest of `from` is only hashed
+pub fn copy_digest(
+    algorithm: Algorithm,
+    mut from: impl Read,
+    mut to: impl Write,
+) -> io::Result<String> {
+    let mut hasher = algorithm.hasher();
+    let mut buf = vec![0; BUF];
+    let mut open = true;
+
+    loop {
+        let n = match from.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        hasher.update(&buf[..n]);
+
+        if open {
+            match to.write_all(&buf[..n]) {
+                // the pipeline stopped reading before the end of the file
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => open = false,
+                res => res?,
+            }
+        }
+    }
+
+    if open {
+        to.flush()?;
+    }
+
+    Ok(hex(&hasher.finalize()))
+}
+
+// the hex digest of `bytes`
+pub fn digest(algorithm: Algorithm, bytes: &[u8]) -> String {
+    let mut hasher = algorithm.hasher();
+    hasher.update(bytes);
+
+    hex(&hasher.finalize())
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+struct Hashed {
+    file: String,
+    output: bool,
+    // set once an output pipe has been opened for reading
+    opened: Arc<AtomicBool>,
+    pipe: PathBuf,
+    stream: JoinHandle<io::Result<String>>,
+}
+
+// the input and output files, replaced by the pipes they are hashed
+// through. the pipes are removed once dropped
+pub struct Checksums {
+    pub algorithm: Algorithm,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pipes: Vec<PathBuf>,
+    hashed: Vec<Hashed>,
+}
+
+impl Checksums {
+    // empty outputs are not written and are kept as they are
+    pub fn start(algorithm: Algorithm, inputs: &[String], outputs: &[String]) -> io::Result<Self> {
+        let mut checksums = Self {
+            algorithm,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            pipes: Vec::new(),
+            hashed: Vec::new(),
+        };
+
+        for (i, file) in inputs.iter().enumerate() {
+            let pipe = checksums.pipe("in", i, file)?;
+            checksums.inputs.push(pipe.display().to_string());
+
+            let from =
+                File::open(file).map_err(|e| io::Error::new(e.kind(), format!("{file}: {e}")))?;
+            let to = pipe.clone();
+            checksums.hashed.push(Hashed {
+                file: file.clone(),
+                output: false,
+                opened: Arc::default(),
+                pipe,
+                // opening the pipe waits until the pipeline opens it for reading
+                stream: thread::spawn(move || {
+                    copy_digest(algorithm, from, OpenOptions::new().write(true).open(to)?)
+                }),
+            });
+        }
+
+        for (i, file) in outputs.iter().enumerate() {
+            if file.is_empty() {
+                checksums.outputs.push(file.clone());
+                continue;
+            }
+
+            let pipe = checksums.pipe("out", i, file)?;
+            checksums.outputs.push(pipe.display().to_string());
+
+            let to = BufWriter::new(File::create(file)?);
+            let (from, opened) = (pipe.clone(), Arc::new(AtomicBool::new(false)));
+            let opening = opened.clone();
+            checksums.hashed.push(Hashed {
+                file: file.clone(),
+                output: true,
+                opened,
+                pipe,
+                stream: thread::spawn(move || {
+                    let from = File::open(from)?;
+                    opening.store(true, Ordering::SeqCst);
+                    copy_digest(algorithm, from, to)
+                }),
+            });
+        }
+
+        Ok(checksums)
+    }
+
+    // the file name is kept, compression is still recognized by it
+    fn pipe(&mut self, kind: &str, i: usize, file: &str) -> io::Result<PathBuf> {
+        let name = Path::new(file)
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+        let pipe = env::temp_dir().join(format!(
+            "seqproc-{}-checksum-{kind}{}-{name}",
+            process::id(),
+            i + 1
+        ));
+
+        make_pipe(&pipe)?;
+        self.pipes.push(pipe.clone());
+
+        Ok(pipe)
+    }
+
+    // the digests, once the pipeline is done with every file
+    pub fn finish(mut self) -> io::Result<Digests> {
+        let mut digests = Digests {
+            algorithm: self.algorithm,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+
+        for hashed in self.hashed.drain(..) {
+            // an output the pipeline never opened is left empty, the thread
+            // waiting on its pipe is let go by opening the other end
+            if hashed.output && !hashed.opened.load(Ordering::SeqCst) {
+                OpenOptions::new().write(true).open(&hashed.pipe)?;
+            }
+
+            let digest = hashed.stream.join().unwrap()?;
+
+            if hashed.output {
+                digests.outputs.push((hashed.file, digest));
+            } else {
+                digests.inputs.push((hashed.file, digest));
+            }
+        }
+
+        Ok(digests)
+    }
+}
+
+impl Drop for Checksums {
+    // a failed run may never have opened the pipes, the threads are left
+    fn drop(&mut self) {
+        for pipe in &self.pipes {
+            let _ = fs::remove_file(pipe);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Digests {
+    pub algorithm: Algorithm,
+    // files with the hex digests of their bytes
+    pub inputs: Vec<(String, String)>,
+    pub outputs: Vec<(String, String)>,
+}
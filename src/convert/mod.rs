@@ -0,0 +1,36 @@
+/*
+   `seqproc convert` writes the reads described for another tool as the
+   FGDL of a geometry, to move a pipeline to seqproc, or with `--to` a
+   geometry in another format, seqspec only. What each format understands
+   is given with it.
+*/
+
+use std::fmt;
+
+pub mod seqspec;
+pub mod umitools;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    UmiTools,
+    Seqspec,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::UmiTools => write!(f, "umitools"),
+            Format::Seqspec => write!(f, "seqspec"),
+        }
+    }
+}
+
+pub fn convert_format(s: &str) -> Result<Format, String> {
+    match s {
+        "umitools" => Ok(Format::UmiTools),
+        "seqspec" => Ok(Format::Seqspec),
+        _ => Err(format!(
+            "Unknown format `{s}`, expected umitools or seqspec"
+        )),
+    }
+}
@@ -0,0 +1,496 @@
+/*
+   seqspec, the YAML description of the read structure of an assay
+   (https://github.com/pachterlab/seqspec). A spec has the regions of each
+   modality of a library in the order of the molecule, and reads starting
+   at a primer region: a read on the positive strand reads the regions
+   after its primer, one on the negative strand those before it, from the
+   last. A read covers its regions up to its maximum length.
+
+   Reading a spec, the reads of a modality become the reads of a
+   geometry: a barcode or UMI region a piece of its type, mapped to its
+   onlist if it has one, a fixed region a fixed sequence, cDNA and gDNA
+   biological reads and any other region a discard, each labeled with its
+   region id. The region a read ends in is unbounded, as is one of variable
+   length up to the end of the read. Writing one, each piece of a geometry
+   becomes a region under a primer of its read, the regions of read 2
+   before its primer on the negative strand. seqspec has no reads of
+   unknown length, the unbounded pieces take what the pieces before them
+   leave of the read length given.
+
+   Only the block YAML seqspec is written in is read: mappings, sequences,
+   plain and quoted scalars and tags, which are ignored.
+*/
+
+use std::fmt::Write;
+
+use crate::{
+    compile::{utils::GeometryMeta, CompiledData},
+    long_read::revcomp,
+    parser::{Size, Type},
+};
+
+pub const SEQSPEC_VERSION: &str = "0.3.0";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Yaml {
+    Null,
+    Scalar(String),
+    Seq(Vec<Yaml>),
+    Map(Vec<(String, Yaml)>),
+}
+
+impl Yaml {
+    pub fn get(&self, key: &str) -> Option<&Yaml> {
+        match self {
+            Yaml::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Yaml::Scalar(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn length(&self, key: &str) -> Result<usize, String> {
+        self.str(key)
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| format!("{key} is not a length"))
+    }
+
+    pub fn items(&self, key: &str) -> &[Yaml] {
+        match self.get(key) {
+            Some(Yaml::Seq(items)) => items,
+            _ => &[],
+        }
+    }
+}
+
+// a line of YAML, its indent and what follows it without a comment
+struct Line<'a> {
+    indent: usize,
+    text: &'a str,
+}
+
+fn lines(yaml: &str) -> Vec<Line<'_>> {
+    yaml.lines()
+        .filter_map(|line| {
+            let text = line.trim_start();
+            let text = match text.find(" #") {
+                Some(i) if !text[..i].contains(['\'', '"']) => &text[..i],
+                _ => text,
+            };
+            let text = text.trim_end();
+
+            if text.is_empty() || text.starts_with('#') || text == "---" {
+                return None;
+            }
+
+            Some(Line {
+                indent: line.len() - line.trim_start().len(),
+                text,
+            })
+        })
+        .collect()
+}
+
+fn unquote(s: &str) -> &str {
+    for quote in ['\'', '"'] {
+        if let Some(s) = s.strip_prefix(quote).and_then(|s| s.strip_suffix(quote)) {
+            return s;
+        }
+    }
+    s
+}
+
+// a value on the line of its key or item, with any tag before it dropped
+fn scalar(text: &str) -> Option<Yaml> {
+    let text = match text.strip_prefix('!') {
+        Some(tagged) => tagged.split_once(' ').map_or("", |(_, rest)| rest.trim()),
+        None => text,
+    };
+
+    match text {
+        "" => None,
+        "null" | "~" => Some(Yaml::Null),
+        "[]" => Some(Yaml::Seq(Vec::new())),
+        "{}" => Some(Yaml::Map(Vec::new())),
+        _ => match text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            Some(items) => Some(Yaml::Seq(
+                items
+                    .split(',')
+                    .map(|item| Yaml::Scalar(unquote(item.trim()).to_string()))
+                    .collect(),
+            )),
+            None => Some(Yaml::Scalar(unquote(text).to_string())),
+        },
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Line<'a>> {
+        self.lines.get(self.next)
+    }
+
+    // the block nested under a key or item without a value on its line
+    fn nested(&mut self, indent: usize, in_seq: bool) -> Yaml {
+        match self.peek() {
+            Some(line) if line.indent > indent => {
+                let indent = line.indent;
+                self.block(indent)
+            }
+            // a sequence may be as indented as the key it is the value of
+            Some(line) if line.indent == indent && !in_seq && line.text.starts_with('-') => {
+                self.block(indent)
+            }
+            _ => Yaml::Null,
+        }
+    }
+
+    fn block(&mut self, indent: usize) -> Yaml {
+        match self.peek() {
+            Some(line) if line.text.starts_with('-') => self.seq(indent),
+            _ => self.map(indent),
+        }
+    }
+
+    fn seq(&mut self, indent: usize) -> Yaml {
+        let mut items = Vec::new();
+
+        while let Some(&Line { indent: at, text }) = self.peek() {
+            if at != indent || !text.starts_with('-') {
+                break;
+            }
+            let item = text[1..].trim_start();
+            let offset = text.len() - item.len();
+            let text = item;
+
+            let item = match text
+                .split_once(": ")
+                .or(text.strip_suffix(':').map(|k| (k, "")))
+            {
+                // the item is a mapping starting on its line
+                Some(_) if !text.starts_with(['!', '\'', '"', '[']) => {
+                    self.lines[self.next] = Line {
+                        indent: indent + offset,
+                        text,
+                    };
+                    self.map(indent + offset)
+                }
+                _ => {
+                    self.next += 1;
+                    match scalar(text) {
+                        Some(value) => value,
+                        None => self.nested(indent, true),
+                    }
+                }
+            };
+            items.push(item);
+        }
+
+        Yaml::Seq(items)
+    }
+
+    fn map(&mut self, indent: usize) -> Yaml {
+        let mut entries = Vec::new();
+
+        while let Some(&Line { indent: at, text }) = self.peek() {
+            if at != indent || text.starts_with('-') {
+                break;
+            }
+            let (key, value) = match text.split_once(": ") {
+                Some((key, value)) => (key, value.trim()),
+                None => (text.trim_end_matches(':'), ""),
+            };
+            self.next += 1;
+
+            let value = match scalar(value) {
+                Some(value) => value,
+                None => self.nested(indent, false),
+            };
+            entries.push((unquote(key).to_string(), value));
+        }
+
+        Yaml::Map(entries)
+    }
+}
+
+pub fn parse_yaml(yaml: &str) -> Yaml {
+    let mut parser = Parser {
+        lines: lines(yaml),
+        next: 0,
+    };
+
+    match parser.peek() {
+        Some(line) => {
+            let indent = line.indent;
+            parser.block(indent)
+        }
+        None => Yaml::Null,
+    }
+}
+
+// the regions of the library without regions of their own, in order
+fn leaves<'y>(region: &'y Yaml, out: &mut Vec<&'y Yaml>) {
+    match region.get("regions") {
+        Some(Yaml::Seq(regions)) if !regions.is_empty() => {
+            for region in regions {
+                leaves(region, out);
+            }
+        }
+        _ => out.push(region),
+    }
+}
+
+// a region id as an FGDL label
+fn label(id: &str) -> String {
+    let label = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+
+    if label.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        label
+    } else {
+        format!("r_{label}")
+    }
+}
+
+// the FGDL of a region, unbounded where the read ends in it. a read on the
+// negative strand reads the complement of fixed sequences
+fn region_piece(region: &Yaml, unbounded: bool, neg: bool) -> Result<String, String> {
+    let id = region.str("region_id").unwrap_or_default();
+    let (min, max) = (region.length("min_len")?, region.length("max_len")?);
+    let label = label(id);
+
+    let size = if unbounded {
+        ":".to_string()
+    } else if min == max {
+        format!("[{min}]")
+    } else {
+        format!("[{min}-{max}]")
+    };
+
+    let type_ = match region.str("region_type").unwrap_or_default() {
+        "barcode" => "b",
+        "umi" => "u",
+        "cdna" | "gdna" => "r",
+        _ => "x",
+    };
+
+    match (region.str("sequence_type"), region.str("sequence")) {
+        (Some("fixed"), Some(seq)) if !unbounded && !seq.is_empty() => {
+            let seq = seq.to_ascii_uppercase().into_bytes();
+            let seq = if neg { revcomp(&seq) } else { seq };
+
+            Ok(format!("f<{label}>[{}]", String::from_utf8_lossy(&seq)))
+        }
+        _ => {
+            let piece = format!("{type_}<{label}>{size}");
+            let onlist = region
+                .get("onlist")
+                .and_then(|onlist| onlist.str("filename"));
+
+            match onlist {
+                Some(file) if type_ != "x" => Ok(format!("map({piece}, \"{file}\", self)")),
+                _ => Ok(piece),
+            }
+        }
+    }
+}
+
+// the FGDL of the reads of `modality` in a seqspec
+pub fn read_seqspec(yaml: &str, modality: &str) -> Result<String, String> {
+    let spec = parse_yaml(yaml);
+
+    let library = spec
+        .items("library_spec")
+        .iter()
+        .find(|region| region.str("region_id") == Some(modality))
+        .ok_or_else(|| format!("the spec has no library of modality {modality}"))?;
+    let mut regions = Vec::new();
+    leaves(library, &mut regions);
+
+    let reads = spec
+        .items("sequence_spec")
+        .iter()
+        .filter(|read| read.str("modality") == Some(modality))
+        .collect::<Vec<_>>();
+    if reads.is_empty() {
+        return Err(format!("the spec has no reads of modality {modality}"));
+    }
+
+    let mut geometry = String::new();
+    for (n, read) in reads.iter().enumerate() {
+        let read_id = read.str("read_id").unwrap_or_default();
+        let primer = read.str("primer_id").unwrap_or_default();
+        let at = regions
+            .iter()
+            .position(|r| r.str("region_id") == Some(primer))
+            .ok_or_else(|| format!("read {read_id} starts at {primer}, which is not a region"))?;
+
+        let neg = read.str("strand") == Some("neg");
+        let read_regions = if neg {
+            regions[..at].iter().rev().collect::<Vec<_>>()
+        } else {
+            regions[at + 1..].iter().collect()
+        };
+
+        let read_len = read.length("max_len")?;
+        let mut pieces = String::new();
+        let mut covered = 0;
+        for region in read_regions {
+            if covered >= read_len {
+                break;
+            }
+            let (min, max) = (region.length("min_len")?, region.length("max_len")?);
+
+            // a region of variable length up to the end of the read ends with it
+            let ends = covered + max > read_len || (min < max && covered + max == read_len);
+            pieces.push_str(&region_piece(region, ends, neg)?);
+            covered += max;
+        }
+
+        write!(geometry, "{}{{{pieces}}}", n + 1).unwrap();
+    }
+    geometry.push('\n');
+
+    Ok(geometry)
+}
+
+// the region type, sequence type and sequence of a piece `len` long, on
+// the negative strand for read 2
+fn region_kind(gm: &GeometryMeta, len: usize, neg: bool) -> (&'static str, &'static str, String) {
+    let random = |base: &str| base.repeat(len);
+
+    match (&gm.expr.0.type_, &gm.expr.0.size) {
+        (_, Size::FixedSeq((seq, _), _, _)) if neg => {
+            let seq = String::from_utf8_lossy(&revcomp(seq.as_bytes())).into_owned();
+            ("linker", "fixed", seq)
+        }
+        (_, Size::FixedSeq((seq, _), _, _)) => ("linker", "fixed", seq.clone()),
+        (Type::Barcode, _) if gm.whitelist().is_some() => ("barcode", "onlist", random("N")),
+        (Type::Barcode, _) => ("barcode", "random", random("N")),
+        (Type::Umi, _) => ("umi", "random", random("N")),
+        (Type::ReadSeq, _) => ("cdna", "random", random("X")),
+        _ => ("linker", "random", random("X")),
+    }
+}
+
+fn write_region(
+    out: &mut String,
+    indent: usize,
+    id: &str,
+    (region_type, sequence_type, sequence): (&str, &str, String),
+    (min, max): (usize, usize),
+    onlist: Option<String>,
+) {
+    let pad = " ".repeat(indent);
+    writeln!(out, "{pad}- !Region").unwrap();
+    for (key, value) in [
+        ("region_id", id.to_string()),
+        ("region_type", region_type.to_string()),
+        ("name", id.to_string()),
+        ("sequence_type", sequence_type.to_string()),
+        ("sequence", format!("'{sequence}'")),
+        ("min_len", min.to_string()),
+        ("max_len", max.to_string()),
+    ] {
+        writeln!(out, "{pad}  {key}: {value}").unwrap();
+    }
+    match onlist {
+        Some(file) => writeln!(
+            out,
+            "{pad}  onlist: !Onlist\n{pad}    file_id: {file}\n{pad}    filename: {file}"
+        )
+        .unwrap(),
+        None => writeln!(out, "{pad}  onlist: null").unwrap(),
+    }
+    writeln!(out, "{pad}  regions: null").unwrap();
+}
+
+// a geometry as a seqspec of one modality, reads `read_len` long
+pub fn write_seqspec(
+    compiled: &CompiledData,
+    name: &str,
+    modality: &str,
+    read_len: usize,
+) -> String {
+    let mut library = String::new();
+    let mut read_specs = String::new();
+    let mut regions = Vec::new();
+    let (mut lib_min, mut lib_max) = (0, 0);
+
+    for (i, geometry) in compiled.geometry.iter().enumerate() {
+        let n = i + 1;
+        let primer = format!("R{n}_primer");
+        let strand = if n == 2 { "neg" } else { "pos" };
+
+        let mut read = Vec::new();
+        let mut covered = 0;
+        for (p, gm) in geometry.iter().enumerate() {
+            let (min, max) = match gm.expr.0.size {
+                Size::FixedSeq((ref seq, _), ..) => (seq.len(), seq.len()),
+                Size::FixedLen((len, _)) => (len, len),
+                Size::RangedLen(((a, b), _)) => (a, b),
+                Size::UnboundedLen => (1, read_len.saturating_sub(covered).max(1)),
+            };
+            covered += max;
+
+            let id = match &gm.expr.0.label {
+                Some(label) => label.clone(),
+                None => format!("R{n}_{}", p + 1),
+            };
+            let onlist = gm.whitelist().map(|(file, _)| file);
+
+            read.push((id, region_kind(gm, max, n == 2), (min, max), onlist));
+        }
+
+        let len = covered.min(read_len);
+        lib_min += read.iter().map(|(_, _, (min, _), _)| min).sum::<usize>();
+        lib_max += covered;
+
+        writeln!(
+            read_specs,
+            "- !Read\n  read_id: R{n}\n  name: Read {n}\n  modality: {modality}\n  \
+            primer_id: {primer}\n  min_len: {len}\n  max_len: {len}\n  strand: {strand}\n  files: []"
+        )
+        .unwrap();
+
+        // read 2 reads the end of the molecule from its last base
+        let primer = (
+            primer,
+            ("custom_primer", "fixed", String::new()),
+            (0, 0),
+            None,
+        );
+        if n == 2 {
+            read.reverse();
+            read.push(primer);
+        } else {
+            read.insert(0, primer);
+        }
+        regions.extend(read);
+    }
+
+    for (id, kind, lens, onlist) in regions {
+        write_region(&mut library, 2, &id, kind, lens, onlist);
+    }
+
+    format!(
+        "!Assay\nseqspec_version: {SEQSPEC_VERSION}\nassay_id: {name}\nname: {name}\n\
+        doi: ''\ndate: ''\ndescription: converted from FGDL by seqproc\n\
+        modalities:\n- {modality}\nlib_struct: ''\n\
+        sequence_protocol: ''\nsequence_kit: ''\nlibrary_protocol: ''\nlibrary_kit: ''\n\
+        sequence_spec:\n{read_specs}library_spec:\n- !Region\n  region_id: {modality}\n  \
+        region_type: {modality}\n  name: {modality}\n  sequence_type: joined\n  sequence: ''\n  \
+        min_len: {lib_min}\n  max_len: {lib_max}\n  onlist: null\n  regions:\n{library}"
+    )
+}
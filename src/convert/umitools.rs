@@ -0,0 +1,252 @@
+/*
+   The barcode patterns of `umi_tools extract`, in either of its methods.
+   A string pattern such as `CCCCCCCCNNNNXX` gives the cell barcode as C,
+   the UMI as N and bases kept in the read as X. A regex pattern names its
+   groups `cell_<n>`, `umi_<n>` and `discard_<n>`, with the fuzzy matching
+   of a group given as `{s<=k}`; bases matched outside of a group are kept
+   in the read. A pattern is a regex if it has a group. Either way the
+   read after the pattern is kept.
+
+   The pieces become those of FGDL, each group keeping its name as a
+   label: cell barcodes, UMIs and discards, a sequence of fixed bases
+   with the mismatches of its fuzzy matching. Only regexes of fixed bases
+   and `.` with a count, a range of counts or `+` and `*` are understood,
+   anything else is an error naming it. umi_tools moves the barcodes and
+   UMIs to the read name, seqproc leaves them in the read unless they are
+   captured with `--capture`.
+*/
+
+// the length of a piece of the pattern, or its fixed bases
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Len {
+    Fixed(usize),
+    Ranged(usize, usize),
+    Unbounded,
+    Seq(String),
+}
+
+impl Len {
+    fn size(&self) -> String {
+        match self {
+            Len::Fixed(n) => format!("[{n}]"),
+            Len::Ranged(a, b) => format!("[{a}-{b}]"),
+            Len::Unbounded => ":".to_string(),
+            Len::Seq(seq) => format!("[{seq}]"),
+        }
+    }
+}
+
+// the names of the groups umi_tools knows, with the type of their pieces
+fn group_type(name: &str) -> Option<char> {
+    let (kind, n) = name.rsplit_once('_')?;
+    if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    match kind {
+        "cell" => Some('b'),
+        "umi" => Some('u'),
+        "discard" => Some('x'),
+        _ => None,
+    }
+}
+
+// the FGDL of a piece of type `type_`, labeled with `label` if given
+fn piece(type_: char, label: Option<&str>, len: &Len, mismatches: usize) -> String {
+    let label = label.map_or(String::new(), |l| format!("<{l}>"));
+
+    match len {
+        Len::Seq(_) => {
+            let mut fixed = format!("f{label}{}", len.size());
+            if mismatches > 0 {
+                fixed = format!("hamming({fixed}, {mismatches})");
+            }
+            if type_ == 'x' {
+                fixed = format!("remove({fixed})");
+            }
+            fixed
+        }
+        _ => format!("{type_}{label}{}", len.size()),
+    }
+}
+
+// a string pattern, a run of each letter a piece
+fn string_pieces(pattern: &str) -> Result<Vec<String>, String> {
+    let mut pieces = Vec::new();
+    let mut counts = [0; 2];
+    let bytes = pattern.as_bytes();
+
+    let mut start = 0;
+    while start < bytes.len() {
+        let c = bytes[start];
+        let len = bytes[start..].iter().take_while(|b| **b == c).count();
+
+        let (type_, kind) = match c {
+            b'C' => ('b', Some(("cell", 0))),
+            b'N' => ('u', Some(("umi", 1))),
+            b'X' => ('r', None),
+            _ => return Err(format!("`{}` of the pattern is not C, N or X", c as char)),
+        };
+        let label = kind.map(|(name, i)| {
+            counts[i] += 1;
+            format!("{name}_{}", counts[i])
+        });
+
+        pieces.push(piece(type_, label.as_deref(), &Len::Fixed(len), 0));
+        start += len;
+    }
+
+    Ok(pieces)
+}
+
+// a `{n}`, `{a,b}` or `{s<=k}` at the start of `s`, with what follows it
+fn braces(s: &str) -> Option<(&str, &str)> {
+    let s = s.strip_prefix('{')?;
+    let end = s.find('}')?;
+
+    Some((&s[..end], &s[end + 1..]))
+}
+
+// the pieces of a regex outside of a group, or of the inside of a group
+fn atoms(mut regex: &str) -> Result<Vec<Len>, String> {
+    let mut lens = Vec::new();
+
+    while let Some(c) = regex.chars().next() {
+        regex = &regex[c.len_utf8()..];
+
+        let (count, rest) = match regex.chars().next() {
+            Some('+') | Some('*') => (None, &regex[1..]),
+            Some('{') => match braces(regex) {
+                Some((count, rest)) => (Some(count), rest),
+                None => return Err(format!("unclosed `{{` in `{regex}`")),
+            },
+            _ => (Some("1"), regex),
+        };
+        regex = rest;
+
+        let len = match count {
+            None => Len::Unbounded,
+            Some(count) => match count.split_once(',') {
+                Some((a, b)) => match (a.parse(), b.parse()) {
+                    (Ok(a), Ok(b)) => Len::Ranged(a, b),
+                    _ => return Err(format!("`{{{count}}}` is not a range of counts")),
+                },
+                None => match count.parse() {
+                    Ok(n) => Len::Fixed(n),
+                    Err(_) => return Err(format!("`{{{count}}}` is not a count")),
+                },
+            },
+        };
+
+        let len = match (c, len) {
+            ('.', len) => len,
+            (c, Len::Fixed(n)) if "ACGTN".contains(c) => Len::Seq(c.to_string().repeat(n)),
+            (c, _) if "ACGTN".contains(c) => {
+                return Err(format!("`{c}` is repeated a variable number of times"))
+            }
+            (c, _) => return Err(format!("`{c}` is not understood in a pattern")),
+        };
+
+        // consecutive fixed bases or counts of any base are one piece
+        match (lens.last_mut(), len) {
+            (Some(Len::Seq(seq)), Len::Seq(more)) => seq.push_str(&more),
+            (Some(Len::Fixed(n)), Len::Fixed(more)) => *n += more,
+            (_, len) => lens.push(len),
+        }
+    }
+
+    Ok(lens)
+}
+
+fn regex_pieces(regex: &str) -> Result<Vec<String>, String> {
+    let mut regex = regex.strip_prefix('^').unwrap_or(regex);
+    regex = regex.strip_suffix('$').unwrap_or(regex);
+
+    let mut pieces = Vec::new();
+
+    while !regex.is_empty() {
+        let (outside, group) = match regex.find("(?P<") {
+            Some(start) => (&regex[..start], Some(&regex[start + 4..])),
+            None => (regex, None),
+        };
+
+        for len in atoms(outside)? {
+            let type_ = if matches!(len, Len::Seq(_)) { 'f' } else { 'r' };
+            pieces.push(piece(type_, None, &len, 0));
+        }
+
+        let group = match group {
+            Some(group) => group,
+            None => break,
+        };
+        let (name, rest) = group
+            .split_once('>')
+            .ok_or_else(|| "unclosed group name".to_string())?;
+        let (inside, rest) = rest
+            .split_once(')')
+            .ok_or_else(|| format!("unclosed group {name}"))?;
+        if inside.contains('(') {
+            return Err(format!("group {name} has a group inside it"));
+        }
+
+        let type_ = group_type(name)
+            .ok_or_else(|| format!("group {name} is not named cell_<n>, umi_<n> or discard_<n>"))?;
+
+        // fuzzy matching of the group, substitutions only
+        let (mismatches, rest) = match braces(rest) {
+            Some((fuzzy, rest)) if fuzzy.contains("<=") => match fuzzy.strip_prefix("s<=") {
+                Some(k) => (
+                    k.parse()
+                        .map_err(|_| format!("`{{{fuzzy}}}` of group {name} is not understood"))?,
+                    rest,
+                ),
+                None => {
+                    return Err(format!(
+                        "`{{{fuzzy}}}` of group {name} allows other errors than substitutions"
+                    ))
+                }
+            },
+            _ => (0, rest),
+        };
+
+        match &atoms(inside)?[..] {
+            [len] => pieces.push(piece(type_, Some(name), len, mismatches)),
+            _ => {
+                return Err(format!(
+                    "group {name} is not one sequence nor one count of any base"
+                ))
+            }
+        }
+
+        regex = rest;
+    }
+
+    Ok(pieces)
+}
+
+// the FGDL of a read whose barcodes are given by `pattern`
+pub fn read_geometry(pattern: &str) -> Result<String, String> {
+    let mut pieces = if pattern.contains('(') {
+        regex_pieces(pattern)?
+    } else {
+        string_pieces(pattern)?
+    };
+
+    if !pieces.last().is_some_and(|p| p.ends_with(':')) {
+        pieces.push("r:".to_string());
+    }
+
+    Ok(pieces.concat())
+}
+
+// the FGDL of a pattern for read 1 and, for paired reads, one for read 2.
+// without one read 2 is kept whole
+pub fn geometry(pattern: &str, pattern2: Option<&str>, paired: bool) -> Result<String, String> {
+    let read1 = read_geometry(pattern)?;
+
+    match pattern2 {
+        Some(pattern2) => Ok(format!("1{{{read1}}}2{{{}}}\n", read_geometry(pattern2)?)),
+        None if paired => Ok(format!("1{{{read1}}}2{{r:}}\n")),
+        None => Ok(format!("1{{{read1}}}\n")),
+    }
+}
@@ -0,0 +1,231 @@
+/*
+   Tool descriptors of the command line, for wrapping seqproc in CWL
+   or Galaxy workflows. They are generated from the clap definition, so
+   they keep up with the options as they are added. Arguments hinted as
+   file paths are input files, those hinted as any path are files the
+   tool writes. Flags become booleans, numbers become numbers, and all
+   other values are passed on as strings.
+*/
+
+use std::{any::TypeId, fmt::Write};
+
+use clap::{builder::ValueHint, Arg, ArgAction, Command, ValueEnum};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ToolFormat {
+    Cwl,
+    Galaxy,
+}
+
+enum Kind {
+    Flag,
+    Int,
+    Float,
+    Text,
+    Input,
+    Output,
+}
+
+fn kind(arg: &Arg) -> Kind {
+    if matches!(arg.get_action(), ArgAction::SetTrue) {
+        return Kind::Flag;
+    }
+
+    match arg.get_value_hint() {
+        ValueHint::FilePath => return Kind::Input,
+        ValueHint::AnyPath => return Kind::Output,
+        _ => (),
+    }
+
+    let type_id = arg.get_value_parser().type_id();
+    if type_id == TypeId::of::<usize>() || type_id == TypeId::of::<u64>() {
+        Kind::Int
+    } else if type_id == TypeId::of::<f64>() {
+        Kind::Float
+    } else {
+        Kind::Text
+    }
+}
+
+// the arguments of the command, without help and version
+fn arguments(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments().filter(|arg| {
+        arg.get_long().is_some()
+            && !arg.is_hide_set()
+            && !matches!(arg.get_action(), ArgAction::Help | ArgAction::Version)
+    })
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_help()
+        .map_or(String::new(), |help| help.to_string())
+}
+
+fn default(arg: &Arg) -> Option<String> {
+    arg.get_default_values()
+        .first()
+        .map(|value| value.to_string_lossy().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn many(arg: &Arg) -> bool {
+    arg.get_num_args().is_some_and(|n| n.max_values() > 1)
+}
+
+pub fn describe(cmd: &Command, format: ToolFormat) -> String {
+    match format {
+        ToolFormat::Cwl => cwl(cmd),
+        ToolFormat::Galaxy => galaxy(cmd),
+    }
+}
+
+pub fn cwl(cmd: &Command) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "cwlVersion: v1.2").unwrap();
+    writeln!(out, "class: CommandLineTool").unwrap();
+    writeln!(out, "baseCommand: {}", cmd.get_name()).unwrap();
+    if let Some(about) = cmd.get_about() {
+        writeln!(out, "doc: {}", yaml(&about.to_string())).unwrap();
+    }
+
+    writeln!(out, "inputs:").unwrap();
+    for arg in arguments(cmd) {
+        let type_ = match kind(arg) {
+            Kind::Flag => "boolean",
+            Kind::Int => "int",
+            Kind::Float => "float",
+            Kind::Input => "File",
+            Kind::Text | Kind::Output => "string",
+        };
+        let array = if many(arg) { "[]" } else { "" };
+        let optional = if arg.is_required_set() { "" } else { "?" };
+
+        writeln!(out, "  {}:", arg.get_id()).unwrap();
+        writeln!(out, "    type: {type_}{array}{optional}").unwrap();
+        writeln!(out, "    doc: {}", yaml(&help(arg))).unwrap();
+        if let Some(value) = default(arg) {
+            writeln!(out, "    default: {}", yaml(&value)).unwrap();
+        }
+        writeln!(out, "    inputBinding:").unwrap();
+        writeln!(out, "      prefix: --{}", arg.get_long().unwrap()).unwrap();
+    }
+
+    writeln!(out, "outputs:").unwrap();
+    for arg in arguments(cmd).filter(|arg| matches!(kind(arg), Kind::Output)) {
+        writeln!(out, "  {}_file:", arg.get_id()).unwrap();
+        writeln!(out, "    type: File?").unwrap();
+        writeln!(out, "    outputBinding:").unwrap();
+        writeln!(out, "      glob: $(inputs.{})", arg.get_id()).unwrap();
+    }
+
+    out
+}
+
+pub fn galaxy(cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let mut command = String::new();
+    let mut inputs = String::new();
+    let mut outputs = String::new();
+
+    for arg in arguments(cmd) {
+        let id = arg.get_id().as_str();
+        let long = arg.get_long().unwrap();
+        let label = xml(&help(arg));
+        let optional = !arg.is_required_set();
+
+        match kind(arg) {
+            Kind::Flag => {
+                writeln!(command, "    ${id}").unwrap();
+                writeln!(
+                    inputs,
+                    "        <param name=\"{id}\" type=\"boolean\" truevalue=\"--{long}\" falsevalue=\"\" checked=\"false\" label=\"{label}\"/>"
+                )
+                .unwrap();
+                continue;
+            }
+            // files the tool writes are only asked for when switched on
+            Kind::Output => {
+                writeln!(
+                    command,
+                    "    #if ${id}_enabled\n      --{long} '${id}'\n    #end if"
+                )
+                .unwrap();
+                writeln!(
+                    inputs,
+                    "        <param name=\"{id}_enabled\" type=\"boolean\" checked=\"false\" label=\"{label}\"/>"
+                )
+                .unwrap();
+                writeln!(
+                    outputs,
+                    "        <data name=\"{id}\" format=\"data\" label=\"{label}\">\n            <filter>{id}_enabled</filter>\n        </data>"
+                )
+                .unwrap();
+                continue;
+            }
+            _ => (),
+        }
+
+        let type_ = match kind(arg) {
+            Kind::Int => "integer",
+            Kind::Float => "float",
+            Kind::Input => "data",
+            _ => "text",
+        };
+        let value = default(arg).map_or(String::new(), |v| format!(" value=\"{}\"", xml(&v)));
+
+        if optional {
+            writeln!(
+                command,
+                "    #if str(${id})\n      --{long} '${id}'\n    #end if"
+            )
+            .unwrap();
+        } else {
+            writeln!(command, "    --{long} '${id}'").unwrap();
+        }
+        writeln!(
+            inputs,
+            "        <param name=\"{id}\" type=\"{type_}\" optional=\"{optional}\"{value} label=\"{label}\"/>"
+        )
+        .unwrap();
+    }
+
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "<tool id=\"{name}\" name=\"{name}\" version=\"{}\">",
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    if let Some(about) = cmd.get_about() {
+        writeln!(
+            out,
+            "    <description>{}</description>",
+            xml(&about.to_string())
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "    <command><![CDATA[\n{name}\n{command}    ]]></command>"
+    )
+    .unwrap();
+    writeln!(out, "    <inputs>\n{inputs}    </inputs>").unwrap();
+    writeln!(out, "    <outputs>\n{outputs}    </outputs>").unwrap();
+    writeln!(out, "</tool>").unwrap();
+
+    out
+}
+
+// a double quoted yaml string
+fn yaml(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,168 @@
+/*
+   `seqproc graph` draws the plan of a geometry as a Graphviz DOT or
+   Mermaid diagram, for reviewing geometries with many anchors or showing
+   them in a paper. The stages of each read are a chain in a box of their
+   own, between the stages shared by every read.
+*/
+
+use std::fmt::{self, Write};
+
+use super::{quote, Stage};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphFormat::Dot => write!(f, "dot"),
+            GraphFormat::Mermaid => write!(f, "mermaid"),
+        }
+    }
+}
+
+pub fn graph_format(s: &str) -> Result<GraphFormat, String> {
+    match s {
+        "dot" => Ok(GraphFormat::Dot),
+        "mermaid" => Ok(GraphFormat::Mermaid),
+        _ => Err(format!(
+            "Unknown graph format `{s}`, expected dot or mermaid"
+        )),
+    }
+}
+
+// a run of stages, of one read or of all of them
+struct Step<'a> {
+    read: Option<String>,
+    stages: Vec<(usize, &'a Stage)>,
+}
+
+fn read_of(stage: &Stage) -> Option<&str> {
+    stage
+        .params
+        .iter()
+        .find(|(key, _)| *key == "read")
+        .map(|(_, read)| read.as_str())
+        .filter(|read| *read != "all")
+}
+
+// consecutive stages of the reads are one step, split by read
+fn steps(stages: &[Stage]) -> Vec<Vec<Step<'_>>> {
+    let mut steps: Vec<Vec<Step<'_>>> = Vec::new();
+    let mut reads = false;
+
+    for (i, stage) in stages.iter().enumerate() {
+        let read = read_of(stage).map(str::to_string);
+
+        if steps.is_empty() || reads != read.is_some() || read.is_none() {
+            steps.push(Vec::new());
+        }
+        reads = read.is_some();
+
+        let step = steps.last_mut().unwrap();
+        match step.iter_mut().find(|s| s.read == read) {
+            Some(s) => s.stages.push((i, stage)),
+            None => step.push(Step {
+                read,
+                stages: vec![(i, stage)],
+            }),
+        }
+    }
+
+    steps
+}
+
+// the name of the stage and its parameters a line each, then its labels
+fn node_label(stage: &Stage, newline: &str) -> String {
+    let mut lines = vec![stage.name.to_string()];
+    lines.extend(
+        stage
+            .params
+            .iter()
+            .filter(|(key, _)| *key != "read")
+            .map(|(key, value)| format!("{key}={value}")),
+    );
+    if !stage.labels.is_empty() {
+        lines.push(format!("[{}]", stage.labels.join(" ")));
+    }
+
+    lines.join(newline)
+}
+
+pub fn graph(stages: &[Stage], format: GraphFormat) -> String {
+    let steps = steps(stages);
+    let mut out = String::new();
+
+    match format {
+        GraphFormat::Dot => out.push_str("digraph seqproc {\n  node [shape=box];\n"),
+        GraphFormat::Mermaid => out.push_str("flowchart TD\n"),
+    }
+
+    for (n, step) in steps.iter().enumerate() {
+        for Step { read, stages } in step {
+            if let Some(read) = read {
+                match format {
+                    GraphFormat::Dot => writeln!(
+                        out,
+                        "  subgraph cluster{n}_read{read} {{\n    label=\"read {read}\";"
+                    ),
+                    GraphFormat::Mermaid => {
+                        writeln!(out, "  subgraph step{n}_read{read} [read {read}]")
+                    }
+                }
+                .unwrap();
+            }
+
+            let indent = if read.is_some() { "    " } else { "  " };
+            for (i, stage) in stages {
+                match format {
+                    GraphFormat::Dot => {
+                        let label = quote(&node_label(stage, "\n"));
+                        writeln!(out, "{indent}s{i} [label={label}];")
+                    }
+                    GraphFormat::Mermaid => {
+                        let label = node_label(stage, "<br/>").replace('"', "#quot;");
+                        writeln!(out, "{indent}s{i}[\"{label}\"]")
+                    }
+                }
+                .unwrap();
+            }
+
+            if read.is_some() {
+                match format {
+                    GraphFormat::Dot => out.push_str("  }\n"),
+                    GraphFormat::Mermaid => out.push_str("  end\n"),
+                }
+            }
+        }
+    }
+
+    // the stages of a read follow one another, each step follows every
+    // read of the step before it
+    let edge = |from: usize, to: usize| match format {
+        GraphFormat::Dot => format!("  s{from} -> s{to};\n"),
+        GraphFormat::Mermaid => format!("  s{from} --> s{to}\n"),
+    };
+    for (n, step) in steps.iter().enumerate() {
+        for Step { stages, .. } in step {
+            for pair in stages.windows(2) {
+                out.push_str(&edge(pair[0].0, pair[1].0));
+            }
+
+            if let Some(before) = n.checked_sub(1).map(|n| &steps[n]) {
+                for last in before.iter().filter_map(|s| s.stages.last()) {
+                    out.push_str(&edge(last.0, stages[0].0));
+                }
+            }
+        }
+    }
+
+    if format == GraphFormat::Dot {
+        out.push_str("}\n");
+    }
+
+    out
+}
@@ -0,0 +1,383 @@
+/*
+   The plan of a run, what `--explain` prints instead of processing reads.
+   Stages are listed in the order reads go through them, each with the
+   parameters it was given and the labels of the segments it cuts.
+   `--json` prints the same plan on one line, so workflow managers can
+   hash it as a cache key or render it themselves.
+*/
+
+pub mod graph;
+
+use std::fmt::Write;
+
+use crate::{
+    compile::CompiledData,
+    filters::dedup::DedupMode,
+    interpret::InterpretOptions,
+    matchers::{backend_for, Backend},
+    parser::{PreStep, Size, SPEC_VERSION},
+    sink::{
+        fastq::TechPiece,
+        route::kind_name,
+        shard::{ShardSize, Sharding},
+    },
+    verify::Verifier,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    pub name: &'static str,
+    pub params: Vec<(&'static str, String)>,
+    pub labels: Vec<String>,
+}
+
+impl Stage {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            params: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    fn param(mut self, key: &'static str, value: impl ToString) -> Self {
+        self.params.push((key, value.to_string()));
+        self
+    }
+}
+
+pub fn plan(compiled: &CompiledData, options: &InterpretOptions) -> Vec<Stage> {
+    let mut stages = Vec::new();
+
+    let names = &options.name_filter;
+    if names.is_active() {
+        let mut stage = Stage::new("filter_name");
+        if let Some(include) = &names.include {
+            stage = stage.param("include", include);
+        }
+        if let Some(exclude) = &names.exclude {
+            stage = stage.param("exclude", exclude);
+        }
+        if !names.tiles.is_empty() {
+            let tiles = names.tiles.iter().map(|tile| tile.to_string());
+            stage = stage.param("tiles", tiles.collect::<Vec<_>>().join(","));
+        }
+        stages.push(stage);
+    }
+
+    if options.phred64 {
+        stages.push(Stage::new("qualities").param("from", "phred+64"));
+    }
+
+    stages.push(Stage::new("normalize").param("uppercase", options.uppercase));
+
+    for step in &compiled.pre {
+        stages.push(match step {
+            PreStep::QualTrim(min_qual) => Stage::new("pre").param("qualtrim", min_qual),
+            PreStep::Adapters(name) => Stage::new("pre").param("adapters", name),
+            PreStep::AdapterFile(path) => Stage::new("pre").param("adapter_file", path),
+            PreStep::AssertLen {
+                read,
+                len,
+                at_least,
+            } => Stage::new("pre")
+                .param("assert_len", len)
+                .param("at_least", at_least)
+                .param("read", read.map_or("all".to_string(), |n| n.to_string())),
+        });
+    }
+
+    if options.orient {
+        stages.push(Stage::new("orient").param("anchors", compiled.anchors(0).join(",")));
+    }
+
+    if let Some(delimiter) = &compiled.repeat {
+        stages.push(Stage::new("repeat").param("delimiter", delimiter));
+    }
+
+    for (i, read_geometry) in compiled.geometry.iter().enumerate() {
+        let read = i + 1;
+
+        if let Some(indices) = compiled.header.get(i).filter(|h| !h.is_empty()) {
+            let positions = indices.iter().map(|(_, index)| index.to_string());
+            let mut stage = Stage::new("header")
+                .param("read", read)
+                .param("indices", positions.collect::<Vec<_>>().join(","));
+            stage.labels = indices.iter().map(|(label, _)| label.clone()).collect();
+            stages.push(stage);
+        }
+
+        if read_geometry.is_empty() {
+            stages.push(Stage::new("passthrough").param("read", read));
+            continue;
+        }
+
+        let optional = compiled.optional.get(i);
+        let alternatives = compiled.alternatives.get(i);
+
+        for (j, gm) in read_geometry.iter().enumerate() {
+            let piece = &gm.expr.0;
+
+            let mut stage = Stage::new(match piece.size {
+                Size::FixedSeq(..) => "match",
+                _ => "cut",
+            })
+            .param("read", read)
+            .param("type", &piece.type_)
+            .param("size", &piece.size);
+
+            if let Size::FixedSeq(_, miss, _) = &piece.size {
+                let backend = match backend_for(&options.matchers, piece.label.as_deref()) {
+                    Backend::Antisequence => "antisequence",
+                    Backend::Kmer => "kmer",
+                    Backend::Myers => "myers",
+                };
+                stage = stage.param("miss", miss).param("matcher", backend);
+            }
+
+            let branch = alternatives
+                .into_iter()
+                .flatten()
+                .find_map(|set| set.iter().position(|g| g.contains(&j)));
+
+            if let Some(branch) = branch {
+                stage = stage.param("branch", branch + 1);
+            } else if optional.is_some_and(|groups| groups.iter().any(|g| g.contains(&j))) {
+                stage = stage.param("optional", true);
+            }
+
+            if !gm.stack.is_empty() {
+                let functions = gm.stack.iter().map(|(f, _)| f.to_string());
+                stage = stage.param("functions", functions.collect::<Vec<_>>().join(","));
+            }
+
+            stage.labels.extend(piece.label.clone());
+            stages.push(stage);
+        }
+    }
+
+    let umi = &options.umi_filter;
+    if umi.is_active() {
+        let mut stage = Stage::new("umi_filter")
+            .param("homopolymer", umi.homopolymer)
+            .param("ambiguous", umi.ambiguous)
+            .param("flag", umi.flag);
+        if let Some(qual) = umi.min_mean_qual {
+            stage = stage.param("min_mean_qual", qual);
+        }
+        stages.push(stage);
+    }
+
+    if let Some(filter) = &options.complexity {
+        let mut stage = Stage::new("complexity").param("poly_g", filter.poly_g);
+        if let Some(max) = filter.max_dust {
+            stage = stage.param("max_dust", max);
+        }
+        stages.push(stage);
+    }
+
+    if let Some(config) = &options.primers {
+        let mut stage = Stage::new("primers")
+            .param("fasta", &config.fasta)
+            .param("max_mismatch", config.max_mismatch);
+        if let Some(tsv) = &config.tsv {
+            stage = stage.param("tsv", tsv);
+        }
+        stages.push(stage);
+    }
+
+    if let Some(estimate) = &options.duplication {
+        stages.push(Stage::new("duplication").param("prefix_len", estimate.prefix_len));
+    }
+
+    if let Some(config) = &options.dedup {
+        let mut stage = Stage::new("dedup")
+            .param("prefix_len", config.prefix_len)
+            .param("flag", config.flag);
+        stage = match &config.mode {
+            DedupMode::Approximate => stage.param("mode", "approximate"),
+            DedupMode::Exact(path) => stage.param("mode", "exact").param("table", path.display()),
+        };
+        stages.push(stage);
+    }
+
+    if let Some(counts) = &options.umi_counts {
+        stages.push(Stage::new("umi_counts").param("out", &counts.out));
+    }
+
+    if let Some(composition) = &options.composition {
+        stages.push(Stage::new("composition").param("out", &composition.out));
+    }
+
+    if let Some(hopping) = &options.index_hopping {
+        stages.push(Stage::new("index_hopping").param("out", &hopping.out));
+    }
+
+    if options.verify_sample > 0 {
+        let mut stage = Stage::new("verify").param("reads", options.verify_sample);
+        stage.labels = Verifier::new(compiled)
+            .labels()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        stages.push(stage);
+    }
+
+    for (name, path) in &options.segment_out {
+        let mut stage = Stage::new("segment_out").param("out", path);
+        stage.labels.push(name.clone());
+        stages.push(stage);
+    }
+
+    if let Some(tech_read) = &options.tech_read {
+        let mut constants = Vec::new();
+        let mut stage = Stage::new("tech_read").param("out", &tech_read.out);
+        for piece in &tech_read.pieces {
+            match piece {
+                TechPiece::Segment(name) => stage.labels.push(name.clone()),
+                TechPiece::Constant(bases) => constants.push(String::from_utf8_lossy(bases)),
+            }
+        }
+        if !constants.is_empty() {
+            stage = stage.param("constants", constants.join(","));
+        }
+        stages.push(stage);
+    }
+
+    if let Some(capture) = &options.capture {
+        let mut stage = Stage::new("capture");
+        if let Some(tsv) = &capture.tsv {
+            stage = stage.param("tsv", tsv);
+        }
+        stage.labels = capture.names.clone();
+        stages.push(stage);
+    }
+
+    if let Some(wells) = &options.wells {
+        let mut stage = Stage::new("wells").param("barcodes", wells.barcodes);
+        if let Some(tsv) = &wells.tsv {
+            stage = stage.param("tsv", tsv);
+        }
+        stages.push(stage);
+    }
+
+    if let Some(raw_tags) = &options.raw_tags {
+        let mut stage = Stage::new("raw tags");
+        if let Some(tsv) = &raw_tags.tsv {
+            stage = stage.param("tsv", tsv);
+        }
+        stages.push(stage);
+    }
+
+    if let Some(features) = &options.features {
+        let modalities = features
+            .modalities
+            .iter()
+            .map(|(modality, outs)| format!("{modality}={}", outs.join(",").trim_end_matches(',')))
+            .collect::<Vec<_>>();
+        let mut stage = Stage::new("features")
+            .param("features", features.features.len())
+            .param("max_mismatch", features.max_mismatch)
+            .param("modalities", modalities.join(" "));
+        stage.labels.push(features.segment.clone());
+        stages.push(stage);
+    }
+
+    for (type_, route) in &options.routes {
+        stages.push(
+            Stage::new("route")
+                .param("kind", kind_name(type_))
+                .param("route", route),
+        );
+    }
+
+    if let Some(trs) = &compiled.transformation {
+        for (i, tr) in trs.iter().enumerate().filter(|(_, tr)| !tr.is_empty()) {
+            let mut stage = Stage::new("transform").param("read", i + 1);
+            stage.labels.extend(tr.iter().cloned());
+            stages.push(stage);
+        }
+    }
+
+    if let Some(config) = &options.merge {
+        stages.push(
+            Stage::new("merge")
+                .param("min_overlap", config.min_overlap)
+                .param("max_diff", config.max_diff)
+                .param("out", &config.out),
+        );
+    }
+
+    if let Some(sharding) = options.shards {
+        stages.push(match sharding {
+            Sharding::Size(ShardSize::Reads(n)) => Stage::new("shard").param("reads", n),
+            Sharding::Size(ShardSize::Bytes(n)) => Stage::new("shard").param("bytes", n),
+            Sharding::RoundRobin(n) => Stage::new("split").param("files", n),
+        });
+    }
+
+    stages
+}
+
+// one stage a line, `name key=value.. [labels]`
+pub fn text(stages: &[Stage]) -> String {
+    let mut out = String::new();
+
+    for stage in stages {
+        write!(out, "{:<12}", stage.name).unwrap();
+        for (key, value) in &stage.params {
+            write!(out, " {key}={value}").unwrap();
+        }
+        if !stage.labels.is_empty() {
+            write!(out, " [{}]", stage.labels.join(" ")).unwrap();
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+// `{"spec_version":2,"stages":[{"stage":..,"params":{..},"labels":[..]}]}`
+pub fn json(stages: &[Stage]) -> String {
+    let stages = stages
+        .iter()
+        .map(|stage| {
+            let params = stage
+                .params
+                .iter()
+                .map(|(key, value)| format!("{}:{}", quote(key), quote(value)))
+                .collect::<Vec<_>>();
+            let labels = stage.labels.iter().map(|l| quote(l)).collect::<Vec<_>>();
+
+            format!(
+                "{{\"stage\":{},\"params\":{{{}}},\"labels\":[{}]}}",
+                quote(stage.name),
+                params.join(","),
+                labels.join(",")
+            )
+        })
+        .collect::<Vec<_>>();
+
+    format!(
+        "{{\"spec_version\":{SPEC_VERSION},\"stages\":[{}]}}\n",
+        stages.join(",")
+    )
+}
+
+pub(crate) fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
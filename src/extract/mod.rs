@@ -0,0 +1,296 @@
+/*
+   Segments of a single record cut in memory, for tools embedding seqproc
+   that hold their reads as bytes rather than in fastq files. The pieces of
+   each read are walked as the pipeline does: fixed lengths are cut in
+   place, a fixed sequence is searched for from the end of the piece before
+   it or in its window, allowing the mismatches of a `hamming` around it,
+   and a ranged or unbounded piece before a fixed sequence ends where the
+   sequence is found. Segments are returned as they were cut, before the
+   functions on them and the transformation, with their range in the read
+   so qualities can be cut alongside.
+
+   Geometries the walk cannot follow give `ExtractError::Unsupported`:
+   optional groups, alternatives, repeats, steps before the geometry, and
+   ranged pieces whose length is told by their whitelist.
+*/
+
+use std::{fmt, ops::Range};
+
+use crate::{
+    compile::{functions::CompiledFunction, utils::GeometryMeta, CompiledData},
+    parser::{Miss, Size, Type},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    // the read it was cut from, from 1
+    pub read: usize,
+    pub type_: Type,
+    pub label: Option<String>,
+    pub range: Range<usize>,
+    pub seq: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Extraction {
+    pub segments: Vec<Segment>,
+    // a fixed sequence with `miss=keep-raw` was not found, the record is
+    // kept as it was read and has no segments
+    pub raw: bool,
+}
+
+impl Extraction {
+    pub fn get(&self, label: &str) -> Option<&Segment> {
+        self.segments
+            .iter()
+            .find(|s| s.label.as_deref() == Some(label))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtractError {
+    // the geometry describes another number of reads
+    Reads { expected: usize, found: usize },
+    // the record was dropped at this piece of the read
+    Unmatched { read: usize, piece: String },
+    Unsupported(String),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExtractError::Reads { expected, found } => {
+                write!(f, "the geometry has {expected} reads, {found} were given")
+            }
+            ExtractError::Unmatched { read, piece } => {
+                write!(f, "read {read} does not match at {piece}")
+            }
+            ExtractError::Unsupported(what) => {
+                write!(f, "{what} cannot be extracted from a single record")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+// a ranged or unbounded piece waiting for the fixed sequence after it
+struct Pending<'a> {
+    gm: &'a GeometryMeta,
+    start: usize,
+    min: usize,
+    max: Option<usize>,
+}
+
+// the mismatches allowed in a fixed sequence by a `hamming` around it
+fn mismatches(gm: &GeometryMeta) -> usize {
+    match gm.stack.last() {
+        Some((CompiledFunction::Hamming(n), _)) => *n,
+        _ => 0,
+    }
+}
+
+// the first start in `starts` where `seq` is found in `read`
+fn find(read: &[u8], seq: &[u8], starts: Range<usize>, max_dist: usize) -> Option<usize> {
+    starts
+        .take_while(|start| start + seq.len() <= read.len())
+        .find(|start| {
+            let diff = seq
+                .iter()
+                .zip(&read[*start..])
+                .filter(|(s, r)| !s.eq_ignore_ascii_case(r))
+                .count();
+
+            diff <= max_dist
+        })
+}
+
+fn segment(read: usize, gm: &GeometryMeta, seq: &[u8], range: Range<usize>) -> Segment {
+    Segment {
+        read,
+        type_: gm.expr.0.type_.clone(),
+        label: gm.expr.0.label.clone(),
+        seq: seq[range.clone()].to_vec(),
+        range,
+    }
+}
+
+fn unmatched(read: usize, gm: &GeometryMeta) -> ExtractError {
+    ExtractError::Unmatched {
+        read,
+        piece: gm.expr.0.to_string(),
+    }
+}
+
+// the segments of one read, `None` where it is kept raw
+fn extract_read(
+    n: usize,
+    geometry: &[GeometryMeta],
+    seq: &[u8],
+) -> Result<Option<Vec<Segment>>, ExtractError> {
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    let mut pending: Option<Pending> = None;
+
+    for gm in geometry {
+        match &gm.expr.0.size {
+            Size::FixedSeq((fixed, _), miss, window) => {
+                let starts = match window {
+                    Some((a, b)) => pos + a..pos + b + 1,
+                    None => pos + pending.as_ref().map_or(0, |p| p.min)..seq.len(),
+                };
+
+                let hit = match find(seq, fixed.as_bytes(), starts, mismatches(gm)) {
+                    Some(hit) => hit,
+                    None => match miss {
+                        Miss::Drop => return Err(unmatched(n, gm)),
+                        Miss::KeepRaw => return Ok(None),
+                        Miss::Fallback => pos + pending.as_ref().and_then(|p| p.max).unwrap_or(0),
+                    },
+                };
+                if hit + fixed.len() > seq.len() {
+                    return Err(unmatched(n, gm));
+                }
+
+                if let Some(p) = pending.take() {
+                    let len = hit - p.start;
+                    if len < p.min || p.max.is_some_and(|max| len > max) {
+                        return Err(unmatched(n, p.gm));
+                    }
+                    segments.push(segment(n, p.gm, seq, p.start..hit));
+                }
+
+                segments.push(segment(n, gm, seq, hit..hit + fixed.len()));
+                pos = hit + fixed.len();
+            }
+            _ if pending.is_some() => {
+                return Err(ExtractError::Unsupported(format!(
+                    "{} after a piece of variable length",
+                    gm.expr.0
+                )))
+            }
+            Size::FixedLen((len, _)) => {
+                if pos + len > seq.len() {
+                    return Err(unmatched(n, gm));
+                }
+
+                segments.push(segment(n, gm, seq, pos..pos + len));
+                pos += len;
+            }
+            Size::RangedLen(((a, b), _)) => {
+                if gm.whitelist().is_some() {
+                    return Err(ExtractError::Unsupported(format!(
+                        "{} with the length of its whitelist",
+                        gm.expr.0
+                    )));
+                }
+
+                pending = Some(Pending {
+                    gm,
+                    start: pos,
+                    min: *a,
+                    max: Some(*b),
+                });
+            }
+            Size::UnboundedLen => {
+                pending = Some(Pending {
+                    gm,
+                    start: pos,
+                    min: 0,
+                    max: None,
+                })
+            }
+        }
+    }
+
+    // a variable piece at the end takes the rest of the read, up to its maximum
+    if let Some(p) = pending {
+        let end = p.max.map_or(seq.len(), |max| seq.len().min(p.start + max));
+        if end < p.start + p.min {
+            return Err(unmatched(n, p.gm));
+        }
+
+        segments.push(segment(n, p.gm, seq, p.start..end));
+    }
+
+    Ok(Some(segments))
+}
+
+// whether the walk can follow the geometry at all, whatever the reads
+pub fn supported(compiled: &CompiledData) -> Result<(), ExtractError> {
+    if compiled.repeat.is_some() {
+        return Err(ExtractError::Unsupported("a repeat".to_string()));
+    }
+    if !compiled.pre.is_empty() {
+        return Err(ExtractError::Unsupported(
+            "a step before the geometry".to_string(),
+        ));
+    }
+    if compiled.optional.iter().any(|o| !o.is_empty()) {
+        return Err(ExtractError::Unsupported("an optional group".to_string()));
+    }
+    if compiled.alternatives.iter().any(|a| !a.is_empty()) {
+        return Err(ExtractError::Unsupported("alternatives".to_string()));
+    }
+
+    Ok(())
+}
+
+// the segments of a record, given the sequence of each of its reads
+pub fn process_record(
+    compiled: &CompiledData,
+    reads: &[&[u8]],
+) -> Result<Extraction, ExtractError> {
+    if reads.len() != compiled.geometry.len() {
+        return Err(ExtractError::Reads {
+            expected: compiled.geometry.len(),
+            found: reads.len(),
+        });
+    }
+
+    supported(compiled)?;
+
+    let mut extraction = Extraction::default();
+    for (i, (geometry, seq)) in compiled.geometry.iter().zip(reads).enumerate() {
+        match extract_read(i + 1, geometry, seq)? {
+            Some(segments) => extraction.segments.extend(segments),
+            None => {
+                return Ok(Extraction {
+                    segments: Vec::new(),
+                    raw: true,
+                })
+            }
+        }
+    }
+
+    Ok(extraction)
+}
+
+// the segments of the `read`th read of a record alone, from 1, whatever its
+// mates hold
+pub fn process_read(
+    compiled: &CompiledData,
+    read: usize,
+    seq: &[u8],
+) -> Result<Extraction, ExtractError> {
+    let geometry = read
+        .checked_sub(1)
+        .and_then(|i| compiled.geometry.get(i))
+        .ok_or(ExtractError::Reads {
+            expected: compiled.geometry.len(),
+            found: read,
+        })?;
+
+    supported(compiled)?;
+
+    Ok(match extract_read(read, geometry, seq)? {
+        Some(segments) => Extraction {
+            segments,
+            raw: false,
+        },
+        None => Extraction {
+            segments: Vec::new(),
+            raw: true,
+        },
+    })
+}
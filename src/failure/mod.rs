@@ -0,0 +1,103 @@
+/*
+   Why a run failed, for workflow managers deciding whether to retry.
+   Each kind of failure exits with its own code, and `--error-json`
+   writes it to a sidecar file. Stages fail by panicking with a
+   `Failure`, any other panic is an internal error. The panic is taken
+   from the thread it happened on, since a worker thread panicking only
+   reaches the main thread as a generic message.
+*/
+
+use std::{any::Any, fmt, panic};
+
+use crate::explain::quote;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    // the geometry does not lex, parse or compile
+    Geometry,
+    // a file could not be read or written
+    Io,
+    // too few reads passed `--min-pass-rate`
+    LowPassRate,
+    // a read is not as long as the geometry's `assert_len`
+    ReadLength,
+    Internal,
+}
+
+impl FailureKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureKind::Geometry => 2,
+            FailureKind::Io => 3,
+            FailureKind::LowPassRate => 4,
+            FailureKind::ReadLength => 5,
+            // EX_SOFTWARE
+            FailureKind::Internal => 70,
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailureKind::Geometry => write!(f, "geometry"),
+            FailureKind::Io => write!(f, "io"),
+            FailureKind::LowPassRate => write!(f, "low_pass_rate"),
+            FailureKind::ReadLength => write!(f, "read_length"),
+            FailureKind::Internal => write!(f, "internal"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, message: impl ToString) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    // the failure a panic carries, anything but a `Failure` is internal
+    pub fn from_panic(payload: &(dyn Any + Send)) -> Self {
+        if let Some(failure) = payload.downcast_ref::<Failure>() {
+            failure.clone()
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            Self::new(FailureKind::Internal, msg)
+        } else if let Some(msg) = payload.downcast_ref::<&str>() {
+            Self::new(FailureKind::Internal, msg)
+        } else {
+            Self::new(FailureKind::Internal, "the run failed")
+        }
+    }
+
+    // `{"kind":"io","exit_code":3,"message":".."}`
+    pub fn json(&self) -> String {
+        format!(
+            "{{\"kind\":{},\"exit_code\":{},\"message\":{}}}\n",
+            quote(&self.kind.to_string()),
+            self.kind.exit_code(),
+            quote(&self.message)
+        )
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// stop the run with a failure of this kind
+pub fn fail(kind: FailureKind, message: impl ToString) -> ! {
+    panic::panic_any(Failure::new(kind, message))
+}
+
+pub fn io_failed(e: impl ToString) -> ! {
+    fail(FailureKind::Io, e)
+}
@@ -0,0 +1,283 @@
+/*
+   Multi-modal runs, such as CITE-seq with cell hashing, where a library of
+   antibody derived tags (ADT) or hashtags (HTO) is sequenced with the gene
+   expression reads. `--features features.csv` lists each feature barcode
+   as `<name>,<sequence>,<modality>`, and `--feature-segment` names the
+   segment of the geometry it is read from. A read whose segment starts
+   with a feature, allowing `--feature-mismatch` mismatches, goes to the
+   outputs of its modality given by `--modality-out`, any other read to the
+   outputs of the run. Reads matching two features equally well match none.
+
+   A modality given to `--modality-out` without outputs has them named by
+   `--out-template`, so that they drop into the layout downstream tools
+   expect: `{sample}_S{idx}_R{read}_001.fastq.gz` names them as bcl2fastq
+   does, with the modality as the sample, its number among the modalities
+   from 1 as the index, and the read.
+*/
+
+use std::{
+    fs, io,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[derive(Debug)]
+pub struct Feature {
+    pub name: String,
+    pub seq: Vec<u8>,
+    // index among the modalities
+    pub modality: usize,
+}
+
+#[derive(Debug)]
+pub struct Features {
+    pub features: Vec<Feature>,
+    // modalities with their outputs, in the order of `--modality-out`
+    pub modalities: Vec<(String, [String; 2])>,
+    // the segment holding the feature barcode, by its name in the geometry
+    pub segment: String,
+    pub max_mismatch: usize,
+    // reads of each feature, then of none
+    counts: Vec<AtomicU64>,
+}
+
+// a `<modality>=<r1 out>[,<r2 out>]` given to `--modality-out`, or a bare
+// `<modality>` whose outputs are named by `--out-template`
+pub fn modality_output(spec: &str) -> Result<(String, [String; 2]), String> {
+    let (modality, outs) = match spec.split_once('=') {
+        Some((modality, outs)) if !outs.is_empty() => (modality, outs),
+        Some(_) => ("", ""),
+        None => (spec, ""),
+    };
+    if modality.is_empty() {
+        return Err(format!(
+            "expected <modality>[=<r1 out>[,<r2 out>]], found `{spec}`"
+        ));
+    }
+
+    let (out1, out2) = outs.split_once(',').unwrap_or((outs, ""));
+
+    Ok((modality.to_string(), [out1.to_string(), out2.to_string()]))
+}
+
+// the fields an `--out-template` may hold
+const TEMPLATE_FIELDS: [&str; 3] = ["sample", "idx", "read"];
+
+// an `--out-template`, naming each modality apart
+pub fn out_template(spec: &str) -> Result<String, String> {
+    let mut rest = spec;
+    while let Some(start) = rest.find('{') {
+        let len = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("`{spec}` has a `{{` that is not closed"))?;
+        let field = &rest[start + 1..start + len];
+
+        if !TEMPLATE_FIELDS.contains(&field) {
+            return Err(format!(
+                "`{spec}` has the field {{{field}}}, expected {{sample}}, {{idx}} or {{read}}"
+            ));
+        }
+
+        rest = &rest[start + len + 1..];
+    }
+
+    if !spec.contains("{sample}") && !spec.contains("{idx}") {
+        return Err(format!(
+            "`{spec}` names every modality alike, it needs {{sample}} or {{idx}}"
+        ));
+    }
+
+    Ok(spec.to_string())
+}
+
+// the outputs of the `idx`th modality, from 1, by `template`, for runs
+// writing `reads` reads
+pub fn template_outputs(
+    template: &str,
+    modality: &str,
+    idx: usize,
+    reads: usize,
+) -> Result<[String; 2], String> {
+    if reads == 2 && !template.contains("{read}") {
+        return Err(format!(
+            "`{template}` names both reads alike, it needs {{read}}"
+        ));
+    }
+
+    let name = |read: usize| {
+        template
+            .replace("{sample}", modality)
+            .replace("{idx}", &idx.to_string())
+            .replace("{read}", &read.to_string())
+    };
+
+    Ok(match reads {
+        2 => [name(1), name(2)],
+        _ => [name(1), String::new()],
+    })
+}
+
+impl Features {
+    pub fn new(
+        csv: &str,
+        segment: String,
+        max_mismatch: usize,
+        outputs: Vec<(String, [String; 2])>,
+    ) -> Result<Self, String> {
+        if let Some((modality, _)) = outputs.iter().find(|(_, [out1, _])| out1.is_empty()) {
+            return Err(format!(
+                "modality {modality} has no outputs, give them as {modality}=<r1 out> or with --out-template"
+            ));
+        }
+
+        let mut features = Vec::new();
+
+        for (n, line) in csv.lines().enumerate() {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+
+            match fields[..] {
+                [""] => {}
+                [name, seq, modality]
+                    if !name.is_empty() && !seq.is_empty() && !modality.is_empty() =>
+                {
+                    if !seq.bytes().all(|b| b"ACGTNacgtn".contains(&b)) {
+                        // a header line names the columns
+                        if n == 0 {
+                            continue;
+                        }
+                        return Err(format!("line {} of the features is not a sequence", n + 1));
+                    }
+
+                    let modality = match outputs.iter().position(|(m, _)| m == modality) {
+                        Some(i) => i,
+                        None => return Err(format!(
+                            "feature {name} is of modality {modality}, which has no --modality-out"
+                        )),
+                    };
+
+                    features.push(Feature {
+                        name: name.to_string(),
+                        seq: seq.to_ascii_uppercase().into_bytes(),
+                        modality,
+                    });
+                }
+                _ => {
+                    return Err(format!(
+                        "line {} of the features is not `<name>,<sequence>,<modality>`",
+                        n + 1
+                    ))
+                }
+            }
+        }
+
+        if features.is_empty() {
+            return Err("no features are given".to_string());
+        }
+
+        Ok(Self {
+            counts: (0..=features.len()).map(|_| AtomicU64::new(0)).collect(),
+            features,
+            modalities: outputs,
+            segment,
+            max_mismatch,
+        })
+    }
+
+    pub fn open(
+        path: &str,
+        segment: String,
+        max_mismatch: usize,
+        outputs: Vec<(String, [String; 2])>,
+    ) -> io::Result<Self> {
+        let csv = fs::read_to_string(path)?;
+
+        Self::new(&csv, segment, max_mismatch, outputs)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // the feature starting `seq`, counted
+    pub fn classify(&self, seq: &[u8]) -> Option<&Feature> {
+        let mut best: Option<(usize, usize)> = None;
+        let mut tied = false;
+
+        for (i, feature) in self.features.iter().enumerate() {
+            if seq.len() < feature.seq.len() {
+                continue;
+            }
+
+            let diff = feature
+                .seq
+                .iter()
+                .zip(seq)
+                .filter(|(f, s)| !f.eq_ignore_ascii_case(s))
+                .count();
+
+            match best {
+                _ if diff > self.max_mismatch => {}
+                Some((best_diff, _)) if diff > best_diff => {}
+                Some((best_diff, _)) if diff == best_diff => tied = true,
+                _ => {
+                    best = Some((diff, i));
+                    tied = false;
+                }
+            }
+        }
+
+        let i = best.filter(|_| !tied).map(|(_, i)| i);
+        self.counts[i.unwrap_or(self.features.len())].fetch_add(1, Ordering::Relaxed);
+
+        i.map(|i| &self.features[i])
+    }
+
+    pub fn count(&self, feature: usize) -> u64 {
+        self.counts[feature].load(Ordering::Relaxed)
+    }
+
+    pub fn unmatched(&self) -> u64 {
+        self.count(self.features.len())
+    }
+
+    // the reads of each feature by `features.<modality>.<feature>` in the
+    // `--report`
+    pub fn metrics(&self) -> Vec<(String, f64)> {
+        let mut metrics = self
+            .features
+            .iter()
+            .enumerate()
+            .map(|(i, feature)| {
+                (
+                    format!(
+                        "features.{}.{}",
+                        self.modalities[feature.modality].0, feature.name
+                    ),
+                    self.count(i) as f64,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        metrics.push(("features.unmatched".to_string(), self.unmatched() as f64));
+        metrics
+    }
+
+    // reads of each modality, and of each feature
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+
+        for (m, (modality, _)) in self.modalities.iter().enumerate() {
+            let of_modality = self
+                .features
+                .iter()
+                .enumerate()
+                .filter(|(_, f)| f.modality == m)
+                .collect::<Vec<_>>();
+            let reads = of_modality.iter().map(|(i, _)| self.count(*i)).sum::<u64>();
+
+            report.push_str(&format!("{modality}: {reads} reads\n"));
+            for (i, feature) in of_modality {
+                report.push_str(&format!("  {}: {}\n", feature.name, self.count(i)));
+            }
+        }
+
+        report.push_str(&format!("{} reads matching no feature\n", self.unmatched()));
+        report
+    }
+}
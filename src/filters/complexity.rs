@@ -0,0 +1,174 @@
+/*
+   Low complexity biological reads, such as adapter dimers, and the poly-G
+   tails of two colour chemistry, where a dark cycle reads as G.
+   `--trim-poly-g` trims a run of Gs from the end of each biological read,
+   allowing one other base in every eight, and `--dust` drops reads whose
+   biological read scores above the given DUST score once trimmed. Reads
+   left without bases by the trimming are dropped as poly-G. How many reads
+   were trimmed and dropped is printed once the run is done.
+
+   The DUST score counts the repeats of each triplet of bases, a random
+   sequence scores below 1, a dinucleotide repeat about a quarter of its
+   length and a homopolymer about half of it.
+*/
+
+use std::{
+    fmt::Write as _,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// shorter runs of Gs are left, they are as likely to be sequence
+pub const MIN_POLY_G: usize = 10;
+
+// the length of the run of Gs ending `seq`, if at least `MIN_POLY_G` long
+pub fn poly_g_len(seq: &[u8]) -> usize {
+    let mut best = 0;
+    let mut mismatches = 0;
+
+    for (i, base) in seq.iter().rev().enumerate() {
+        let len = i + 1;
+
+        if base.eq_ignore_ascii_case(&b'G') {
+            if mismatches <= len / 8 {
+                best = len;
+            }
+        } else {
+            mismatches += 1;
+            if mismatches > len / 8 + 1 {
+                break;
+            }
+        }
+    }
+
+    if best >= MIN_POLY_G {
+        best
+    } else {
+        0
+    }
+}
+
+// repeats of each triplet over the triplets of `seq` but one
+pub fn dust_score(seq: &[u8]) -> f64 {
+    if seq.len() < 4 {
+        return 0.0;
+    }
+
+    let mut counts = [0usize; 64];
+    let index = |b: &u8| match b.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    };
+
+    for triplet in seq.windows(3) {
+        if let [Some(a), Some(b), Some(c)] = [&triplet[0], &triplet[1], &triplet[2]].map(index) {
+            counts[a * 16 + b * 4 + c] += 1;
+        }
+    }
+
+    let repeats = counts
+        .iter()
+        .map(|c| c * c.saturating_sub(1) / 2)
+        .sum::<usize>();
+
+    repeats as f64 / (seq.len() - 3) as f64
+}
+
+// the checks of the biological reads, with the reads each one caught
+#[derive(Debug, Default)]
+pub struct ComplexityFilter {
+    pub poly_g: bool,
+    pub max_dust: Option<f64>,
+    trimmed: AtomicUsize,
+    trimmed_bases: AtomicUsize,
+    poly_g_reads: AtomicUsize,
+    low_complexity: AtomicUsize,
+}
+
+impl ComplexityFilter {
+    pub fn new(poly_g: bool, max_dust: Option<f64>) -> Self {
+        Self {
+            poly_g,
+            max_dust,
+            ..Default::default()
+        }
+    }
+
+    // the length a biological read is trimmed to and whether it is kept.
+    // an empty read comes from an optional group and is not checked
+    pub fn check(&self, seq: &[u8]) -> (usize, bool) {
+        if seq.is_empty() {
+            return (0, true);
+        }
+
+        let poly_g = if self.poly_g { poly_g_len(seq) } else { 0 };
+        let len = seq.len() - poly_g;
+
+        if poly_g > 0 {
+            self.trimmed.fetch_add(1, Ordering::Relaxed);
+            self.trimmed_bases.fetch_add(poly_g, Ordering::Relaxed);
+        }
+
+        if len == 0 {
+            self.poly_g_reads.fetch_add(1, Ordering::Relaxed);
+            return (0, false);
+        }
+
+        let trimmed = &seq[..len];
+        if self.max_dust.is_some_and(|max| dust_score(trimmed) > max) {
+            self.low_complexity.fetch_add(1, Ordering::Relaxed);
+            return (len, false);
+        }
+
+        (len, true)
+    }
+
+    // the counts of `report` by their name in the `--report`
+    pub fn metrics(&self) -> Vec<(String, f64)> {
+        let count = |n: &AtomicUsize| n.load(Ordering::Relaxed) as f64;
+        let mut metrics = Vec::new();
+
+        if self.poly_g {
+            metrics.push(("poly_g.trimmed".to_string(), count(&self.trimmed)));
+            metrics.push(("poly_g.bases".to_string(), count(&self.trimmed_bases)));
+            metrics.push(("poly_g.dropped".to_string(), count(&self.poly_g_reads)));
+        }
+        if self.max_dust.is_some() {
+            metrics.push((
+                "low_complexity.dropped".to_string(),
+                count(&self.low_complexity),
+            ));
+        }
+
+        metrics
+    }
+
+    pub fn report(&self) -> String {
+        let count = |n: &AtomicUsize| n.load(Ordering::Relaxed);
+        let mut out = String::new();
+
+        if self.poly_g {
+            writeln!(
+                out,
+                "Poly-G: {} reads trimmed of {} bases, {} left empty and dropped",
+                count(&self.trimmed),
+                count(&self.trimmed_bases),
+                count(&self.poly_g_reads)
+            )
+            .unwrap();
+        }
+
+        if let Some(max) = self.max_dust {
+            writeln!(
+                out,
+                "Low complexity: {} reads scoring above {max} dropped",
+                count(&self.low_complexity)
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
@@ -0,0 +1,142 @@
+/*
+   Streaming removal of obvious PCR duplicates.
+   A read is keyed by its barcodes, UMIs and the first
+   few bases of its biological sequence. The approximate
+   mode only remembers a 64 bit hash of each key, so a
+   hash collision may drop a unique read. The exact mode
+   appends every key to a table on disk and only keeps
+   the hash index in memory, comparing full keys when
+   two hashes collide.
+*/
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fs::{File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DedupMode {
+    Approximate,
+    Exact(PathBuf),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DedupConfig {
+    pub prefix_len: usize,
+    pub mode: DedupMode,
+    pub flag: bool,
+}
+
+enum KeyStore {
+    Hashes(HashSet<u64>),
+    Table {
+        index: HashMap<u64, Vec<u64>>,
+        file: File,
+        path: PathBuf,
+        len: u64,
+    },
+}
+
+pub struct DuplicateSet {
+    store: Mutex<KeyStore>,
+}
+
+fn hash_key(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl DuplicateSet {
+    pub fn new(mode: &DedupMode) -> io::Result<Self> {
+        let store = match mode {
+            DedupMode::Approximate => KeyStore::Hashes(HashSet::new()),
+            DedupMode::Exact(path) => KeyStore::Table {
+                index: HashMap::new(),
+                file: OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)?,
+                path: path.clone(),
+                len: 0,
+            },
+        };
+
+        Ok(Self {
+            store: Mutex::new(store),
+        })
+    }
+
+    // returns true if the key has not been seen before
+    pub fn insert(&self, key: &[u8]) -> io::Result<bool> {
+        let hash = hash_key(key);
+        let mut store = self.store.lock().unwrap();
+
+        match &mut *store {
+            KeyStore::Hashes(hashes) => Ok(hashes.insert(hash)),
+            KeyStore::Table {
+                index, file, len, ..
+            } => {
+                let offsets = index.entry(hash).or_default();
+
+                for offset in offsets.iter() {
+                    if read_key(file, *offset)? == key {
+                        return Ok(false);
+                    }
+                }
+
+                file.seek(SeekFrom::End(0))?;
+                file.write_all(&(key.len() as u32).to_le_bytes())?;
+                file.write_all(key)?;
+
+                offsets.push(*len);
+                *len += 4 + key.len() as u64;
+
+                Ok(true)
+            }
+        }
+    }
+}
+
+fn read_key(file: &mut File, offset: u64) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut len)?;
+
+    let mut key = vec![0; u32::from_le_bytes(len) as usize];
+    file.read_exact(&mut key)?;
+
+    Ok(key)
+}
+
+impl Drop for DuplicateSet {
+    fn drop(&mut self) {
+        if let Ok(KeyStore::Table { path, .. }) = self.store.get_mut() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// join the key segments, only the first `prefix_len` bases of read segments are used
+pub fn dedup_key(barcodes: &[&[u8]], reads: &[&[u8]], prefix_len: usize) -> Vec<u8> {
+    let mut key = Vec::new();
+
+    for seq in barcodes {
+        key.extend_from_slice(seq);
+        key.push(b'|');
+    }
+
+    for seq in reads {
+        key.extend_from_slice(&seq[..prefix_len.min(seq.len())]);
+        key.push(b'|');
+    }
+
+    key
+}
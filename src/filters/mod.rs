@@ -0,0 +1,23 @@
+pub mod complexity;
+pub mod dedup;
+pub mod name;
+pub mod umi;
+pub mod whitelist;
+
+// what happens to a read failing a check
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnFail {
+    // the read is dropped
+    #[default]
+    Drop,
+    // the run stops, naming the read and the check
+    Abort,
+    // the read is kept as it was read, tagged `QC:fail:<check>`
+    Flag,
+}
+
+// the reads failing `selector` that are flagged by `OnFail::Flag`. reads
+// already kept raw failed an earlier check, so a read is flagged once
+pub fn flagged(selector: &str) -> String {
+    format!("!({selector}) & !seq1.*.raw")
+}
@@ -0,0 +1,91 @@
+/*
+   Filtering reads by name, to take a single lane or tile out of a run
+   or leave out reads from a bad one in the same pass. Patterns are
+   regexes matched against the header line of the first read, `@`
+   included, such as `^@A00.*:1:` for lane 1. Reads filtered out here
+   were not asked for rather than failing a check, so they are dropped
+   whatever `--strict` or `--flag-failed` say and do not count against
+   `--min-pass-rate`.
+
+   `--exclude-tiles 1:1101,2204` drops the reads of tiles known to be bad,
+   by lane and tile or by tile in every lane, read from the coordinates in
+   the read name. Reads without coordinates are kept.
+*/
+
+use std::fmt;
+
+use regex::bytes::Regex;
+
+use crate::header::illumina_tile;
+
+// a tile of the flow cell, in one lane or in all of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub lane: Option<u32>,
+    pub tile: u32,
+}
+
+impl Tile {
+    pub fn contains(&self, lane: u32, tile: u32) -> bool {
+        self.lane.is_none_or(|l| l == lane) && self.tile == tile
+    }
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.lane {
+            Some(lane) => write!(f, "{lane}:{}", self.tile),
+            None => write!(f, "{}", self.tile),
+        }
+    }
+}
+
+// a `<lane>:<tile>` or `<tile>` given to `--exclude-tiles`
+pub fn tile(spec: &str) -> Result<Tile, String> {
+    let number = |n: &str| {
+        n.parse::<u32>()
+            .map_err(|_| format!("expected <lane>:<tile> or <tile>, found `{spec}`"))
+    };
+
+    match spec.split_once(':') {
+        Some((lane, tile)) => Ok(Tile {
+            lane: Some(number(lane)?),
+            tile: number(tile)?,
+        }),
+        None => Ok(Tile {
+            lane: None,
+            tile: number(spec)?,
+        }),
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NameFilter {
+    // reads whose header does not match are dropped
+    pub include: Option<Regex>,
+    // reads whose header matches are dropped
+    pub exclude: Option<Regex>,
+    // reads from these tiles are dropped
+    pub tiles: Vec<Tile>,
+}
+
+// a regex given to `--filter-name` or `--exclude-name`
+pub fn name_pattern(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid pattern `{pattern}`: {e}"))
+}
+
+impl NameFilter {
+    pub fn is_active(&self) -> bool {
+        self.include.is_some() || self.exclude.is_some() || !self.tiles.is_empty()
+    }
+
+    // whether a read is kept, given its name without the `@`
+    pub fn keeps(&self, name: &[u8]) -> bool {
+        let header = [b"@", name].concat();
+
+        self.include.as_ref().is_none_or(|re| re.is_match(&header))
+            && !self.exclude.as_ref().is_some_and(|re| re.is_match(&header))
+            && !illumina_tile(name)
+                .is_some_and(|(lane, tile)| self.tiles.iter().any(|t| t.contains(lane, tile)))
+    }
+}
@@ -0,0 +1,83 @@
+/*
+   Filters applied to UMI segments after extraction.
+   UMIs that are homopolymers, contain ambiguous
+   bases or have a low mean quality tend to produce
+   spurious molecules downstream, so they are either
+   dropped here or flagged in the read header.
+*/
+
+use std::fmt;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UmiFilter {
+    pub homopolymer: bool,
+    pub ambiguous: bool,
+    pub min_mean_qual: Option<f64>,
+    pub flag: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UmiFailure {
+    Homopolymer,
+    Ambiguous,
+    LowQuality,
+}
+
+impl fmt::Display for UmiFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use UmiFailure::*;
+        match self {
+            Homopolymer => write!(f, "homopolymer"),
+            Ambiguous => write!(f, "ambiguous"),
+            LowQuality => write!(f, "low_quality"),
+        }
+    }
+}
+
+impl UmiFilter {
+    pub fn is_active(&self) -> bool {
+        self.homopolymer || self.ambiguous || self.min_mean_qual.is_some()
+    }
+
+    // returns the first check the UMI fails, if any. an empty UMI comes from an
+    // optional group missing from the read and is not checked
+    pub fn check(&self, seq: &[u8], qual: Option<&[u8]>) -> Option<UmiFailure> {
+        if seq.is_empty() {
+            return None;
+        }
+
+        if self.ambiguous && seq.iter().any(|c| !b"ACGTacgt".contains(c)) {
+            return Some(UmiFailure::Ambiguous);
+        }
+
+        if self.homopolymer && is_homopolymer(seq) {
+            return Some(UmiFailure::Homopolymer);
+        }
+
+        if let (Some(min), Some(qual)) = (self.min_mean_qual, qual) {
+            if mean_qual(qual) < min {
+                return Some(UmiFailure::LowQuality);
+            }
+        }
+
+        None
+    }
+}
+
+pub fn is_homopolymer(seq: &[u8]) -> bool {
+    match seq.first() {
+        Some(first) => seq.iter().all(|c| c.eq_ignore_ascii_case(first)),
+        None => false,
+    }
+}
+
+// mean phred score of a phred+33 encoded quality string
+pub fn mean_qual(qual: &[u8]) -> f64 {
+    if qual.is_empty() {
+        return 0.0;
+    }
+
+    let sum: usize = qual.iter().map(|q| q.saturating_sub(33) as usize).sum();
+
+    sum as f64 / qual.len() as f64
+}
@@ -0,0 +1,186 @@
+/*
+   Barcode whitelists read for telling the length of a ranged barcode.
+   inDrop style geometries such as `1{map(b[8-12], "wl.txt", self)u[8]r:}`
+   have no anchor after the barcode, so where it ends is taken from the
+   whitelist it is mapped to: of the lengths it may have, the longest one
+   starting the read with a listed barcode is cut. Reads without one keep
+   the longest length and are left to the map.
+
+   With `--len-from-whitelist` the length is the one whose prefix is
+   closest to a listed barcode, within the mismatches `map_with_mismatch`
+   allows, so a barcode with a sequencing error is cut where it would be
+   corrected. Of equally close lengths the longest is taken. How many
+   reads were cut at each length, and how many needed a correction to
+   tell, is printed once the run is done.
+*/
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write as _,
+    fs, io,
+    ops::RangeInclusive,
+    sync::Mutex,
+};
+
+const BASES: [u8; 4] = *b"ACGT";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Whitelist {
+    barcodes: HashSet<Vec<u8>>,
+}
+
+impl Whitelist {
+    // one barcode a line, anything after the first tab or space is ignored
+    pub fn open(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{path}: {e}")))?;
+
+        Ok(Self::from_lines(&text))
+    }
+
+    pub fn from_lines(text: &str) -> Self {
+        let barcodes = text
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|barcode| barcode.to_ascii_uppercase().into_bytes())
+            .collect();
+
+        Self { barcodes }
+    }
+
+    pub fn contains(&self, barcode: &[u8]) -> bool {
+        self.barcodes.contains(barcode)
+    }
+
+    // the longest length in `lens` whose prefix of `seq` is listed
+    pub fn length(&self, seq: &[u8], lens: RangeInclusive<usize>) -> Option<usize> {
+        lens.rev()
+            .filter(|&len| len <= seq.len())
+            .find(|&len| self.contains(&seq[..len]))
+    }
+
+    // the length in `lens` whose prefix of `seq` is closest to a listed
+    // barcode, with its mismatches, if any is within `max_mismatch`
+    pub fn best_length(
+        &self,
+        seq: &[u8],
+        lens: RangeInclusive<usize>,
+        max_mismatch: usize,
+    ) -> Option<(usize, usize)> {
+        lens.rev()
+            .filter(|&len| len <= seq.len())
+            .filter_map(|len| Some((len, self.distance(&seq[..len], max_mismatch)?)))
+            .min_by_key(|&(_, dist)| dist)
+    }
+
+    // mismatches to the closest listed barcode of the same length
+    fn distance(&self, barcode: &[u8], max_mismatch: usize) -> Option<usize> {
+        let mut barcode = barcode.to_ascii_uppercase();
+
+        (0..=max_mismatch).find(|&dist| self.within(&mut barcode, 0, dist))
+    }
+
+    // whether a listed barcode differs from `barcode` in exactly `dist` of
+    // the positions from `from` on, trying each substitution in place
+    fn within(&self, barcode: &mut [u8], from: usize, dist: usize) -> bool {
+        if dist == 0 {
+            return self.contains(barcode);
+        }
+
+        for i in from..barcode.len() {
+            let base = barcode[i];
+
+            for sub in BASES.into_iter().filter(|&b| b != base) {
+                barcode[i] = sub;
+
+                if self.within(barcode, i + 1, dist - 1) {
+                    barcode[i] = base;
+                    return true;
+                }
+            }
+
+            barcode[i] = base;
+        }
+
+        false
+    }
+}
+
+// the lengths told by whitelists over a run, for `--len-from-whitelist`
+#[derive(Debug, Default)]
+pub struct WhitelistLengths {
+    counts: Mutex<LengthCounts>,
+}
+
+#[derive(Debug, Default)]
+struct LengthCounts {
+    // reads cut at each length
+    lengths: BTreeMap<usize, usize>,
+    // reads whose closest barcode needed a correction
+    corrected: usize,
+    // reads with no barcode close enough, kept at the longest length
+    unresolved: usize,
+}
+
+impl WhitelistLengths {
+    pub fn insert(&self, best: Option<(usize, usize)>) {
+        let mut counts = self.counts.lock().unwrap();
+
+        match best {
+            Some((len, dist)) => {
+                *counts.lengths.entry(len).or_default() += 1;
+                counts.corrected += (dist > 0) as usize;
+            }
+            None => counts.unresolved += 1,
+        }
+    }
+
+    pub fn resolved(&self) -> usize {
+        self.counts.lock().unwrap().lengths.values().sum()
+    }
+
+    // the counts of `report` by their name in the `--report`, the reads cut
+    // at each length by `whitelist_lengths.<length>`
+    pub fn metrics(&self) -> Vec<(String, f64)> {
+        let counts = self.counts.lock().unwrap();
+
+        let mut metrics = vec![
+            (
+                "whitelist_lengths.corrected".to_string(),
+                counts.corrected as f64,
+            ),
+            (
+                "whitelist_lengths.unresolved".to_string(),
+                counts.unresolved as f64,
+            ),
+        ];
+        metrics.extend(
+            counts
+                .lengths
+                .iter()
+                .map(|(len, n)| (format!("whitelist_lengths.{len}"), *n as f64)),
+        );
+
+        metrics
+    }
+
+    pub fn report(&self) -> String {
+        let counts = self.counts.lock().unwrap();
+        let mut out = String::new();
+
+        writeln!(
+            out,
+            "Lengths from whitelist: {} reads, {} corrected, {} unresolved",
+            counts.lengths.values().sum::<usize>(),
+            counts.corrected,
+            counts.unresolved
+        )
+        .unwrap();
+
+        for (len, n) in &counts.lengths {
+            writeln!(out, "{len:>6} {n}").unwrap();
+        }
+
+        out
+    }
+}
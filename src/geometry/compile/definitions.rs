@@ -56,32 +56,29 @@ pub fn compile_definitions(expr: Spanned<Expr>) -> Result<HashMap<String, Geomet
         let mut err: Option<Error> = None;
 
         for (def, def_span) in defs {
-            if let Expr::LabeledGeomPiece(label, expr) = def.clone() {
+            if let Expr::LabeledGeomPiece((l, span), expr) = def.clone() {
                 let expr = expr.deref().clone();
-                let label = label.deref().clone();
 
-                if let Expr::Label((l, span)) = label {
-                    let res = validate_definition(expr.clone(), l.clone());
-                    if let Err(e) = res {
-                        err = Some(e);
-                        break;
-                    } else if map.insert(l.clone(), res.ok().unwrap()).is_some() {
-                        err = Some(Error {
-                            // span labels
-                            span,
-                            msg: format!(
-                                "Repeated label in definition block: \"{}\" already defined",
-                                l
-                            ),
-                        });
-                        break;
-                    }
-                } else {
+                let res = validate_definition(expr.clone(), l.clone());
+                if let Err(e) = res {
+                    err = Some(e);
+                    break;
+                } else if map.insert(l.clone(), res.ok().unwrap()).is_some() {
                     err = Some(Error {
-                        span: def_span,
-                        msg: format!("Expected a Labeled Geometry piece, found: {}", def),
-                    })
+                        // span labels
+                        span,
+                        msg: format!(
+                            "Repeated label in definition block: \"{}\" already defined",
+                            l
+                        ),
+                    });
+                    break;
                 }
+            } else {
+                err = Some(Error {
+                    span: def_span,
+                    msg: format!("Expected a Labeled Geometry piece, found: {}", def),
+                })
             }
         }
 
@@ -0,0 +1,204 @@
+/*
+   Warnings about geometries which compile but may not do what was meant.
+   They are reported alongside the geometry and the run goes on, each one
+   points at a behavior that is otherwise only visible in the output.
+
+   A fixed sequence is searched for in the bases after the piece before
+   it, its window if it has one or the rest of the read, and one short
+   enough to be found there by chance cuts reads at random places. Each
+   base of the sequence makes a chance match four times less likely, a
+   base repeating the one before it only two times, as homopolymers are
+   common in reads.
+
+   A labeled piece the transformation leaves out is cut and then dropped,
+   which is more often a forgotten or misspelled label than meant.
+*/
+
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    functions::CompiledFunction,
+    utils::{Error, GeometryMeta, Transformation},
+};
+
+use crate::parser::{Size, Type};
+
+// the bases searched for a fixed sequence without a window, a short read
+pub const SEARCHED_BASES: usize = 150;
+
+// chance matches expected in the bases searched above which a sequence is
+// reported, a random sequence of 6 bases is found 0.04 times in 150 bases
+pub const MAX_CHANCE_MATCHES: f64 = 0.1;
+
+pub fn warnings(geometry: &[Vec<GeometryMeta>]) -> Vec<Error> {
+    let mut warnings = Vec::new();
+
+    for read in geometry {
+        for (i, gm) in read.iter().enumerate() {
+            let (piece, span) = &gm.expr;
+
+            // the piece before a fixed sequence gets every base up to it
+            if piece.size == Size::UnboundedLen
+                && piece.type_ != Type::Discard
+                && i + 1 < read.len()
+            {
+                warnings.push(Error {
+                    span: span.clone(),
+                    msg: format!(
+                        "{}{} is unbounded, every base up to the next fixed sequence is kept untrimmed",
+                        piece.type_, piece.size
+                    ),
+                });
+            }
+
+            warnings.extend(length_warnings(gm));
+            warnings.extend(anchor_warning(gm));
+        }
+    }
+
+    warnings
+}
+
+// the chance matches of a fixed sequence expected in the bases searched
+pub fn chance_matches(seq: &str, searched: usize) -> f64 {
+    let seq = seq.as_bytes();
+    let bits = (0..seq.len())
+        .map(|i| if i > 0 && seq[i] == seq[i - 1] { 1 } else { 2 })
+        .sum::<i32>();
+
+    searched.saturating_sub(seq.len() - 1) as f64 * 0.5f64.powi(bits)
+}
+
+fn anchor_warning(gm: &GeometryMeta) -> Option<Error> {
+    let (piece, span) = &gm.expr;
+    let (seq, window) = match &piece.size {
+        Size::FixedSeq((seq, _), _, window) => (seq, window),
+        _ => return None,
+    };
+
+    let searched = match window {
+        Some((start, end)) => end - start + seq.len(),
+        None => SEARCHED_BASES,
+    };
+    let matches = chance_matches(seq, searched);
+
+    (matches > MAX_CHANCE_MATCHES).then(|| Error {
+        span: span.clone(),
+        msg: format!(
+            "f[{seq}] is likely found by chance, about {matches:.2} times in {searched} bases searched"
+        ),
+    })
+}
+
+// functions padding or truncating a piece to a length it already has, or to
+// one base past its longest length
+fn length_warnings(gm: &GeometryMeta) -> Vec<Error> {
+    let mut bounds = match &gm.expr.0.size {
+        Size::FixedSeq((seq, _), ..) => (seq.len(), seq.len()),
+        Size::FixedLen((n, _)) => (*n, *n),
+        Size::RangedLen(((a, b), _)) => (*a, *b),
+        Size::UnboundedLen => return Vec::new(),
+    };
+
+    let mut warnings = Vec::new();
+
+    // the innermost function is applied first
+    for (fn_, span) in gm.stack.iter().rev() {
+        let (min, max) = bounds;
+        let warn = |msg: String| Error {
+            span: span.clone(),
+            msg,
+        };
+
+        bounds = match fn_ {
+            CompiledFunction::PadTo(n, _) | CompiledFunction::PadToLeft(n, _) => {
+                if *n <= min {
+                    warnings.push(warn(format!(
+                        "{fn_} has no effect, the piece is at least {min} bases"
+                    )));
+                } else if min < max && *n == max + 1 {
+                    warnings.push(warn(format!(
+                        "{fn_} pads one base past the longest piece, which is {max} bases"
+                    )));
+                }
+                (min.max(*n), max.max(*n))
+            }
+            CompiledFunction::TruncateTo(n) | CompiledFunction::TruncateToLeft(n) => {
+                if *n >= max {
+                    warnings.push(warn(format!(
+                        "{fn_} has no effect, the piece is at most {max} bases"
+                    )));
+                }
+                (min.min(*n), max.min(*n))
+            }
+            CompiledFunction::Pad(n, _) | CompiledFunction::PadLeft(n, _) => (min + n, max + n),
+            CompiledFunction::Truncate(n) | CompiledFunction::TruncateLeft(n) => {
+                (min.saturating_sub(*n), max.saturating_sub(*n))
+            }
+            CompiledFunction::Remove => (0, 0),
+            CompiledFunction::Reverse
+            | CompiledFunction::ReverseComp
+            | CompiledFunction::Hamming(_)
+            | CompiledFunction::FilterWithinDist(..) => bounds,
+            // the length of a normalized or mapped piece depends on the read
+            _ => break,
+        };
+    }
+
+    warnings
+}
+
+// the labels of the geometry which the transformation leaves out. the
+// pieces of a group named after it are reported as the group, at the first
+// of them, unless another of its pieces is used
+pub fn unused_labels(
+    geometry: &[Vec<GeometryMeta>],
+    transformation: &Transformation,
+    groups: &HashMap<String, Vec<String>>,
+) -> Vec<Error> {
+    let used = transformation
+        .iter()
+        .flatten()
+        .filter_map(|label| Some(label.split_once('.')?.1))
+        .collect::<HashSet<_>>();
+
+    // the pieces of a group without a label of their own
+    let named = |group: &str, members: &[String]| {
+        members
+            .iter()
+            .enumerate()
+            .filter(|(i, m)| **m == format!("{group}_{}", i + 1))
+            .map(|(_, m)| m.clone())
+            .collect::<Vec<_>>()
+    };
+    let member_of = |label: &str| {
+        groups
+            .iter()
+            .map(|(group, members)| (group, members, named(group, members)))
+            .find(|(_, _, named)| named.iter().any(|m| m == label))
+    };
+
+    let mut warnings = Vec::new();
+
+    for gm in geometry.iter().flatten() {
+        let (piece, span) = &gm.expr;
+        let label = match &piece.label {
+            Some(label) if !label.starts_with('_') && !used.contains(label.as_str()) => label,
+            _ => continue,
+        };
+
+        let unused = match member_of(label) {
+            Some((group, members, named)) => (named.first() == Some(label)
+                && !members.iter().any(|m| used.contains(m.as_str())))
+            .then_some(group),
+            None => Some(label),
+        };
+
+        warnings.extend(unused.map(|label| Error {
+            span: span.clone(),
+            msg: format!("<{label}> is labeled but left out of the transformation"),
+        }));
+    }
+
+    warnings
+}
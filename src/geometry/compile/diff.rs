@@ -0,0 +1,197 @@
+/*
+   Comparing geometries by what they do rather than how they are written,
+   for keeping presets and geometry repositories in check. `normalize`
+   leaves out spans, definitions and warnings, writing each piece back as
+   FGDL with its definitions filled in, so two geometries are equivalent
+   when their normalized forms are equal. `diff` lists what differs, piece
+   by piece for the reads and as a whole for the rest.
+*/
+
+use std::{fmt, ops::Range};
+
+use super::{
+    utils::{GeometryMeta, Transformation},
+    CompiledData,
+};
+use crate::parser::{PreStep, Type};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Normalized {
+    pub pre: Vec<PreStep>,
+    // each piece of each read, as FGDL
+    pub reads: Vec<Vec<String>>,
+    pub optional: Vec<Vec<Range<usize>>>,
+    pub alternatives: Vec<Vec<Vec<Range<usize>>>>,
+    pub header: Vec<Vec<(String, usize)>>,
+    pub repeat: Option<String>,
+    pub transformation: Option<Transformation>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Difference {
+    // a piece of a read, none on the side whose read is shorter
+    Piece {
+        read: usize,
+        index: usize,
+        left: Option<String>,
+        right: Option<String>,
+    },
+    // anything but the pieces, what it is and both sides
+    Other {
+        what: &'static str,
+        left: String,
+        right: String,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Piece {
+                read,
+                index,
+                left,
+                right,
+            } => {
+                write!(f, "read {} piece {}: ", read + 1, index + 1)?;
+                match (left, right) {
+                    (Some(left), Some(right)) => write!(f, "{left} -> {right}"),
+                    (Some(left), None) => write!(f, "{left} removed"),
+                    (None, Some(right)) => write!(f, "{right} added"),
+                    (None, None) => unreachable!(),
+                }
+            }
+            Difference::Other { what, left, right } => write!(f, "{what}: {left} -> {right}"),
+        }
+    }
+}
+
+// a piece as it would be written, `rev(b<brc>[16])`
+pub(super) fn piece(gm: &GeometryMeta) -> String {
+    let gp = &gm.expr.0;
+    let type_ = match gp.type_ {
+        Type::Barcode => "b",
+        Type::Umi => "u",
+        Type::Discard => "x",
+        Type::ReadSeq => "r",
+        Type::FixedSeq => "f",
+        Type::Header => "h",
+    };
+    let label = gp
+        .label
+        .as_ref()
+        .map(|l| format!("<{l}>"))
+        .unwrap_or_default();
+
+    // the stack is applied from the first function, the innermost
+    gm.stack.iter().fold(
+        format!("{type_}{label}{}", gp.size),
+        |inner, (fn_, _)| match fn_.to_string().split_once('(') {
+            Some((name, args)) => format!("{name}({inner}, {args}"),
+            None => format!("{fn_}({inner})"),
+        },
+    )
+}
+
+fn transformation(t: &Option<Transformation>) -> String {
+    match t {
+        Some(reads) => reads
+            .iter()
+            .enumerate()
+            .map(|(i, labels)| {
+                let labels = labels.iter().map(|l| format!("<{l}>")).collect::<String>();
+                format!("{}{{{labels}}}", i + 1)
+            })
+            .collect(),
+        None => "none".to_string(),
+    }
+}
+
+impl CompiledData {
+    pub fn normalize(&self) -> Normalized {
+        Normalized {
+            pre: self.pre.clone(),
+            reads: self
+                .geometry
+                .iter()
+                .map(|read| read.iter().map(piece).collect())
+                .collect(),
+            optional: self.optional.clone(),
+            alternatives: self.alternatives.clone(),
+            header: self.header.clone(),
+            repeat: self.repeat.clone(),
+            transformation: self.transformation.clone(),
+        }
+    }
+
+    // what `other` changes from this geometry, empty if they are equivalent
+    pub fn diff(&self, other: &CompiledData) -> Vec<Difference> {
+        let (left, right) = (self.normalize(), other.normalize());
+        let mut diffs = Vec::new();
+
+        for read in 0..left.reads.len().max(right.reads.len()) {
+            let l = left.reads.get(read).map(Vec::as_slice).unwrap_or_default();
+            let r = right.reads.get(read).map(Vec::as_slice).unwrap_or_default();
+
+            for index in 0..l.len().max(r.len()) {
+                let (l, r) = (l.get(index), r.get(index));
+                if l != r {
+                    diffs.push(Difference::Piece {
+                        read,
+                        index,
+                        left: l.cloned(),
+                        right: r.cloned(),
+                    });
+                }
+            }
+        }
+
+        let mut compare = |what, l: String, r: String| {
+            if l != r {
+                diffs.push(Difference::Other {
+                    what,
+                    left: l,
+                    right: r,
+                });
+            }
+        };
+
+        let pre = |n: &Normalized| {
+            let steps = n.pre.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            format!("pre{{{}}}", steps.join(" "))
+        };
+        compare("pre", pre(&left), pre(&right));
+        compare(
+            "reads",
+            left.reads.len().to_string(),
+            right.reads.len().to_string(),
+        );
+        compare(
+            "optional",
+            format!("{:?}", left.optional),
+            format!("{:?}", right.optional),
+        );
+        compare(
+            "alternatives",
+            format!("{:?}", left.alternatives),
+            format!("{:?}", right.alternatives),
+        );
+        compare(
+            "header",
+            format!("{:?}", left.header),
+            format!("{:?}", right.header),
+        );
+        compare(
+            "repeat",
+            format!("{:?}", left.repeat),
+            format!("{:?}", right.repeat),
+        );
+        compare(
+            "transformation",
+            transformation(&left.transformation),
+            transformation(&right.transformation),
+        );
+
+        diffs
+    }
+}
@@ -6,7 +6,7 @@
    place for this to happen will be useful
 */
 
-use std::ops::Deref;
+use std::{fmt, ops::Deref};
 
 use crate::parser::{Expr, Function, Spanned};
 
@@ -32,6 +32,30 @@ pub enum CompiledFunction {
     Hamming(usize),
 }
 
+impl fmt::Display for CompiledFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CompiledFunction::*;
+        match self {
+            Reverse => write!(f, "rev"),
+            ReverseComp => write!(f, "revcomp"),
+            Truncate(n) => write!(f, "trunc({n})"),
+            TruncateLeft(n) => write!(f, "trunc_left({n})"),
+            TruncateTo(n) => write!(f, "trunc_to({n})"),
+            TruncateToLeft(n) => write!(f, "trunc_to_left({n})"),
+            Remove => write!(f, "remove"),
+            Pad(n, nuc) => write!(f, "pad({n}, {nuc})"),
+            PadLeft(n, nuc) => write!(f, "pad_left({n}, {nuc})"),
+            PadTo(n, nuc) => write!(f, "pad_to({n}, {nuc})"),
+            PadToLeft(n, nuc) => write!(f, "pad_to_left({n}, {nuc})"),
+            Normalize => write!(f, "norm"),
+            Map(p, _) => write!(f, "map({p})"),
+            MapWithMismatch(p, _, n) => write!(f, "map_with_mismatch({p}, {n})"),
+            FilterWithinDist(p, n) => write!(f, "filter_within_dist({p}, {n})"),
+            Hamming(n) => write!(f, "hamming({n})"),
+        }
+    }
+}
+
 pub fn compile_fn(
     fn_: Spanned<Function>,
     parent_expr: Spanned<Expr>,
@@ -44,7 +68,7 @@ pub fn compile_fn(
         Function::Truncate(n) => CompiledFunction::Truncate(n),
         Function::TruncateLeft(n) => CompiledFunction::TruncateLeft(n),
         Function::TruncateTo(n) => CompiledFunction::TruncateTo(n),
-        Function::TruncateToLeft(n) => CompiledFunction::TruncateToLeft(n),
+        Function::TruncateToLeft(n) | Function::Slack(n) => CompiledFunction::TruncateToLeft(n),
         Function::Remove => CompiledFunction::Remove,
         Function::Pad(n, nuc) => CompiledFunction::Pad(n, nuc),
         Function::PadLeft(n, nuc) => CompiledFunction::PadLeft(n, nuc),
@@ -64,6 +88,13 @@ pub fn compile_fn(
             CompiledFunction::FilterWithinDist(path, mismatch)
         }
         Function::Hamming(n) => CompiledFunction::Hamming(n),
+        // taken apart where a read is compiled, anywhere else it is misplaced
+        Function::Anchored => {
+            return Err(Error {
+                span,
+                msg: "An anchored segment can only be written in a read".to_string(),
+            })
+        }
     };
 
     Ok((comp_fn, span))
@@ -103,16 +134,10 @@ fn compile_inner_expr(
 
     let geom_piece = {
         let (mut expr, span) = parent_expr;
-        loop {
-            match expr {
-                Expr::LabeledGeomPiece(_, b) => {
-                    let (gp, _) = b.deref();
-                    expr = gp.clone();
-                }
-                Expr::GeomPiece(_, _) => break,
-                Expr::Label(_) => todo!(),
-                _ => todo!(), // throw error
-            };
+
+        // anything but a piece is reported below
+        while let Expr::LabeledGeomPiece(_, gp) = expr {
+            expr = gp.0;
         }
 
         if let Expr::GeomPiece(type_, size) = expr {
@@ -0,0 +1,85 @@
+/*
+   `seqproc lint`, constructs which compile but are likely mistakes, each
+   with the rewrite that was probably meant where there is one. The
+   warnings of compiling, see `diagnostics`, are reported with them.
+*/
+
+use std::ops::Range;
+
+use super::{diff::piece, CompiledData};
+use crate::parser::{Size, Type};
+
+// shorter anchors are found by chance every few hundred bases
+pub const MIN_ANCHOR: usize = 5;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub span: Range<usize>,
+    pub msg: String,
+    // the rewrite suggested
+    pub fix: Option<String>,
+}
+
+pub fn lint(compiled: &CompiledData) -> Vec<Lint> {
+    let mut lints: Vec<Lint> = Vec::new();
+
+    for (n, read) in compiled.geometry.iter().enumerate() {
+        for (i, gm) in read.iter().enumerate() {
+            let (gp, span) = &gm.expr;
+            let mut flag = |msg: String, fix: String| {
+                lints.push(Lint {
+                    span: span.clone(),
+                    msg,
+                    fix: Some(fix),
+                })
+            };
+
+            // an unbounded discard is how the rest of a read is left out
+            if gp.type_ == Type::Discard && gp.size != Size::UnboundedLen && i + 1 == read.len() {
+                flag(
+                    format!("{} ends read {}, nothing follows it", piece(gm), n + 1),
+                    "write `x:` to discard the rest of the read".to_string(),
+                );
+            }
+
+            if let (Type::Barcode, None, Some((file, _))) = (&gp.type_, &gp.label, gm.whitelist()) {
+                flag(
+                    format!("barcode mapped to {file} has no label"),
+                    format!(
+                        "label it, as in `b<brc>{}`, to refer to the corrected barcode",
+                        gp.size
+                    ),
+                );
+            }
+
+            if let Size::FixedSeq((seq, _), ..) = &gp.size {
+                if seq.len() < MIN_ANCHOR {
+                    flag(
+                        format!(
+                            "f[{seq}] is {} bases, it is found by chance about every {} bases",
+                            seq.len(),
+                            4usize.pow(seq.len() as u32)
+                        ),
+                        format!(
+                            "extend it to at least {MIN_ANCHOR} bases of the library around it"
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    // a piece flagged here is not warned about again without its fix
+    for w in &compiled.warnings {
+        if !lints.iter().any(|l| l.span == w.span) {
+            lints.push(Lint {
+                span: w.span.clone(),
+                msg: w.msg.clone(),
+                fix: None,
+            });
+        }
+    }
+
+    lints.sort_by_key(|l| l.span.start);
+    lints
+}
@@ -1,28 +1,215 @@
 pub mod definitions;
+pub mod diagnostics;
+pub mod diff;
 pub mod functions;
+pub mod lint;
+pub mod plan;
+mod preprocess;
 pub mod reads;
 mod transformation;
 pub mod utils;
 
 use definitions::compile_definitions;
+use diagnostics::{unused_labels, warnings};
+use plan::{plan_reads, Step};
+use preprocess::compile_pre;
 use reads::compile_reads;
 use transformation::compile_transformation;
 use utils::Error;
 
-use std::{collections::HashMap, ops::Deref};
+use std::{
+    collections::HashMap,
+    ops::{Deref, Range},
+};
 
-use crate::parser::Expr;
+use crate::{
+    interpret::InterpretOptions,
+    parser::{Expr, PreStep, Size, Type},
+    sink::{
+        fastq::{TechPiece, TechRead},
+        tsv::Capture,
+    },
+};
 
 use self::{
+    functions::CompiledFunction,
     reads::standardize_geometry,
     transformation::label_transformation,
-    utils::{GeometryMeta, Interval, Transformation},
+    utils::{gp_return_type, GeometryMeta, Interval, Transformation},
 };
 
 #[derive(Debug)]
 pub struct CompiledData {
     pub geometry: Vec<Vec<GeometryMeta>>,
     pub transformation: Option<Transformation>,
+    pub repeat: Option<String>,
+    pub optional: Vec<Vec<Range<usize>>>,
+    pub alternatives: Vec<Vec<Vec<Range<usize>>>>,
+    pub header: Vec<Vec<(String, usize)>>,
+    pub pre: Vec<PreStep>,
+    // how each read is cut, see `plan`
+    pub plan: Vec<Vec<Step>>,
+    // compiled, but worth pointing out, see `diagnostics`
+    pub warnings: Vec<Error>,
+}
+
+impl CompiledData {
+    // correct the UMI labeled `label` to the set of UMIs in `file`, as
+    // barcodes are by `map_with_mismatch`. a UMI out of reach of the set is
+    // kept as it was read
+    pub fn whitelist_umi(
+        &mut self,
+        label: &str,
+        file: String,
+        mismatch: usize,
+    ) -> Result<(), String> {
+        let gm = self
+            .geometry
+            .iter_mut()
+            .flatten()
+            .find(|gm| gm.expr.0.label.as_deref() == Some(label));
+
+        let gm = match gm {
+            Some(gm) if gm.expr.0.type_ != Type::Umi => {
+                return Err(format!(
+                    "<{label}> is not a UMI, barcodes are mapped in the geometry"
+                ))
+            }
+            Some(gm) if gm.whitelist().is_some() => {
+                return Err(format!("<{label}> is already mapped in the geometry"))
+            }
+            Some(gm) => gm,
+            None => return Err(format!("no UMI is labeled <{label}>")),
+        };
+
+        // the UMI is corrected as it is written, after its other functions
+        let map = match mismatch {
+            0 => CompiledFunction::Map(file, Vec::new()),
+            n => CompiledFunction::MapWithMismatch(file, Vec::new(), n),
+        };
+        gm.stack.insert(0, (map, gm.expr.1.clone()));
+
+        gp_return_type(gm.clone())
+            .map_err(|e| format!("<{label}> cannot be whitelisted: {}", e.msg))?;
+
+        // a whitelisted ranged UMI may now be cut by itself
+        self.plan =
+            plan_reads(&self.geometry, &self.optional, &self.alternatives).map_err(|e| e.msg)?;
+
+        Ok(())
+    }
+
+    // the labels of the geometry's pieces, read by read
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = Vec::new();
+
+        let pieces = self
+            .geometry
+            .iter()
+            .flatten()
+            .filter_map(|gm| gm.expr.0.label.clone());
+        let header = self.header.iter().flatten().map(|(label, _)| label.clone());
+
+        for label in pieces.chain(header) {
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+
+        labels
+    }
+
+    // fails on the first label an option refers to that the geometry does
+    // not have, `(option, label)` pairs, listing the labels it has
+    pub fn check_labels(&self, referenced: &[(&str, &str)]) -> Result<(), String> {
+        let labels = self.labels();
+
+        match referenced
+            .iter()
+            .find(|(_, label)| !labels.iter().any(|l| l == label))
+        {
+            Some((option, label)) if labels.is_empty() => Err(format!(
+                "{option} refers to <{label}>, but the geometry labels no segments"
+            )),
+            Some((option, label)) => Err(format!(
+                "{option} refers to <{label}>, which the geometry does not label, expected one of {}",
+                labels
+                    .iter()
+                    .map(|l| format!("<{l}>"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // the labels the options refer to, with the option naming each
+    fn referenced<'a>(options: &'a InterpretOptions) -> Vec<(&'static str, &'a str)> {
+        let mut referenced = Vec::new();
+
+        referenced.extend(
+            options
+                .segment_out
+                .iter()
+                .map(|(label, _)| ("--segment-out", label.as_str())),
+        );
+        if let Some(TechRead { pieces, .. }) = &options.tech_read {
+            referenced.extend(pieces.iter().filter_map(|piece| match piece {
+                TechPiece::Segment(label) => Some(("--tech-read", label.as_str())),
+                TechPiece::Constant(_) => None,
+            }));
+        }
+        if let Some(Capture { names, .. }) = &options.capture {
+            referenced.extend(names.iter().map(|label| ("--capture", label.as_str())));
+        }
+        if let Some(features) = &options.features {
+            referenced.push(("--feature-segment", features.segment.as_str()));
+        }
+        referenced.extend(
+            options
+                .matchers
+                .iter()
+                .filter_map(|choice| choice.segment.as_deref())
+                .map(|label| ("--matcher", label)),
+        );
+        if options.index_hopping.is_some() {
+            referenced.extend([("--index-hopping", "i7"), ("--index-hopping", "i5")]);
+        }
+
+        referenced
+    }
+
+    // fails on an option the geometry cannot follow, or a label an option
+    // refers to that the geometry does not have, before the pipeline is built
+    pub fn check_options(&self, options: &InterpretOptions) -> Result<(), String> {
+        self.check_labels(&Self::referenced(options))?;
+
+        let has = |type_: Type| {
+            self.geometry
+                .iter()
+                .flatten()
+                .any(|gm| gm.expr.0.type_ == type_)
+        };
+
+        if options.umi_counts.is_some() && !(has(Type::Barcode) && has(Type::Umi)) {
+            return Err("Counting UMIs needs a barcode and a UMI in the geometry".to_string());
+        }
+
+        if options.merge.is_some() && self.output_reads() != 2 {
+            return Err("Only pairs of output reads can be merged".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+// a repeated geometry is split on its leading fixed sequence
+fn repeat_delimiter(geometry: &[Vec<GeometryMeta>]) -> Option<String> {
+    if let Size::FixedSeq((seq, _), ..) = &geometry.first()?.first()?.expr.0.size {
+        Some(seq.clone())
+    } else {
+        None
+    }
 }
 
 // this should be more of a compile and also should return a kind of
@@ -41,9 +228,14 @@ pub fn compile(expr: Expr) -> Result<CompiledData, Error> {
             HashMap::new()
         };
 
+        let (pre, r) = compile_pre(r)?;
+
+        let is_repeat = matches!(r.0.first(), Some(Expr::Repeat(_)));
+        let reads_span = r.1.clone();
+
         let validate_read_res = compile_reads(r, &mut map);
 
-        let (mut map, geometry) = if let Ok(cd) = validate_read_res {
+        let (mut map, geometry, groups) = if let Ok(cd) = validate_read_res {
             cd
         } else {
             return Err(validate_read_res.err().unwrap());
@@ -51,7 +243,8 @@ pub fn compile(expr: Expr) -> Result<CompiledData, Error> {
 
         // this needs a bit more thought
         let compiled_transformation = if let (Some(transform), span) = t.deref() {
-            let res = compile_transformation((transform.clone(), span.clone()), &mut map);
+            let res =
+                compile_transformation((transform.clone(), span.clone()), &mut map, &groups.labels);
 
             if let Err(e) = res {
                 return Err(e);
@@ -80,23 +273,50 @@ pub fn compile(expr: Expr) -> Result<CompiledData, Error> {
             .cloned()
             .collect::<Vec<_>>();
 
-        if let Some((transformation, map)) = compiled_transformation {
-            let transformation = label_transformation(transformation, numbered_labels);
+        let (geometry, transformation) =
+            if let Some((transformation, map)) = compiled_transformation {
+                let transformation =
+                    label_transformation(transformation, numbered_labels, &groups.header);
 
-            let geometry = standardize_geometry(&mut map.clone(), geometry);
+                let geometry = standardize_geometry(&mut map.clone(), geometry);
 
-            Ok(CompiledData {
-                geometry,
-                transformation: Some(transformation),
-            })
+                (geometry, Some(transformation))
+            } else {
+                let geometry = standardize_geometry(&mut map, geometry);
+
+                (geometry, None)
+            };
+
+        let repeat = if is_repeat {
+            if let Some(delimiter) = repeat_delimiter(&geometry) {
+                Some(delimiter)
+            } else {
+                return Err(Error {
+                    span: reads_span,
+                    msg: "A repeated geometry must start with a fixed sequence".to_string(),
+                });
+            }
         } else {
-            let geometry = standardize_geometry(&mut map, geometry);
+            None
+        };
 
-            Ok(CompiledData {
-                geometry,
-                transformation: None,
-            })
+        let mut warnings = warnings(&geometry);
+        if let Some(transformation) = &transformation {
+            warnings.extend(unused_labels(&geometry, transformation, &groups.labels));
         }
+        let plan = plan_reads(&geometry, &groups.optional, &groups.alternatives)?;
+
+        Ok(CompiledData {
+            geometry,
+            transformation,
+            repeat,
+            optional: groups.optional,
+            alternatives: groups.alternatives,
+            header: groups.header,
+            pre,
+            plan,
+            warnings,
+        })
     } else {
         unreachable!()
     }
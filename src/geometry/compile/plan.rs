@@ -0,0 +1,398 @@
+/*
+How each read is cut, worked out once as its geometry is compiled.
+
+Every piece of a read's geometry is cut from the bases the piece before it
+left, `init`, into its own label, `this`, and the bases after it, `next`. The
+labels, and the selectors and transforms antisequence cuts along them, are
+parsed here rather than each time a pipeline is built, so the runs sharing a
+compiled geometry only clone them, and a label antisequence cannot parse fails
+the compile instead of a run.
+*/
+
+use std::{fmt, ops::Range};
+
+use antisequence::expr::{Attr, Label, SelectorExpr, TransformExpr};
+
+use crate::{lexer::Span, parser::Size};
+
+use super::utils::{Error, GeometryMeta};
+
+const LEFT: &str = "l";
+const RIGHT: &str = "r";
+
+// an antisequence expression, with the text it was parsed from
+#[derive(Clone)]
+pub struct Parsed<T> {
+    pub text: String,
+    expr: T,
+}
+
+impl<T: Clone> Parsed<T> {
+    pub fn get(&self) -> T {
+        self.expr.clone()
+    }
+}
+
+impl<T> fmt::Debug for Parsed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn parsed<T, E, F>(text: String, span: &Span, kind: &str, parse: F) -> Result<Parsed<T>, Error>
+where
+    F: Fn(&[u8]) -> Result<T, E>,
+{
+    match parse(text.as_bytes()) {
+        Ok(expr) => Ok(Parsed { text, expr }),
+        Err(_) => Err(Error {
+            span: span.clone(),
+            msg: format!("`{text}` cannot be read as a {kind}"),
+        }),
+    }
+}
+
+fn label(text: String, span: &Span) -> Result<Parsed<Label>, Error> {
+    parsed(text, span, "label", Label::new)
+}
+
+fn attr(text: String, span: &Span) -> Result<Parsed<Attr>, Error> {
+    parsed(text, span, "attribute", Attr::new)
+}
+
+fn selector(text: String, span: &Span) -> Result<Parsed<SelectorExpr>, Error> {
+    parsed(text, span, "selector", SelectorExpr::new)
+}
+
+fn transform(text: String, span: &Span) -> Result<Parsed<TransformExpr>, Error> {
+    parsed(text, span, "transform", TransformExpr::new)
+}
+
+// the labels a piece is cut along, `prev` keeps the bases skipped before a
+// fixed sequence and is `_` where nothing before it keeps them
+#[derive(Clone, Debug)]
+pub struct PieceLabels {
+    pub init: String,
+    pub prev: String,
+    pub this: String,
+    pub next: String,
+}
+
+// a piece of a read's geometry, the `index`th
+#[derive(Clone, Debug)]
+pub struct PiecePlan {
+    pub index: usize,
+    pub labels: PieceLabels,
+    pub this_label: Parsed<Label>,
+    pub next_label: Parsed<Label>,
+    // reads with the bases it is cut from, and with the piece
+    pub init: Parsed<SelectorExpr>,
+    pub this: Parsed<SelectorExpr>,
+    // `init` cut into the piece and the bases after it, an unbounded piece
+    // takes all of them
+    pub cut: Parsed<TransformExpr>,
+    // its length checked in `{this}.v_len`
+    pub v_len: Parsed<TransformExpr>,
+    // where a fixed sequence is searched for
+    pub search: Option<SearchPlan>,
+}
+
+// a fixed sequence is searched for in `from`, all of `init` or its window of
+// it, with the bases skipped before a hit given to `prev`
+#[derive(Clone, Debug)]
+pub struct SearchPlan {
+    pub from: String,
+    pub prev: String,
+    // reads it was not found in yet, nor any of the branches tried before it
+    pub unfound: Parsed<SelectorExpr>,
+    // `from` cut around a hit starting it, and around a hit anywhere in it
+    pub aligned: Parsed<TransformExpr>,
+    pub split: Parsed<TransformExpr>,
+}
+
+// an optional group, matched where it would start
+#[derive(Clone, Debug)]
+pub struct GroupPlan {
+    pub pieces: Vec<PiecePlan>,
+    // the group's leading piece, only found on reads with the group
+    pub anchor: String,
+    // whether a read has the group, noted before the reads without it are
+    // given an empty anchor
+    pub found: Parsed<Attr>,
+    // `found` of the branches of its set of alternatives tried before it,
+    // and of every branch if it is the last
+    pub except: Vec<String>,
+    pub branches: Option<Vec<String>>,
+    // what follows the group cut from its last piece, and on reads without
+    // it, the empty pieces and what follows cut from where it would start
+    pub end: Parsed<SelectorExpr>,
+    pub end_cut: Parsed<TransformExpr>,
+    pub absent: Parsed<SelectorExpr>,
+    pub skips: Vec<Parsed<TransformExpr>>,
+    pub skip: Parsed<TransformExpr>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Step {
+    // a piece cut by itself
+    Piece(PiecePlan),
+    // a ranged or unbounded piece, given what is before the fixed sequence
+    // after it once that is found
+    Dual(PiecePlan, PiecePlan),
+    Optional(GroupPlan),
+}
+
+// the steps cutting each read, passthrough reads have none
+pub fn plan_reads(
+    geometry: &[Vec<GeometryMeta>],
+    optional: &[Vec<Range<usize>>],
+    alternatives: &[Vec<Vec<Range<usize>>>],
+) -> Result<Vec<Vec<Step>>, Error> {
+    geometry
+        .iter()
+        .enumerate()
+        .map(|(i, read)| {
+            plan_read(
+                read,
+                format!("seq{}.", i + 1),
+                optional.get(i).map_or(&[][..], Vec::as_slice),
+                alternatives.get(i).map_or(&[][..], Vec::as_slice),
+            )
+        })
+        .collect()
+}
+
+// the bases left to cut and the prefix of the labels cut from them, given
+// the labels of the pieces so far
+fn labels(read_label: &[String]) -> (String, String) {
+    let next_label = read_label.join("");
+
+    if read_label.len() == 1 {
+        (format!("{}*", read_label[0]), next_label)
+    } else {
+        (next_label.clone(), next_label)
+    }
+}
+
+fn own_label(gm: &GeometryMeta, read_label: &[String]) -> Option<String> {
+    let label = gm.expr.0.label.as_ref()?;
+
+    Some(format!("{}{label}", read_label[0]))
+}
+
+fn plan_read(
+    geometry: &[GeometryMeta],
+    init_label: String,
+    optional: &[Range<usize>],
+    alternatives: &[Vec<Range<usize>>],
+) -> Result<Vec<Step>, Error> {
+    let mut pieces = geometry.iter().enumerate().peekable();
+
+    let mut label = vec![init_label];
+    let mut steps = Vec::new();
+    // where the branches already tried in a set of alternatives are noted
+    let mut branch_anchors: Vec<String> = Vec::new();
+
+    while let Some((i, gm)) = pieces.next() {
+        if let Some(range) = optional.iter().find(|range| range.start == i) {
+            let set = alternatives.iter().find(|set| set.contains(range));
+
+            let group = std::iter::once((i, gm))
+                .chain(pieces.by_ref().take(range.len() - 1))
+                .collect::<Vec<_>>();
+            let mut group = plan_optional(&group, &mut label, &branch_anchors)?;
+
+            if let Some(set) = set {
+                branch_anchors.push(group.found.text.clone());
+
+                if set.last() == Some(range) {
+                    group.branches = Some(std::mem::take(&mut branch_anchors));
+                }
+            }
+
+            steps.push(Step::Optional(group));
+
+            continue;
+        }
+
+        let step = match &gm.expr.0.size {
+            Size::FixedSeq(..) | Size::FixedLen(_) => Step::Piece(plan_piece(i, gm, &label)?),
+            // with no sequence after it, a whitelisted piece is cut by itself
+            Size::RangedLen(_)
+                if gm.whitelist().is_some()
+                    && !matches!(
+                        pieces.peek(),
+                        Some((_, next)) if matches!(next.expr.0.size, Size::FixedSeq(..))
+                    ) =>
+            {
+                Step::Piece(plan_piece(i, gm, &label)?)
+            }
+            // by rules of geometry what follows is either nothing or a sequence
+            Size::RangedLen(_) | Size::UnboundedLen => match pieces.next() {
+                Some(next) => plan_dual((i, gm), next, &label)?,
+                None => Step::Piece(plan_piece(i, gm, &label)?),
+            },
+        };
+
+        steps.push(step);
+        label.push(format!("_{RIGHT}"));
+    }
+
+    Ok(steps)
+}
+
+fn plan_piece(index: usize, gm: &GeometryMeta, label: &[String]) -> Result<PiecePlan, Error> {
+    let (init, cur) = labels(label);
+
+    let labels = PieceLabels {
+        init,
+        prev: "_".to_string(),
+        this: own_label(gm, label).unwrap_or_else(|| format!("{cur}_{LEFT}")),
+        next: format!("{cur}_{RIGHT}"),
+    };
+
+    piece(index, gm, labels, &[], true)
+}
+
+fn plan_dual(
+    (prev_index, prev_gm): (usize, &GeometryMeta),
+    (index, gm): (usize, &GeometryMeta),
+    label: &[String],
+) -> Result<Step, Error> {
+    let (init, cur) = labels(label);
+
+    let mut left_label = label.to_vec();
+    left_label.push(match &prev_gm.expr.0.label {
+        Some(l) => l.clone(),
+        None => format!("_{LEFT}"),
+    });
+    let prev = own_label(prev_gm, label).unwrap_or_else(|| format!("{cur}_{LEFT}"));
+    let next = format!("{cur}_{RIGHT}");
+
+    let this = PieceLabels {
+        init,
+        prev: prev.clone(),
+        this: own_label(gm, label).unwrap_or_else(|| format!("{cur}_anchor")),
+        next: next.clone(),
+    };
+
+    // the piece before is not cut, the search for the sequence gave it its bases
+    let (prev_init, _) = labels(&left_label);
+    let prev = PieceLabels {
+        init: prev_init,
+        prev: "_".to_string(),
+        this: prev,
+        next,
+    };
+
+    Ok(Step::Dual(
+        piece(prev_index, prev_gm, prev, &[], true)?,
+        piece(index, gm, this, &[], true)?,
+    ))
+}
+
+// a branch of a set of alternatives is not looked for on reads where an
+// earlier branch was found, as noted in `except`
+fn plan_optional(
+    group: &[(usize, &GeometryMeta)],
+    label: &mut Vec<String>,
+    except: &[String],
+) -> Result<GroupPlan, Error> {
+    let (init, cur) = labels(label);
+    let next = format!("{cur}_o");
+    let span = &group[0].1.expr.1;
+
+    let mut group_label = label.clone();
+    let mut pieces = Vec::new();
+
+    for &(i, gm) in group {
+        let (piece_init, piece_cur) = labels(&group_label);
+
+        let labels = PieceLabels {
+            init: piece_init,
+            prev: "_".to_string(),
+            this: own_label(gm, &group_label).unwrap_or_else(|| format!("{piece_cur}_{LEFT}")),
+            next: format!("{piece_cur}_{RIGHT}"),
+        };
+
+        // the group is not searched for, it has to be where it is expected
+        pieces.push(piece(i, gm, labels, except, false)?);
+        group_label.push(format!("_{RIGHT}"));
+    }
+
+    let (end, _) = labels(&group_label);
+    let anchor = pieces[0].labels.this.clone();
+
+    label.push("_o".to_string());
+
+    Ok(GroupPlan {
+        end: selector(end.clone(), span)?,
+        end_cut: transform(format!("{end} -> _, {next}"), span)?,
+        found: attr(format!("{init}.found"), span)?,
+        absent: selector(format!("!{init}.found"), span)?,
+        skips: pieces
+            .iter()
+            .map(|piece| transform(format!("{init} -> {}, _", piece.labels.this), span))
+            .collect::<Result<_, _>>()?,
+        skip: transform(format!("{init} -> _, {next}"), span)?,
+        pieces,
+        anchor,
+        except: except.to_vec(),
+        branches: None,
+    })
+}
+
+fn piece(
+    index: usize,
+    gm: &GeometryMeta,
+    labels: PieceLabels,
+    except: &[String],
+    windowed: bool,
+) -> Result<PiecePlan, Error> {
+    let span = &gm.expr.1;
+    let PieceLabels {
+        init,
+        prev,
+        this,
+        next,
+    } = &labels;
+
+    let cut = match gm.expr.0.size {
+        Size::UnboundedLen => format!("{init} -> _, {this}"),
+        _ => format!("{init} -> {this}, {next}"),
+    };
+
+    let search = match &gm.expr.0.size {
+        Size::FixedSeq(_, _, window) => {
+            let (from, prev) = match window {
+                Some(_) if windowed => (format!("{this}_win"), format!("{this}_gap")),
+                _ => (init.clone(), prev.clone()),
+            };
+            let unfound = std::iter::once(from.clone())
+                .chain(except.iter().chain([this]).map(|l| format!("!{l}")))
+                .collect::<Vec<_>>()
+                .join(" & ");
+
+            Some(SearchPlan {
+                unfound: selector(unfound, span)?,
+                aligned: transform(format!("{from} -> {this}, {next}"), span)?,
+                split: transform(format!("{from} -> {prev}, {this}, {next}"), span)?,
+                from,
+                prev,
+            })
+        }
+        _ => None,
+    };
+
+    Ok(PiecePlan {
+        index,
+        this_label: label(this.clone(), span)?,
+        next_label: label(next.clone(), span)?,
+        init: selector(init.clone(), span)?,
+        this: selector(this.clone(), span)?,
+        cut: transform(cut, span)?,
+        v_len: transform(format!("{this} -> {this}.v_len"), span)?,
+        search,
+        labels,
+    })
+}
@@ -0,0 +1,59 @@
+/*
+   Compile the `pre{..}` block, the steps applied to whole reads
+   before any piece is cut from them.
+*/
+
+use crate::{
+    adapters::builtin,
+    parser::{Expr, PreStep, Spanned},
+};
+
+use super::utils::Error;
+
+// the preprocessing steps leading the reads, and the reads without them
+pub fn compile_pre(reads: Spanned<Vec<Expr>>) -> Result<(Vec<PreStep>, Spanned<Vec<Expr>>), Error> {
+    let (mut exprs, span) = reads;
+
+    let steps = if matches!(exprs.first(), Some(Expr::Pre(_))) {
+        match exprs.remove(0) {
+            Expr::Pre(steps) => steps,
+            _ => unreachable!(),
+        }
+    } else {
+        Vec::new()
+    };
+
+    // a repeated geometry is a single read
+    let reads = exprs
+        .iter()
+        .filter(|expr| matches!(expr, Expr::Read(..) | Expr::Repeat(_)))
+        .count();
+
+    let mut steps = steps
+        .into_iter()
+        .map(|(step, span)| match &step {
+            PreStep::QualTrim(q) if *q > 93 => Err(Error {
+                span,
+                msg: format!("Quality {q} is above the highest phred score of 93"),
+            }),
+            PreStep::Adapters(name) if builtin(name).is_none() => Err(Error {
+                span,
+                msg: format!(
+                    "Unknown adapter set `{name}`, expected truseq, nextera, smallrna or a fasta file in quotes"
+                ),
+            }),
+            PreStep::AssertLen {
+                read: Some(n), ..
+            } if *n == 0 || *n > reads => Err(Error {
+                span,
+                msg: format!("Cannot assert the length of read {n}, the geometry has {reads}"),
+            }),
+            _ => Ok(step),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // lengths are checked on the reads as they were sequenced
+    steps.sort_by_key(|step| !matches!(step, PreStep::AssertLen { .. }));
+
+    Ok((steps, (exprs, span)))
+}
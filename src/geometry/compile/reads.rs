@@ -3,9 +3,15 @@ use super::{
     utils::*,
 };
 
-use std::{collections::HashMap, ops::Deref};
+use std::{
+    collections::HashMap,
+    ops::{Deref, Range},
+};
 
-use crate::parser::{Expr, Function, Size, Spanned};
+use crate::{
+    lexer::Span,
+    parser::{Expr, Function, Miss, Size, Spanned, Type},
+};
 
 pub fn validate_geometry(
     map: HashMap<String, GeometryMeta>,
@@ -34,8 +40,10 @@ pub fn validate_geometry(
         let (gp, span) = gm.expr.clone();
 
         let type_ = match gp.size {
-            Size::FixedSeq(_) => ReturnType::FixedSeq,
+            Size::FixedSeq(..) => ReturnType::FixedSeq,
             Size::FixedLen(_) => ReturnType::FixedLen,
+            // its length is told by the whitelist, nothing needs to follow it
+            Size::RangedLen(_) if gm.whitelist().is_some() => ReturnType::FixedLen,
             Size::RangedLen(_) => ReturnType::Ranged,
             Size::UnboundedLen => ReturnType::Unbounded,
         };
@@ -79,8 +87,8 @@ pub fn standardize_geometry(
         let mut geom: Vec<GeometryMeta> = Vec::new();
         for interval in read {
             match interval {
-                (Interval::Named(l), _) => geom.push(map.get(&l).unwrap().clone()),
-                (Interval::Temporary(gp), _) => geom.push(gp.clone()),
+                (Interval::Named(l), _) => geom.push(rna_to_dna(map.get(&l).unwrap().clone())),
+                (Interval::Temporary(gp), _) => geom.push(rna_to_dna(gp.clone())),
             }
         }
 
@@ -90,13 +98,459 @@ pub fn standardize_geometry(
     std_geom
 }
 
+// fixed sequences are matched as DNA, `U` is read as `T`
+fn rna_to_dna(mut gm: GeometryMeta) -> GeometryMeta {
+    if let Size::FixedSeq((seq, _), ..) = &mut gm.expr.0.size {
+        *seq = seq.replace('U', "T");
+    }
+
+    gm
+}
+
+// number the labels in one copy of a repeated group, labels refering to
+// definitions are given their own numbered definition
+fn number_labels(
+    expr: Spanned<Expr>,
+    n: usize,
+    map: &mut HashMap<String, GeometryMeta>,
+) -> Result<Spanned<Expr>, Error> {
+    let (expr, span) = expr;
+
+    let mut number_all = |exprs: Vec<Spanned<Expr>>| {
+        exprs
+            .into_iter()
+            .map(|expr| number_labels(expr, n, map))
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let expr = match expr {
+        Expr::Group(exprs) => Expr::Group(number_all(exprs)?),
+        Expr::Repeated(exprs, m) => Expr::Repeated(number_all(exprs)?, m),
+        Expr::Optional(exprs) => Expr::Optional(number_all(exprs)?),
+        Expr::Alternatives(branches) => Expr::Alternatives(
+            branches
+                .into_iter()
+                .map(&mut number_all)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Expr::Function(fn_, gp) => {
+            Expr::Function(fn_, Box::new(number_labels(gp.deref().clone(), n, map)?))
+        }
+        Expr::LabeledGeomPiece((l, l_span), gp) => Expr::LabeledGeomPiece(
+            (format!("{l}{n}"), l_span),
+            Box::new(number_labels(gp.deref().clone(), n, map)?),
+        ),
+        Expr::Label((l, l_span)) => {
+            if let Some(gm) = map.get(&l) {
+                let numbered = format!("{l}{n}");
+
+                if map.contains_key(&numbered) {
+                    return Err(Error {
+                        span: l_span,
+                        msg: format!(
+                            "Cannot number repeated label `{}`, `{}` is already defined",
+                            l, numbered
+                        ),
+                    });
+                }
+
+                let mut gm = gm.clone();
+                gm.expr.0.label = Some(numbered.clone());
+                map.insert(numbered.clone(), gm);
+
+                Expr::Label((numbered, l_span))
+            } else {
+                Expr::Label((l, l_span))
+            }
+        }
+        expr => expr,
+    };
+
+    Ok((expr, span))
+}
+
+// the label of a piece, either its own or that of the definition it refers to
+fn piece_label(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Function(_, gp) => piece_label(&gp.0),
+        Expr::LabeledGeomPiece((l, _), _) => Some(l.clone()),
+        Expr::Label((l, _)) => Some(l.clone()),
+        _ => None,
+    }
+}
+
+fn label_piece(expr: Spanned<Expr>, label: String) -> Spanned<Expr> {
+    let (expr, span) = expr;
+
+    let expr = match expr {
+        Expr::Function(fn_, gp) => {
+            Expr::Function(fn_, Box::new(label_piece(gp.deref().clone(), label)))
+        }
+        gp @ Expr::GeomPiece(..) => {
+            Expr::LabeledGeomPiece((label, span.clone()), Box::new((gp, span.clone())))
+        }
+        expr => expr,
+    };
+
+    (expr, span)
+}
+
+fn is_header(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::LabeledGeomPiece(_, gp) if matches!(gp.0, Expr::GeomPiece(Type::Header, _))
+    )
+}
+
+// header pieces lead the read and take nothing from its sequence, they are
+// given their own labels and left out of the geometry
+fn take_header(
+    read: Vec<Spanned<Expr>>,
+    labels: &mut Vec<String>,
+    groups: &mut Groups,
+    map: &mut HashMap<String, GeometryMeta>,
+) -> Result<Vec<Spanned<Expr>>, Error> {
+    let n = read.iter().take_while(|(expr, _)| is_header(expr)).count();
+    let mut read = read.into_iter();
+    let mut header = Vec::new();
+
+    for (expr, span) in read.by_ref().take(n) {
+        let ((l, l_span), gp) = match expr {
+            Expr::LabeledGeomPiece(l, gp) => (l, *gp),
+            _ => unreachable!(),
+        };
+
+        let (type_, size, len) = match gp.0 {
+            Expr::GeomPiece(type_, size @ Size::FixedLen((len, _))) => (type_, size, len),
+            _ => unreachable!(),
+        };
+
+        if labels.contains(&l) || map.contains_key(&l) || groups.labels.contains_key(&l) {
+            return Err(Error {
+                span: l_span,
+                msg: format!("Variable: {}, already defined above.", l),
+            });
+        }
+
+        if header.len() == 2 {
+            return Err(Error {
+                span,
+                msg: "A header has at most two indices, i7 and i5".to_string(),
+            });
+        }
+
+        map.insert(
+            l.clone(),
+            GeometryMeta {
+                expr: (
+                    GeometryPiece {
+                        type_,
+                        size,
+                        label: Some(l.clone()),
+                    },
+                    gp.1,
+                ),
+                stack: vec![],
+            },
+        );
+        labels.push(l.clone());
+        header.push((l, len));
+    }
+
+    groups.header.push(header);
+
+    Ok(read.collect())
+}
+
+// unroll groups into a flat read, numbering labels in repeated groups from 1.
+// unlabeled pieces of a labeled group are named after the group, and each
+// branch of a set of alternatives becomes an optional group
+fn expand_groups(
+    exprs: Vec<Spanned<Expr>>,
+    expanded: &mut Vec<Spanned<Expr>>,
+    optional: &mut Vec<Range<usize>>,
+    alternatives: &mut Vec<Vec<Range<usize>>>,
+    groups: &mut Groups,
+    map: &mut HashMap<String, GeometryMeta>,
+) -> Result<(), Error> {
+    for (expr, span) in exprs {
+        match expr {
+            Expr::Group(group) => {
+                expand_groups(group, expanded, optional, alternatives, groups, map)?
+            }
+            Expr::Repeated(group, n) => {
+                for i in 1..=n {
+                    let group = group
+                        .iter()
+                        .map(|expr| number_labels(expr.clone(), i, map))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    expand_groups(group, expanded, optional, alternatives, groups, map)?;
+                }
+            }
+            Expr::Optional(group) => {
+                let start = expanded.len();
+
+                expand_groups(group, expanded, optional, alternatives, groups, map)?;
+
+                if optional.iter().any(|range| range.start >= start) {
+                    return Err(Error {
+                        span,
+                        msg: "Optional groups cannot be nested".to_string(),
+                    });
+                }
+
+                if expanded.len() > start {
+                    optional.push(start..expanded.len());
+                }
+            }
+            Expr::Alternatives(branches) => {
+                let mut set = Vec::new();
+
+                for branch in branches {
+                    let start = expanded.len();
+
+                    expand_groups(branch, expanded, optional, alternatives, groups, map)?;
+
+                    if optional.iter().any(|range| range.start >= start) {
+                        return Err(Error {
+                            span: span.clone(),
+                            msg: "Alternatives cannot be nested or contain optional groups"
+                                .to_string(),
+                        });
+                    }
+
+                    optional.push(start..expanded.len());
+                    set.push(start..expanded.len());
+                }
+
+                alternatives.push(set);
+            }
+            Expr::LabeledGeomPiece((l, l_span), group)
+                if matches!(
+                    group.0,
+                    Expr::Group(_) | Expr::Repeated(..) | Expr::Optional(_)
+                ) =>
+            {
+                if groups.labels.contains_key(&l) || map.contains_key(&l) {
+                    return Err(Error {
+                        span: l_span,
+                        msg: format!("Variable: {}, already defined above.", l),
+                    });
+                }
+
+                let start = expanded.len();
+
+                expand_groups(
+                    vec![group.deref().clone()],
+                    expanded,
+                    optional,
+                    alternatives,
+                    groups,
+                    map,
+                )?;
+
+                let mut members = Vec::new();
+
+                for (i, piece) in expanded[start..].iter_mut().enumerate() {
+                    if piece_label(&piece.0).is_none() {
+                        *piece = label_piece(piece.clone(), format!("{l}_{}", i + 1));
+                    }
+
+                    members.extend(piece_label(&piece.0));
+                }
+
+                groups.labels.insert(l, members);
+            }
+            expr => expanded.push((expr, span)),
+        }
+    }
+
+    Ok(())
+}
+
+// optional groups are found by their leading fixed sequence at a known position
+fn validate_optional(
+    map: &HashMap<String, GeometryMeta>,
+    geom: &[(Interval, usize)],
+    optional: &[Range<usize>],
+) -> Result<(), Error> {
+    let meta = |i: usize| match &geom[i] {
+        (Interval::Named(l), _) => map.get(l).unwrap().clone(),
+        (Interval::Temporary(gm), _) => gm.clone(),
+    };
+
+    // a piece failing to compile leaves the read geometry incomplete
+    for range in optional.iter().filter(|range| range.end <= geom.len()) {
+        let (first, span) = meta(range.start).expr;
+
+        if !matches!(first.size, Size::FixedSeq(..)) {
+            return Err(Error {
+                span,
+                msg: "An optional group must start with a fixed sequence".to_string(),
+            });
+        }
+
+        if range.start > 0 {
+            let (prev, span) = meta(range.start - 1).expr;
+
+            if matches!(prev.size, Size::RangedLen(_) | Size::UnboundedLen) {
+                return Err(Error {
+                    span,
+                    msg: "An optional group cannot follow a variable length piece".to_string(),
+                });
+            }
+        }
+
+        for gm in range.clone().map(meta) {
+            let (gp, span) = gm.expr;
+
+            if !matches!(gp.size, Size::FixedSeq(..) | Size::FixedLen(_)) {
+                return Err(Error {
+                    span,
+                    msg: "An optional group can only contain fixed length pieces".to_string(),
+                });
+            }
+
+            if let Some((_, fn_span)) = gm
+                .stack
+                .iter()
+                .find(|(fn_, _)| matches!(fn_, CompiledFunction::FilterWithinDist(..)))
+            {
+                return Err(Error {
+                    span: fn_span.clone(),
+                    msg: "Cannot filter a piece inside an optional group".to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// a read missing a fixed sequence can only fall back where the piece before it
+// has a maximum length, and optional groups are matched in place, so neither
+// a miss policy nor a search window applies to them
+fn validate_search(
+    map: &HashMap<String, GeometryMeta>,
+    geom: &[(Interval, usize)],
+    optional: &[Range<usize>],
+) -> Result<(), Error> {
+    let meta = |i: usize| match &geom[i] {
+        (Interval::Named(l), _) => map.get(l).unwrap().clone(),
+        (Interval::Temporary(gm), _) => gm.clone(),
+    };
+
+    for i in 0..geom.len() {
+        let (gp, span) = meta(i).expr;
+
+        let (miss, window) = match gp.size {
+            Size::FixedSeq(_, miss, window) => (miss, window),
+            _ => continue,
+        };
+
+        if miss != Miss::Drop && optional.iter().any(|range| range.contains(&i)) {
+            return Err(Error {
+                span,
+                msg: "A fixed sequence in an optional group cannot have a miss policy".to_string(),
+            });
+        }
+
+        if let Some((start, end)) = window {
+            if optional.iter().any(|range| range.contains(&i)) {
+                return Err(Error {
+                    span,
+                    msg: "A fixed sequence in an optional group cannot have a search window"
+                        .to_string(),
+                });
+            }
+
+            if end < start {
+                return Err(Error {
+                    span,
+                    msg: format!("The search window {start}..{end} ends before it starts"),
+                });
+            }
+
+            if miss == Miss::Fallback {
+                return Err(Error {
+                    span,
+                    msg: "A fixed sequence with a search window cannot fall back".to_string(),
+                });
+            }
+        }
+
+        if miss == Miss::Fallback && i > 0 && meta(i - 1).expr.0.size == Size::UnboundedLen {
+            return Err(Error {
+                span,
+                msg: "Cannot fall back to a fixed sequence after an unbounded piece".to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// an anchored segment is cut between the fixed sequences on either side of
+// it, when only one of them is found that sequence's miss policy applies
+fn validate_anchored(
+    map: &HashMap<String, GeometryMeta>,
+    geom: &[(Interval, usize)],
+    anchored: &[(usize, Span)],
+) -> Result<(), Error> {
+    let is_fixed_seq = |i: usize| match &geom[i] {
+        (Interval::Named(l), _) => matches!(map[l].expr.0.size, Size::FixedSeq(..)),
+        (Interval::Temporary(gm), _) => matches!(gm.expr.0.size, Size::FixedSeq(..)),
+    };
+
+    for (i, span) in anchored {
+        if *i == 0 || *i + 1 == geom.len() || !is_fixed_seq(i - 1) || !is_fixed_seq(i + 1) {
+            return Err(Error {
+                span: span.clone(),
+                msg: "An anchored segment needs a fixed sequence on both sides".to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// a fixed sequence after a ranged piece is only searched for where the ranged
+// piece can end, so the same sequence repeated further into the read is not
+// taken for it. a sequence falling back on a miss is left to search the read
+fn constrain_search(map: &mut HashMap<String, GeometryMeta>, geom: &mut [(Interval, usize)]) {
+    for i in 1..geom.len() {
+        let prev = match &geom[i - 1].0 {
+            Interval::Named(l) => &map[l],
+            Interval::Temporary(gm) => gm,
+        };
+
+        let (min, max) = match prev.expr.0.size {
+            Size::RangedLen(((min, max), _)) => (min, max),
+            _ => continue,
+        };
+
+        let gm = match &mut geom[i].0 {
+            Interval::Named(l) => map.get_mut(l).unwrap(),
+            Interval::Temporary(gm) => gm,
+        };
+
+        if let Size::FixedSeq(_, miss, window @ None) = &mut gm.expr.0.size {
+            if *miss != Miss::Fallback {
+                *window = Some((min, max));
+            }
+        }
+    }
+}
+
 // this should take both reads and parse them. Allowing for combined label_map
 pub fn compile_reads(
     exprs: Spanned<Vec<Expr>>,
     map: &mut HashMap<String, GeometryMeta>,
-) -> Result<(HashMap<String, GeometryMeta>, Geometry), Error> {
+) -> Result<(HashMap<String, GeometryMeta>, Geometry, Groups), Error> {
     let mut err: Option<Error> = None;
     let mut geometry: Geometry = Vec::new();
+    let mut groups = Groups::default();
     let mut labels: Vec<String> = Vec::new();
 
     // create a vector of labels which have already been used. help with errors
@@ -105,8 +559,10 @@ pub fn compile_reads(
     let (exprs, span) = exprs;
 
     'outer_outer: for read in exprs {
-        let (read, num) = if let Expr::Read((num, _), read) = read {
+        let (read, (num, num_span)) = if let Expr::Read(num, read) = read {
             (read, num)
+        } else if let Expr::Repeat(read) = read {
+            (read, (1, span.clone()))
         } else {
             return Err(Error {
                 span,
@@ -114,8 +570,31 @@ pub fn compile_reads(
             });
         };
 
+        if num == 0 && !read.is_empty() {
+            return Err(Error {
+                span: num_span,
+                msg: "Read 0 is passed through unchanged and cannot have pieces, use `0{}`"
+                    .to_string(),
+            });
+        }
+
+        let read = take_header(read, &mut labels, &mut groups, map)?;
+
+        let mut expanded = Vec::new();
+        let mut optional = Vec::new();
+        let mut alternatives = Vec::new();
+        expand_groups(
+            read,
+            &mut expanded,
+            &mut optional,
+            &mut alternatives,
+            &mut groups,
+            map,
+        )?;
+
         let mut read_geom: Vec<(Interval, usize)> = Vec::new();
-        'outer: for expr in read {
+        let mut anchored: Vec<(usize, Span)> = Vec::new();
+        'outer: for expr in expanded {
             let mut expr = expr;
             let mut spanned_geom_piece: Option<Spanned<GeometryPiece>> = None;
             let mut compiled_stack: Vec<Spanned<CompiledFunction>> = Vec::new();
@@ -124,23 +603,28 @@ pub fn compile_reads(
 
             'inner: loop {
                 match expr.0 {
+                    Expr::Function((Function::Anchored, span), gp) => {
+                        expr = gp.deref().clone();
+                        anchored.push((read_geom.len(), span));
+                    }
                     Expr::Function(inner_fn, gp) => {
                         expr = gp.deref().clone();
                         stack.push(inner_fn);
                     }
-                    Expr::LabeledGeomPiece(l, gp) => {
-                        if let Expr::Label((l, span)) = l.deref() {
-                            if labels.contains(l) || map.clone().contains_key(l) {
-                                err = Some(Error {
-                                    span: span.clone(),
-                                    msg: format!("Variable: {}, already defined above.", l),
-                                });
-
-                                break 'outer;
-                            }
+                    Expr::LabeledGeomPiece((l, span), gp) => {
+                        if labels.contains(&l)
+                            || map.clone().contains_key(&l)
+                            || groups.labels.contains_key(&l)
+                        {
+                            err = Some(Error {
+                                span,
+                                msg: format!("Variable: {}, already defined above.", l),
+                            });
 
-                            label = Some(l.clone());
+                            break 'outer;
                         }
+
+                        label = Some(l);
                         // maybe return from this and add labeled elements to the map outside of this
                         // would have to unpack labeled values to validate at the end
                         expr = gp.deref().clone();
@@ -220,9 +704,23 @@ pub fn compile_reads(
                     span,
                 )
             } else {
-                unreachable!()
+                // only left by a parse error which has already been reported
+                err = Some(Error {
+                    span: expr.1.clone(),
+                    msg: format!("Expected a geometry piece, found {}", expr.0),
+                });
+                break 'outer;
             };
 
+            if spanned_gp.0.type_ == Type::Header {
+                err = Some(Error {
+                    span: spanned_gp.1,
+                    msg: "Header pieces must come first in a read and cannot be transformed"
+                        .to_string(),
+                });
+                break 'outer;
+            }
+
             let gm = GeometryMeta {
                 expr: spanned_gp,
                 stack: compiled_stack,
@@ -241,17 +739,25 @@ pub fn compile_reads(
             }
         }
 
-        if let Err(e) = validate_geometry(map.clone(), read_geom.clone()) {
+        if let Err(e) = validate_geometry(map.clone(), read_geom.clone())
+            .and_then(|_| validate_optional(map, &read_geom, &optional))
+            .and_then(|_| validate_search(map, &read_geom, &optional))
+            .and_then(|_| validate_anchored(map, &read_geom, &anchored))
+        {
             err = Some(e);
             break 'outer_outer;
         }
 
+        constrain_search(map, &mut read_geom);
+
         geometry.push(read_geom);
+        groups.optional.push(optional);
+        groups.alternatives.push(alternatives);
     }
 
     if let Some(e) = err {
         return Err(e);
     }
 
-    Ok((map.clone(), geometry))
+    Ok((map.clone(), geometry, groups))
 }
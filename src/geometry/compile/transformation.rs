@@ -5,12 +5,13 @@ use super::{
     utils::*,
 };
 
-use crate::parser::{Expr, Function, Spanned};
+use crate::parser::{Expr, Function, Spanned, Type};
 
-pub fn compile_transformation(
+pub fn compile_transformation<'a>(
     transformation: Spanned<Expr>,
-    map: &mut HashMap<String, GeometryMeta>,
-) -> Result<(Transformation, &mut HashMap<String, GeometryMeta>), Error> {
+    map: &'a mut HashMap<String, GeometryMeta>,
+    groups: &HashMap<String, Vec<String>>,
+) -> Result<(Transformation, &'a mut HashMap<String, GeometryMeta>), Error> {
     let (expr, span) = transformation;
 
     let exprs = if let Expr::Transform(exprs) = expr {
@@ -31,7 +32,7 @@ pub fn compile_transformation(
 
        Lets see if we can make this a bit cleaner
     */
-    compile((exprs, span), map)
+    compile((exprs, span), map, groups)
 }
 
 /*
@@ -40,10 +41,11 @@ pub fn compile_transformation(
    Update the map with the new geometry pieces
    Return the new map and a list of labels which represents the final transformation
 */
-fn compile(
+fn compile<'a>(
     exprs: Spanned<Vec<Expr>>,
-    map: &mut HashMap<String, GeometryMeta>,
-) -> Result<(Transformation, &mut HashMap<String, GeometryMeta>), Error> {
+    map: &'a mut HashMap<String, GeometryMeta>,
+    groups: &HashMap<String, Vec<String>>,
+) -> Result<(Transformation, &'a mut HashMap<String, GeometryMeta>), Error> {
     let mut transformation: Transformation = Vec::new();
 
     let (exprs, span) = exprs;
@@ -91,7 +93,31 @@ fn compile(
                 });
             };
 
+            // a group label stands for its pieces in order
+            if let Some(members) = groups.get(&label) {
+                if !stack.is_empty() {
+                    return Err(Error {
+                        span: label_span,
+                        msg: format!("Functions cannot be applied to the group \"{}\"", label),
+                    });
+                }
+
+                inner_transformation.extend(members.iter().cloned());
+
+                continue;
+            }
+
             let gp = if let Some(gp) = map.get(&label) {
+                if gp.expr.0.type_ == Type::Header && !stack.is_empty() {
+                    return Err(Error {
+                        span: label_span,
+                        msg: format!(
+                            "Functions cannot be applied to the header index \"{}\"",
+                            label
+                        ),
+                    });
+                }
+
                 for fn_ in stack {
                     compiled_stack.push(compile_fn(fn_, expr.clone())?)
                 }
@@ -137,6 +163,7 @@ fn find_num(l: String, list: Vec<(Interval, usize)>) -> String {
 pub fn label_transformation(
     transformation: Transformation,
     numbered_labels: Vec<(Interval, usize)>,
+    header: &[Vec<(String, usize)>],
 ) -> Transformation {
     let mut numbered_transformation: Transformation = Vec::new();
 
@@ -144,6 +171,13 @@ pub fn label_transformation(
         let mut inner_transformation: Vec<String> = Vec::new();
 
         for l in t {
+            // header indices are cut from the name of the read
+            if let Some(n) = header.iter().position(|h| h.iter().any(|(hl, _)| *hl == l)) {
+                inner_transformation.push(format!("name{}.{}", n + 1, l));
+
+                continue;
+            }
+
             let num = find_num(l.clone(), numbered_labels.clone());
 
             inner_transformation.push(format!("seq{num}.{}", l));
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{collections::HashMap, fmt, ops::Range};
 
 use crate::{
     lexer::Span,
@@ -11,6 +11,18 @@ pub type Geometry = Vec<Vec<(Interval, usize)>>;
 
 pub type Transformation = Vec<Vec<String>>;
 
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Groups {
+    // labels given to a whole group, mapped to the labels of its pieces
+    pub labels: HashMap<String, Vec<String>>,
+    // for each read, the pieces making up each optional group
+    pub optional: Vec<Vec<Range<usize>>>,
+    // for each read, the optional groups making up each set of alternatives
+    pub alternatives: Vec<Vec<Vec<Range<usize>>>>,
+    // for each read, the indices taken from its header and their lengths
+    pub header: Vec<Vec<(String, usize)>>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ReturnType {
     Ranged,
@@ -78,6 +90,18 @@ pub struct GeometryPiece {
     pub label: Option<String>,
 }
 
+impl GeometryMeta {
+    // the file a piece is mapped to, whose barcodes tell a ranged piece's
+    // length, and the mismatches its map corrects
+    pub fn whitelist(&self) -> Option<(String, usize)> {
+        self.stack.iter().find_map(|(fn_, _)| match fn_ {
+            CompiledFunction::Map(file, _) => Some((file.clone(), 0)),
+            CompiledFunction::MapWithMismatch(file, _, n) => Some((file.clone(), *n)),
+            _ => None,
+        })
+    }
+}
+
 impl fmt::Display for GeometryMeta {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Geometry Meta: {}, {:?}", self.expr.0, self.stack)
@@ -104,7 +128,7 @@ pub fn gp_return_type(gp: GeometryMeta) -> Result<ReturnType, Error> {
             ReturnType::Void
         } else {
             match expr.size {
-                Size::FixedSeq(_) => ReturnType::FixedSeq,
+                Size::FixedSeq(..) => ReturnType::FixedSeq,
                 Size::FixedLen(_) => ReturnType::FixedLen,
                 Size::RangedLen(_) => ReturnType::Ranged,
                 Size::UnboundedLen => ReturnType::Unbounded,
@@ -130,7 +154,7 @@ pub fn validate_composition(
     let (return_type, return_type_span) = return_type;
 
     let (min, max) = match size {
-        Size::FixedSeq((seq, _)) => (0, seq.len()),
+        Size::FixedSeq((seq, _), ..) => (0, seq.len()),
         Size::FixedLen((n, _)) => (0, n),
         Size::RangedLen(((a, b), _)) => (a, b),
         Size::UnboundedLen => (100, 100),
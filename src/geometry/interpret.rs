@@ -1,5 +1,8 @@
+use std::sync::Arc;
+
 use antisequence::{
-    MatchType::{ExactSearch, HammingSearch, PrefixAln},
+    expr::SelectorExpr,
+    MatchType::{ExactSearch, Hamming, HammingSearch, PrefixAln},
     Threshold::Frac,
     *,
 };
@@ -7,24 +10,251 @@ use antisequence::{
 use crate::{
     compile::{
         functions::CompiledFunction,
+        plan::{GroupPlan, PieceLabels, PiecePlan, Step},
         utils::{GeometryMeta, GeometryPiece},
         CompiledData,
     },
-    parser::{Size, Spanned, Type},
+    features::Features,
+    filters::{
+        complexity::ComplexityFilter, dedup::DedupConfig, name::NameFilter, umi::UmiFilter,
+        whitelist::WhitelistLengths, OnFail,
+    },
+    matchers::{backend_for, Backend, KmerMatcher, Matcher, MatcherChoice, PrefixMatcher},
+    merge::MergeConfig,
+    monitor::{metrics::Metrics, PassRate, Timings, PASS_RATE_WINDOW},
+    parser::{Miss, Size, Spanned, Type},
+    primers::PrimerConfig,
     processors::*,
+    quality::DEFAULT_PAD_QUAL,
+    sink::{
+        fastq::{TechPiece, TechRead},
+        route::{kind_name, routed, Route},
+        segment_name,
+        shard::Sharding,
+        tags::RawTags,
+        tsv::Capture,
+        well::WellMap,
+        Record,
+    },
+    summary::{
+        composition::Composition,
+        hopping::{index_labels, IndexHopping},
+        BaseCounts, DuplicationEstimate, UmiCounts,
+    },
+    verify::Verifier,
 };
 
-fn labels(read_label: &mut Vec<String>) -> (String, String) {
-    let next_label = read_label.join("");
+pub type BoxedReads = Box<dyn antisequence::Reads>;
 
-    if read_label.len() == 1 {
-        (format!("{}*", read_label.first().unwrap()), next_label)
+// with `check_invariants` each record is verified after a stage
+fn checkpoint(read: BoxedReads, options: &InterpretOptions, stage: &str) -> BoxedReads {
+    if options.check_invariants {
+        check_invariants(read, stage.to_string())
     } else {
-        (next_label.clone(), next_label)
+        read
     }
 }
 
-pub type BoxedReads = Box<dyn antisequence::Reads>;
+fn timed(read: BoxedReads, options: &InterpretOptions, stage: &'static str) -> BoxedReads {
+    match &options.timings {
+        Some(timings) => mark(read, timings.clone(), stage),
+        None => read,
+    }
+}
+
+// a search by another backend finds the hit and cuts the read around it, the
+// match type is returned for antisequence to search the reads it left, none if
+// it searched every read. reads with soft-masked bases are searched the same
+// way, as antisequence is case sensitive. reads with any of `except` are not
+// searched, and bases before a hit without `prev_label` are left unlabeled
+fn search(
+    read: BoxedReads,
+    options: &InterpretOptions,
+    (init_label, prev_label, this_label, next_label): (&str, Option<&str>, &str, &str),
+    except: &[String],
+    seq: &str,
+    match_type: MatchType,
+) -> (BoxedReads, Option<MatchType>) {
+    let dist = |frac: f64| ((1.0 - frac) * seq.len() as f64).round() as usize;
+
+    let (max_dist, anchored) = match match_type {
+        ExactSearch => (0, false),
+        HammingSearch(Frac(frac)) => (dist(frac), false),
+        PrefixAln { .. } => (0, true),
+        Hamming(Frac(frac)) => (dist(frac), true),
+        _ => return (read, Some(match_type)),
+    };
+
+    // sequences matched in place are always left to antisequence
+    let backend = if anchored {
+        Backend::Antisequence
+    } else {
+        backend_for(&options.matchers, segment_name(this_label))
+    };
+
+    let (matcher, match_type, masked_only) = match backend.matcher() {
+        Some(matcher) => (matcher, None, false),
+        // antisequence is case sensitive, reads with soft-masked bases are searched first
+        None if !options.uppercase => {
+            let matcher: Arc<dyn Matcher> = if anchored {
+                Arc::new(PrefixMatcher)
+            } else {
+                Arc::new(KmerMatcher)
+            };
+
+            (matcher, Some(match_type), true)
+        }
+        None => return (read, Some(match_type)),
+    };
+
+    let prev_label = prev_label.map(|prev| match prev {
+        "_" => format!("{this_label}_skip"),
+        prev => prev.to_string(),
+    });
+
+    let read = locate(
+        read,
+        (
+            init_label.to_string(),
+            prev_label,
+            this_label.to_string(),
+            next_label.to_string(),
+        ),
+        except.to_vec(),
+        seq.to_string(),
+        matcher,
+        max_dist,
+        masked_only,
+    );
+
+    (read, match_type)
+}
+
+// a sequence with a search window is only searched for in the window, which
+// `find` searches as the piece's plan says
+fn windowed<F>(
+    read: BoxedReads,
+    window: Option<(usize, usize)>,
+    labels: &PieceLabels,
+    seq_len: usize,
+    find: F,
+) -> BoxedReads
+where
+    F: FnOnce(BoxedReads) -> BoxedReads,
+{
+    let PieceLabels {
+        init,
+        prev,
+        this,
+        next,
+    } = labels.clone();
+
+    match window {
+        Some(window) => {
+            let read = open_window(read, init, prev.clone(), this.clone(), window, seq_len);
+
+            close_window(find(read), prev, this, next)
+        }
+        None => find(read),
+    }
+}
+
+// reads where the fixed sequence `this` was not found after `prev`
+fn unmatched(
+    read: BoxedReads,
+    options: &InterpretOptions,
+    miss: Miss,
+    seq: &str,
+    (init, prev, this, next): (String, String, String, String),
+    prev_len: usize,
+) -> BoxedReads {
+    match miss {
+        Miss::Drop => drop_unmatched(read, seq, this, options.on_fail),
+        Miss::KeepRaw => keep_unmatched(read, this),
+        Miss::Fallback => fallback_unmatched(read, init, prev, this, next, prev_len, seq.len()),
+    }
+}
+
+// unbounded pieces cannot be fallen back past, see `validate_search`
+fn max_len(size: &Size) -> usize {
+    match size {
+        Size::FixedLen((n, _)) | Size::RangedLen(((_, n), _)) => *n,
+        _ => 0,
+    }
+}
+
+// fixed sequences are searched for, everything else is cut
+fn size_stage(size: &Size) -> &'static str {
+    match size {
+        Size::FixedSeq(..) => "match",
+        _ => "cut",
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct InterpretOptions {
+    pub additional_args: Vec<String>,
+    pub umi_filter: UmiFilter,
+    // reads kept by their name, see `filters::name`
+    pub name_filter: NameFilter,
+    // poly-G trimming and low complexity filtering of biological reads
+    pub complexity: Option<Arc<ComplexityFilter>>,
+    pub dedup: Option<DedupConfig>,
+    pub orient: bool,
+    pub merge: Option<MergeConfig>,
+    pub primers: Option<PrimerConfig>,
+    // named segments written to their own fastq file
+    pub segment_out: Vec<(String, String)>,
+    pub tech_read: Option<TechRead>,
+    // named segments added to the read header or a tsv
+    pub capture: Option<Capture>,
+    // the plate well of each read's barcodes, see `sink::well`
+    pub wells: Option<Arc<WellMap>>,
+    // barcodes and UMIs as read and corrected, see `sink::tags`
+    pub raw_tags: Option<RawTags>,
+    // feature barcodes sending reads to the outputs of their modality, see
+    // `features`
+    pub features: Option<Arc<Features>>,
+    // quality given to padded bases, `DEFAULT_PAD_QUAL` if not set
+    pub pad_qual: Option<u8>,
+    pub check_invariants: bool,
+    // the first reads whose segments are checked against the geometry, see
+    // `verify`
+    pub verify_sample: usize,
+    // what happens to reads failing a check
+    pub on_fail: OnFail,
+    // abort if fewer reads than this fraction pass over a window
+    pub min_pass_rate: Option<f64>,
+    // wall time of each stage, see `monitor`
+    pub timings: Option<Arc<Timings>>,
+    // reads counted for `--metrics`, see `monitor::metrics`
+    pub metrics: Option<Arc<Metrics>>,
+    // backends searching for fixed sequences, antisequence's if empty
+    pub matchers: Vec<MatcherChoice>,
+    // uppercase soft-masked bases, which are otherwise matched regardless of case
+    pub uppercase: bool,
+    // the input qualities are phred+64, see `quality`
+    pub phred64: bool,
+    // split each output into shards, written with its mates
+    pub shards: Option<Sharding>,
+    // distinct UMIs of each barcode, written once the run is done
+    pub umi_counts: Option<Arc<UmiCounts>>,
+    // sketch of the duplicate keys, reported once the run is done
+    pub duplication: Option<Arc<DuplicationEstimate>>,
+    // seed of every random choice, see `rng`
+    pub seed: u64,
+    // lengths told by whitelists allowing the map's mismatches, counted
+    // for `--len-from-whitelist`
+    pub whitelist_lengths: Option<Arc<WhitelistLengths>>,
+    // bases cut into each kind of segment and written, see `summary`
+    pub base_counts: Option<Arc<BaseCounts>>,
+    // bases at each position of the named segments, see `summary::composition`
+    pub composition: Option<Arc<Composition>>,
+    // i7 and i5 combinations against the sample sheet, see `summary::hopping`
+    pub index_hopping: Option<Arc<IndexHopping>>,
+    // where each kind of segment goes, see `sink::route`
+    pub routes: Vec<(Type, Route)>,
+}
 
 impl CompiledData {
     pub fn interpret(
@@ -32,89 +262,700 @@ impl CompiledData {
         read: BoxedReads,
         out1: String,
         out2: String,
-        additional_args: Vec<String>,
+        options: InterpretOptions,
     ) -> BoxedReads {
-        let Self {
-            geometry,
-            transformation,
-        } = self;
+        let merge_config = options.merge.clone();
+        let features = options.features.clone();
+        let shards = options.shards;
+        let check_options = options.clone();
+        let read = self.pipeline(read, options);
 
-        let mut read = read;
+        let collect = |read: BoxedReads, sel_expr: SelectorExpr, outs: Vec<String>| {
+            if let Some(sharding) = shards {
+                return write_shards(read, sel_expr, outs, sharding);
+            }
 
-        for (i, read_geometry) in geometry.iter().enumerate() {
-            read = interpret_geometry(
-                read_geometry.to_vec(),
+            write_fastq(read, sel_expr, outs)
+        };
+
+        let outs = |out1: String, out2: String| {
+            if out1.is_empty() && out2.is_empty() {
+                vec!["/dev/null".to_string()]
+            } else if out2.is_empty() || self.output_reads() == 1 {
+                vec![out1]
+            } else {
+                vec![out1, out2]
+            }
+        };
+
+        // the reads of each modality go to its outputs, the others on
+        if let Some(features) = features {
+            let mut read = read;
+            let mut others = Vec::new();
+
+            for (i, (_, [mod1, mod2])) in features.modalities.iter().enumerate() {
+                let attr = modality_attr(i);
+                let sel_expr = SelectorExpr::new(attr.as_bytes()).unwrap();
+
+                read = collect(read, sel_expr, outs(mod1.clone(), mod2.clone()));
+                others.push(format!("!{attr}"));
+            }
+
+            let sel_expr = SelectorExpr::new(others.join(" & ").as_bytes()).unwrap();
+
+            return collect(read, sel_expr, outs(out1, out2));
+        }
+
+        if let Some(config) = merge_config.filter(|_| self.output_reads() == 2) {
+            let out = config.out.clone();
+
+            let read = merge(read, self.processed(&check_options), config);
+            let read = timed(read, &check_options, "merge");
+            let read = checkpoint(read, &check_options, "merging");
+
+            let read = collect(
+                read,
+                SelectorExpr::new(b"seq1.*.merged").unwrap(),
+                vec![out],
+            );
+            collect(
+                read,
+                SelectorExpr::new(b"!seq1.*.merged").unwrap(),
+                vec![out1, out2],
+            )
+        } else if out1.is_empty() && out2.is_empty() {
+            read.collect_fastq1(sel!(), "/dev/null").boxed()
+        } else if out2.is_empty() || self.output_reads() == 1 {
+            collect(read, sel!(), vec![out1])
+        } else {
+            collect(read, sel!(), vec![out1, out2])
+        }
+    }
+
+    // build the processing of the geometry and transformation on any source of
+    // reads, how the processed reads are collected is left to the caller
+    pub fn pipeline(&self, read: BoxedReads, options: InterpretOptions) -> BoxedReads {
+        let segment_out = options.segment_out.clone();
+        let tech_read = options.tech_read.clone();
+        let capture = options.capture.clone();
+        let wells = options.wells.clone();
+        let raw_tags = options.raw_tags.clone();
+        let features = options.features.clone();
+        let routes = options.routes.clone();
+        let check_options = options.clone();
+        let (mut read, segments) = self.process(read, options);
+
+        if check_options.verify_sample > 0 {
+            let verifier = Verifier::new(self);
+            let labels = segments
+                .iter()
+                .filter_map(|(_, label)| {
+                    let name = segment_name(label)?;
+                    verifier
+                        .labels()
+                        .contains(&name)
+                        .then(|| (name.to_string(), label.clone()))
+                })
+                .collect();
+
+            read = verify_segments(
                 read,
-                format!("seq{}.", i + 1),
-                "r",
-                "l",
-                additional_args.clone(),
+                self.processed(&check_options),
+                labels,
+                Arc::new(verifier),
+                check_options.verify_sample,
             );
         }
 
-        read = if let Some(trs) = transformation {
+        let label_of = |name: &str| {
+            segments
+                .iter()
+                .find(|(_, label)| segment_name(label) == Some(name))
+                .map(|(_, label)| label.clone())
+                .unwrap_or_else(|| panic!("No segment labeled `{name}` in the geometry"))
+        };
+
+        // written before the transformation rearranges the reads
+        if !segment_out.is_empty() {
+            let outputs = segment_out
+                .into_iter()
+                .map(|(name, path)| (label_of(&name), path))
+                .collect();
+
+            read = write_segments(read, self.processed(&check_options), outputs);
+        }
+
+        if let Some(TechRead { pieces, out }) = tech_read {
+            let pieces = pieces
+                .into_iter()
+                .map(|piece| match piece {
+                    TechPiece::Segment(name) => TechPiece::Segment(label_of(&name)),
+                    constant => constant,
+                })
+                .collect();
+
+            read = write_tech_read(read, self.processed(&check_options), pieces, out);
+        }
+
+        if let Some(Capture { names, tsv }) = capture {
+            let labels = names
+                .into_iter()
+                .map(|name| {
+                    let label = label_of(&name);
+                    (name, label)
+                })
+                .collect();
+
+            read = capture_segments(read, self.processed(&check_options), labels, tsv);
+        }
+
+        if let Some(wells) = wells {
+            let barcodes = segments
+                .iter()
+                .filter(|(type_, _)| *type_ == Type::Barcode)
+                .map(|(_, label)| label.clone())
+                .collect::<Vec<_>>();
+
+            if barcodes.len() != wells.barcodes {
+                panic!(
+                    "The well map gives {} barcodes for each well, the geometry has {}",
+                    wells.barcodes,
+                    barcodes.len()
+                );
+            }
+
+            read = annotate_wells(read, self.processed(&check_options), barcodes, wells);
+        }
+
+        if let Some(RawTags { tsv }) = raw_tags {
+            let labels_of = |kind: Type| {
+                segments
+                    .iter()
+                    .filter(|(type_, _)| *type_ == kind)
+                    .map(|(_, label)| label.clone())
+                    .collect::<Vec<_>>()
+            };
+            let (barcodes, umis) = (labels_of(Type::Barcode), labels_of(Type::Umi));
+
+            read = tag_raw_corrected(read, self.processed(&check_options), barcodes, umis, tsv);
+        }
+
+        // classified before the segment may be routed out of the read
+        if let Some(features) = features {
+            let label = label_of(&features.segment);
+
+            read = classify_features(read, self.processed(&check_options), label, features);
+        }
+
+        read = route_segments(read, self.processed(&check_options), &segments, &routes);
+
+        if !self.processed_selector(&check_options).is_empty() {
+            read = restore_raw(read);
+        }
+
+        read = timed(read, &check_options, "io");
+
+        if let Some(counts) = check_options.base_counts.clone() {
+            let segments = segments
+                .iter()
+                .filter(|(type_, _)| *type_ != Type::Header)
+                .map(|(type_, label)| (type_.clone(), label.clone(), self.writes(label)))
+                .collect();
+
+            read = count_segment_bases(read, self.processed(&check_options), segments, counts);
+        }
+
+        if let Some(trs) = &self.transformation {
             for (i, tr) in trs.iter().enumerate() {
+                if tr.is_empty() {
+                    continue;
+                }
+
                 let seq_name = format!("seq{}.*", i + 1);
                 let tr = format!("{{{}}}", tr.join("}{"));
-                read = set(read, sel!(), seq_name, tr);
+                read = set(read, self.processed(&check_options), seq_name, tr);
             }
 
-            if trs.len() == 1 {
-                read.collect_fastq1(sel!(), out1).boxed()
-            } else if out1.is_empty() && out2.is_empty() {
-                read.collect_fastq1(sel!(), "/dev/null").boxed()
-            } else {
-                read.collect_fastq2(sel!(), out1, out2).boxed()
+            read = timed(read, &check_options, "transform");
+            read = checkpoint(read, &check_options, "the transformation");
+        }
+
+        if let Some(counts) = check_options.base_counts {
+            let seqs = (1..=self.output_reads())
+                .map(|i| format!("seq{i}.*"))
+                .collect();
+            read = measure(read, sel!(), seqs, move |bases| counts.written(bases));
+        }
+
+        read
+    }
+
+    // whether a segment is in the output reads, reads left out of the
+    // transformation are written as they are
+    fn writes(&self, label: &str) -> bool {
+        match &self.transformation {
+            Some(trs) => trs.iter().enumerate().any(|(i, tr)| {
+                tr.iter().any(|l| l == label)
+                    || (tr.is_empty() && label.starts_with(&format!("seq{}.", i + 1)))
+            }),
+            None => true,
+        }
+    }
+
+    // hand each processed record to `f`, e.g. to send it down a channel, instead
+    // of collecting it to a file. the transformation is applied to the record's
+    // reads but not to the reads themselves, reads kept raw are skipped
+    pub fn for_each_record<F>(
+        &self,
+        read: BoxedReads,
+        options: InterpretOptions,
+        f: F,
+    ) -> BoxedReads
+    where
+        F: Fn(Record) + Send + Sync + 'static,
+    {
+        let sel_expr = self.processed(&options);
+        let (read, segments) = self.process(read, options);
+
+        let reads = (0..self.output_reads())
+            .map(|i| match &self.transformation {
+                Some(trs) if !trs[i].is_empty() => trs[i].clone(),
+                _ => vec![format!("seq{}.*", i + 1)],
+            })
+            .collect();
+
+        for_each_record(read, sel_expr, segments, reads, f)
+    }
+
+    // the geometry of each read, returning the labels of the segments cut from them
+    fn process(
+        &self,
+        read: BoxedReads,
+        options: InterpretOptions,
+    ) -> (BoxedReads, Vec<(Type, String)>) {
+        let Self {
+            geometry,
+            repeat,
+            header,
+            pre,
+            plan,
+            ..
+        } = self;
+
+        let mut read = read;
+        let mut segments: Vec<(Type, String)> = Vec::new();
+
+        // before anything is counted, these reads were never asked for
+        if options.name_filter.is_active() {
+            read = filter_name(read, options.name_filter.clone());
+            read = timed(read, &options, "filter_name");
+        }
+
+        let pass_rate = options
+            .min_pass_rate
+            .map(|rate| Arc::new(PassRate::new(rate, PASS_RATE_WINDOW)));
+
+        if let Some(pass_rate) = pass_rate.clone() {
+            read = count_seen(read, pass_rate);
+        }
+
+        if let Some(metrics) = options.metrics.clone() {
+            read = inspect(read, move || metrics.seen());
+        }
+
+        if let Some(counts) = options.base_counts.clone() {
+            let seqs = vec!["seq1.*".to_string(), "seq2.*".to_string()];
+            read = measure(read, sel!(), seqs, move |bases| counts.read(bases));
+        }
+
+        read = timed(read, &options, "io");
+
+        if options.phred64 {
+            read = convert_phred64(read);
+        }
+
+        // reads kept raw are written as they were read, see `restore_raw`, a
+        // repeated geometry's records as they were split from the read
+        let keeps_raw = !self.processed_selector(&options).is_empty();
+        if keeps_raw && repeat.is_none() {
+            read = keep_read(read);
+        }
+
+        read = normalize_bases(read, options.uppercase);
+
+        for step in pre {
+            read = preprocess(read, step);
+        }
+        if !pre.is_empty() {
+            read = timed(read, &options, "pre");
+        }
+
+        if options.orient {
+            let oriented = orient(read, self.anchors(0), options.on_fail);
+            read = timed(oriented, &options, "orient");
+            read = checkpoint(read, &options, "orientation");
+        }
+
+        if let Some(delimiter) = repeat {
+            read = split_repeats(read, delimiter.clone());
+
+            if keeps_raw {
+                read = keep_read(read);
             }
-        } else if out1.is_empty() && out2.is_empty() {
-            read.collect_fastq1(sel!(), "/dev/null").boxed()
-        } else if out2.is_empty() {
-            read.collect_fastq1(sel!(), out1).boxed()
-        } else {
-            read.collect_fastq2(sel!(), out1, out2).boxed()
+        }
+
+        for (i, read_geometry) in geometry.iter().enumerate() {
+            if let Some(indices) = header.get(i).filter(|h| !h.is_empty()) {
+                read = header_index(read, i + 1, indices.clone(), options.on_fail);
+                read = timed(read, &options, "header");
+                segments.extend(
+                    indices
+                        .iter()
+                        .map(|(label, _)| (Type::Header, format!("name{}.{label}", i + 1))),
+                );
+            }
+
+            // passthrough reads, `0{}`, are left as they are
+            if read_geometry.is_empty() {
+                continue;
+            }
+
+            let (next_read, read_segments) =
+                interpret_geometry(read_geometry, &plan[i], read, options.clone());
+
+            read = checkpoint(
+                next_read,
+                &options,
+                &format!("the geometry of read {}", i + 1),
+            );
+            segments.extend(read_segments);
+        }
+
+        // primers start the biological read, or the first read without one
+        if let Some(config) = options.primers.clone() {
+            let label = segments
+                .iter()
+                .find(|(type_, _)| *type_ == Type::ReadSeq)
+                .map_or("seq1.*".to_string(), |(_, label)| label.clone());
+
+            read = match_primers(read, self.processed(&options), label, config);
+            read = timed(read, &options, "primers");
+            read = checkpoint(read, &options, "primer trimming");
+        }
+
+        let labels_of = |types: &[Type]| {
+            segments
+                .iter()
+                .filter(|(type_, _)| types.contains(type_))
+                .map(|(_, label)| label.clone())
+                .collect::<Vec<_>>()
         };
 
-        read
+        // the estimate is of the reads before any duplicate is dropped
+        if let Some(estimate) = options.duplication.clone() {
+            read = estimate_duplication(
+                read,
+                self.processed(&options),
+                labels_of(&[Type::Barcode, Type::Umi]),
+                labels_of(&[Type::ReadSeq]),
+                estimate,
+            );
+            read = timed(read, &options, "duplication");
+        }
+
+        if let Some(config) = options.dedup.clone() {
+            read = dedup(
+                read,
+                self.processed(&options),
+                labels_of(&[Type::Barcode, Type::Umi]),
+                labels_of(&[Type::ReadSeq]),
+                config,
+                options.on_fail,
+            );
+            read = timed(read, &options, "filter");
+        }
+
+        if let Some(counts) = options.umi_counts.clone() {
+            // the geometry has both, see `check_options`
+            let (barcodes, umis) = (labels_of(&[Type::Barcode]), labels_of(&[Type::Umi]));
+            read = count_umis(read, self.processed(&options), barcodes, umis, counts);
+            read = timed(read, &options, "umi_counts");
+        }
+
+        if let Some(composition) = options.composition.clone() {
+            let named = segments
+                .iter()
+                .filter(|(type_, _)| *type_ != Type::Header)
+                .filter_map(|(type_, label)| {
+                    let name = segment_name(label)?;
+                    Some((composition.register(name, type_.clone()), label.clone()))
+                })
+                .collect();
+
+            read = count_composition(read, self.processed(&options), named, composition);
+            read = timed(read, &options, "composition");
+        }
+
+        if let Some(hopping) = options.index_hopping.clone() {
+            let (i7, i5) = match index_labels(&segments) {
+                Some(labels) => labels,
+                None => panic!(
+                    "Counting index hopping needs barcodes labeled <i7> and <i5> in the geometry"
+                ),
+            };
+
+            read = count_index_hopping(read, self.processed(&options), i7, i5, hopping);
+            read = timed(read, &options, "index_hopping");
+        }
+
+        if let Some(pass_rate) = pass_rate {
+            read = count_passed(read, self.processed(&options), move || pass_rate.passed());
+        }
+
+        if let Some(metrics) = options.metrics.clone() {
+            read = count_passed(read, self.processed(&options), move || metrics.passed());
+        }
+
+        (read, segments)
+    }
+
+    // reads kept as they were read after missing a fixed sequence or failing
+    // a check have no segments, and are not counted as passed
+    pub fn processed_selector(&self, options: &InterpretOptions) -> &'static str {
+        let keeps_raw = self
+            .geometry
+            .iter()
+            .flatten()
+            .any(|gm| matches!(gm.expr.0.size, Size::FixedSeq(_, Miss::KeepRaw, _)));
+
+        if keeps_raw || options.on_fail == OnFail::Flag {
+            "!seq1.*.raw"
+        } else {
+            ""
+        }
+    }
+
+    fn processed(&self, options: &InterpretOptions) -> SelectorExpr {
+        SelectorExpr::new(self.processed_selector(options).as_bytes()).unwrap()
+    }
+
+    // number of reads in each output record
+    pub fn output_reads(&self) -> usize {
+        if let Some(trs) = &self.transformation {
+            trs.len()
+        } else {
+            self.geometry.len()
+        }
+    }
+
+    // fixed sequences declared in the geometry of a read
+    pub fn anchors(&self, read: usize) -> Vec<String> {
+        self.geometry
+            .get(read)
+            .into_iter()
+            .flatten()
+            .filter_map(|gm| match &gm.expr.0.size {
+                Size::FixedSeq((seq, _), ..) => Some(seq.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // the fewest bases a read needs for its geometry, and the most it uses
+    // unless it ends in an unbounded piece. optional groups may be absent,
+    // every branch of a set of alternatives is counted towards the most
+    pub fn read_lengths(&self, read: usize) -> (usize, Option<usize>) {
+        let optional = self.optional.get(read).cloned().unwrap_or_default();
+        let mut lengths = (0, Some(0));
+
+        for (i, gm) in self.geometry.get(read).into_iter().flatten().enumerate() {
+            let (min, max) = match &gm.expr.0.size {
+                Size::FixedSeq((seq, _), ..) => (seq.len(), Some(seq.len())),
+                Size::FixedLen((n, _)) => (*n, Some(*n)),
+                Size::RangedLen(((a, b), _)) => (*a, Some(*b)),
+                Size::UnboundedLen => (0, None),
+            };
+
+            if !optional.iter().any(|range| range.contains(&i)) {
+                lengths.0 += min;
+            }
+            lengths.1 = lengths.1.zip(max).map(|(total, max)| total + max);
+        }
+
+        // a repeated geometry uses as many copies as the read holds
+        if self.repeat.is_some() {
+            lengths.1 = None;
+        }
+
+        lengths
     }
 }
 
+// cut a read along the steps of its plan
 fn interpret_geometry(
-    geometry: Vec<GeometryMeta>,
+    geometry: &[GeometryMeta],
+    steps: &[Step],
     read: BoxedReads,
-    init_label: String,
-    right: &'static str,
-    left: &'static str,
-    additional_args: Vec<String>,
-) -> BoxedReads {
-    let mut geometry_iter = geometry.into_iter();
+    options: InterpretOptions,
+) -> (BoxedReads, Vec<(Type, String)>) {
+    let mut read = read;
+    let mut segments: Vec<(Type, String)> = Vec::new();
+
+    for step in steps {
+        let (next_read, next_segments) = match step {
+            Step::Piece(piece) => geometry[piece.index].interpret(read, piece, options.clone()),
+            Step::Dual(prev, piece) => geometry[piece.index].interpret_dual(
+                &geometry[prev.index],
+                read,
+                (prev, piece),
+                options.clone(),
+            ),
+            Step::Optional(group) => interpret_optional(geometry, group, read, options.clone()),
+        };
+
+        read = next_read;
+
+        // reads without any of the branches are unmatched
+        if let Step::Optional(GroupPlan {
+            branches: Some(found),
+            ..
+        }) = step
+        {
+            read = any_branch(read, found.clone(), options.on_fail);
+            read = checkpoint(read, &options, "alternatives");
+        }
+
+        segments.extend(next_segments);
+    }
+
+    (read, segments)
+}
 
+// an optional group is matched where it would start, reads without it get empty
+// pieces and continue from the same place
+fn interpret_optional(
+    geometry: &[GeometryMeta],
+    group: &GroupPlan,
+    read: BoxedReads,
+    options: InterpretOptions,
+) -> (BoxedReads, Vec<(Type, String)>) {
     let mut read = read;
+    let mut segments: Vec<(Type, String)> = Vec::new();
+
+    for piece in &group.pieces {
+        let (next_read, next_segments) =
+            geometry[piece.index].interpret_optional(read, piece, group, options.clone());
+
+        read = next_read;
+        segments.extend(next_segments);
+    }
+
+    read = skip_optional(read, group);
 
-    let mut label: Vec<String> = vec![init_label];
+    for (type_, l) in &segments {
+        read = filter_segment(read, type_, l.clone(), &options);
+    }
+
+    (read, segments)
+}
 
-    while let Some(gp) = geometry_iter.next() {
-        let (_, size, _, _) = gp.unpack();
+// each kind of segment routed out of the read is added to the read name or
+// written to its sidecar, then removed
+fn route_segments(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    segments: &[(Type, String)],
+    routes: &[(Type, Route)],
+) -> BoxedReads {
+    let mut read = read;
+    let mut kinds: Vec<&Type> = Vec::new();
 
-        read = match size {
-            Size::FixedSeq(_) | Size::FixedLen(_) => {
-                gp.interpret(read, &mut label, left, right, additional_args.clone())
+    for (type_, _) in routes {
+        if !kinds.contains(&type_) {
+            kinds.push(type_);
+        }
+    }
+
+    for type_ in kinds {
+        let labels = segments
+            .iter()
+            .filter(|(t, _)| t == type_)
+            .map(|(_, label)| label.clone())
+            .collect::<Vec<_>>();
+
+        match routed(routes, type_).unwrap() {
+            Route::Keep => continue,
+            Route::Header => {
+                let named = labels
+                    .iter()
+                    .map(|label| {
+                        let name = segment_name(label).unwrap_or(kind_name(type_));
+                        (name.to_string(), label.clone())
+                    })
+                    .collect();
+
+                read = capture_segments(read, sel_expr.clone(), named, None);
             }
-            Size::RangedLen(_) | Size::UnboundedLen => {
-                // by rules of geometry this should either be None or a sequence
-                if let Some(next) = geometry_iter.next() {
-                    next.interpret_dual(gp, read, &mut label, right, left, additional_args.clone())
-                } else {
-                    gp.interpret(read, &mut label, left, right, additional_args.clone())
-                }
+            Route::Sidecar(path) => {
+                let pieces = labels.iter().cloned().map(TechPiece::Segment).collect();
+
+                read = write_tech_read(read, sel_expr.clone(), pieces, path.clone());
             }
-        };
+            Route::Hash => {
+                let tag = format!("{}_hash", kind_name(type_));
+
+                read = hash_segments(read, sel_expr.clone(), labels.clone(), tag);
+            }
+            Route::Drop => {}
+        }
 
-        label.push(format!("_{right}"));
+        for label in labels {
+            read = remove(read, label, String::new());
+        }
     }
 
     read
 }
 
+// with `--count-bases` the length of a segment is noted as it is cut
+fn note_cut(read: BoxedReads, options: &InterpretOptions, label: &str, size: &Size) -> BoxedReads {
+    let read = if options.base_counts.is_some() {
+        note_cut_len(read, label.to_string())
+    } else {
+        read
+    };
+
+    // a captured fixed sequence is kept as it was found, before the functions
+    // on it change or remove its bases
+    let captured = options.capture.as_ref().is_some_and(|capture| {
+        segment_name(label).is_some_and(|name| capture.names.iter().any(|n| n == name))
+    });
+    if captured && matches!(size, Size::FixedSeq(..)) {
+        note_cut_seq(read, label.to_string())
+    } else {
+        read
+    }
+}
+
+fn filter_segment(
+    read: BoxedReads,
+    type_: &Type,
+    label: String,
+    options: &InterpretOptions,
+) -> BoxedReads {
+    if *type_ == Type::Umi && options.umi_filter.is_active() {
+        let read = filter_umi(read, label, options.umi_filter.clone(), options.on_fail);
+
+        timed(read, options, "filter")
+    } else if let (Type::ReadSeq, Some(filter)) = (type_, &options.complexity) {
+        let read = filter_complexity(read, label, filter.clone(), options.on_fail);
+
+        timed(read, options, "filter")
+    } else {
+        read
+    }
+}
+
 fn parse_additional_args(arg: String, args: Vec<String>) -> String {
     match arg.parse::<usize>() {
         Ok(n) => args
@@ -136,17 +977,47 @@ fn execute_stack(
     attr: String,
     read: BoxedReads,
     size: Size,
-    additional_args: Vec<String>,
+    options: InterpretOptions,
 ) -> BoxedReads {
     let mut read = read;
+    let additional_args = options.additional_args.clone();
+    let pad_qual = options.pad_qual.unwrap_or(DEFAULT_PAD_QUAL);
 
+    // ranges include both ends wherever they are used, `[9-11]` is 9 to 11
+    // bases long and a padding target is the length given to `pad_to`
     let range = if let Size::RangedLen(((a, b), _)) = size {
         Some(a..=b)
     } else {
         None
     };
 
+    // the segment as read is kept before any map corrects it
+    let corrects = stack.iter().any(|(fn_, _)| {
+        matches!(
+            fn_,
+            CompiledFunction::Map(..) | CompiledFunction::MapWithMismatch(..)
+        )
+    });
+    if attr.is_empty() && corrects && options.raw_tags.is_some() {
+        read = keep_raw(read, label.clone());
+    }
+
     for (fn_, _) in stack.into_iter().rev() {
+        let stage = match fn_ {
+            CompiledFunction::Truncate(_)
+            | CompiledFunction::TruncateLeft(_)
+            | CompiledFunction::TruncateTo(_)
+            | CompiledFunction::TruncateToLeft(_) => "trim",
+            CompiledFunction::Pad(..)
+            | CompiledFunction::PadLeft(..)
+            | CompiledFunction::PadTo(..)
+            | CompiledFunction::PadToLeft(..) => "pad",
+            CompiledFunction::Map(..)
+            | CompiledFunction::MapWithMismatch(..)
+            | CompiledFunction::FilterWithinDist(..) => "map",
+            _ => "transform",
+        };
+
         read = match fn_ {
             CompiledFunction::Reverse => reverse(read, label.clone(), attr.clone()),
             CompiledFunction::ReverseComp => reverse_comp(read, label.clone(), attr.clone()),
@@ -169,17 +1040,27 @@ fn execute_stack(
                 LeftEnd(n),
             ),
             CompiledFunction::Remove => remove(read, label.clone(), attr.clone()),
-            CompiledFunction::Pad(n, nuc) => {
-                pad_by(read, label.clone(), attr.clone(), RightEnd(n), nuc)
-            }
+            CompiledFunction::Pad(n, nuc) => pad_by(
+                read,
+                label.clone(),
+                attr.clone(),
+                RightEnd(n),
+                nuc,
+                pad_qual,
+            ),
             CompiledFunction::PadLeft(n, nuc) => {
-                pad_by(read, label.clone(), attr.clone(), LeftEnd(n), nuc)
-            }
-            CompiledFunction::PadTo(n, nuc) => {
-                pad_to(read, label.clone(), attr.clone(), RightEnd(n), nuc)
+                pad_by(read, label.clone(), attr.clone(), LeftEnd(n), nuc, pad_qual)
             }
+            CompiledFunction::PadTo(n, nuc) => pad_to(
+                read,
+                label.clone(),
+                attr.clone(),
+                RightEnd(n),
+                nuc,
+                pad_qual,
+            ),
             CompiledFunction::PadToLeft(n, nuc) => {
-                pad_to(read, label.clone(), attr.clone(), LeftEnd(n), nuc)
+                pad_to(read, label.clone(), attr.clone(), LeftEnd(n), nuc, pad_qual)
             }
             CompiledFunction::Normalize => {
                 normalize(read, label.clone(), attr.clone(), range.clone().unwrap())
@@ -194,7 +1075,7 @@ fn execute_stack(
                     String::from("not_mapped"),
                     mapped,
                     size.clone(),
-                    additional_args.clone(),
+                    options.clone(),
                 )
             }
             CompiledFunction::MapWithMismatch(file, fns, mismatch) => {
@@ -207,99 +1088,163 @@ fn execute_stack(
                     String::from("mapped"),
                     mapped,
                     size.clone(),
-                    additional_args.clone(),
+                    options.clone(),
                 )
             }
             CompiledFunction::FilterWithinDist(file, mismatch) => {
                 let file = parse_additional_args(file, additional_args.clone());
 
-                filter(read, label.clone(), attr.clone(), file, mismatch)
+                filter(
+                    read,
+                    label.clone(),
+                    attr.clone(),
+                    file,
+                    mismatch,
+                    options.on_fail,
+                )
             }
             CompiledFunction::Hamming(_) => unreachable!(),
         };
+        read = timed(read, &options, stage);
     }
 
     read
 }
 
 impl GeometryMeta {
-    fn unpack(&self) -> (Type, Size, Option<String>, Vec<Spanned<CompiledFunction>>) {
+    fn unpack(&self) -> (Type, Size, Vec<Spanned<CompiledFunction>>) {
         let GeometryMeta {
-            expr: (GeometryPiece { type_, size, label }, _),
+            expr: (GeometryPiece { type_, size, .. }, _),
             stack,
         } = self.clone();
 
-        (type_, size, label, stack)
+        (type_, size, stack)
     }
 
-    fn interpret_no_cut(
+    // the piece with the functions on it, with discards that are not routed removed
+    fn functions(
         &self,
-        read: BoxedReads,
-        label: &mut Vec<String>,
-        additional_args: Vec<String>,
-    ) -> BoxedReads {
-        let (type_, size, self_label, mut stack) = self.unpack();
-
-        let (init_label, cur_label) = labels(label);
-        let seq_name = label.get(0).unwrap();
-
-        let this_label = if let Some(l) = self_label {
-            format!("{seq_name}{l}")
-        } else {
-            cur_label
-        };
+        options: &InterpretOptions,
+    ) -> (Type, Size, Vec<Spanned<CompiledFunction>>) {
+        let (type_, size, mut stack) = self.unpack();
 
-        if type_ == Type::Discard {
+        // routed discards are removed with the other routed segments
+        if type_ == Type::Discard && routed(&options.routes, &type_).is_none() {
             stack.push((CompiledFunction::Remove, 0..1))
         }
 
+        (type_, size, stack)
+    }
+
+    fn interpret_no_cut(
+        &self,
+        read: BoxedReads,
+        piece: &PiecePlan,
+        options: InterpretOptions,
+    ) -> (BoxedReads, Vec<(Type, String)>) {
+        let (type_, size, stack) = self.functions(&options);
+        let this_label = piece.labels.this.clone();
+
         // this is only called from `interpret_dual` which is for variable to fixedSeq
         // thus this is only for variable sized segments
         let read = match size {
             Size::RangedLen(((a, b), _)) => {
-                process_ranged_len_no_cut(read, this_label.clone(), a..=b)
+                process_ranged_len_no_cut(read, piece, a..=b, options.on_fail)
             }
-            Size::UnboundedLen => process_unbounded_no_cut(read, init_label, this_label.clone()),
+            Size::UnboundedLen => process_unbounded_no_cut(read, piece),
             _ => unreachable!(),
         };
+        let read = timed(read, &options, "validate");
+
+        let read = note_cut(read, &options, &this_label, &size);
 
-        execute_stack(
+        let read = execute_stack(
             stack,
-            this_label,
+            this_label.clone(),
             String::from(""),
             read,
             size,
-            additional_args,
-        )
+            options.clone(),
+        );
+
+        let read = filter_segment(read, &type_, this_label.clone(), &options);
+
+        (read, vec![(type_, this_label)])
     }
 
-    fn interpret(
+    // a piece of an optional group, only checked on reads where the group's
+    // leading piece was found
+    fn interpret_optional(
         &self,
         read: BoxedReads,
-        label: &mut Vec<String>,
-        left: &'static str,
-        right: &'static str,
-        additional_args: Vec<String>,
-    ) -> BoxedReads {
-        let (type_, size, self_label, mut stack) = self.unpack();
+        piece: &PiecePlan,
+        group: &GroupPlan,
+        options: InterpretOptions,
+    ) -> (BoxedReads, Vec<(Type, String)>) {
+        let (type_, size, mut stack) = self.functions(&options);
+        let PieceLabels {
+            init, this, next, ..
+        } = piece.labels.clone();
 
-        let (init_label, cur_label) = labels(label);
-        let seq_name = label.get(0).unwrap();
+        let read = match size.clone() {
+            Size::FixedSeq((seq, _), ..) => {
+                // the group is not searched for, it has to be where it is expected
+                let match_type = if let Some(&(CompiledFunction::Hamming(n), _)) = stack.last() {
+                    stack.pop();
+                    Hamming(Frac(1.0 - (n as f64 / seq.len() as f64)))
+                } else {
+                    PrefixAln {
+                        identity: 1.0,
+                        overlap: 1.0,
+                    }
+                };
 
-        let this_label = if let Some(l) = self_label {
-            format!("{seq_name}{l}")
-        } else {
-            format!("{cur_label}_{left}")
+                let (read, match_type) = search(
+                    read,
+                    &options,
+                    (&init, None, &this, &next),
+                    &group.except,
+                    &seq,
+                    match_type,
+                );
+
+                process_optional_sequence(
+                    read,
+                    seq,
+                    piece,
+                    &group.anchor,
+                    match_type,
+                    options.on_fail,
+                )
+            }
+            Size::FixedLen((len, _)) => {
+                process_optional_fixed_len(read, piece, &group.anchor, len, options.on_fail)
+            }
+            _ => unreachable!(),
         };
-        let next_label = format!("{cur_label}_{right}");
+        let read = timed(read, &options, size_stage(&size));
 
-        if type_ == Type::Discard {
-            stack.push((CompiledFunction::Remove, 0..1))
-        }
+        let read = note_cut(read, &options, &this, &size);
+
+        let read = execute_stack(stack, this.clone(), String::from(""), read, size, options);
+
+        (read, vec![(type_, this)])
+    }
+
+    fn interpret(
+        &self,
+        read: BoxedReads,
+        piece: &PiecePlan,
+        options: InterpretOptions,
+    ) -> (BoxedReads, Vec<(Type, String)>) {
+        let (type_, size, stack) = self.functions(&options);
+        let PieceLabels {
+            init, this, next, ..
+        } = piece.labels.clone();
 
         // execute the requisite process here
         let read = match size.clone() {
-            Size::FixedSeq((seq, _)) => {
+            Size::FixedSeq((seq, _), miss, window) => {
                 let match_type = if !stack.is_empty() {
                     match stack.last().unwrap() {
                         (CompiledFunction::Hamming(n), _) => {
@@ -315,70 +1260,71 @@ impl GeometryMeta {
                     ExactSearch
                 };
 
-                process_sequence(
+                let read = windowed(read, window, &piece.labels, seq.len(), |read| {
+                    find_sequence(read, &options, piece, &seq, match_type)
+                });
+
+                // nothing is searched past before the sequence
+                unmatched(
                     read,
-                    seq,
-                    init_label,
-                    this_label.clone(),
-                    "_".to_string(),
-                    next_label,
-                    match_type,
+                    &options,
+                    miss,
+                    &seq,
+                    (init, "_".to_string(), this.clone(), next),
+                    0,
                 )
             }
-            Size::FixedLen((len, _)) => {
-                process_fixed_len(read, init_label, this_label.clone(), next_label, len)
-            }
-            Size::RangedLen(((a, b), _)) => {
-                process_ranged_len(read, init_label, this_label.clone(), next_label, a..=b)
-            }
-            Size::UnboundedLen => process_unbounded(read, init_label, this_label.clone()),
+            Size::FixedLen((len, _)) => process_fixed_len(read, piece, len, options.on_fail),
+            Size::RangedLen(((a, b), _)) => match self.whitelist() {
+                Some((file, mismatch)) => process_whitelisted_len(
+                    read,
+                    piece,
+                    a..=b,
+                    parse_additional_args(file, options.additional_args.clone()),
+                    mismatch,
+                    options.whitelist_lengths.clone(),
+                    options.on_fail,
+                ),
+                None => process_ranged_len(read, piece, a..=b, options.on_fail),
+            },
+            Size::UnboundedLen => process_unbounded(read, piece),
         };
+        let read = timed(read, &options, size_stage(&size));
+
+        let read = note_cut(read, &options, &this, &size);
 
-        execute_stack(
+        let read = execute_stack(
             stack,
-            this_label,
+            this.clone(),
             String::from(""),
             read,
             size,
-            additional_args,
-        )
+            options.clone(),
+        );
+
+        let read = filter_segment(read, &type_, this.clone(), &options);
+
+        (read, vec![(type_, this)])
     }
 
     fn interpret_dual(
         &self,
-        prev: Self,
+        prev: &Self,
         read: BoxedReads,
-        label: &mut Vec<String>,
-        right: &'static str,
-        left: &'static str,
-        additional_args: Vec<String>,
-    ) -> BoxedReads {
-        // unpack label for self
-        let (_, size, this_label, mut stack) = self.unpack();
-        let (_, _, prev_label, _) = prev.unpack();
-        // execute the processing for next
-
-        let (init_label, cur_label) = labels(label);
-        let seq_name = label.get(0).unwrap();
-
-        let mut left_label = label.clone();
-
-        let prev_label = if let Some(l) = prev_label {
-            left_label.push(l.to_string());
-            format!("{seq_name}{l}")
-        } else {
-            left_label.push(format!("_{left}"));
-            format!("{cur_label}_{left}")
-        };
-        let this_label = if let Some(l) = this_label {
-            format!("{seq_name}{l}")
-        } else {
-            format!("{cur_label}_anchor")
-        };
-        let next_label = format!("{cur_label}_{right}");
+        (prev_piece, piece): (&PiecePlan, &PiecePlan),
+        options: InterpretOptions,
+    ) -> (BoxedReads, Vec<(Type, String)>) {
+        let (_, size, mut stack) = self.unpack();
+        let (_, prev_size, _) = prev.unpack();
+        let PieceLabels {
+            init,
+            prev: prev_label,
+            this,
+            next,
+        } = piece.labels.clone();
 
         let read = match size.clone() {
-            Size::FixedSeq((seq, _)) => {
+            Size::FixedSeq((seq, _), miss, window) => {
                 // check if the first function on the stack is a hamming search
                 // else do an exact match
                 let match_type = if !stack.is_empty() {
@@ -393,30 +1339,61 @@ impl GeometryMeta {
                     ExactSearch
                 };
 
-                let read = process_sequence(
+                let read = windowed(read, window, &piece.labels, seq.len(), |read| {
+                    find_sequence(read, &options, piece, &seq, match_type)
+                });
+                let read = unmatched(
                     read,
-                    seq,
-                    init_label,
-                    this_label.clone(),
-                    prev_label,
-                    next_label,
-                    match_type,
+                    &options,
+                    miss,
+                    &seq,
+                    (init, prev_label, this.clone(), next),
+                    max_len(&prev_size),
                 );
+                let read = timed(read, &options, "match");
+                let read = note_cut(read, &options, &this, &size);
 
                 execute_stack(
                     stack,
-                    this_label,
+                    this.clone(),
                     String::from(""),
                     read,
                     size,
-                    additional_args.clone(),
+                    options.clone(),
                 )
             }
             _ => unreachable!(),
         };
 
-        // call interpret for self
-        // this is just an unbounded or ranged segment. No cut just set or validate
-        prev.interpret_no_cut(read, &mut left_label, additional_args)
+        // the piece before is just an unbounded or ranged segment, it is not
+        // cut, only set or validated
+        let (read, mut segments) = prev.interpret_no_cut(read, prev_piece, options);
+        segments.push((Type::FixedSeq, this));
+
+        (read, segments)
     }
 }
+
+// search for the fixed sequence of `piece` where its plan says, by another
+// backend and then antisequence, see `search`
+fn find_sequence(
+    read: BoxedReads,
+    options: &InterpretOptions,
+    piece: &PiecePlan,
+    seq: &str,
+    match_type: MatchType,
+) -> BoxedReads {
+    let plan = piece.search.as_ref().unwrap();
+    let PieceLabels { this, next, .. } = &piece.labels;
+
+    let (read, match_type) = search(
+        read,
+        options,
+        (&plan.from, Some(&plan.prev), this, next),
+        &[],
+        seq,
+        match_type,
+    );
+
+    process_sequence(read, seq.to_string(), plan, match_type)
+}
@@ -1,4 +1,4 @@
 pub mod compile;
 pub mod interpret;
-pub mod lexer;
-pub mod parser;
+
+pub use seqproc_core::{lexer, parser};
@@ -0,0 +1,198 @@
+/*
+   Regression cases for a geometry, run with `seqproc test --case <dir>`.
+   A case is a directory holding the geometry as `geom.fgdl`, the reads as
+   `r1.fastq` and, for paired reads, `r2.fastq`, and the outputs expected
+   of them as `expected_r1.fastq` and, where the geometry outputs two
+   reads, `expected_r2.fastq`. Files given to the geometry, such as the
+   whitelist of a `map`, are listed a line each in `args`, relative to the
+   case. The reads are run through the geometry and each output compared
+   with what is expected, a case passing if all are the same. Where they
+   differ, the first records that differ are shown line by line, so a lab
+   keeping cases for its own geometries sees what a change to the geometry
+   or to seqproc did to them.
+*/
+
+use std::{
+    env,
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::{
+    interpret::InterpretOptions,
+    runner::{run, CancellationToken, RunConfig},
+};
+
+// differing lines shown of an output before the rest are only counted
+pub const SHOWN_LINES: usize = 8;
+
+// the lines of a fastq record
+const FASTQ_LINES: [&str; 4] = ["name", "sequence", "+", "quality"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Case {
+    pub name: String,
+    pub geometry: String,
+    pub inputs: Vec<PathBuf>,
+    // the expected outputs, a second one where the geometry outputs two reads
+    pub expected: Vec<PathBuf>,
+    pub additional_args: Vec<String>,
+}
+
+fn existing(path: PathBuf) -> Option<PathBuf> {
+    path.is_file().then_some(path)
+}
+
+impl Case {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let geometry = fs::read_to_string(dir.join("geom.fgdl"))?;
+
+        let inputs = ["r1.fastq", "r2.fastq"]
+            .iter()
+            .map_while(|file| existing(dir.join(file)))
+            .collect::<Vec<_>>();
+        if inputs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no r1.fastq", dir.display()),
+            ));
+        }
+
+        let expected = ["expected_r1.fastq", "expected_r2.fastq"]
+            .iter()
+            .map_while(|file| existing(dir.join(file)))
+            .collect::<Vec<_>>();
+        if expected.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no expected_r1.fastq", dir.display()),
+            ));
+        }
+
+        let additional_args = match fs::read_to_string(dir.join("args")) {
+            Ok(args) => args
+                .lines()
+                .map(str::trim)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| dir.join(arg).to_string_lossy().into_owned())
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        let name = dir.file_name().map_or_else(
+            || dir.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+
+        Ok(Self {
+            name,
+            geometry,
+            inputs,
+            expected,
+            additional_args,
+        })
+    }
+
+    // the differences of each output from what is expected, none if the
+    // case passes
+    pub fn run(&self, threads: usize) -> Result<Vec<String>, String> {
+        let dir = env::temp_dir();
+        let outputs = (1..=2)
+            .map(|n| {
+                dir.join(format!(
+                    "seqproc-{}-{}_r{n}.fastq",
+                    process::id(),
+                    self.name
+                ))
+            })
+            .collect::<Vec<_>>();
+        let path = |p: &PathBuf| p.to_string_lossy().into_owned();
+
+        let config = RunConfig {
+            geometry: self.geometry.clone(),
+            file1: path(&self.inputs[0]),
+            file2: self.inputs.get(1).map(path),
+            out1: path(&outputs[0]),
+            out2: if self.expected.len() > 1 {
+                path(&outputs[1])
+            } else {
+                String::new()
+            },
+            threads,
+            options: InterpretOptions {
+                additional_args: self.additional_args.clone(),
+                ..InterpretOptions::default()
+            },
+        };
+
+        let res = run(config, |_| (), CancellationToken::new())
+            .map_err(|e| e.to_string())
+            .and_then(|_| {
+                let mut diffs = Vec::new();
+                for (output, expected) in outputs.iter().zip(&self.expected) {
+                    let read = |p: &PathBuf| {
+                        fs::read_to_string(p).map_err(|e| format!("{}: {e}", p.display()))
+                    };
+                    if let Some(d) = diff(&path(expected), &read(expected)?, &read(output)?) {
+                        diffs.push(d);
+                    }
+                }
+                Ok(diffs)
+            });
+
+        for output in &outputs {
+            let _ = fs::remove_file(output);
+        }
+
+        res
+    }
+}
+
+// the lines of `actual` differing from `expected`, with the record and
+// the line of the record each is, none if they are the same
+pub fn diff(name: &str, expected: &str, actual: &str) -> Option<String> {
+    let (expected, actual) = (
+        expected.lines().collect::<Vec<_>>(),
+        actual.lines().collect::<Vec<_>>(),
+    );
+
+    let differing = (0..expected.len().max(actual.len()))
+        .filter(|i| expected.get(*i) != actual.get(*i))
+        .collect::<Vec<_>>();
+    if differing.is_empty() {
+        return None;
+    }
+
+    let mut out = format!("--- {name}\n+++ output\n");
+    for i in differing.iter().take(SHOWN_LINES) {
+        writeln!(
+            out,
+            "@@ record {}, {} @@",
+            i / FASTQ_LINES.len() + 1,
+            FASTQ_LINES[i % FASTQ_LINES.len()]
+        )
+        .unwrap();
+        if let Some(line) = expected.get(*i) {
+            writeln!(out, "-{line}").unwrap();
+        }
+        if let Some(line) = actual.get(*i) {
+            writeln!(out, "+{line}").unwrap();
+        }
+    }
+
+    if differing.len() > SHOWN_LINES {
+        writeln!(out, "and {} more lines", differing.len() - SHOWN_LINES).unwrap();
+    }
+    writeln!(
+        out,
+        "{} records expected, {} output",
+        expected.len() / FASTQ_LINES.len(),
+        actual.len() / FASTQ_LINES.len()
+    )
+    .unwrap();
+
+    Some(out)
+}
@@ -0,0 +1,59 @@
+/*
+   Illumina (Casava 1.8 and later) read headers of the form
+   `@<name> <read>:<is filtered>:<control number>:<index>`
+   where the index is the sample index read, `ACGTACGT+TTGGCCAA`
+   for dual indexed runs. Header pieces of the geometry take the
+   indices from here rather than from separate I1/I2 files.
+
+   The read name itself holds the position of the cluster on the flow
+   cell, `<instrument>:<run>:<flowcell>:<lane>:<tile>:<x>:<y>`, or
+   `<instrument>:<lane>:<tile>:<x>:<y>#<index>/<read>` before Casava 1.8.
+   Reads from known bad tiles are dropped by their lane and tile.
+*/
+
+// the sample indices in the comment ending a Casava 1.8 header
+pub fn casava_indices(name: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut fields = name
+        .split(|c| c.is_ascii_whitespace())
+        .filter(|f| !f.is_empty());
+
+    let (_, comment) = (fields.next()?, fields.next()?);
+
+    if fields.next().is_some() {
+        return None;
+    }
+
+    let numeric = |f: &[u8]| !f.is_empty() && f.iter().all(u8::is_ascii_digit);
+
+    match comment.split(|c| *c == b':').collect::<Vec<_>>()[..] {
+        [read, b"Y" | b"N", control, index] if numeric(read) && numeric(control) => index
+            .split(|c| *c == b'+')
+            .map(|i| (!i.is_empty() && i.iter().all(|c| b"ACGTN".contains(c))).then_some(i))
+            .collect(),
+        _ => None,
+    }
+}
+
+// whether a header carries exactly one index of each length in order
+pub fn has_indices(name: &[u8], lens: &[usize]) -> bool {
+    casava_indices(name).is_some_and(|indices| {
+        indices.len() == lens.len() && indices.iter().zip(lens).all(|(i, len)| i.len() == *len)
+    })
+}
+
+// the lane and tile of a read from the coordinates in its name
+pub fn illumina_tile(name: &[u8]) -> Option<(u32, u32)> {
+    let name = name.split(|c| c.is_ascii_whitespace()).next()?;
+    // the index and read number of older headers
+    let name = name.split(|c| *c == b'#').next()?;
+
+    let number = |f: &[u8]| std::str::from_utf8(f).ok()?.parse::<u32>().ok();
+
+    match name.split(|c| *c == b':').collect::<Vec<_>>()[..] {
+        [_, _, _, lane, tile, x, y] | [_, lane, tile, x, y] => {
+            let (lane, tile, _, _) = (number(lane)?, number(tile)?, number(x)?, number(y)?);
+            Some((lane, tile))
+        }
+        _ => None,
+    }
+}
@@ -1,4 +1,33 @@
+pub mod adapters;
+pub mod checksum;
+pub mod convert;
+#[cfg(feature = "cli")]
+pub mod describe;
+pub mod explain;
+pub mod extract;
+pub mod failure;
+pub mod features;
+pub mod filters;
+pub mod golden;
 mod geometry;
+pub mod header;
+pub mod long_read;
+pub mod matchers;
+pub mod merge;
+pub mod monitor;
+pub mod presets;
+pub mod preview;
+pub mod primers;
+pub mod quality;
 mod processors;
+pub mod repair;
+pub mod report;
+pub mod rng;
+pub mod runner;
+pub mod sink;
+pub mod source;
+pub mod summary;
+pub mod verify;
 
 pub use crate::geometry::*;
+pub use crate::runner::run;
@@ -0,0 +1,116 @@
+/*
+   Support for single end long reads (ONT/PacBio) where
+   the read structure may appear on either strand.
+   The fixed sequence anchors of the geometry are searched
+   for on both strands, allowing for the errors of long reads,
+   and the read is reverse complemented into the canonical
+   orientation before the FGDL is applied. Reads whose strand
+   cannot be told fail the orientation check.
+*/
+
+use std::{cmp::Ordering, ops::Range};
+
+use crate::matchers::{KmerMatcher, Matcher, MyersMatcher};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+pub fn complement(nuc: u8) -> u8 {
+    match nuc {
+        b'A' => b'T',
+        b'T' | b'U' => b'A',
+        b'G' => b'C',
+        b'C' => b'G',
+        b'a' => b't',
+        b't' | b'u' => b'a',
+        b'g' => b'c',
+        b'c' => b'g',
+        n => n,
+    }
+}
+
+pub fn revcomp(seq: &[u8]) -> Vec<u8> {
+    seq.iter().rev().map(|n| complement(*n)).collect()
+}
+
+// edits allowed in an anchor, one for every `BASES_PER_EDIT` of its bases.
+// long reads carry frequent indels and substitutions, so few would hold
+// their anchors exactly
+pub const BASES_PER_EDIT: usize = 8;
+
+// the closest hit of `anchor` in `seq`, allowing indels in anchors up to 64
+// bases and only substitutions in longer ones
+fn find_anchor(seq: &[u8], anchor: &[u8]) -> Option<Range<usize>> {
+    let max_dist = anchor.len() / BASES_PER_EDIT;
+
+    if anchor.len() <= 64 {
+        MyersMatcher.find(anchor, seq, max_dist)
+    } else {
+        KmerMatcher.find(anchor, seq, max_dist)
+    }
+}
+
+// number of anchors which occur in the read, anchors are counted at most once
+pub fn count_anchors(seq: &[u8], anchors: &[Vec<u8>]) -> usize {
+    anchors
+        .iter()
+        .filter(|a| find_anchor(seq, a).is_some())
+        .count()
+}
+
+// the strand on which more anchors are found, none on a tie, as when no
+// anchor is found at all
+pub fn orientation(seq: &[u8], anchors: &[Vec<u8>]) -> Option<Strand> {
+    let forward = count_anchors(seq, anchors);
+    let reverse = anchors
+        .iter()
+        .filter(|a| find_anchor(seq, &revcomp(a)).is_some())
+        .count();
+
+    match forward.cmp(&reverse) {
+        Ordering::Greater => Some(Strand::Forward),
+        Ordering::Less => Some(Strand::Reverse),
+        Ordering::Equal => None,
+    }
+}
+
+/*
+   Concatemer (MAS-seq style) reads hold several copies of
+   the geometry back to back. Each copy starts with the same
+   fixed sequence so the read is split at every occurrence
+   of it, allowing the edits of an anchor, bases before the
+   first occurrence are dropped.
+*/
+pub fn split_concatemer(seq: &[u8], delimiter: &[u8]) -> Vec<Range<usize>> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+
+    while let Some(mut hit) = find_anchor(&seq[i..], delimiter) {
+        // the closest hit may not be the first, the copies do not overlap
+        while let Some(earlier) = find_anchor(&seq[i..i + hit.start], delimiter) {
+            hit = earlier;
+        }
+
+        starts.push(i + hit.start);
+        i += hit.end;
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, start)| *start..*starts.get(n + 1).unwrap_or(&seq.len()))
+        .collect()
+}
+
+// name of the `n`th segment, keeps read names unique for downstream tools
+pub fn segment_name(name: &[u8], n: usize) -> Vec<u8> {
+    let id_len = name
+        .iter()
+        .position(|c| c.is_ascii_whitespace())
+        .unwrap_or(name.len());
+
+    [&name[..id_len], format!("_{n}").as_bytes(), &name[id_len..]].concat()
+}
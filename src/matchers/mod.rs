@@ -0,0 +1,234 @@
+/*
+   Backends for searching fixed sequences.
+   By default fixed sequences are searched for by antisequence.
+   `--matcher` picks another backend for every fixed sequence, or
+   for one labeled piece with `label=backend`. A backend only finds
+   where the sequence is, the read is then cut around the hit with
+   the bases it has, a hit with mismatches or indels is kept as read.
+
+   kmer  - seeds from the sequence, one of which has to match
+           exactly, are looked up and each candidate is checked
+           for mismatches
+   myers - bit-parallel edit distance, which allows indels in the
+           hit, for sequences of up to 64 bases
+
+   Every backend ignores case. Antisequence does not, so unless
+   `--uppercase` is given reads with soft-masked, lowercase, bases
+   are searched and cut by a backend before antisequence looks for
+   the sequence in the others. Their bases keep their case.
+*/
+
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+pub trait Matcher: Send + Sync {
+    // the leftmost of the closest hits of `pattern` in `text` at most `max_dist` away
+    fn find(&self, pattern: &[u8], text: &[u8], max_dist: usize) -> Option<Range<usize>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Antisequence,
+    Kmer,
+    Myers,
+}
+
+impl Backend {
+    // `None` leaves the search to antisequence
+    pub fn matcher(self) -> Option<Arc<dyn Matcher>> {
+        match self {
+            Backend::Antisequence => None,
+            Backend::Kmer => Some(Arc::new(KmerMatcher)),
+            Backend::Myers => Some(Arc::new(MyersMatcher)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatcherChoice {
+    // every fixed sequence if not set
+    pub segment: Option<String>,
+    pub backend: Backend,
+}
+
+// `backend` or `label=backend`
+pub fn matcher_choice(s: &str) -> Result<MatcherChoice, String> {
+    let (segment, backend) = match s.split_once('=') {
+        Some((segment, backend)) => (Some(segment.to_string()), backend),
+        None => (None, s),
+    };
+
+    let backend = match backend {
+        "antisequence" => Backend::Antisequence,
+        "kmer" => Backend::Kmer,
+        "myers" => Backend::Myers,
+        _ => {
+            return Err(format!(
+                "Unknown matcher `{backend}`, expected antisequence, kmer or myers"
+            ))
+        }
+    };
+
+    Ok(MatcherChoice { segment, backend })
+}
+
+// the backend for the piece named `segment`, a choice for it wins over a global one
+pub fn backend_for(choices: &[MatcherChoice], segment: Option<&str>) -> Backend {
+    choices
+        .iter()
+        .find(|c| c.segment.is_some() && c.segment.as_deref() == segment)
+        .or_else(|| choices.iter().find(|c| c.segment.is_none()))
+        .map_or(Backend::Antisequence, |c| c.backend)
+}
+
+// the hit of `pattern` in `text` by `matcher` regardless of case. only the
+// bases compared are folded, the range is of `text` as it is
+pub fn find_ignoring_case(
+    matcher: &dyn Matcher,
+    pattern: &[u8],
+    text: &[u8],
+    max_dist: usize,
+) -> Option<Range<usize>> {
+    matcher.find(
+        &pattern.to_ascii_uppercase(),
+        &text.to_ascii_uppercase(),
+        max_dist,
+    )
+}
+
+// `seq` cut into the bases before `hit`, the hit and the bases after it
+pub fn split_hit<'a>(seq: &'a [u8], hit: &Range<usize>) -> [&'a [u8]; 3] {
+    [&seq[..hit.start], &seq[hit.clone()], &seq[hit.end..]]
+}
+
+pub struct KmerMatcher;
+
+impl Matcher for KmerMatcher {
+    fn find(&self, pattern: &[u8], text: &[u8], max_dist: usize) -> Option<Range<usize>> {
+        let m = pattern.len();
+
+        if m == 0 || m > text.len() {
+            return None;
+        }
+
+        // with `max_dist` mismatches one of `max_dist + 1` disjoint seeds is exact
+        let k = (m / (max_dist + 1)).max(1);
+
+        let mut seeds: HashMap<&[u8], Vec<usize>> = HashMap::new();
+        for offset in (0..=m - k).step_by(k) {
+            seeds
+                .entry(&pattern[offset..offset + k])
+                .or_default()
+                .push(offset);
+        }
+
+        let mut best: Option<(usize, usize)> = None;
+
+        for (pos, kmer) in text.windows(k).enumerate() {
+            for &offset in seeds.get(kmer).into_iter().flatten() {
+                let start = match pos.checked_sub(offset) {
+                    Some(start) if start + m <= text.len() => start,
+                    _ => continue,
+                };
+
+                let dist = hamming(pattern, &text[start..start + m]);
+
+                if dist <= max_dist && best.is_none_or(|(d, s)| (dist, start) < (d, s)) {
+                    best = Some((dist, start));
+                }
+            }
+        }
+
+        best.map(|(_, start)| start..start + m)
+    }
+}
+
+fn hamming(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).filter(|(x, y)| x != y).count()
+}
+
+// only a hit at the start of the text, for sequences matched in place
+pub struct PrefixMatcher;
+
+impl Matcher for PrefixMatcher {
+    fn find(&self, pattern: &[u8], text: &[u8], max_dist: usize) -> Option<Range<usize>> {
+        let m = pattern.len();
+
+        (m <= text.len() && hamming(pattern, &text[..m]) <= max_dist).then_some(0..m)
+    }
+}
+
+pub struct MyersMatcher;
+
+impl Matcher for MyersMatcher {
+    fn find(&self, pattern: &[u8], text: &[u8], max_dist: usize) -> Option<Range<usize>> {
+        let m = pattern.len();
+
+        if m == 0 || m > 64 {
+            return None;
+        }
+
+        let mut peq = [0u64; 256];
+        for (i, &c) in pattern.iter().enumerate() {
+            peq[c as usize] |= 1 << i;
+        }
+
+        let high = 1 << (m - 1);
+        let (mut pv, mut mv) = (!0u64, 0u64);
+        let mut score = m;
+        let mut best: Option<(usize, usize)> = None;
+
+        for (j, &c) in text.iter().enumerate() {
+            let eq = peq[c as usize];
+            let xv = eq | mv;
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+            let ph = mv | !(xh | pv);
+            let mh = pv & xh;
+
+            if ph & high != 0 {
+                score += 1;
+            } else if mh & high != 0 {
+                score -= 1;
+            }
+
+            // a hit may start anywhere in the text
+            let (ph, mh) = (ph << 1, mh << 1);
+            pv = mh | !(xv | ph);
+            mv = ph & xv;
+
+            if score <= max_dist && best.is_none_or(|(d, _)| score < d) {
+                best = Some((score, j + 1));
+            }
+        }
+
+        let (dist, end) = best?;
+
+        Some(hit_start(pattern, &text[..end], dist)..end)
+    }
+}
+
+// where the hit of `pattern` with `dist` edits ending at the end of `text` starts
+fn hit_start(pattern: &[u8], text: &[u8], dist: usize) -> usize {
+    let m = pattern.len();
+    let window = text.len().min(m + dist);
+
+    // edit distance of the reversed pattern against the reversed text, which
+    // has to be aligned from its start
+    let mut prev: Vec<usize> = (0..=window).collect();
+
+    for i in 1..=m {
+        let mut row = vec![i; window + 1];
+        for j in 1..=window {
+            let same = pattern[m - i] == text[text.len() - j];
+            row[j] = (prev[j - 1] + usize::from(!same))
+                .min(prev[j] + 1)
+                .min(row[j - 1] + 1);
+        }
+        prev = row;
+    }
+
+    // the shortest of the closest alignments
+    let closest = prev.iter().min().unwrap();
+    let len = prev.iter().position(|d| d == closest).unwrap();
+
+    text.len() - len
+}
@@ -0,0 +1,78 @@
+/*
+   Merging of overlapping read pairs for amplicon workflows.
+   The second read is reverse complemented and slid along the
+   first, the overlap with the fewest mismatches per base is
+   taken as long as it is long enough. Mismatches in the overlap
+   are resolved to the base with the higher quality.
+*/
+
+use crate::long_read::complement;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeConfig {
+    pub min_overlap: usize,
+    // fraction of the overlap allowed to mismatch
+    pub max_diff: f64,
+    // merged reads are written here, unmerged pairs go to the usual outputs
+    pub out: String,
+}
+
+fn mismatches(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).filter(|(a, b)| a != b).count()
+}
+
+// the merged sequence and quality of a pair, `None` if the reads do not overlap
+pub fn merge_pair(
+    seq1: &[u8],
+    qual1: &[u8],
+    seq2: &[u8],
+    qual2: &[u8],
+    config: &MergeConfig,
+) -> Option<(Vec<u8>, Vec<u8>)> {
+    let seq2 = seq2
+        .iter()
+        .rev()
+        .map(|n| complement(*n))
+        .collect::<Vec<_>>();
+    let qual2 = qual2.iter().rev().copied().collect::<Vec<_>>();
+
+    // where the second read starts in the first, ties go to the longer overlap
+    let (offset, _) = (0..seq1.len())
+        .filter_map(|offset| {
+            let overlap = (seq1.len() - offset).min(seq2.len());
+            let diff = mismatches(&seq1[offset..offset + overlap], &seq2[..overlap]);
+
+            (overlap >= config.min_overlap && diff as f64 <= config.max_diff * overlap as f64)
+                .then_some((offset, diff as f64 / overlap as f64))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+    let overlap = (seq1.len() - offset).min(seq2.len());
+
+    let mut seq = seq1[..offset].to_vec();
+    let mut qual = qual1[..offset].to_vec();
+
+    for i in 0..overlap {
+        let (n1, q1) = (seq1[offset + i], qual1[offset + i]);
+        let (n2, q2) = (seq2[i], qual2[i]);
+
+        if n1 == n2 || q1 >= q2 {
+            seq.push(n1);
+            qual.push(q1.max(q2));
+        } else {
+            seq.push(n2);
+            qual.push(q2);
+        }
+    }
+
+    // whichever read runs past the end of the overlap
+    if offset + overlap < seq1.len() {
+        seq.extend_from_slice(&seq1[offset + overlap..]);
+        qual.extend_from_slice(&qual1[offset + overlap..]);
+    } else {
+        seq.extend_from_slice(&seq2[overlap..]);
+        qual.extend_from_slice(&qual2[overlap..]);
+    }
+
+    Some((seq, qual))
+}
@@ -0,0 +1,297 @@
+/*
+   Metrics of a live run for the dashboards of a service running seqproc.
+   `--metrics` sends the reads seen, the reads per second since the last
+   sample, the pass rate and the bytes written to the outputs every
+   `--metrics-interval` seconds, and once more when the run is done.
+   A `statsd://host:port` target gets gauges over udp, an
+   `http://host:port/path` target is a Prometheus pushgateway given the
+   text format, `/metrics/job/seqproc` if no path is given. A target
+   that cannot be reached does not stop the run.
+
+   `--tui` draws the same samples in the terminal every second instead,
+   over the previous one, with the reads that reached each stage of
+   `--timings` and their share of the reads seen.
+*/
+
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use super::Timings;
+
+pub const DEFAULT_PUSH_PATH: &str = "/metrics/job/seqproc";
+
+// how long a target is given to answer
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetricsTarget {
+    Statsd(String),
+    Pushgateway { host: String, path: String },
+    // stderr, for `--tui`
+    Terminal,
+}
+
+// `statsd://host:port` or `http://host:port[/path]`
+pub fn metrics_target(s: &str) -> Result<MetricsTarget, String> {
+    if let Some(host) = s.strip_prefix("statsd://") {
+        return Ok(MetricsTarget::Statsd(host.to_string()));
+    }
+
+    match s.strip_prefix("http://") {
+        Some(rest) => {
+            let (host, path) = match rest.find('/') {
+                Some(i) if i + 1 < rest.len() => rest.split_at(i),
+                Some(i) => (&rest[..i], DEFAULT_PUSH_PATH),
+                None => (rest, DEFAULT_PUSH_PATH),
+            };
+
+            Ok(MetricsTarget::Pushgateway {
+                host: host.to_string(),
+                path: path.to_string(),
+            })
+        }
+        None => Err(format!(
+            "Unknown metrics target `{s}`, expected statsd://host:port or http://host:port/path"
+        )),
+    }
+}
+
+// reads counted as they enter and leave the pipeline
+#[derive(Debug, Default)]
+pub struct Metrics {
+    seen: AtomicUsize,
+    passed: AtomicUsize,
+    // the reads of each stage, if they are timed
+    timings: Option<Arc<Timings>>,
+}
+
+impl Metrics {
+    pub fn with_timings(timings: Arc<Timings>) -> Self {
+        Self {
+            timings: Some(timings),
+            ..Self::default()
+        }
+    }
+
+    pub fn seen(&self) {
+        self.seen.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn passed(&self) {
+        self.passed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // the reads seen and passed so far
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.seen.load(Ordering::Relaxed),
+            self.passed.load(Ordering::Relaxed),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub seen: usize,
+    pub passed: usize,
+    pub reads_per_sec: f64,
+    pub pass_rate: f64,
+    pub bytes_written: u64,
+    pub stages: Vec<(&'static str, usize)>,
+}
+
+impl Sample {
+    // one gauge a line, sent as a single packet
+    pub fn statsd(&self) -> String {
+        format!(
+            "seqproc.reads:{}|g\nseqproc.reads_passed:{}|g\nseqproc.reads_per_sec:{:.1}|g\nseqproc.pass_rate:{:.4}|g\nseqproc.bytes_written:{}|g",
+            self.seen, self.passed, self.reads_per_sec, self.pass_rate, self.bytes_written
+        )
+    }
+
+    pub fn prometheus(&self) -> String {
+        let mut out = String::new();
+
+        let counters = [
+            ("seqproc_reads_total", self.seen.to_string()),
+            ("seqproc_reads_passed_total", self.passed.to_string()),
+        ];
+        let gauges = [
+            (
+                "seqproc_reads_per_sec",
+                format!("{:.1}", self.reads_per_sec),
+            ),
+            ("seqproc_pass_rate", format!("{:.4}", self.pass_rate)),
+            ("seqproc_bytes_written", self.bytes_written.to_string()),
+        ];
+
+        for (kind, metrics) in [("counter", &counters[..]), ("gauge", &gauges[..])] {
+            for (name, value) in metrics {
+                writeln!(out, "# TYPE {name} {kind}\n{name} {value}").unwrap();
+            }
+        }
+
+        out
+    }
+
+    // the sample drawn over the one before it
+    pub fn terminal(&self) -> String {
+        let mut out = String::from("\x1b[H\x1b[J");
+
+        writeln!(
+            out,
+            "seqproc  {} reads  {} passed ({:.1}%)  {:.0} reads/s  {} bytes written\n",
+            self.seen,
+            self.passed,
+            self.pass_rate * 100.0,
+            self.reads_per_sec,
+            self.bytes_written
+        )
+        .unwrap();
+
+        if !self.stages.is_empty() {
+            writeln!(out, "{:<12}{:>12}{:>8}", "stage", "reads", "share").unwrap();
+        }
+        for (stage, reads) in &self.stages {
+            let share = if self.seen > 0 {
+                *reads as f64 / self.seen as f64 * 100.0
+            } else {
+                0.0
+            };
+
+            writeln!(out, "{stage:<12}{reads:>12}{share:>7.1}%").unwrap();
+        }
+
+        out
+    }
+}
+
+// sends a sample every interval until dropped, and a last one then
+pub struct Emitter {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Emitter {
+    // `outputs` are the files whose size is reported as the bytes written
+    pub fn start(
+        target: MetricsTarget,
+        metrics: Arc<Metrics>,
+        outputs: Vec<String>,
+        interval: Duration,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let thread = thread::spawn(move || {
+            let mut last = (Instant::now(), 0);
+
+            loop {
+                let done = !matches!(
+                    stopped.recv_timeout(interval),
+                    Err(RecvTimeoutError::Timeout)
+                );
+
+                let (seen, passed) = (
+                    metrics.seen.load(Ordering::Relaxed),
+                    metrics.passed.load(Ordering::Relaxed),
+                );
+                let elapsed = last.0.elapsed().as_secs_f64();
+
+                let sample = Sample {
+                    seen,
+                    passed,
+                    reads_per_sec: if elapsed > 0.0 {
+                        (seen - last.1) as f64 / elapsed
+                    } else {
+                        0.0
+                    },
+                    pass_rate: if seen > 0 {
+                        passed as f64 / seen as f64
+                    } else {
+                        0.0
+                    },
+                    bytes_written: outputs
+                        .iter()
+                        .filter_map(|path| fs::metadata(path).ok())
+                        .map(|meta| meta.len())
+                        .sum(),
+                    stages: metrics
+                        .timings
+                        .as_ref()
+                        .map_or_else(Vec::new, |timings| timings.reads()),
+                };
+                last = (Instant::now(), seen);
+
+                // a dashboard going down does not stop the run
+                let _ = send(&target, &sample);
+
+                if done {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+fn send(target: &MetricsTarget, sample: &Sample) -> io::Result<()> {
+    match target {
+        MetricsTarget::Statsd(host) => {
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            socket.send_to(sample.statsd().as_bytes(), host.as_str())?;
+        }
+        MetricsTarget::Terminal => {
+            let mut stderr = io::stderr().lock();
+            stderr.write_all(sample.terminal().as_bytes())?;
+            stderr.flush()?;
+        }
+        MetricsTarget::Pushgateway { host, path } => {
+            let addr = host
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| io::Error::other(format!("{host} has no address")))?;
+
+            let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+
+            let body = sample.prometheus();
+            write!(
+                stream,
+                "PUT {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )?;
+
+            // the gateway answers once the sample is stored
+            let mut status = [0; 12];
+            let _ = stream.read(&mut status)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Drop for Emitter {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
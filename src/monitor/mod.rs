@@ -0,0 +1,172 @@
+/*
+   Watching a run while it goes.
+   A geometry that does not fit the data drops nearly every read,
+   which otherwise only shows once the run is over. `--min-pass-rate`
+   counts the reads entering and leaving the pipeline and aborts when
+   too few of the most recent reads made it through. The counts are
+   kept as snapshots every tenth of a window, the rate is taken
+   between the oldest and newest one.
+
+   `--timings` breaks the run time down by stage. Reads pass through
+   a mark after each stage, the time since the previous mark on the
+   same thread is given to the stage. The mark at the start of the
+   pipeline gets the time spent reading and writing the last chunk.
+   The reads passing each mark are counted as well.
+
+   `--metrics` sends counts of the run to statsd or a Prometheus
+   pushgateway while it goes, `--tui` draws them in the terminal with
+   the reads reaching each stage, see `metrics`.
+*/
+
+pub mod metrics;
+
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+pub const PASS_RATE_WINDOW: usize = 10_000;
+
+const SNAPSHOTS: usize = 10;
+
+pub struct PassRate {
+    min_rate: f64,
+    window: usize,
+    counts: Mutex<Counts>,
+}
+
+#[derive(Default)]
+struct Counts {
+    seen: usize,
+    passed: usize,
+    // (seen, passed) every `window / SNAPSHOTS` reads
+    snapshots: VecDeque<(usize, usize)>,
+}
+
+impl PassRate {
+    pub fn new(min_rate: f64, window: usize) -> Self {
+        let mut counts = Counts::default();
+        counts.snapshots.push_back((0, 0));
+
+        Self {
+            min_rate,
+            window,
+            counts: Mutex::new(counts),
+        }
+    }
+
+    // a read entered the pipeline, fails once a full window is under the minimum
+    pub fn seen(&self) -> Result<(), String> {
+        let mut counts = self.counts.lock().unwrap();
+        counts.seen += 1;
+
+        let step = (self.window / SNAPSHOTS).max(1);
+
+        if !counts.seen.is_multiple_of(step) {
+            return Ok(());
+        }
+
+        let now = (counts.seen, counts.passed);
+        counts.snapshots.push_back(now);
+        if counts.snapshots.len() > SNAPSHOTS + 1 {
+            counts.snapshots.pop_front();
+        }
+
+        let (seen, passed) = counts.snapshots[0];
+        let seen = now.0 - seen;
+
+        if seen < self.window {
+            return Ok(());
+        }
+
+        let rate = (now.1 - passed) as f64 / seen as f64;
+
+        if rate < self.min_rate {
+            Err(format!(
+                "Only {:.1}% of the last {seen} reads passed, below the minimum of {:.1}%, the geometry may not fit the data",
+                rate * 100.0,
+                self.min_rate * 100.0
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    // a read left the pipeline
+    pub fn passed(&self) {
+        self.counts.lock().unwrap().passed += 1;
+    }
+}
+
+thread_local! {
+    static LAST_MARK: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+// wall time and reads per stage summed over threads, in the order the stages
+// were first seen
+#[derive(Debug, Default)]
+pub struct Timings {
+    stages: Mutex<Vec<(&'static str, Duration, usize)>>,
+}
+
+impl Timings {
+    // the first mark on a thread counts the read but no time
+    pub fn mark(&self, stage: &'static str) {
+        let now = Instant::now();
+
+        let time = LAST_MARK
+            .replace(Some(now))
+            .map_or(Duration::ZERO, |last| now - last);
+        self.add(stage, time);
+    }
+
+    // a read passed the stage, taking `time`
+    pub fn add(&self, stage: &'static str, time: Duration) {
+        let mut stages = self.stages.lock().unwrap();
+
+        match stages.iter_mut().find(|(s, _, _)| *s == stage) {
+            Some((_, total, reads)) => {
+                *total += time;
+                *reads += 1;
+            }
+            None => stages.push((stage, time, 1)),
+        }
+    }
+
+    // the reads that passed each stage so far
+    pub fn reads(&self) -> Vec<(&'static str, usize)> {
+        let stages = self.stages.lock().unwrap();
+
+        stages
+            .iter()
+            .map(|(stage, _, reads)| (*stage, *reads))
+            .collect()
+    }
+
+    // the time and reads of each stage so far
+    pub fn stages(&self) -> Vec<(&'static str, Duration, usize)> {
+        self.stages.lock().unwrap().clone()
+    }
+
+    pub fn report(&self) -> String {
+        let stages = self.stages.lock().unwrap();
+        let total = stages.iter().map(|(_, t, _)| t.as_secs_f64()).sum::<f64>();
+
+        let mut out = String::new();
+
+        for (stage, time, _) in stages.iter() {
+            let share = if total > 0.0 {
+                time.as_secs_f64() / total * 100.0
+            } else {
+                0.0
+            };
+
+            writeln!(out, "{stage:<10}{:>10.3}s{share:>7.1}%", time.as_secs_f64()).unwrap();
+        }
+
+        out
+    }
+}
@@ -0,0 +1,123 @@
+/*
+   Whitelists of chemistries fetched with `--fetch-whitelist`, so a preset
+   naming its whitelist runs on a fresh machine with a single command. The
+   whitelist is downloaded once into `$XDG_CACHE_HOME/seqproc/whitelists`,
+   by default `~/.cache`, and the cached file used by every run after it.
+   Where the chemistry gives the sha256 of its whitelist as
+   `whitelist_sha256`, a download is only kept if its digest is that one,
+   and a cached file whose digest is not is downloaded again. A whitelist
+   that is not a url is used in place, checked against its digest alike.
+*/
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use super::Chemistry;
+use crate::{
+    checksum::{digest, Algorithm},
+    source::remote::remote,
+};
+
+// the directory whitelists are cached in
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+
+    Some(cache.join("seqproc").join("whitelists"))
+}
+
+// the cached file of the whitelist at `url`. the name of the file is kept,
+// so a compressed whitelist is still recognized by it, after a digest of
+// the url so whitelists of the same name do not replace one another
+pub fn cached_path(dir: &Path, url: &str) -> PathBuf {
+    let name = url.split(['?', '#']).next().unwrap();
+    let name = name.rsplit('/').next().unwrap();
+
+    dir.join(format!(
+        "{}-{name}",
+        &digest(Algorithm::Sha256, url.as_bytes())[..12]
+    ))
+}
+
+// an error if the digest of `path` is not `sha256`
+pub fn verify(path: &Path, sha256: &str) -> io::Result<()> {
+    let found = digest(Algorithm::Sha256, &fs::read(path)?);
+
+    if found.eq_ignore_ascii_case(sha256.trim()) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} has sha256 {found}, expected {}",
+                path.display(),
+                sha256.trim()
+            ),
+        ))
+    }
+}
+
+// the local file of the whitelist of `chemistry`, downloaded into `dir`
+// unless it was before
+pub fn fetch_whitelist(chemistry: &Chemistry, dir: &Path) -> io::Result<PathBuf> {
+    let url = match &chemistry.whitelist {
+        Some(url) => url,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("chemistry {} has no whitelist", chemistry.name),
+            ))
+        }
+    };
+    let sha256 = chemistry.whitelist_sha256.as_deref();
+
+    let source = match remote(url) {
+        Some(source) => source,
+        None => {
+            let path = PathBuf::from(url);
+            if let Some(sha256) = sha256 {
+                verify(&path, sha256)?;
+            }
+            return Ok(path);
+        }
+    };
+
+    let path = cached_path(dir, url);
+    if path.is_file() {
+        match sha256.map_or(Ok(()), |sha256| verify(&path, sha256)) {
+            Ok(()) => return Ok(path),
+            // a download cut short or a changed file is fetched again
+            Err(e) => eprintln!("{e}, downloading it again"),
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+
+    // downloaded beside the cache and moved into it once whole, so a run
+    // never finds a partial whitelist
+    let partial = dir.join(format!(
+        ".{}.{}",
+        path.file_name().unwrap().to_string_lossy(),
+        process::id()
+    ));
+    let fetched = source.download(&partial).and_then(|_| match sha256 {
+        Some(sha256) => verify(&partial, sha256),
+        None => Ok(()),
+    });
+
+    match fetched {
+        Ok(()) => {
+            fs::rename(&partial, &path)?;
+            Ok(path)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(io::Error::new(e.kind(), format!("{url}: {e}")))
+        }
+    }
+}
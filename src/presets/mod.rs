@@ -0,0 +1,227 @@
+/*
+   Geometries built into seqproc for common assays, given as
+   `--geom preset:<name>` in place of a file. Each is plain FGDL, to be
+   copied and adapted where an assay differs.
+
+   `crispr` extracts the protospacer of guide-RNA screens read from the
+   lentiGuide family of vectors: the guide sits between the end of the U6
+   promoter and the start of the sgRNA scaffold, and is 19 to 21 bases
+   long as some libraries add a G for transcription. A stagger of up to 40
+   bases may come before the promoter. Both anchors allow two mismatches,
+   and the guide is cut exactly between them: the right anchor is only
+   searched for where a guide of an allowed length would end.
+
+   Chemistries of a lab's own are written to preset files, each a section
+   per chemistry:
+
+       [tenx-v3]
+       description = 10x Genomics 3' v3
+       geometry = 1{map(b[16], $0, self)u[12]x:}2{r:}
+       whitelist = https://example.org/3M-february-2018.txt.gz
+       whitelist_sha256 = <the sha256 of the whitelist>
+       options = --len-from-whitelist
+
+   where `options` are given to seqproc before those of its command line,
+   which take precedence over them. Files ending in `.preset` in
+   `$XDG_CONFIG_HOME/seqproc/presets`, by default `~/.config`, are read,
+   then any given with `--preset-file`, a chemistry replacing one of its
+   name read before it. `seqproc chemistries` lists them all. With
+   `--fetch-whitelist` the whitelist of the chemistry is downloaded and
+   cached, see `fetch`, and given to the geometry as `$0`.
+*/
+
+pub mod fetch;
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub geometry: &'static str,
+}
+
+pub const PRESETS: [Preset; 1] = [Preset {
+    name: "crispr",
+    description: "guide-RNA screens, the 19-21 base protospacer between the U6 promoter and the sgRNA scaffold",
+    geometry: "1{x[0-40]hamming(f[TTGTGGAAAGGACGAAACACCG], 2)r<guide>[19-21]hamming(f[GTTTTAGAGCTAGAAATAGC], 2)x:}",
+}];
+
+pub fn preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Chemistry {
+    pub name: String,
+    pub description: String,
+    pub geometry: String,
+    // where the whitelist of the geometry is published
+    pub whitelist: Option<String>,
+    // the digest a downloaded whitelist is checked against
+    pub whitelist_sha256: Option<String>,
+    // given before the options of the command line
+    pub options: Vec<String>,
+    // the file it was read from, none if built in
+    pub file: Option<PathBuf>,
+}
+
+impl From<&Preset> for Chemistry {
+    fn from(preset: &Preset) -> Self {
+        Self {
+            name: preset.name.to_string(),
+            description: preset.description.to_string(),
+            geometry: preset.geometry.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+// the chemistries of a preset file
+pub fn parse_presets(text: &str) -> Result<Vec<Chemistry>, String> {
+    let mut chemistries: Vec<Chemistry> = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            chemistries.push(Chemistry {
+                name: name.trim().to_string(),
+                ..Chemistry::default()
+            });
+            continue;
+        }
+
+        let chemistry = match chemistries.last_mut() {
+            Some(chemistry) => chemistry,
+            None => return Err(format!("line {} is not in a [chemistry]", n + 1)),
+        };
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim().to_string()),
+            None => return Err(format!("line {} is not `<key> = <value>`", n + 1)),
+        };
+
+        match key {
+            "description" => chemistry.description = value,
+            "geometry" => chemistry.geometry = value,
+            "whitelist" => chemistry.whitelist = Some(value),
+            "whitelist_sha256" => chemistry.whitelist_sha256 = Some(value),
+            "options" => chemistry.options = value.split_whitespace().map(str::to_string).collect(),
+            _ => {
+                return Err(format!(
+                    "line {}: {key} is not one of description, geometry, whitelist, whitelist_sha256 or options",
+                    n + 1
+                ))
+            }
+        }
+    }
+
+    match chemistries
+        .iter()
+        .find(|c| c.name.is_empty() || c.geometry.is_empty())
+    {
+        Some(c) if c.name.is_empty() => Err("a chemistry has no name".to_string()),
+        Some(c) => Err(format!("chemistry {} has no geometry", c.name)),
+        None => Ok(chemistries),
+    }
+}
+
+// the directory of the preset files of the user
+pub fn config_dir() -> Option<PathBuf> {
+    let config = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config.join("seqproc").join("presets"))
+}
+
+fn read_presets(path: &Path) -> io::Result<Vec<Chemistry>> {
+    let text = fs::read_to_string(path)?;
+    let mut chemistries = parse_presets(&text).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {e}", path.display()),
+        )
+    })?;
+
+    for chemistry in &mut chemistries {
+        chemistry.file = Some(path.to_path_buf());
+    }
+    Ok(chemistries)
+}
+
+// the built-in chemistries, then those of `dir` and of `files`, each
+// replacing one of its name before it
+pub fn chemistries(dir: Option<&Path>, files: &[String]) -> io::Result<Vec<Chemistry>> {
+    let mut all = PRESETS.iter().map(Chemistry::from).collect::<Vec<_>>();
+
+    let mut paths = match dir.map(fs::read_dir) {
+        Some(Ok(entries)) => entries
+            .map(|entry| entry.map(|e| e.path()))
+            .filter(|path| {
+                path.as_ref()
+                    .map_or(true, |p| p.extension().is_some_and(|ext| ext == "preset"))
+            })
+            .collect::<io::Result<Vec<_>>>()?,
+        Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => Vec::new(),
+    };
+    paths.sort();
+    paths.extend(files.iter().map(PathBuf::from));
+
+    for path in paths {
+        for chemistry in read_presets(&path)? {
+            all.retain(|c| c.name != chemistry.name);
+            all.push(chemistry);
+        }
+    }
+
+    Ok(all)
+}
+
+// the chemistry of `--geom`, if given as `preset:<name>`
+pub fn find_chemistry<'c>(
+    path: &str,
+    chemistries: &'c [Chemistry],
+) -> io::Result<Option<&'c Chemistry>> {
+    let name = match path.strip_prefix("preset:") {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+
+    match chemistries.iter().find(|c| c.name == name) {
+        Some(chemistry) => Ok(Some(chemistry)),
+        None => {
+            let names = chemistries
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>();
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no preset {name}, expected one of {}", names.join(", ")),
+            ))
+        }
+    }
+}
+
+// the geometry of `--geom`, a preset of `chemistries` if given as
+// `preset:<name>`
+pub fn read_geometry_of(path: &str, chemistries: &[Chemistry]) -> io::Result<String> {
+    match find_chemistry(path, chemistries)? {
+        Some(chemistry) => Ok(chemistry.geometry.clone()),
+        None => fs::read_to_string(path),
+    }
+}
+
+// the geometry of `--geom`, a built-in preset if given as `preset:<name>`
+pub fn read_geometry(path: &str) -> io::Result<String> {
+    let builtin = PRESETS.iter().map(Chemistry::from).collect::<Vec<_>>();
+
+    read_geometry_of(path, &builtin)
+}
@@ -0,0 +1,270 @@
+/*
+   `seqproc preview` shows how a few reads are cut by the geometry before
+   a run is started. Reads are sampled at random from the whole input,
+   with `--seed`, so a preview is not only of the first tile of a flow
+   cell. Each read of a record is printed with its segments colored by
+   their type and fixed sequences underlined, with a line below marking
+   the type of each base for terminals without colors, and the range of
+   each segment. A record that is dropped is explained: the piece it was
+   dropped at and, for a fixed sequence, the closest the read comes to it.
+   Segments cut though outside what the geometry describes, as checked
+   by `verify`, are listed under their record.
+
+   Reads are cut as by `extract::process_record`, so geometries it cannot
+   follow cannot be previewed.
+*/
+
+use std::{fmt::Write, io};
+
+use crate::{
+    compile::{functions::CompiledFunction, CompiledData},
+    extract::{process_record, ExtractError, Segment},
+    parser::{Size, Type},
+    rng::Rng,
+    source::fastq::{FastqReader, FastqRecord},
+    verify::Verifier,
+};
+
+// records previewed unless `-n` says otherwise
+pub const DEFAULT_READS: usize = 20;
+
+// the records of `readers` sampled at random, keeping their order in the
+// files, with the number of records read
+pub fn sample(
+    readers: &mut [FastqReader],
+    n: usize,
+    seed: u64,
+) -> io::Result<(Vec<Vec<FastqRecord>>, usize)> {
+    let mut rng = Rng::new(seed);
+    let mut sampled: Vec<(usize, Vec<FastqRecord>)> = Vec::new();
+    let mut total = 0;
+
+    loop {
+        let mut record = Vec::new();
+        for reader in readers.iter_mut() {
+            record.extend(reader.next_record()?);
+        }
+
+        if record.is_empty() {
+            break;
+        }
+        if record.len() < readers.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the inputs differ in length from record {}", total + 1),
+            ));
+        }
+
+        // reservoir sampling, each record kept with the same chance
+        if sampled.len() < n {
+            sampled.push((total, record));
+        } else {
+            let i = rng.below(total as u64 + 1) as usize;
+            if i < n {
+                sampled[i] = (total, record);
+            }
+        }
+        total += 1;
+    }
+
+    sampled.sort_by_key(|(i, _)| *i);
+
+    Ok((sampled.into_iter().map(|(_, r)| r).collect(), total))
+}
+
+// the ANSI style of a segment, and the letter marking its bases
+fn style(type_: &Type) -> (&'static str, char) {
+    match type_ {
+        Type::Barcode => ("34", 'b'),
+        Type::Umi => ("35", 'u'),
+        Type::ReadSeq => ("32", 'r'),
+        Type::Discard => ("2", 'x'),
+        Type::FixedSeq => ("33;4", 'f'),
+        Type::Header => ("36", 'h'),
+    }
+}
+
+// the position in `read` closest to `seq`, with its mismatches
+fn closest(read: &[u8], seq: &[u8]) -> Option<(usize, usize)> {
+    (0..(read.len() + 1).checked_sub(seq.len())?)
+        .map(|start| {
+            let diff = seq
+                .iter()
+                .zip(&read[start..])
+                .filter(|(s, r)| !s.eq_ignore_ascii_case(r))
+                .count();
+            (start, diff)
+        })
+        .min_by_key(|(_, diff)| *diff)
+}
+
+pub struct Preview<'a> {
+    compiled: &'a CompiledData,
+    verifier: Verifier,
+    color: bool,
+}
+
+impl<'a> Preview<'a> {
+    pub fn new(compiled: &'a CompiledData, color: bool) -> Self {
+        Self {
+            compiled,
+            verifier: Verifier::new(compiled),
+            color,
+        }
+    }
+
+    // a read with its segments, and the line marking them
+    fn read(&self, out: &mut String, n: usize, seq: &[u8], segments: &[&Segment]) {
+        let mut bases = String::new();
+        let mut marks = String::new();
+        let mut pos = 0;
+
+        for segment in segments {
+            let (ansi, mark) = style(&segment.type_);
+            let start = segment.range.start.max(pos);
+
+            bases.push_str(&String::from_utf8_lossy(&seq[pos..start]));
+            marks.push_str(&" ".repeat(start - pos));
+
+            let cut = String::from_utf8_lossy(&seq[start..segment.range.end]);
+            if self.color {
+                write!(bases, "\x1b[{ansi}m{cut}\x1b[0m").unwrap();
+            } else {
+                bases.push_str(&cut);
+            }
+            marks.extend(std::iter::repeat_n(mark, cut.len()));
+
+            pos = segment.range.end.max(pos);
+        }
+        bases.push_str(&String::from_utf8_lossy(&seq[pos.min(seq.len())..]));
+
+        writeln!(out, "  r{n} {bases}").unwrap();
+        if !segments.is_empty() {
+            writeln!(out, "     {}", marks.trim_end()).unwrap();
+
+            let ranges = segments
+                .iter()
+                .map(|s| {
+                    let name = s.label.clone().unwrap_or_else(|| style(&s.type_).1.into());
+                    format!("{name} {}-{}", s.range.start, s.range.end)
+                })
+                .collect::<Vec<_>>();
+            writeln!(out, "     {}", ranges.join(", ")).unwrap();
+        }
+    }
+
+    // why a read is dropped at `piece`, the piece written as in FGDL
+    fn explain(&self, read: usize, piece: &str, seq: &[u8]) -> String {
+        let gm = match self.compiled.geometry[read - 1]
+            .iter()
+            .find(|gm| gm.expr.0.to_string() == piece)
+        {
+            Some(gm) => gm,
+            None => return format!("dropped: read {read} does not match at {piece}"),
+        };
+
+        let mark = style(&gm.expr.0.type_).1;
+        let label = gm
+            .expr
+            .0
+            .label
+            .as_ref()
+            .map_or(String::new(), |l| format!("<{l}>"));
+
+        let (size, why) = match &gm.expr.0.size {
+            Size::FixedSeq((fixed, _), _, _) => {
+                let allowed = match gm.stack.last() {
+                    Some((CompiledFunction::Hamming(n), _)) => *n,
+                    _ => 0,
+                };
+
+                let why = match closest(seq, fixed.as_bytes()) {
+                    Some((start, diff)) => format!(
+                        "closest is {} at {start}, {diff} mismatches where {allowed} are allowed",
+                        String::from_utf8_lossy(&seq[start..start + fixed.len()])
+                    ),
+                    None => format!("the read is {} bases long", seq.len()),
+                };
+                (format!("[{fixed}]"), why)
+            }
+            Size::FixedLen((n, _)) => (
+                format!("[{n}]"),
+                format!("the read is {} bases long", seq.len()),
+            ),
+            Size::RangedLen(((a, b), _)) => (
+                format!("[{a}-{b}]"),
+                "the sequence after it is not where the piece can end".to_string(),
+            ),
+            Size::UnboundedLen => (
+                ":".to_string(),
+                format!("the read is {} bases long", seq.len()),
+            ),
+        };
+
+        format!("dropped: read {read} does not match at {mark}{label}{size}, {why}")
+    }
+
+    // a record as it is cut, and whether it is cut as the geometry describes
+    pub fn record(&self, record: &[FastqRecord]) -> Result<(String, bool), ExtractError> {
+        let reads = record.iter().map(|r| &r.seq[..]).collect::<Vec<_>>();
+
+        let mut out = format!("@{}\n", String::from_utf8_lossy(&record[0].name));
+
+        let (extraction, mut issues) = match process_record(self.compiled, &reads) {
+            Ok(extraction) if extraction.raw => (
+                None,
+                vec!["kept raw: a fixed sequence with miss=keep-raw was not found".to_string()],
+            ),
+            Ok(extraction) => {
+                let issues = self.verifier.check_extraction(&reads, &extraction);
+                (Some(extraction), issues)
+            }
+            Err(ExtractError::Unmatched { read, piece }) => {
+                (None, vec![self.explain(read, &piece, reads[read - 1])])
+            }
+            Err(e) => return Err(e),
+        };
+
+        for (i, seq) in reads.iter().enumerate() {
+            let segments = extraction
+                .iter()
+                .flat_map(|e| &e.segments)
+                .filter(|s| s.read == i + 1)
+                .collect::<Vec<_>>();
+
+            self.read(&mut out, i + 1, seq, &segments);
+        }
+
+        let ok = extraction.is_some() && issues.is_empty();
+        if ok {
+            issues.push("matched".to_string());
+        }
+        for issue in issues {
+            writeln!(out, "  {issue}").unwrap();
+        }
+
+        Ok((out, ok))
+    }
+
+    // the records sampled from `total` records, then how many matched
+    pub fn show(&self, records: &[Vec<FastqRecord>], total: usize) -> Result<String, ExtractError> {
+        let mut out = String::new();
+        let mut matched = 0;
+
+        for record in records {
+            let (shown, ok) = self.record(record)?;
+            out.push_str(&shown);
+            out.push('\n');
+            matched += ok as usize;
+        }
+
+        writeln!(
+            out,
+            "{matched} of {} reads sampled from {total} matched",
+            records.len()
+        )
+        .unwrap();
+
+        Ok(out)
+    }
+}
@@ -0,0 +1,109 @@
+/*
+   Primer pools of amplicon panels.
+   The start of the biological read is compared against every
+   primer of a FASTA file allowing a few mismatches. The primer
+   with the fewest mismatches is trimmed off and its name noted,
+   reads matching two primers equally well are left untouched.
+*/
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrimerConfig {
+    pub fasta: String,
+    pub max_mismatch: usize,
+    // write `<read name>\t<primer>` here instead of tagging the read name
+    pub tsv: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrimerPool {
+    pub primers: Vec<(String, Vec<u8>)>,
+}
+
+impl PrimerPool {
+    pub fn from_fasta<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    pub fn parse<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut primers: Vec<(String, Vec<u8>)> = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+
+            if let Some(name) = line.strip_prefix('>') {
+                let name = name.split_whitespace().next().unwrap_or_default();
+                primers.push((name.to_string(), Vec::new()));
+            } else if let Some((_, seq)) = primers.last_mut() {
+                seq.extend(line.bytes().map(|c| c.to_ascii_uppercase()));
+            } else if !line.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "primer sequence found before its name",
+                ));
+            }
+        }
+
+        Ok(Self { primers })
+    }
+
+    // the primer starting `seq` and its length
+    pub fn best_match(&self, seq: &[u8], max_mismatch: usize) -> Option<(&str, usize)> {
+        let mut best: Option<(usize, &str, usize)> = None;
+        let mut tied = false;
+
+        for (name, primer) in &self.primers {
+            if primer.is_empty() || seq.len() < primer.len() {
+                continue;
+            }
+
+            let diff = primer
+                .iter()
+                .zip(seq)
+                .filter(|(p, s)| !p.eq_ignore_ascii_case(s))
+                .count();
+
+            if diff > max_mismatch {
+                continue;
+            }
+
+            match best {
+                Some((best_diff, _, _)) if diff > best_diff => {}
+                Some((best_diff, _, _)) if diff == best_diff => tied = true,
+                _ => {
+                    best = Some((diff, name, primer.len()));
+                    tied = false;
+                }
+            }
+        }
+
+        best.filter(|_| !tied).map(|(_, name, len)| (name, len))
+    }
+}
+
+// the primer matched by each read, one line per read
+pub struct PrimerTable {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl PrimerTable {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            file: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub fn insert(&self, name: &[u8], primer: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+
+        file.write_all(name)?;
+        writeln!(file, "\t{primer}")
+    }
+}
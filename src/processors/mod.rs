@@ -1,11 +1,55 @@
-use std::ops::{Bound, RangeBounds};
+use std::{
+    io,
+    ops::{Bound, RangeBounds, RangeInclusive},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use antisequence::{
-    expr::{Label, SelectorExpr, TransformExpr},
+    expr::{Attr, Label, SelectorExpr, TransformExpr},
     *,
 };
 
-use crate::interpret::BoxedReads;
+use crate::{
+    adapters::{builtin, trimmed_len},
+    compile::plan::{GroupPlan, PieceLabels, PiecePlan, SearchPlan},
+    failure::{fail, io_failed, FailureKind},
+    features::Features,
+    filters::{
+        complexity::ComplexityFilter,
+        dedup::{dedup_key, DedupConfig, DuplicateSet},
+        flagged,
+        name::NameFilter,
+        umi::UmiFilter,
+        whitelist::{Whitelist, WhitelistLengths},
+        OnFail,
+    },
+    header::has_indices,
+    interpret::BoxedReads,
+    long_read::{orientation, segment_name, split_concatemer, Strand},
+    matchers::{find_ignoring_case, split_hit, Matcher},
+    merge::{merge_pair, MergeConfig},
+    monitor::{PassRate, Timings},
+    parser::{PreStep, Type},
+    primers::{PrimerConfig, PrimerPool, PrimerTable},
+    quality::{check_lengths, pad, phred64_to_33, quality_trim_len},
+    sink::{
+        self,
+        fastq::{FastqWriter, TechPiece},
+        route::segment_hash,
+        shard::{ShardedWriter, Sharding},
+        tags::{raw_attr, sam_tag, BARCODE_TAGS, UMI_TAGS},
+        tsv::SegmentTsv,
+        well::WellMap,
+        Record,
+    },
+    summary::{
+        composition::Composition, hopping::IndexHopping, BaseCounts, DuplicationEstimate, UmiCounts,
+    },
+    verify::Verifier,
+};
 
 fn get_selector(label: String, attr: String) -> SelectorExpr {
     if attr.is_empty() {
@@ -34,6 +78,53 @@ fn cut(
     read.cut(sel_expr, tr_expr, index).boxed()
 }
 
+// drop reads not matching `selector`, or stop at the first one, or keep
+// them to be written as they were read, see `restore_raw`, with their names
+// tagged as failing `check`. reads already kept raw, by a miss policy or an
+// earlier check, are not checked again
+fn retain(
+    read: BoxedReads,
+    selector: String,
+    on_fail: OnFail,
+    check: &'static str,
+    stage: String,
+) -> BoxedReads {
+    if selector.is_empty() {
+        return read;
+    }
+
+    match on_fail {
+        OnFail::Drop => read
+            .retain(SelectorExpr::new(format!("({selector}) | seq1.*.raw").as_bytes()).unwrap())
+            .boxed(),
+        OnFail::Abort => {
+            let name = Label::new(b"name1.*").unwrap();
+            let failed = SelectorExpr::new(flagged(&selector).as_bytes()).unwrap();
+
+            read.for_each(failed, move |read| {
+                let name = read.substring(name.str_type, name.label).unwrap();
+
+                panic!(
+                    "Read {} failed {stage}",
+                    String::from_utf8_lossy(sink::read_name(name))
+                );
+            })
+            .boxed()
+        }
+        OnFail::Flag => {
+            let names = name_labels();
+            let raw = Attr::new(b"seq1.*.raw").unwrap();
+            let failed = SelectorExpr::new(flagged(&selector).as_bytes()).unwrap();
+
+            read.for_each(failed, move |read| {
+                tag_names(read, &names, &format!("QC:fail:{check}"));
+                *read.data_mut(raw.str_type, raw.label, raw.attr).unwrap() = Data::Bool(true);
+            })
+            .boxed()
+        }
+    }
+}
+
 pub fn remove(read: BoxedReads, label: String, attr: String) -> BoxedReads {
     let sel_expr = get_selector(label.clone(), attr);
     let label = Label::new(label.as_bytes()).unwrap();
@@ -41,25 +132,61 @@ pub fn remove(read: BoxedReads, label: String, attr: String) -> BoxedReads {
     read.trim(sel_expr, vec![label]).boxed()
 }
 
-pub fn pad_by(read: BoxedReads, label: String, attr: String, by: EndIdx, nuc: char) -> BoxedReads {
+// pad by the number of bases to add to each end given the length of the segment
+fn pad_segment<F>(
+    read: BoxedReads,
+    label: String,
+    attr: String,
+    nuc: char,
+    pad_qual: u8,
+    ends: F,
+) -> BoxedReads
+where
+    F: Fn(usize) -> (usize, usize) + Send + Sync + 'static,
+{
     let sel_expr = get_selector(label.clone(), attr);
-    let a_label = Label::new(label.as_bytes()).unwrap();
+    let label = Label::new(label.as_bytes()).unwrap();
 
-    match by {
-        LeftEnd(n) => read
-            .set(sel_expr, a_label, format!("{{'{nuc}';{n}}}{{{label}}}"))
-            .boxed(),
-        RightEnd(n) => read
-            .set(sel_expr, a_label, format!("{{{label}}}{{'{nuc}';{n}}}"))
-            .boxed(),
-    }
+    read.for_each(sel_expr, move |read| {
+        let (seq, qual) = {
+            let seq = read.substring(label.str_type, label.label).unwrap();
+            let qual = read.substring_qual(label.str_type, label.label).unwrap();
+
+            pad(seq, qual, ends(seq.len()), nuc as u8, pad_qual)
+        };
+
+        read.set(label.str_type, label.label, &seq, qual.as_deref())
+            .unwrap();
+    })
+    .boxed()
 }
 
-pub fn pad_to(read: BoxedReads, label: String, attr: String, to: EndIdx, nuc: char) -> BoxedReads {
-    let sel_expr = get_selector(label.clone(), attr);
-    let label = Label::new(label.as_bytes()).unwrap();
+pub fn pad_by(
+    read: BoxedReads,
+    label: String,
+    attr: String,
+    by: EndIdx,
+    nuc: char,
+    pad_qual: u8,
+) -> BoxedReads {
+    pad_segment(read, label, attr, nuc, pad_qual, move |_| match by {
+        LeftEnd(n) => (n, 0),
+        RightEnd(n) => (0, n),
+    })
+}
 
-    read.pad(sel_expr, vec![label], to, nuc as u8).boxed()
+pub fn pad_to(
+    read: BoxedReads,
+    label: String,
+    attr: String,
+    to: EndIdx,
+    nuc: char,
+    pad_qual: u8,
+) -> BoxedReads {
+    pad_segment(read, label, attr, nuc, pad_qual, move |len| match to {
+        LeftEnd(n) => (n.saturating_sub(len), 0),
+        RightEnd(n) => (0, n.saturating_sub(len)),
+    })
 }
 
 pub fn truncate_by(read: BoxedReads, label: String, attr: String, by: EndIdx) -> BoxedReads {
@@ -106,15 +233,21 @@ pub fn filter(
     attr: String,
     filename: String,
     mismatch: usize,
+    on_fail: OnFail,
 ) -> BoxedReads {
     let sel_expr = get_selector(label.clone(), attr);
-    let sel_retain_expr = get_selector(label.clone(), "_f".to_string());
 
     let tr_expr = TransformExpr::new(format!("{0} -> {0}._f", label).as_bytes()).unwrap();
 
-    read.filter(sel_expr, tr_expr, filename, mismatch)
-        .retain(sel_retain_expr)
-        .boxed()
+    let read = read.filter(sel_expr, tr_expr, filename.clone(), mismatch);
+
+    retain(
+        read.boxed(),
+        format!("{label}._f"),
+        on_fail,
+        "filter",
+        format!("filtering {label} by {filename}"),
+    )
 }
 
 pub fn map(
@@ -130,145 +263,1969 @@ pub fn map(
     read.map(sel_expr, tr_expr, file, mismatch).boxed()
 }
 
+// append a tag to the name of each mate, single end reads only have `name1`
+fn tag_names(read: &mut Read, names: &[Label], tag: &str) {
+    for name in names {
+        if let Ok(old_name) = read.substring(name.str_type, name.label) {
+            let new_name = [old_name, b" ", tag.as_bytes()].concat();
+
+            read.set(name.str_type, name.label, &new_name, None)
+                .unwrap();
+        }
+    }
+}
+
+fn name_labels() -> Vec<Label> {
+    vec![
+        Label::new(b"name1.*").unwrap(),
+        Label::new(b"name2.*").unwrap(),
+    ]
+}
+
+// drop reads whose name `filter` does not keep
+pub fn filter_name(read: BoxedReads, filter: NameFilter) -> BoxedReads {
+    let name = Label::new(b"name1.*").unwrap();
+    let kept = Attr::new(b"name1.*.kept").unwrap();
+
+    read.for_each(sel!(), move |read| {
+        let keeps = filter.keeps(read.substring(name.str_type, name.label).unwrap());
+        *read.data_mut(kept.str_type, kept.label, kept.attr).unwrap() = Data::Bool(keeps);
+    })
+    .retain(SelectorExpr::new(b"name1.*.kept").unwrap())
+    .boxed()
+}
+
+pub fn filter_umi(
+    read: BoxedReads,
+    label: String,
+    umi_filter: UmiFilter,
+    on_fail: OnFail,
+) -> BoxedReads {
+    let sel_expr = get_selector(label.clone(), String::new());
+    let umi = Label::new(label.as_bytes()).unwrap();
+    let attr = Attr::new(format!("{label}.umi_ok").as_bytes()).unwrap();
+    let names = name_labels();
+    let flag = umi_filter.flag;
+
+    let read = read.for_each(sel_expr, move |read| {
+        let failure = {
+            let seq = read.substring(umi.str_type, umi.label).unwrap();
+            let qual = read.substring_qual(umi.str_type, umi.label).unwrap();
+
+            umi_filter.check(seq, qual)
+        };
+
+        if let (true, Some(failure)) = (umi_filter.flag, &failure) {
+            tag_names(read, &names, &format!("QC:fail:umi_{failure}"));
+        }
+
+        *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() =
+            Data::Bool(failure.is_none());
+    });
+
+    if flag {
+        read.boxed()
+    } else {
+        retain(
+            read.boxed(),
+            format!("{label}.umi_ok"),
+            on_fail,
+            "umi",
+            format!("the UMI filter on {label}"),
+        )
+    }
+}
+
+// trim poly-G tails from a biological read and drop it if it is of low complexity
+pub fn filter_complexity(
+    read: BoxedReads,
+    label: String,
+    filter: Arc<ComplexityFilter>,
+    on_fail: OnFail,
+) -> BoxedReads {
+    let sel_expr = get_selector(label.clone(), String::new());
+    let seq = Label::new(label.as_bytes()).unwrap();
+    let attr = Attr::new(format!("{label}.complex").as_bytes()).unwrap();
+
+    let read = read.for_each(sel_expr, move |read| {
+        let (kept, trimmed) = {
+            let s = read.substring(seq.str_type, seq.label).unwrap();
+            let qual = read.substring_qual(seq.str_type, seq.label).unwrap();
+            let (len, kept) = filter.check(s);
+
+            let trimmed =
+                (len < s.len()).then(|| (s[..len].to_vec(), qual.map(|q| q[..len].to_vec())));
+            (kept, trimmed)
+        };
+
+        if let Some((s, qual)) = trimmed {
+            read.set(seq.str_type, seq.label, &s, qual.as_deref())
+                .unwrap();
+        }
+
+        *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() = Data::Bool(kept);
+    });
+
+    retain(
+        read.boxed(),
+        format!("{label}.complex"),
+        on_fail,
+        "complexity",
+        format!("the complexity filter on {label}"),
+    )
+}
+
+pub fn dedup(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    key_labels: Vec<String>,
+    read_labels: Vec<String>,
+    config: DedupConfig,
+    on_fail: OnFail,
+) -> BoxedReads {
+    let duplicates = DuplicateSet::new(&config.mode).unwrap_or_else(|e| io_failed(e));
+    let key_labels = key_labels
+        .iter()
+        .map(|l| Label::new(l.as_bytes()).unwrap())
+        .collect::<Vec<_>>();
+    let read_labels = read_labels
+        .iter()
+        .map(|l| Label::new(l.as_bytes()).unwrap())
+        .collect::<Vec<_>>();
+    let attr = Attr::new(b"seq1.*.unique").unwrap();
+    let names = name_labels();
+    let flag = config.flag;
+
+    let read = read.for_each(sel_expr, move |read| {
+        let key = {
+            let substrings = |labels: &Vec<Label>| {
+                labels
+                    .iter()
+                    .map(|l| read.substring(l.str_type, l.label).unwrap())
+                    .collect::<Vec<_>>()
+            };
+
+            dedup_key(
+                &substrings(&key_labels),
+                &substrings(&read_labels),
+                config.prefix_len,
+            )
+        };
+
+        let unique = duplicates.insert(&key).unwrap_or_else(|e| io_failed(e));
+
+        if config.flag && !unique {
+            tag_names(read, &names, "QC:fail:duplicate");
+        }
+
+        *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() = Data::Bool(unique);
+    });
+
+    if flag {
+        read.boxed()
+    } else {
+        retain(
+            read.boxed(),
+            "seq1.*.unique".to_string(),
+            on_fail,
+            "duplicate",
+            "deduplication as a duplicate".to_string(),
+        )
+    }
+}
+
+// add the duplicate key of each read to the sketch of `estimate`
+pub fn estimate_duplication(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    key_labels: Vec<String>,
+    read_labels: Vec<String>,
+    estimate: Arc<DuplicationEstimate>,
+) -> BoxedReads {
+    let to_labels = |labels: Vec<String>| {
+        labels
+            .iter()
+            .map(|l| Label::new(l.as_bytes()).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let (key_labels, read_labels) = (to_labels(key_labels), to_labels(read_labels));
+
+    read.for_each(sel_expr, move |read| {
+        let substrings = |labels: &Vec<Label>| {
+            labels
+                .iter()
+                .map(|l| read.substring(l.str_type, l.label).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        estimate.insert(&dedup_key(
+            &substrings(&key_labels),
+            &substrings(&read_labels),
+            estimate.prefix_len,
+        ));
+    })
+    .boxed()
+}
+
+// count the distinct UMIs of each barcode, given by the labels of their segments
+pub fn count_umis(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    barcode_labels: Vec<String>,
+    umi_labels: Vec<String>,
+    counts: Arc<UmiCounts>,
+) -> BoxedReads {
+    let to_labels = |labels: Vec<String>| {
+        labels
+            .iter()
+            .map(|l| Label::new(l.as_bytes()).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let (barcode_labels, umi_labels) = (to_labels(barcode_labels), to_labels(umi_labels));
+
+    read.for_each(sel_expr, move |read| {
+        let joined = |labels: &Vec<Label>| {
+            labels
+                .iter()
+                .map(|l| read.substring(l.str_type, l.label).unwrap())
+                .collect::<Vec<_>>()
+                .concat()
+        };
+
+        counts.insert(&joined(&barcode_labels), &joined(&umi_labels));
+    })
+    .boxed()
+}
+
+// count the bases at each position of the segments, given with the index
+// each was registered under
+pub fn count_composition(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    segments: Vec<(usize, String)>,
+    composition: Arc<Composition>,
+) -> BoxedReads {
+    let segments = segments
+        .into_iter()
+        .map(|(i, label)| (i, Label::new(label.as_bytes()).unwrap()))
+        .collect::<Vec<_>>();
+
+    read.for_each(sel_expr, move |read| {
+        for (i, label) in &segments {
+            // an optional group missing from the read has no segments
+            if let Ok(seq) = read.substring(label.str_type, label.label) {
+                composition.insert(*i, seq);
+            }
+        }
+    })
+    .boxed()
+}
+
+// count the combination of the i7 and i5 barcodes, given by their labels
+pub fn count_index_hopping(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    i7: String,
+    i5: String,
+    hopping: Arc<IndexHopping>,
+) -> BoxedReads {
+    let (i7, i5) = (
+        Label::new(i7.as_bytes()).unwrap(),
+        Label::new(i5.as_bytes()).unwrap(),
+    );
+
+    read.for_each(sel_expr, move |read| {
+        // an optional index missing from the read has no segments
+        if let (Ok(seq7), Ok(seq5)) = (
+            read.substring(i7.str_type, i7.label),
+            read.substring(i5.str_type, i5.label),
+        ) {
+            hopping.insert(seq7, seq5);
+        }
+    })
+    .boxed()
+}
+
+// trim the primer starting the segment `label` and note which primer it was
+pub fn match_primers(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    label: String,
+    config: PrimerConfig,
+) -> BoxedReads {
+    let pool = PrimerPool::from_fasta(&config.fasta).unwrap_or_else(|e| io_failed(e));
+    let table = config
+        .tsv
+        .as_ref()
+        .map(|path| PrimerTable::create(path).unwrap_or_else(|e| io_failed(e)));
+    let seq = Label::new(label.as_bytes()).unwrap();
+    let name = Label::new(b"name1.*").unwrap();
+    let names = name_labels();
+
+    read.for_each(sel_expr, move |read| {
+        let (primer, trimmed, trimmed_qual) = {
+            let s = read.substring(seq.str_type, seq.label).unwrap();
+            let q = read.substring_qual(seq.str_type, seq.label).unwrap();
+
+            if let Some((primer, len)) = pool.best_match(s, config.max_mismatch) {
+                (
+                    primer.to_string(),
+                    s[len..].to_vec(),
+                    q.map(|q| q[len..].to_vec()),
+                )
+            } else {
+                return;
+            }
+        };
+
+        read.set(seq.str_type, seq.label, &trimmed, trimmed_qual.as_deref())
+            .unwrap();
+
+        if let Some(table) = &table {
+            let read_name = sink::read_name(read.substring(name.str_type, name.label).unwrap());
+
+            table
+                .insert(read_name, &primer)
+                .unwrap_or_else(|e| io_failed(e));
+        } else {
+            tag_names(read, &names, &format!("primer:{primer}"));
+        }
+    })
+    .boxed()
+}
+
+// find `sequence` in `init_label` with `matcher` regardless of case, cutting
+// it around the hit into `prev_label`, `this_label` and `next_label` with the
+// bases and case the read has. without `prev_label` the hit starts the read.
+// with `masked_only` reads without lowercase bases are left alone
+#[allow(clippy::too_many_arguments)]
+pub fn locate(
+    read: BoxedReads,
+    (init_label, prev_label, this_label, next_label): (String, Option<String>, String, String),
+    except: Vec<String>,
+    sequence: String,
+    matcher: Arc<dyn Matcher>,
+    max_dist: usize,
+    masked_only: bool,
+) -> BoxedReads {
+    let found = format!("{init_label}.{}_hit", this_label.replace('.', "_"));
+    let [hit, start, end] =
+        ["", "_start", "_end"].map(|s| Attr::new(format!("{found}{s}").as_bytes()).unwrap());
+    let init = Label::new(init_label.as_bytes()).unwrap();
+
+    let sel_expr = SelectorExpr::new(excluding(&init_label, &except).as_bytes()).unwrap();
+    let read = read.for_each(sel_expr, move |read| {
+        let range = {
+            let s = read.substring(init.str_type, init.label).unwrap();
+
+            if masked_only && !s.iter().any(u8::is_ascii_lowercase) {
+                None
+            } else {
+                find_ignoring_case(&*matcher, sequence.as_bytes(), s, max_dist)
+            }
+        };
+
+        if let Some(range) = &range {
+            *read
+                .data_mut(start.str_type, start.label, start.attr)
+                .unwrap() = Data::Int(range.start as isize);
+            *read.data_mut(end.str_type, end.label, end.attr).unwrap() =
+                Data::Int(range.end as isize);
+        }
+        *read.data_mut(hit.str_type, hit.label, hit.attr).unwrap() = Data::Bool(range.is_some());
+    });
+
+    // reads with a hit are cut at their start, the bases are then given out
+    // around the hit
+    let sel_hit = || SelectorExpr::new(found.as_bytes()).unwrap();
+    let rest_label = format!("{this_label}_found");
+    let tr_expr = |from: &str, to: [&String; 2]| {
+        TransformExpr::new(format!("{from} -> {}, {}", to[0], to[1]).as_bytes()).unwrap()
+    };
+
+    let read = match &prev_label {
+        Some(prev) => {
+            let read = cut(
+                read.boxed(),
+                sel_hit(),
+                tr_expr(&init_label, [prev, &rest_label]),
+                LeftEnd(0),
+            );
+            cut(
+                read,
+                sel_hit(),
+                tr_expr(&rest_label, [&this_label, &next_label]),
+                LeftEnd(0),
+            )
+        }
+        None => cut(
+            read.boxed(),
+            sel_hit(),
+            tr_expr(&init_label, [&this_label, &next_label]),
+            LeftEnd(0),
+        ),
+    };
+
+    let labels = prev_label
+        .iter()
+        .chain([&this_label, &next_label])
+        .map(|l| Label::new(l.as_bytes()).unwrap())
+        .collect::<Vec<_>>();
+    let [start, end] = [start, end].map(|attr| {
+        move |read: &Read| match read.data(attr.str_type, attr.label, attr.attr) {
+            Ok(Data::Int(n)) => *n as usize,
+            _ => unreachable!(),
+        }
+    });
+
+    read.for_each(sel_hit(), move |read| {
+        let (parts, quals) = {
+            // everything searched is in the last label after the cuts
+            let last = labels.last().unwrap();
+            let s = read.substring(last.str_type, last.label).unwrap();
+            let q = read.substring_qual(last.str_type, last.label).unwrap();
+
+            let range = start(read)..end(read);
+            let parts = split_hit(s, &range);
+            let quals = q.map(|q| split_hit(q, &range));
+
+            // without a label before it the hit starts the read
+            let skip = 3 - labels.len();
+            (
+                parts[skip..].iter().map(|p| p.to_vec()).collect::<Vec<_>>(),
+                quals.map(|q| q[skip..].iter().map(|q| q.to_vec()).collect::<Vec<_>>()),
+            )
+        };
+
+        for (i, label) in labels.iter().enumerate() {
+            let qual = quals.as_ref().map(|q| &q[i][..]);
+            read.set(label.str_type, label.label, &parts[i], qual)
+                .unwrap();
+        }
+    })
+    .boxed()
+}
+
+// phred+64 qualities as phred+33, before anything reads them
+pub fn convert_phred64(read: BoxedReads) -> BoxedReads {
+    let seqs = [
+        Label::new(b"seq1.*").unwrap(),
+        Label::new(b"seq2.*").unwrap(),
+    ];
+
+    read.for_each(sel!(), move |read| {
+        for seq in &seqs {
+            // single end reads have no `seq2`
+            let (s, qual) = match read.substring(seq.str_type, seq.label) {
+                Ok(s) => match read.substring_qual(seq.str_type, seq.label).unwrap() {
+                    Some(qual) => (
+                        s.to_vec(),
+                        qual.iter().map(|&q| phred64_to_33(q)).collect::<Vec<_>>(),
+                    ),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            read.set(seq.str_type, seq.label, &s, Some(&qual)).unwrap();
+        }
+    })
+    .boxed()
+}
+
+// rna bases as dna, `U` as `T`, and if `uppercase` soft-masked bases uppercased
+pub fn normalize_bases(read: BoxedReads, uppercase: bool) -> BoxedReads {
+    let seqs = [
+        Label::new(b"seq1.*").unwrap(),
+        Label::new(b"seq2.*").unwrap(),
+    ];
+
+    read.for_each(sel!(), move |read| {
+        for seq in &seqs {
+            // single end reads have no `seq2`
+            let normalized = match read.substring(seq.str_type, seq.label) {
+                Ok(s) if s.iter().any(|&b| needs_normalizing(b, uppercase)) => s
+                    .iter()
+                    .map(|&b| match b {
+                        b'U' => b'T',
+                        b'u' if uppercase => b'T',
+                        b'u' => b't',
+                        _ if uppercase => b.to_ascii_uppercase(),
+                        _ => b,
+                    })
+                    .collect::<Vec<_>>(),
+                _ => continue,
+            };
+            let qual = read
+                .substring_qual(seq.str_type, seq.label)
+                .unwrap()
+                .map(<[u8]>::to_vec);
+
+            read.set(seq.str_type, seq.label, &normalized, qual.as_deref())
+                .unwrap();
+        }
+    })
+    .boxed()
+}
+
+fn needs_normalizing(base: u8, uppercase: bool) -> bool {
+    matches!(base, b'U' | b'u') || (uppercase && base.is_ascii_lowercase())
+}
+
+// one step of the `pre{..}` block, applied to both reads before the geometry
+pub fn preprocess(read: BoxedReads, step: &PreStep) -> BoxedReads {
+    match step {
+        PreStep::QualTrim(min_qual) => {
+            let min_qual = *min_qual;
+            // reads without qualities are left alone
+            trim_reads(read, move |seq, qual| {
+                qual.map_or(seq.len(), |qual| quality_trim_len(qual, min_qual))
+            })
+        }
+        PreStep::Adapters(name) => {
+            // the name was checked when the geometry was compiled
+            let adapters = builtin(name).unwrap();
+            trim_reads(read, move |seq, _| trimmed_len(seq, &adapters))
+        }
+        PreStep::AdapterFile(path) => {
+            let adapters = PrimerPool::from_fasta(path)
+                .unwrap_or_else(|e| io_failed(format!("{path}: {e}")))
+                .primers
+                .into_iter()
+                .map(|(_, seq)| seq)
+                .collect::<Vec<_>>();
+            trim_reads(read, move |seq, _| trimmed_len(seq, &adapters))
+        }
+        PreStep::AssertLen {
+            read: n,
+            len,
+            at_least,
+        } => assert_len(read, *n, *len, *at_least),
+    }
+}
+
+// stop at the first read of a length the geometry was not written for, a run
+// sequenced with other cycles would otherwise fail every read one at a time
+fn assert_len(read: BoxedReads, n: Option<usize>, len: usize, at_least: bool) -> BoxedReads {
+    let seqs = match n {
+        Some(n) => vec![n],
+        None => vec![1, 2],
+    }
+    .into_iter()
+    .map(|n| (n, Label::new(format!("seq{n}.*").as_bytes()).unwrap()))
+    .collect::<Vec<_>>();
+    let name = Label::new(b"name1.*").unwrap();
+
+    read.for_each(sel!(), move |read| {
+        for (n, seq) in &seqs {
+            // single end reads have no `seq2`
+            let s = match read.substring(seq.str_type, seq.label) {
+                Ok(s) if s.len() != len && !(at_least && s.len() > len) => s,
+                _ => continue,
+            };
+
+            let name = read.substring(name.str_type, name.label).unwrap();
+            fail(
+                FailureKind::ReadLength,
+                format!(
+                    "Read {} of file {n} is {} bases long, the geometry asserts {}{len}",
+                    String::from_utf8_lossy(sink::read_name(name)),
+                    s.len(),
+                    if at_least { "at least " } else { "" }
+                ),
+            );
+        }
+    })
+    .boxed()
+}
+
+// cut the 3' end of each read to the length `trim` gives for its bases and qualities
+fn trim_reads<F>(read: BoxedReads, trim: F) -> BoxedReads
+where
+    F: Fn(&[u8], Option<&[u8]>) -> usize + Send + Sync + 'static,
+{
+    let seqs = [
+        Label::new(b"seq1.*").unwrap(),
+        Label::new(b"seq2.*").unwrap(),
+    ];
+
+    read.for_each(sel!(), move |read| {
+        for seq in &seqs {
+            // single end reads have no `seq2`
+            let (trimmed, qual) = match read.substring(seq.str_type, seq.label) {
+                Ok(s) => {
+                    let qual = read.substring_qual(seq.str_type, seq.label).unwrap();
+                    let len = trim(s, qual);
+                    if len == s.len() {
+                        continue;
+                    }
+                    (s[..len].to_vec(), qual.map(|q| q[..len].to_vec()))
+                }
+                _ => continue,
+            };
+
+            read.set(seq.str_type, seq.label, &trimmed, qual.as_deref())
+                .unwrap();
+        }
+    })
+    .boxed()
+}
+
+// replace the first read of overlapping pairs with the merged read
+pub fn merge(read: BoxedReads, sel_expr: SelectorExpr, config: MergeConfig) -> BoxedReads {
+    let seq1 = Label::new(b"seq1.*").unwrap();
+    let seq2 = Label::new(b"seq2.*").unwrap();
+    let attr = Attr::new(b"seq1.*.merged").unwrap();
+
+    read.for_each(sel_expr, move |read| {
+        let merged = {
+            let seq = |l: &Label| read.substring(l.str_type, l.label).unwrap();
+            let qual = |l: &Label| {
+                read.substring_qual(l.str_type, l.label)
+                    .unwrap()
+                    .unwrap_or_default()
+            };
+
+            merge_pair(seq(&seq1), qual(&seq1), seq(&seq2), qual(&seq2), &config)
+        };
+
+        if let Some((seq, qual)) = &merged {
+            read.set(seq1.str_type, seq1.label, seq, Some(qual))
+                .unwrap();
+        }
+
+        *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() =
+            Data::Bool(merged.is_some());
+    })
+    .boxed()
+}
+
+// the attribute of reads of a modality, see `features`
+pub fn modality_attr(modality: usize) -> String {
+    format!("seq1.*.modality{modality}")
+}
+
+// note the modality of the feature starting the segment `label`. reads which
+// were not processed match none
+pub fn classify_features(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    label: String,
+    features: Arc<Features>,
+) -> BoxedReads {
+    let seq = Label::new(label.as_bytes()).unwrap();
+    let attrs = || {
+        (0..features.modalities.len())
+            .map(|i| Attr::new(modality_attr(i).as_bytes()).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let (unset, attrs) = (attrs(), attrs());
+
+    let read = read.for_each(sel!(), move |read| {
+        for attr in &unset {
+            *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() = Data::Bool(false);
+        }
+    });
+
+    read.for_each(sel_expr, move |read| {
+        let feature = match read.substring(seq.str_type, seq.label) {
+            Ok(seq) => features.classify(seq),
+            Err(_) => None,
+        };
+
+        if let Some(feature) = feature {
+            let attr = &attrs[feature.modality];
+            *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() = Data::Bool(true);
+        }
+    })
+    .boxed()
+}
+
+// reverse complement reads whose anchors are found on the reverse strand,
+// reads as many anchors are found for on both fail
+pub fn orient(read: BoxedReads, anchors: Vec<String>, on_fail: OnFail) -> BoxedReads {
+    let seq = Label::new(b"seq1.*").unwrap();
+    let attr = Attr::new(b"seq1.*.rc").unwrap();
+    let oriented = Attr::new(b"seq1.*.oriented").unwrap();
+    let anchors = anchors
+        .into_iter()
+        .map(|a| a.into_bytes())
+        .collect::<Vec<_>>();
+
+    let read = read.for_each(sel!(), move |read| {
+        let strand = orientation(read.substring(seq.str_type, seq.label).unwrap(), &anchors);
+
+        *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() =
+            Data::Bool(strand == Some(Strand::Reverse));
+        *read
+            .data_mut(oriented.str_type, oriented.label, oriented.attr)
+            .unwrap() = Data::Bool(strand.is_some());
+    });
+
+    let read = retain(
+        read.boxed(),
+        "seq1.*.oriented".to_string(),
+        on_fail,
+        "orientation",
+        "telling the strand of the read".to_string(),
+    );
+
+    reverse_comp(read, "seq1.*".to_string(), "rc".to_string())
+}
+
+struct SplitReads {
+    reads: BoxedReads,
+    delimiter: Vec<u8>,
+}
+
+impl Reads for SplitReads {
+    // an empty chunk ends the reads, so chunks nothing is split out of are
+    // passed over until one is or the reads upstream end
+    fn next_chunk(&self) -> antisequence::Result<Vec<Read>> {
+        let mut split = Vec::new();
+
+        while split.is_empty() {
+            let chunk = self.reads.next_chunk()?;
+            if chunk.is_empty() {
+                break;
+            }
+
+            for read in chunk {
+                let (name, seq, qual) = read.to_fastq1()?;
+
+                for (n, range) in split_concatemer(seq, &self.delimiter)
+                    .into_iter()
+                    .enumerate()
+                {
+                    split.push(Read::from_fastq1(
+                        &segment_name(name, n),
+                        &seq[range.clone()],
+                        &qual[range],
+                    ));
+                }
+            }
+        }
+
+        Ok(split)
+    }
+
+    fn finish(&mut self) -> antisequence::Result<()> {
+        self.reads.finish()
+    }
+}
+
+// reads passed through, `done` run once they are drained so the files they
+// were written to are finished where their errors can still fail the run
+struct OnFinish<F> {
+    reads: BoxedReads,
+    done: F,
+}
+
+impl<F: Fn() -> io::Result<()> + Send + Sync> Reads for OnFinish<F> {
+    fn next_chunk(&self) -> antisequence::Result<Vec<Read>> {
+        self.reads.next_chunk()
+    }
+
+    fn finish(&mut self) -> antisequence::Result<()> {
+        self.reads.finish()?;
+        (self.done)().unwrap_or_else(|e| io_failed(e));
+
+        Ok(())
+    }
+}
+
+fn on_finish<F>(reads: BoxedReads, done: F) -> BoxedReads
+where
+    F: Fn() -> io::Result<()> + Send + Sync + 'static,
+{
+    OnFinish { reads, done }.boxed()
+}
+
+// split each read into one record per copy of a repeated geometry
+pub fn split_repeats(read: BoxedReads, delimiter: String) -> BoxedReads {
+    SplitReads {
+        reads: read,
+        delimiter: delimiter.into_bytes(),
+    }
+    .boxed()
+}
+
+// cut the sample indices of the Casava 1.8 header of read `n` into their labels,
+// reads without one or with indices of other lengths are dropped
+pub fn header_index(
+    read: BoxedReads,
+    n: usize,
+    indices: Vec<(String, usize)>,
+    on_fail: OnFail,
+) -> BoxedReads {
+    let name = Label::new(format!("name{n}.*").as_bytes()).unwrap();
+    let attr = Attr::new(format!("name{n}.*.casava").as_bytes()).unwrap();
+    let lens = indices.iter().map(|(_, len)| *len).collect::<Vec<_>>();
+
+    let read = read.for_each(sel!(), move |read| {
+        let casava = read
+            .substring(name.str_type, name.label)
+            .is_ok_and(|name| has_indices(name, &lens));
+
+        *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() = Data::Bool(casava);
+    });
+
+    let mut read = retain(
+        read.boxed(),
+        format!("name{n}.*.casava"),
+        on_fail,
+        "header_index",
+        format!("reading the Casava header index of read {n}"),
+    );
+
+    // the indices end the header, cut them from the right skipping the `+` between them
+    let mut rest = format!("name{n}.*");
+
+    for (i, (label, len)) in indices.iter().enumerate().rev() {
+        let next = format!("name{n}._h{i}");
+        let tr_expr =
+            TransformExpr::new(format!("{rest} -> {next}, name{n}.{label}").as_bytes()).unwrap();
+
+        read = read.cut(sel!(), tr_expr, RightEnd(*len)).boxed();
+        rest = next;
+
+        if i > 0 {
+            let next = format!("name{n}._h{i}_l");
+            let tr_expr =
+                TransformExpr::new(format!("{rest} -> {next}, name{n}._h{i}_p").as_bytes())
+                    .unwrap();
+
+            read = read.cut(sel!(), tr_expr, RightEnd(1)).boxed();
+            rest = next;
+        }
+    }
+
+    read
+}
+
+// write each labeled segment to its own fastq file under the name of its read.
+// the files are written together, so a record is in all of them or none
+pub fn write_segments(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    outputs: Vec<(String, String)>,
+) -> BoxedReads {
+    let name = Label::new(b"name1.*").unwrap();
+    let outputs = outputs
+        .into_iter()
+        .map(|(segment, path)| {
+            (
+                Label::new(segment.as_bytes()).unwrap(),
+                segment,
+                FastqWriter::create(path).unwrap_or_else(|e| io_failed(e)),
+            )
+        })
+        .collect::<Vec<_>>();
+    let outputs = Arc::new(Mutex::new(outputs));
+    let finished = Arc::clone(&outputs);
+
+    let read = read.for_each(sel_expr, move |read| {
+        let name = read.substring(name.str_type, name.label).unwrap();
+        let outputs = outputs.lock().unwrap();
+
+        for (label, segment, out) in outputs.iter() {
+            let seq = read.substring(label.str_type, label.label).unwrap();
+            let qual = match read.substring_qual(label.str_type, label.label).unwrap() {
+                Some(qual) => qual,
+                None => fail(
+                    FailureKind::Io,
+                    format!(
+                        "segment {segment} of read {} has no qualities to write",
+                        String::from_utf8_lossy(name)
+                    ),
+                ),
+            };
+
+            out.write(name, seq, qual).unwrap_or_else(|e| io_failed(e));
+        }
+    });
+
+    on_finish(read.boxed(), move || {
+        let outputs = finished.lock().unwrap();
+        outputs.iter().try_for_each(|(_, _, out)| out.finish())
+    })
+}
+
+// the name and sequence labels of the first `n` reads
+fn output_labels(n: usize) -> Vec<(Label, Label)> {
+    (1..=n)
+        .map(|i| {
+            (
+                Label::new(format!("name{i}.*").as_bytes()).unwrap(),
+                Label::new(format!("seq{i}.*").as_bytes()).unwrap(),
+            )
+        })
+        .collect()
+}
+
+// the `(name, seq, qual)` record of each of `labels` in `read`
+fn output_records<'a>(
+    read: &'a Read,
+    labels: &[(Label, Label)],
+) -> Vec<(&'a [u8], &'a [u8], &'a [u8])> {
+    let first_name = &labels[0].0;
+
+    labels
+        .iter()
+        .map(|(name, seq)| {
+            // reads split from a single read share its name
+            let name = read
+                .substring(name.str_type, name.label)
+                .or_else(|_| read.substring(first_name.str_type, first_name.label))
+                .unwrap();
+            let qual = read
+                .substring_qual(seq.str_type, seq.label)
+                .unwrap()
+                .unwrap_or_default();
+
+            (name, read.substring(seq.str_type, seq.label).unwrap(), qual)
+        })
+        .collect()
+}
+
+// write the reads of each record to the files in `paths`, compressed on the
+// workers rather than by a single collector, see `FastqWriter`
+pub fn write_fastq(read: BoxedReads, sel_expr: SelectorExpr, paths: Vec<String>) -> BoxedReads {
+    let labels = output_labels(paths.len());
+    let writer = Arc::new(FastqWriter::create_all(&paths).unwrap_or_else(|e| io_failed(e)));
+    let finished = Arc::clone(&writer);
+
+    let read = read.for_each(sel_expr, move |read| {
+        writer
+            .write_records(&output_records(read, &labels))
+            .unwrap_or_else(|e| io_failed(e));
+    });
+
+    on_finish(read.boxed(), move || finished.finish())
+}
+
+// write the reads of each record to the streams in `paths`, split into shards
+pub fn write_shards(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    paths: Vec<String>,
+    sharding: Sharding,
+) -> BoxedReads {
+    let labels = output_labels(paths.len());
+    let writer = Arc::new(ShardedWriter::create(paths, sharding).unwrap_or_else(|e| io_failed(e)));
+    let finished = Arc::clone(&writer);
+
+    let read = read.for_each(sel_expr, move |read| {
+        writer
+            .write(&output_records(read, &labels))
+            .unwrap_or_else(|e| io_failed(e));
+    });
+
+    on_finish(read.boxed(), move || finished.finish())
+}
+
+// add the segments of `labels`, given with their names, to the read header
+// as `<name>:<bases>`, or to a tsv if given
+pub fn capture_segments(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    labels: Vec<(String, String)>,
+    tsv: Option<String>,
+) -> BoxedReads {
+    let names = labels
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let table = tsv.map(|path| SegmentTsv::create(path, &names).unwrap_or_else(|e| io_failed(e)));
+    let labels = labels
+        .into_iter()
+        .map(|(name, label)| {
+            let cut_seq = Attr::new(format!("{label}.cut_seq").as_bytes()).unwrap();
+            (name, Label::new(label.as_bytes()).unwrap(), cut_seq)
+        })
+        .collect::<Vec<_>>();
+    let name = Label::new(b"name1.*").unwrap();
+    let read_names = name_labels();
+
+    read.for_each(sel_expr, move |read| {
+        // fixed sequences are captured as they were found, see `note_cut_seq`
+        let segments = labels
+            .iter()
+            .map(
+                |(_, l, cut_seq)| match read.data(cut_seq.str_type, cut_seq.label, cut_seq.attr) {
+                    Ok(Data::Bytes(seq)) => seq.clone(),
+                    _ => read.substring(l.str_type, l.label).unwrap().to_vec(),
+                },
+            )
+            .collect::<Vec<_>>();
+
+        if let Some(table) = &table {
+            let read_name = sink::read_name(read.substring(name.str_type, name.label).unwrap());
+            let segments = segments.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+            table
+                .write(read_name, &segments)
+                .unwrap_or_else(|e| io_failed(e));
+        } else {
+            for ((name, _, _), segment) in labels.iter().zip(&segments) {
+                tag_names(
+                    read,
+                    &read_names,
+                    &format!("{name}:{}", String::from_utf8_lossy(segment)),
+                );
+            }
+        }
+    })
+    .boxed()
+}
+
+// add the well of the barcodes of `labels` to the read header as `well:<well>`,
+// or to the tsv of the map if it has one
+pub fn annotate_wells(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    labels: Vec<String>,
+    wells: Arc<WellMap>,
+) -> BoxedReads {
+    let table = wells.tsv.as_ref().map(|path| {
+        SegmentTsv::create(path, &["well".to_string()]).unwrap_or_else(|e| io_failed(e))
+    });
+    let labels = labels
+        .iter()
+        .map(|l| Label::new(l.as_bytes()).unwrap())
+        .collect::<Vec<_>>();
+    let name = Label::new(b"name1.*").unwrap();
+    let read_names = name_labels();
+
+    read.for_each(sel_expr, move |read| {
+        let barcodes = labels
+            .iter()
+            .map(|l| read.substring(l.str_type, l.label).unwrap().to_vec())
+            .collect::<Vec<_>>();
+        let barcodes = barcodes.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let well = wells.well(&barcodes).to_string();
+
+        match &table {
+            Some(table) => {
+                let read_name = sink::read_name(read.substring(name.str_type, name.label).unwrap());
+                table
+                    .write(read_name, &[well.as_bytes()])
+                    .unwrap_or_else(|e| io_failed(e));
+            }
+            None => tag_names(read, &read_names, &format!("well:{well}")),
+        }
+    })
+    .boxed()
+}
+
+// keep the bases of the segment `label` before a map corrects them, see
+// `sink::tags`
+pub fn keep_raw(read: BoxedReads, label: String) -> BoxedReads {
+    let seq = Label::new(label.as_bytes()).unwrap();
+    let attr = Attr::new(raw_attr(&label).as_bytes()).unwrap();
+
+    read.for_each(sel!(), move |read| {
+        if let Ok(bases) = read.substring(seq.str_type, seq.label) {
+            let bases = bases.to_vec();
+            *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() = Data::Bytes(bases);
+        }
+    })
+    .boxed()
+}
+
+// the reads as they were read, for those kept raw to be written as such
+pub fn keep_read(read: BoxedReads) -> BoxedReads {
+    let kept = kept_reads();
+
+    read.for_each(sel!(), move |read| {
+        for (seq, bases, quals) in &kept {
+            // single end reads have no `seq2`
+            let (s, qual) = match read.substring(seq.str_type, seq.label) {
+                Ok(s) => (
+                    s.to_vec(),
+                    read.substring_qual(seq.str_type, seq.label)
+                        .unwrap()
+                        .map(<[u8]>::to_vec),
+                ),
+                _ => continue,
+            };
+
+            *read
+                .data_mut(bases.str_type, bases.label, bases.attr)
+                .unwrap() = Data::Bytes(s);
+            if let Some(qual) = qual {
+                *read
+                    .data_mut(quals.str_type, quals.label, quals.attr)
+                    .unwrap() = Data::Bytes(qual);
+            }
+        }
+    })
+    .boxed()
+}
+
+// put back the reads kept raw as `keep_read` found them, undoing whatever
+// the geometry trimmed or corrected before they missed or failed
+pub fn restore_raw(read: BoxedReads) -> BoxedReads {
+    let kept = kept_reads();
+
+    read.for_each(SelectorExpr::new(b"seq1.*.raw").unwrap(), move |read| {
+        for (seq, bases, quals) in &kept {
+            let (s, qual) = match read.data(bases.str_type, bases.label, bases.attr) {
+                Ok(Data::Bytes(s)) => (
+                    s.clone(),
+                    match read.data(quals.str_type, quals.label, quals.attr) {
+                        Ok(Data::Bytes(qual)) => Some(qual.clone()),
+                        _ => None,
+                    },
+                ),
+                _ => continue,
+            };
+
+            read.set(seq.str_type, seq.label, &s, qual.as_deref())
+                .unwrap();
+        }
+    })
+    .boxed()
+}
+
+fn kept_reads() -> Vec<(Label, Attr, Attr)> {
+    [1, 2]
+        .into_iter()
+        .map(|i| {
+            (
+                Label::new(format!("seq{i}.*").as_bytes()).unwrap(),
+                Attr::new(format!("seq{i}.*.as_read").as_bytes()).unwrap(),
+                Attr::new(format!("seq{i}.*.as_read_qual").as_bytes()).unwrap(),
+            )
+        })
+        .collect()
+}
+
+// add the barcodes and UMIs of `barcodes` and `umis`, as read and corrected,
+// to the read header as `CR:Z:<bases>`.., or to a tsv if given
+pub fn tag_raw_corrected(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    barcodes: Vec<String>,
+    umis: Vec<String>,
+    tsv: Option<String>,
+) -> BoxedReads {
+    let kinds = [(BARCODE_TAGS, barcodes), (UMI_TAGS, umis)]
+        .into_iter()
+        .filter(|(_, labels)| !labels.is_empty())
+        .map(|(tags, labels)| {
+            let labels = labels
+                .iter()
+                .map(|l| {
+                    (
+                        Label::new(l.as_bytes()).unwrap(),
+                        Attr::new(raw_attr(l).as_bytes()).unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            (tags, labels)
+        })
+        .collect::<Vec<_>>();
+    let columns = kinds
+        .iter()
+        .flat_map(|(tags, _)| tags.map(str::to_string))
+        .collect::<Vec<_>>();
+    let table = tsv.map(|path| SegmentTsv::create(path, &columns).unwrap_or_else(|e| io_failed(e)));
+    let name = Label::new(b"name1.*").unwrap();
+    let read_names = name_labels();
+
+    read.for_each(sel_expr, move |read| {
+        let mut values = Vec::new();
+
+        for (_, labels) in &kinds {
+            let mut raw = Vec::new();
+            let mut corrected = Vec::new();
+
+            for (seq, attr) in labels {
+                let bases = read.substring(seq.str_type, seq.label).unwrap();
+
+                // segments without a map were never corrected
+                match read.data(attr.str_type, attr.label, attr.attr) {
+                    Ok(Data::Bytes(read_bases)) => raw.extend_from_slice(read_bases),
+                    _ => raw.extend_from_slice(bases),
+                }
+                corrected.extend_from_slice(bases);
+            }
+
+            values.push(raw);
+            values.push(corrected);
+        }
+
+        match &table {
+            Some(table) => {
+                let read_name = sink::read_name(read.substring(name.str_type, name.label).unwrap());
+                let values = values.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+                table
+                    .write(read_name, &values)
+                    .unwrap_or_else(|e| io_failed(e));
+            }
+            None => {
+                for (tag, bases) in columns.iter().zip(&values) {
+                    tag_names(read, &read_names, &sam_tag(tag, bases));
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+// add a hash of the segments of `labels` joined to the read header as
+// `<tag>:<hash>`, see `sink::route`
+pub fn hash_segments(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    labels: Vec<String>,
+    tag: String,
+) -> BoxedReads {
+    let labels = labels
+        .iter()
+        .map(|l| Label::new(l.as_bytes()).unwrap())
+        .collect::<Vec<_>>();
+    let read_names = name_labels();
+
+    read.for_each(sel_expr, move |read| {
+        let segments = labels
+            .iter()
+            .map(|l| read.substring(l.str_type, l.label).unwrap().to_vec())
+            .collect::<Vec<_>>();
+        let segments = segments.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        tag_names(
+            read,
+            &read_names,
+            &format!("{tag}:{}", segment_hash(&segments)),
+        );
+    })
+    .boxed()
+}
+
+// write a new read joining the segments of `pieces`, given by their labels, and
+// constant sequences
+pub fn write_tech_read(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    pieces: Vec<TechPiece>,
+    path: String,
+) -> BoxedReads {
+    let name = Label::new(b"name1.*").unwrap();
+    let out = Arc::new(FastqWriter::create(path).unwrap_or_else(|e| io_failed(e)));
+    let finished = Arc::clone(&out);
+    let pieces = pieces
+        .into_iter()
+        .map(|piece| match piece {
+            TechPiece::Segment(label) => Ok(Label::new(label.as_bytes()).unwrap()),
+            TechPiece::Constant(seq) => Err(seq),
+        })
+        .collect::<Vec<_>>();
+
+    let read = read.for_each(sel_expr, move |read| {
+        let mut seq = Vec::new();
+        let mut qual = Vec::new();
+
+        for piece in &pieces {
+            match piece {
+                Ok(label) => {
+                    let s = read.substring(label.str_type, label.label).unwrap();
+                    let q = read.substring_qual(label.str_type, label.label).unwrap();
+
+                    seq.extend_from_slice(s);
+                    qual.extend(q.map_or(vec![b'I'; s.len()], <[u8]>::to_vec));
+                }
+                // constant sequences are given the highest quality
+                Err(constant) => {
+                    seq.extend_from_slice(constant);
+                    qual.extend(vec![b'I'; constant.len()]);
+                }
+            }
+        }
+
+        let name = read.substring(name.str_type, name.label).unwrap();
+
+        out.write(name, &seq, &qual)
+            .unwrap_or_else(|e| io_failed(e));
+    });
+
+    on_finish(read.boxed(), move || finished.finish())
+}
+
+// the total length of the labels each read has, e.g. `seq2.*` of single end
+// reads is left out
+pub fn measure<F>(read: BoxedReads, sel_expr: SelectorExpr, labels: Vec<String>, f: F) -> BoxedReads
+where
+    F: Fn(usize) + Send + Sync + 'static,
+{
+    let labels = labels
+        .iter()
+        .map(|l| Label::new(l.as_bytes()).unwrap())
+        .collect::<Vec<_>>();
+
+    read.for_each(sel_expr, move |read| {
+        f(labels
+            .iter()
+            .filter_map(|l| read.substring(l.str_type, l.label).ok())
+            .map(|s| s.len())
+            .sum())
+    })
+    .boxed()
+}
+
+// note the length of a segment as it is cut, before any function changes it
+pub fn note_cut_len(read: BoxedReads, label: String) -> BoxedReads {
+    let sel_expr = SelectorExpr::new(label.as_bytes()).unwrap();
+    let cut_len = Attr::new(format!("{label}.cut_len").as_bytes()).unwrap();
+    let label = Label::new(label.as_bytes()).unwrap();
+
+    read.for_each(sel_expr, move |read| {
+        let len = read.substring(label.str_type, label.label).unwrap().len();
+
+        *read
+            .data_mut(cut_len.str_type, cut_len.label, cut_len.attr)
+            .unwrap() = Data::Int(len as isize);
+    })
+    .boxed()
+}
+
+// the bases of `label` as they were cut, before functions on it change them
+pub fn note_cut_seq(read: BoxedReads, label: String) -> BoxedReads {
+    let sel_expr = SelectorExpr::new(label.as_bytes()).unwrap();
+    let cut_seq = Attr::new(format!("{label}.cut_seq").as_bytes()).unwrap();
+    let label = Label::new(label.as_bytes()).unwrap();
+
+    read.for_each(sel_expr, move |read| {
+        let seq = read
+            .substring(label.str_type, label.label)
+            .unwrap()
+            .to_vec();
+
+        *read
+            .data_mut(cut_seq.str_type, cut_seq.label, cut_seq.attr)
+            .unwrap() = Data::Bytes(seq);
+    })
+    .boxed()
+}
+
+// count the bases of each segment as it was cut, and as it is written if `kept`
+pub fn count_segment_bases(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    segments: Vec<(Type, String, bool)>,
+    counts: Arc<BaseCounts>,
+) -> BoxedReads {
+    let segments = segments
+        .into_iter()
+        .map(|(type_, label, kept)| {
+            (
+                type_,
+                Label::new(label.as_bytes()).unwrap(),
+                Attr::new(format!("{label}.cut_len").as_bytes()).unwrap(),
+                kept,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    read.for_each(sel_expr, move |read| {
+        for (type_, label, cut_len, kept) in &segments {
+            // an optional group missing from the read has no segments
+            if let Ok(Data::Int(len)) = read.data(cut_len.str_type, cut_len.label, cut_len.attr) {
+                counts.cut(type_, *len as usize);
+            }
+
+            if let (true, Ok(s)) = (*kept, read.substring(label.str_type, label.label)) {
+                counts.kept(type_, s.len());
+            }
+        }
+    })
+    .boxed()
+}
+
+// call `f` for each read, it may stop the run by panicking
+pub fn inspect<F>(read: BoxedReads, f: F) -> BoxedReads
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    read.for_each(sel!(), move |_| f()).boxed()
+}
+
+// count reads entering the pipeline, aborting if too few of them leave it
+pub fn count_seen(read: BoxedReads, pass_rate: Arc<PassRate>) -> BoxedReads {
+    read.for_each(sel!(), move |_| {
+        pass_rate
+            .seen()
+            .unwrap_or_else(|e| fail(FailureKind::LowPassRate, e));
+    })
+    .boxed()
+}
+
+// count the reads of `sel_expr` leaving the pipeline as passed, reads
+// flagged as failing are kept but not counted
+pub fn count_passed<F>(read: BoxedReads, sel_expr: SelectorExpr, passed: F) -> BoxedReads
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    read.for_each(sel_expr, move |_| passed()).boxed()
+}
+
+// the time since the previous mark is given to `stage`
+pub fn mark(read: BoxedReads, timings: Arc<Timings>, stage: &'static str) -> BoxedReads {
+    read.for_each(sel!(), move |_| timings.mark(stage)).boxed()
+}
+
+// stop at the first record whose sequence and quality differ in length
+pub fn check_invariants(read: BoxedReads, stage: String) -> BoxedReads {
+    let name = Label::new(b"name1.*").unwrap();
+    let seqs = [
+        Label::new(b"seq1.*").unwrap(),
+        Label::new(b"seq2.*").unwrap(),
+    ];
+
+    read.for_each(sel!(), move |read| {
+        for seq in &seqs {
+            // single end reads have no `seq2`
+            if let Ok(s) = read.substring(seq.str_type, seq.label) {
+                let qual = read.substring_qual(seq.str_type, seq.label).unwrap();
+
+                if let Err(e) = check_lengths(s, qual) {
+                    let name = read.substring(name.str_type, name.label).unwrap();
+
+                    panic!(
+                        "After {stage}, read {} has {e}",
+                        String::from_utf8_lossy(sink::read_name(name))
+                    );
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+// stop at the first of the first `n` records whose segments, keyed by their
+// name, are not as the geometry describes them
+pub fn verify_segments(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    labels: Vec<(String, String)>,
+    verifier: Arc<Verifier>,
+    n: usize,
+) -> BoxedReads {
+    let name = Label::new(b"name1.*").unwrap();
+    let labels = labels
+        .into_iter()
+        .map(|(name, label)| (name, Label::new(label.as_bytes()).unwrap()))
+        .collect::<Vec<_>>();
+    let checked = AtomicUsize::new(0);
+
+    read.for_each(sel_expr, move |read| {
+        if checked.fetch_add(1, Ordering::Relaxed) >= n {
+            return;
+        }
+
+        let segments = labels
+            .iter()
+            .map(|(name, l)| {
+                (
+                    name.clone(),
+                    read.substring(l.str_type, l.label).unwrap().to_vec(),
+                )
+            })
+            .collect();
+
+        if let Some(issue) = verifier.check(&segments).first() {
+            let name = read.substring(name.str_type, name.label).unwrap();
+
+            panic!(
+                "Read {} does not follow the geometry, its {issue}",
+                String::from_utf8_lossy(sink::read_name(name))
+            );
+        }
+    })
+    .boxed()
+}
+
+// build a record from the segments and output reads of each processed read
+pub fn for_each_record<F>(
+    read: BoxedReads,
+    sel_expr: SelectorExpr,
+    segments: Vec<(Type, String)>,
+    reads: Vec<Vec<String>>,
+    f: F,
+) -> BoxedReads
+where
+    F: Fn(Record) + Send + Sync + 'static,
+{
+    let name = Label::new(b"name1.*").unwrap();
+    let of_type = |type_: Type| {
+        segments
+            .iter()
+            .filter(|(t, _)| *t == type_)
+            .map(|(_, l)| Label::new(l.as_bytes()).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let barcodes = of_type(Type::Barcode);
+    let umis = of_type(Type::Umi);
+    let segments = segments
+        .iter()
+        .filter_map(|(_, l)| {
+            Some((
+                sink::segment_name(l)?.to_string(),
+                Label::new(l.as_bytes()).unwrap(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    let reads = reads
+        .iter()
+        .map(|labels| {
+            labels
+                .iter()
+                .map(|l| Label::new(l.as_bytes()).unwrap())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    read.for_each(sel_expr, move |read| {
+        let seq = |l: &Label| read.substring(l.str_type, l.label).unwrap().to_vec();
+        let qual = |l: &Label| {
+            read.substring_qual(l.str_type, l.label)
+                .unwrap()
+                .map(<[u8]>::to_vec)
+                .unwrap_or_default()
+        };
+
+        f(Record {
+            name: seq(&name),
+            segments: segments.iter().map(|(n, l)| (n.clone(), seq(l))).collect(),
+            barcode: barcodes.iter().flat_map(seq).collect(),
+            umi: umis.iter().flat_map(seq).collect(),
+            seq: reads
+                .iter()
+                .map(|r| r.iter().flat_map(seq).collect())
+                .collect(),
+            qual: reads
+                .iter()
+                .map(|r| r.iter().flat_map(qual).collect())
+                .collect(),
+        })
+    })
+    .boxed()
+}
+
 fn validate_length<B>(
     read: BoxedReads,
     sel_expr: SelectorExpr,
     tr_expr: TransformExpr,
-    r_sel_expr: SelectorExpr,
+    label: String,
     bound: B,
+    on_fail: OnFail,
 ) -> BoxedReads
 where
     B: RangeBounds<usize> + Send + Sync + 'static,
 {
-    read.length_in_bounds(sel_expr, tr_expr, bound)
-        .retain(r_sel_expr)
-        .boxed()
+    let read = read.length_in_bounds(sel_expr, tr_expr, bound);
+
+    retain(
+        read.boxed(),
+        format!("{label}.v_len"),
+        on_fail,
+        "length",
+        format!("the length check of {label}"),
+    )
 }
 
+// reads where the sequence is not found are left without the piece, see
+// `drop_unmatched`. reads already cut by another backend are not searched again,
+// without `match_type` every read was
 pub fn process_sequence(
     pipeline: Box<dyn Reads>,
     sequence: String,
-    starting_label: String,
-    this_label: String,
-    prev_label: String,
-    next_label: String,
-    match_type: iter::MatchType,
+    search: &SearchPlan,
+    match_type: Option<iter::MatchType>,
 ) -> Box<dyn Reads> {
+    let match_type = match match_type {
+        Some(match_type) => match_type,
+        None => return pipeline,
+    };
+
     let tr_expr = match match_type {
-        PrefixAln { .. } => TransformExpr::new(
-            format!("{} -> {}, {}", starting_label, this_label, next_label).as_bytes(),
-        )
-        .unwrap(),
-        ExactSearch | HammingSearch(_) => TransformExpr::new(
-            format!(
-                "{} -> {}, {}, {}",
-                starting_label, prev_label, this_label, next_label
-            )
-            .as_bytes(),
-        )
-        .unwrap(),
+        PrefixAln { .. } => search.aligned.get(),
+        ExactSearch | HammingSearch(_) => search.split.get(),
         _ => unreachable!(),
     };
 
-    let sel_expr = SelectorExpr::new(starting_label.as_bytes()).unwrap();
-    let r_sel_expr = SelectorExpr::new(this_label.as_bytes()).unwrap();
-
     pipeline
-        .match_one(sel_expr, tr_expr, sequence, match_type)
-        .retain(r_sel_expr)
+        .match_one(search.unfound.get(), tr_expr, sequence, match_type)
         .boxed()
 }
 
-fn process_sized<B>(
+// `this_label` is searched for in `{this_label}_win`, which starts `start` bases into
+// `init_label` and is long enough for a hit starting up to `end` bases into it
+pub fn open_window(
     read: BoxedReads,
     init_label: String,
+    prev_label: String,
+    this_label: String,
+    (start, end): (usize, usize),
+    seq_len: usize,
+) -> BoxedReads {
+    let rest_label = format!("{this_label}_rest");
+
+    let read = cut(
+        read,
+        SelectorExpr::new(init_label.as_bytes()).unwrap(),
+        TransformExpr::new(format!("{init_label} -> {prev_label}, {rest_label}").as_bytes())
+            .unwrap(),
+        LeftEnd(start),
+    );
+
+    cut(
+        read,
+        SelectorExpr::new(rest_label.as_bytes()).unwrap(),
+        TransformExpr::new(
+            format!("{rest_label} -> {this_label}_win, {this_label}_tail").as_bytes(),
+        )
+        .unwrap(),
+        LeftEnd(end - start + seq_len),
+    )
+}
+
+// give the bases skipped before a hit in the window to `prev_label`, and the bases
+// after the window to `next_label`
+pub fn close_window(
+    read: BoxedReads,
+    prev_label: String,
     this_label: String,
     next_label: String,
-    range: B,
-) -> BoxedReads
+) -> BoxedReads {
+    let gap = format!("{this_label}_gap");
+    let tail = format!("{this_label}_tail");
+
+    let mut read = read;
+
+    // skipped bases are discarded when nothing comes before the sequence
+    if prev_label != "_" {
+        let sel_gap = || SelectorExpr::new(gap.as_bytes()).unwrap();
+
+        read = set(
+            read,
+            sel_gap(),
+            prev_label.clone(),
+            format!("{{{prev_label}}}{{{gap}}}"),
+        );
+        read = set(read, sel_gap(), gap.clone(), String::new());
+    }
+
+    let sel_next = || SelectorExpr::new(next_label.as_bytes()).unwrap();
+
+    let read = set(
+        read,
+        sel_next(),
+        next_label.clone(),
+        format!("{{{next_label}}}{{{tail}}}"),
+    );
+
+    set(read, sel_next(), tail, String::new())
+}
+
+pub fn drop_unmatched(
+    read: BoxedReads,
+    sequence: &str,
+    this_label: String,
+    on_fail: OnFail,
+) -> BoxedReads {
+    let stage = format!("matching {sequence} at {this_label}");
+
+    retain(read, this_label, on_fail, "unmatched", stage)
+}
+
+// reads without `this_label` are flagged in `seq1.*.raw`, to be written as
+// they were read, see `restore_raw`
+pub fn keep_unmatched(read: BoxedReads, this_label: String) -> BoxedReads {
+    let label = Label::new(this_label.as_bytes()).unwrap();
+    let attr = Attr::new(b"seq1.*.raw").unwrap();
+
+    read.for_each(sel!(), move |read| {
+        let raw = matches!(
+            read.data(attr.str_type, attr.label, attr.attr),
+            Ok(Data::Bool(true))
+        ) || read.substring(label.str_type, label.label).is_err();
+
+        *read.data_mut(attr.str_type, attr.label, attr.attr).unwrap() = Data::Bool(raw);
+    })
+    .boxed()
+}
+
+// reads without `this_label` are cut as if the sequence followed `prev_len` bases
+pub fn fallback_unmatched(
+    read: BoxedReads,
+    init_label: String,
+    prev_label: String,
+    this_label: String,
+    next_label: String,
+    prev_len: usize,
+    seq_len: usize,
+) -> BoxedReads {
+    let rest_label = format!("{this_label}_miss");
+
+    let read = cut(
+        read,
+        SelectorExpr::new(format!("{init_label} & !{this_label}").as_bytes()).unwrap(),
+        TransformExpr::new(format!("{init_label} -> {prev_label}, {rest_label}").as_bytes())
+            .unwrap(),
+        LeftEnd(prev_len),
+    );
+
+    cut(
+        read,
+        SelectorExpr::new(rest_label.as_bytes()).unwrap(),
+        TransformExpr::new(format!("{rest_label} -> {this_label}, {next_label}").as_bytes())
+            .unwrap(),
+        LeftEnd(seq_len),
+    )
+}
+
+// `label` on reads without any of the anchors in `except`
+fn excluding(label: &str, except: &[String]) -> String {
+    std::iter::once(label.to_string())
+        .chain(except.iter().map(|anchor| format!("!{anchor}")))
+        .collect::<Vec<_>>()
+        .join(" & ")
+}
+
+// pieces of an optional group are only checked on reads where the group's
+// leading fixed sequence, `anchor`, was found
+fn guarded(label: &str, anchor: &str) -> String {
+    if label == anchor {
+        return String::new();
+    }
+    format!("{label} | !{anchor}")
+}
+
+pub fn process_optional_sequence(
+    read: BoxedReads,
+    sequence: String,
+    piece: &PiecePlan,
+    anchor: &str,
+    match_type: Option<iter::MatchType>,
+    on_fail: OnFail,
+) -> BoxedReads {
+    let this_label = &piece.labels.this;
+    let stage = format!("matching {sequence} at {this_label}");
+
+    // reads already cut by another backend are not searched again
+    let read = match (match_type, &piece.search) {
+        (Some(match_type), Some(search)) => read
+            .match_one(
+                search.unfound.get(),
+                search.aligned.get(),
+                sequence,
+                match_type,
+            )
+            .boxed(),
+        _ => read,
+    };
+
+    retain(
+        read,
+        guarded(this_label, anchor),
+        on_fail,
+        "unmatched",
+        stage,
+    )
+}
+
+pub fn process_optional_fixed_len(
+    read: BoxedReads,
+    piece: &PiecePlan,
+    anchor: &str,
+    len: usize,
+    on_fail: OnFail,
+) -> BoxedReads {
+    let this_label = &piece.labels.this;
+
+    let cut_read = cut(read, piece.init.get(), piece.cut.get(), LeftEnd(len));
+    let read = cut_read.length_in_bounds(piece.this.get(), piece.v_len.get(), len..=len);
+
+    retain(
+        read.boxed(),
+        guarded(&format!("{this_label}.v_len"), anchor),
+        on_fail,
+        "length",
+        format!("the length check of {this_label}"),
+    )
+}
+
+// reads missing an optional group get empty pieces for it and continue from
+// where the group would have started. which reads have the group is noted
+// first, as the empty anchor the others are given passes for it
+pub fn skip_optional(read: BoxedReads, group: &GroupPlan) -> BoxedReads {
+    let (anchor, found) = (group.pieces[0].this_label.get(), group.found.get());
+
+    let read = read.for_each(group.pieces[0].init.get(), move |read| {
+        let has = read.substring(anchor.str_type, anchor.label).is_ok();
+
+        *read
+            .data_mut(found.str_type, found.label, found.attr)
+            .unwrap() = Data::Bool(has);
+    });
+    let mut read = cut(
+        read.boxed(),
+        group.end.get(),
+        group.end_cut.get(),
+        LeftEnd(0),
+    );
+
+    for tr_expr in &group.skips {
+        read = cut(read, group.absent.get(), tr_expr.get(), LeftEnd(0));
+    }
+
+    cut(read, group.absent.get(), group.skip.get(), LeftEnd(0))
+}
+
+// reads need one branch of a set of alternatives, each noted as found in
+// `skip_optional`
+pub fn any_branch(read: BoxedReads, found: Vec<String>, on_fail: OnFail) -> BoxedReads {
+    let stage = format!("matching any of {}", found.join(", "));
+
+    retain(read, found.join(" | "), on_fail, "unmatched", stage)
+}
+
+fn process_sized<B>(read: BoxedReads, piece: &PiecePlan, range: B, on_fail: OnFail) -> BoxedReads
 where
     B: RangeBounds<usize> + Send + Sync + 'static,
 {
-    let cut_sel_expr = SelectorExpr::new(init_label.as_bytes()).unwrap();
-    let cut_tr_expr =
-        TransformExpr::new(format!("{init_label} -> {this_label}, {next_label}").as_bytes())
-            .unwrap();
-
     let end = match RangeBounds::<usize>::end_bound(&range) {
         Bound::Included(end) => *end,
         _ => unreachable!(),
     };
-    let cut_read = cut(read, cut_sel_expr, cut_tr_expr, LeftEnd(end));
-
-    let len_sel_expr = SelectorExpr::new(this_label.as_bytes()).unwrap();
-    let len_tr_expr =
-        TransformExpr::new(format!("{this_label} -> {this_label}.v_len").as_bytes()).unwrap();
-    let r_sel_expr = SelectorExpr::new(format!("{this_label}.v_len").as_bytes()).unwrap();
+    let cut_read = cut(read, piece.init.get(), piece.cut.get(), LeftEnd(end));
 
-    let val_len_read = validate_length(cut_read, len_sel_expr, len_tr_expr, r_sel_expr, range);
-
-    val_len_read
+    validate_length(
+        cut_read,
+        piece.this.get(),
+        piece.v_len.get(),
+        piece.labels.this.clone(),
+        range,
+        on_fail,
+    )
 }
 
 pub fn process_fixed_len(
     read: BoxedReads,
-    init_label: String,
-    this_label: String,
-    next_label: String,
+    piece: &PiecePlan,
     len: usize,
+    on_fail: OnFail,
 ) -> BoxedReads {
-    process_sized(read, init_label, this_label, next_label, len..=len)
+    process_sized(read, piece, len..=len, on_fail)
 }
 
 pub fn process_ranged_len<B>(
     read: BoxedReads,
-    init_label: String,
-    this_label: String,
-    next_label: String,
+    piece: &PiecePlan,
     range: B,
+    on_fail: OnFail,
 ) -> BoxedReads
 where
     B: RangeBounds<usize> + Send + Sync + 'static,
 {
-    process_sized(read, init_label, this_label, next_label, range)
+    process_sized(read, piece, range, on_fail)
 }
 
-pub fn process_unbounded(read: BoxedReads, init_label: String, this_label: String) -> BoxedReads {
-    // set init_label to this_label
-    // cut left end 0
-    let sel_expr = SelectorExpr::new(init_label.as_bytes()).unwrap();
-    let cut_tr_expr =
-        TransformExpr::new(format!("{init_label} -> _, {this_label}").as_bytes()).unwrap();
+// a ranged piece with nothing after it to search for is cut at its longest
+// length, then given back the bases past the longest prefix in the whitelist.
+// with `lengths` counting them the closest prefix within `mismatch` is taken
+pub fn process_whitelisted_len(
+    read: BoxedReads,
+    piece: &PiecePlan,
+    range: RangeInclusive<usize>,
+    whitelist: String,
+    mismatch: usize,
+    lengths: Option<Arc<WhitelistLengths>>,
+    on_fail: OnFail,
+) -> BoxedReads {
+    let whitelist = Whitelist::open(&whitelist).unwrap_or_else(|e| io_failed(e));
+    let (this, next) = (piece.this_label.get(), piece.next_label.get());
+
+    let read = process_sized(read, piece, range.clone(), on_fail);
+
+    read.for_each(piece.this.get(), move |read| {
+        let (barcode, rest) = {
+            let s = read.substring(this.str_type, this.label).unwrap();
+            let q = read.substring_qual(this.str_type, this.label).unwrap();
 
-    let tr = format!("{{{this_label}}}");
+            let len = match &lengths {
+                Some(lengths) => {
+                    let best = whitelist.best_length(s, range.clone(), mismatch);
+                    lengths.insert(best);
 
-    let cut_read = cut(read, sel_expr.clone(), cut_tr_expr, LeftEnd(0));
+                    best.map(|(len, _)| len)
+                }
+                None => whitelist.length(s, range.clone()),
+            };
+
+            // reads without a listed barcode are left to the map
+            let len = match len {
+                Some(len) if len < s.len() => len,
+                _ => return,
+            };
+
+            let n = read.substring(next.str_type, next.label).unwrap();
+            let nq = read.substring_qual(next.str_type, next.label).unwrap();
+
+            (
+                (s[..len].to_vec(), q.map(|q| q[..len].to_vec())),
+                (
+                    [&s[len..], n].concat(),
+                    q.zip(nq).map(|(q, nq)| [&q[len..], nq].concat()),
+                ),
+            )
+        };
 
-    set(cut_read, sel_expr, init_label, tr)
+        read.set(this.str_type, this.label, &barcode.0, barcode.1.as_deref())
+            .unwrap();
+        read.set(next.str_type, next.label, &rest.0, rest.1.as_deref())
+            .unwrap();
+    })
+    .boxed()
 }
 
-pub fn process_ranged_len_no_cut<B>(read: BoxedReads, this_label: String, range: B) -> BoxedReads
-where
-    B: RangeBounds<usize> + Send + Sync + 'static,
-{
-    let len_sel_expr = SelectorExpr::new(this_label.as_bytes()).unwrap();
-    let len_tr_expr =
-        TransformExpr::new(format!("{this_label} -> {this_label}.v_len").as_bytes()).unwrap();
-    let r_sel_expr = SelectorExpr::new(format!("{this_label}.v_len").as_bytes()).unwrap();
+pub fn process_unbounded(read: BoxedReads, piece: &PiecePlan) -> BoxedReads {
+    let PieceLabels { init, this, .. } = &piece.labels;
+
+    let cut_read = cut(read, piece.init.get(), piece.cut.get(), LeftEnd(0));
 
-    validate_length(read, len_sel_expr, len_tr_expr, r_sel_expr, range)
+    set(
+        cut_read,
+        piece.init.get(),
+        init.clone(),
+        format!("{{{this}}}"),
+    )
 }
 
-pub fn process_unbounded_no_cut(
+pub fn process_ranged_len_no_cut<B>(
     read: BoxedReads,
-    init_label: String,
-    this_label: String,
-) -> BoxedReads {
-    // set init_label to this_label
-    // cut left end 0
-    let sel_expr = SelectorExpr::new(init_label.as_bytes()).unwrap();
+    piece: &PiecePlan,
+    range: B,
+    on_fail: OnFail,
+) -> BoxedReads
+where
+    B: RangeBounds<usize> + Send + Sync + 'static,
+{
+    validate_length(
+        read,
+        piece.this.get(),
+        piece.v_len.get(),
+        piece.labels.this.clone(),
+        range,
+        on_fail,
+    )
+}
 
-    let tr = format!("{{{this_label}}}");
+pub fn process_unbounded_no_cut(read: BoxedReads, piece: &PiecePlan) -> BoxedReads {
+    let PieceLabels { init, this, .. } = &piece.labels;
 
-    set(read, sel_expr, init_label, tr)
+    set(read, piece.init.get(), init.clone(), format!("{{{this}}}"))
 }
@@ -0,0 +1,116 @@
+/*
+   Quality strings follow their sequence through every operation.
+   Bases added by padding were never sequenced, they are given a
+   fixed quality which is the lowest one unless set otherwise.
+   Every operation keeps a sequence and its quality the same length,
+   `--check-invariants` verifies this for each record after each stage.
+
+   Qualities are phred+33 throughout. Older Illumina runs wrote phred+64,
+   which is told from the lowest and highest quality of the first reads
+   and converted as the reads are read. Qualities that fit both, only
+   high scores with none above 41, are refused rather than guessed, their
+   offset has to be given with `--qual-offset`.
+*/
+
+pub const DEFAULT_PAD_QUAL: u8 = b'!';
+
+// `seq` with `left` and `right` copies of `nuc` added, its quality padded to match
+pub fn pad(
+    seq: &[u8],
+    qual: Option<&[u8]>,
+    (left, right): (usize, usize),
+    nuc: u8,
+    pad_qual: u8,
+) -> (Vec<u8>, Option<Vec<u8>>) {
+    let padded = |s: &[u8], c: u8| [&vec![c; left][..], s, &vec![c; right]].concat();
+
+    (padded(seq, nuc), qual.map(|q| padded(q, pad_qual)))
+}
+
+// the length a read is cut to so its 3' end is not below the phred score `min_qual`.
+// qualities are summed from the end, so a single good base does not stop the trim
+pub fn quality_trim_len(qual: &[u8], min_qual: usize) -> usize {
+    let (mut sum, mut best, mut len) = (0, 0, qual.len());
+
+    for (i, &q) in qual.iter().enumerate().rev() {
+        sum += min_qual as isize - (q as isize - 33);
+
+        if sum < 0 {
+            break;
+        }
+
+        if sum > best {
+            (best, len) = (sum, i);
+        }
+    }
+
+    len
+}
+
+pub fn check_lengths(seq: &[u8], qual: Option<&[u8]>) -> Result<(), String> {
+    match qual {
+        Some(qual) if qual.len() != seq.len() => {
+            Err(format!("{} bases but {} qualities", seq.len(), qual.len()))
+        }
+        _ => Ok(()),
+    }
+}
+
+// what `--qual-offset` says of the input qualities
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QualOffset {
+    // told from the first reads of each input
+    #[default]
+    Auto,
+    Phred33,
+    Phred64,
+}
+
+pub fn qual_offset(s: &str) -> Result<QualOffset, String> {
+    match s {
+        "auto" => Ok(QualOffset::Auto),
+        "33" => Ok(QualOffset::Phred33),
+        "64" => Ok(QualOffset::Phred64),
+        _ => Err(format!(
+            "Unknown quality offset `{s}`, expected auto, 33 or 64"
+        )),
+    }
+}
+
+// phred+33 or phred+64, told from the range of the qualities given. some
+// below `;` is out of phred+64, as is some above `h`, the highest quality
+// written as phred+64, which long reads reach as phred+33. none below `@`
+// and some above `J` is out of phred+33 for short reads
+pub fn detect_offset<'a>(quals: impl IntoIterator<Item = &'a [u8]>) -> Result<QualOffset, String> {
+    let (min, max) = quals
+        .into_iter()
+        .flatten()
+        .fold((u8::MAX, u8::MIN), |(min, max), &q| {
+            (min.min(q), max.max(q))
+        });
+
+    match (min, max) {
+        _ if min > max => Ok(QualOffset::Phred33),
+        _ if min < b';' || max > b'h' => Ok(QualOffset::Phred33),
+        _ if min >= b'@' && max > b'J' => Ok(QualOffset::Phred64),
+        _ => Err(format!(
+            "qualities from `{}` to `{}` could be phred+33 or phred+64, give --qual-offset 33 or --qual-offset 64",
+            min as char, max as char
+        )),
+    }
+}
+
+// a phred+64 quality character as phred+33
+pub fn phred64_to_33(q: u8) -> u8 {
+    q.saturating_sub(31).max(b'!')
+}
+
+// a single phred+33 quality character
+pub fn parse_qual(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [c] if (b'!'..=b'~').contains(c) => Ok(*c),
+        _ => Err(format!(
+            "expected a quality character from `!` to `~`, found `{s}`"
+        )),
+    }
+}
@@ -0,0 +1,190 @@
+/*
+   Re-pairing of read files whose mates are out of sync.
+   Both files are read in step, a read whose mate has not been
+   seen yet is held back until it turns up in the other file.
+   Mates are matched on their read name up to the first space,
+   without a trailing `/1` or `/2`. Reads whose mate never turns
+   up are dropped, or written to a file of singletons if given.
+   Only the reads out of order are kept in memory.
+
+   The pipeline itself cannot put mates out of step: both are one record,
+   which a filter drops whole. `--verify-pairs` reads the outputs back once
+   the run is done and fails it at the first pair whose names differ, as a
+   check that this holds.
+*/
+
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process,
+};
+
+use crate::{
+    sink::{fastq::fastq_record, read_name},
+    source::fastq::{FastqReader, FastqRecord},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RepairStats {
+    pub pairs: usize,
+    pub singletons: usize,
+}
+
+// the name shared by both mates of a pair
+pub fn mate_name(name: &[u8]) -> &[u8] {
+    let name = read_name(name);
+
+    match name {
+        [rest @ .., b'/', b'1' | b'2'] => rest,
+        _ => name,
+    }
+}
+
+fn write_record<W: Write>(out: &mut W, record: &FastqRecord) -> io::Result<()> {
+    out.write_all(&fastq_record(&record.name, &record.seq, &record.qual))
+}
+
+pub fn repair<W: Write>(
+    r1: &mut FastqReader,
+    r2: &mut FastqReader,
+    out1: &mut W,
+    out2: &mut W,
+    mut singletons: Option<&mut W>,
+) -> io::Result<RepairStats> {
+    let mut stats = RepairStats::default();
+
+    // reads waiting for their mate, keyed by mate name
+    let mut waiting1: HashMap<Vec<u8>, FastqRecord> = HashMap::new();
+    let mut waiting2: HashMap<Vec<u8>, FastqRecord> = HashMap::new();
+
+    loop {
+        let (rec1, rec2) = (r1.next_record()?, r2.next_record()?);
+
+        if rec1.is_none() && rec2.is_none() {
+            break;
+        }
+
+        let mut pair = |rec1: &FastqRecord, rec2: &FastqRecord| -> io::Result<()> {
+            write_record(out1, rec1)?;
+            write_record(out2, rec2)?;
+            stats.pairs += 1;
+            Ok(())
+        };
+
+        match (rec1, rec2) {
+            (Some(rec1), Some(rec2)) if mate_name(&rec1.name) == mate_name(&rec2.name) => {
+                pair(&rec1, &rec2)?
+            }
+            (rec1, rec2) => {
+                if let Some(rec1) = rec1 {
+                    let name = mate_name(&rec1.name).to_vec();
+                    match waiting2.remove(&name) {
+                        Some(rec2) => pair(&rec1, &rec2)?,
+                        None => {
+                            waiting1.insert(name, rec1);
+                        }
+                    }
+                }
+
+                if let Some(rec2) = rec2 {
+                    let name = mate_name(&rec2.name).to_vec();
+                    match waiting1.remove(&name) {
+                        Some(rec1) => pair(&rec1, &rec2)?,
+                        None => {
+                            waiting2.insert(name, rec2);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    stats.singletons = waiting1.len() + waiting2.len();
+
+    if let Some(out) = &mut singletons {
+        // in name order, the order of a hash map differs from run to run
+        let mut waiting = waiting1.into_iter().chain(waiting2).collect::<Vec<_>>();
+        waiting.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (_, record) in &waiting {
+            write_record(out, record)?;
+        }
+    }
+
+    Ok(stats)
+}
+
+// the pairs of two outputs, an error at the first whose mates differ
+pub fn verify_pairs(r1: &mut FastqReader, r2: &mut FastqReader) -> io::Result<usize> {
+    let mut pairs = 0;
+
+    loop {
+        let msg = match (r1.next_record()?, r2.next_record()?) {
+            (Some(rec1), Some(rec2)) if mate_name(&rec1.name) == mate_name(&rec2.name) => {
+                pairs += 1;
+                continue;
+            }
+            (Some(rec1), Some(rec2)) => format!(
+                "the mates of pair {} differ, {} and {}",
+                pairs + 1,
+                String::from_utf8_lossy(read_name(&rec1.name)),
+                String::from_utf8_lossy(read_name(&rec2.name))
+            ),
+            (Some(_), None) => format!("the second output ends after {pairs} pairs"),
+            (None, Some(_)) => format!("the first output ends after {pairs} pairs"),
+            (None, None) => return Ok(pairs),
+        };
+
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+}
+
+// repaired copies of a pair of read files, removed once dropped
+pub struct RepairedFiles {
+    pub file1: PathBuf,
+    pub file2: PathBuf,
+    pub stats: RepairStats,
+}
+
+impl RepairedFiles {
+    pub fn create(file1: &str, file2: &str, singletons: Option<&str>) -> io::Result<Self> {
+        let dir = env::temp_dir();
+        let id = process::id();
+
+        let mut repaired = Self {
+            file1: dir.join(format!("seqproc-{id}-repaired_1.fastq")),
+            file2: dir.join(format!("seqproc-{id}-repaired_2.fastq")),
+            stats: RepairStats::default(),
+        };
+
+        let (mut r1, mut r2) = (FastqReader::open(file1)?, FastqReader::open(file2)?);
+
+        let create = |path: &PathBuf| File::create(path).map(BufWriter::new);
+        let (mut out1, mut out2) = (create(&repaired.file1)?, create(&repaired.file2)?);
+        let mut singletons = singletons
+            .map(|path| File::create(path).map(BufWriter::new))
+            .transpose()?;
+
+        repaired.stats = repair(&mut r1, &mut r2, &mut out1, &mut out2, singletons.as_mut())?;
+
+        r1.finish()?;
+        r2.finish()?;
+        out1.flush()?;
+        out2.flush()?;
+        if let Some(out) = &mut singletons {
+            out.flush()?;
+        }
+
+        Ok(repaired)
+    }
+}
+
+impl Drop for RepairedFiles {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.file1);
+        let _ = fs::remove_file(&self.file2);
+    }
+}
@@ -0,0 +1,188 @@
+/*
+   The json `--report` of a run, versioned so parsers downstream know what
+   to expect. Fields are only ever added within a version; a field removed,
+   renamed or given another type bumps `REPORT_VERSION`. The report is
+   written from `JsonReport`, and `seqproc schema` prints the JSON Schema
+   derived from the same structs, so the two cannot drift apart. The schema
+   allows fields it does not list so a parser checking against it keeps
+   working as fields are added.
+
+   A report holds the reads that entered the run and how many of them
+   passed, the wall time of the run and of each stage timed, QC metrics by
+   their name and, with `--checksum`, the digests of the inputs and
+   outputs. JSON has no NaN or infinity, so a metric that is not a number,
+   such as a rate over no reads, is written as null.
+*/
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use schemars::{json_schema, schema_for, JsonSchema, Schema, SchemaGenerator};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::checksum::{Algorithm, Digests};
+
+pub const REPORT_VERSION: usize = 3;
+
+/// A stage of the pipeline, timed with the reads that passed it
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct StageTiming {
+    pub stage: String,
+    pub seconds: f64,
+    pub reads: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    // reads that entered the run, and those written out of them
+    pub reads: usize,
+    pub passed: usize,
+    pub seconds: f64,
+    // empty unless stages are timed
+    pub stages: Vec<StageTiming>,
+    // metrics by their name, in the order they were added
+    pub qc: Vec<(String, f64)>,
+    pub digests: Option<Digests>,
+}
+
+impl Report {
+    pub fn failed(&self) -> usize {
+        self.reads.saturating_sub(self.passed)
+    }
+
+    // the report as it is written
+    pub fn to_json(&self) -> JsonReport {
+        let files = |files: &[(String, String)]| Ordered(files.to_vec());
+
+        JsonReport {
+            report_version: Version,
+            reads: ReadCounts {
+                total: self.reads,
+                passed: self.passed,
+                failed: self.failed(),
+            },
+            timings: Timings {
+                seconds: self.seconds,
+                stages: self.stages.clone(),
+            },
+            qc: Ordered(
+                self.qc
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.is_finite().then_some(*value)))
+                    .collect(),
+            ),
+            checksum: self.digests.as_ref().map(|d| d.algorithm),
+            inputs: self.digests.as_ref().map(|d| files(&d.inputs)),
+            outputs: self.digests.as_ref().map(|d| files(&d.outputs)),
+        }
+    }
+
+    // on one line
+    pub fn json(&self) -> String {
+        let mut out = serde_json::to_string(&self.to_json()).unwrap();
+        out.push('\n');
+        out
+    }
+}
+
+/// The `--report` of a seqproc run
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[schemars(title = "seqproc report")]
+pub struct JsonReport {
+    /// the version of this schema the report follows
+    pub report_version: Version,
+    /// the reads that entered the run, those written and those that failed
+    pub reads: ReadCounts,
+    /// the wall time of the run in seconds, and of each stage timed with the reads that passed it
+    pub timings: Timings,
+    /// QC metrics of the reads by their name, null where they are not a number
+    pub qc: Ordered<Option<f64>>,
+    /// the algorithm of the digests, with --checksum
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<Algorithm>,
+    /// the input files with the hex digests of their bytes, with --checksum
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Ordered<String>>,
+    /// the output files with the hex digests of their bytes, with --checksum
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Ordered<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct ReadCounts {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+pub struct Timings {
+    pub seconds: f64,
+    pub stages: Vec<StageTiming>,
+}
+
+// written as `REPORT_VERSION`, the only version its schema allows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Version;
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        REPORT_VERSION.serialize(serializer)
+    }
+}
+
+impl JsonSchema for Version {
+    fn schema_name() -> Cow<'static, str> {
+        "ReportVersion".into()
+    }
+
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({ "const": REPORT_VERSION })
+    }
+}
+
+// a JSON object keeping its keys in the order they were added, with the
+// schema of a map
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ordered<V>(pub Vec<(String, V)>);
+
+impl<V: Serialize> Serialize for Ordered<V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<V: JsonSchema> JsonSchema for Ordered<V> {
+    fn schema_name() -> Cow<'static, str> {
+        BTreeMap::<String, V>::schema_name()
+    }
+
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        BTreeMap::<String, V>::json_schema(generator)
+    }
+}
+
+// the JSON Schema of the report, on one line
+pub fn schema() -> String {
+    let mut schema = schema_for!(JsonReport);
+    schema.insert(
+        "$id".to_string(),
+        format!("urn:seqproc:report:{REPORT_VERSION}").into(),
+    );
+    schema.insert("additionalProperties".to_string(), true.into());
+
+    let mut out = serde_json::to_string(&schema).unwrap();
+    out.push('\n');
+    out
+}
@@ -0,0 +1,60 @@
+/*
+   The one source of randomness of a run, seeded by `--seed`.
+   Nothing draws from it yet, it is here so subsampling and the tie
+   breaking of ambiguous corrections share one seed. A read draws from
+   a generator seeded by the run seed, the name of the feature drawing
+   and the name of the read, rather than from one shared generator. Runs
+   with the same seed are then identical whatever the number of threads,
+   and one feature drawing more does not change the draws of another.
+*/
+
+pub const DEFAULT_SEED: u64 = 0;
+
+// splitmix64, small and good enough for sampling
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+// FNV-1a, which unlike the std hasher is the same on every platform and release
+fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // the generator of `stream`, e.g. `subsample`, for the read named `name`
+    pub fn for_read(seed: u64, stream: &str, name: &[u8]) -> Self {
+        let hash = fnv(0xcbf2_9ce4_8422_2325, stream.as_bytes());
+        let hash = fnv(hash ^ 0xff, name);
+
+        let mut rng = Self::new(seed ^ hash);
+        // the first draw mixes the seed in
+        rng.next_u64();
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in `0..n`
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    // true with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
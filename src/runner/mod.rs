@@ -0,0 +1,232 @@
+/*
+   Running a geometry from a program rather than the command line.
+   `run` compiles the geometry and processes the reads like the binary
+   does, but reports every failure as an error instead of a panic. The
+   number of reads entering the pipeline is given to a progress callback
+   every `PROGRESS_EVERY` reads. Cancelling the token stops the run at the
+   next read, the outputs written so far are left as they are. A program
+   running one geometry over a batch of files keeps it compiled in a
+   `GeometryCache` of its own, which holds a bounded number of them.
+*/
+
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use antisequence::{iter_fastq1, iter_fastq2, Reads};
+use chumsky::{prelude::*, Stream};
+
+use crate::{
+    compile::{compile, CompiledData},
+    failure::Failure,
+    interpret::{BoxedReads, InterpretOptions},
+    lexer::lexer,
+    parser::{parser, Expr},
+    processors::inspect,
+};
+
+// reads between calls of the progress callback
+pub const PROGRESS_EVERY: usize = 100_000;
+
+#[derive(Clone, Debug)]
+pub struct RunConfig {
+    // the geometry itself, not the path of a file holding it
+    pub geometry: String,
+    pub file1: String,
+    // omit for single end reads
+    pub file2: Option<String>,
+    pub out1: String,
+    pub out2: String,
+    pub threads: usize,
+    pub options: InterpretOptions,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    // reads that entered the pipeline so far
+    pub reads: usize,
+}
+
+// shared with the run, cancelling any clone cancels it
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunError {
+    // every error found in the geometry
+    Geometry(Vec<String>),
+    Cancelled,
+    Failed(String),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::Geometry(errs) => write!(f, "invalid geometry: {}", errs.join(", ")),
+            RunError::Cancelled => write!(f, "the run was cancelled"),
+            RunError::Failed(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+// the parsed geometry, with every lexing and parsing error found
+pub fn parse_geometry(geometry: &str) -> Result<Expr, RunError> {
+    let (tokens, lex_errs) = lexer().parse_recovery(geometry);
+    let mut errs = lex_errs.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+    let ast = tokens.and_then(|tokens| {
+        let len = tokens.len();
+        let (ast, parse_errs) =
+            parser().parse_recovery(Stream::from_iter(len..len + 1, tokens.into_iter()));
+        errs.extend(parse_errs.iter().map(|e| e.to_string()));
+        ast
+    });
+
+    match ast {
+        Some((ast, _)) if errs.is_empty() => Ok(ast),
+        _ => Err(RunError::Geometry(errs)),
+    }
+}
+
+pub fn compile_geometry(geometry: &str) -> Result<CompiledData, RunError> {
+    compile(parse_geometry(geometry)?).map_err(|e| RunError::Geometry(vec![e.msg]))
+}
+
+// compiled geometries by their text, at most `capacity` of them with the
+// least recently used dropped first. geometries with errors are not kept
+pub struct GeometryCache {
+    capacity: usize,
+    compiled: Mutex<Vec<(String, Arc<CompiledData>)>>,
+}
+
+impl GeometryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            compiled: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, geometry: &str) -> Result<Arc<CompiledData>, RunError> {
+        let mut compiled = self.compiled.lock().unwrap();
+
+        if let Some(i) = compiled.iter().position(|(text, _)| text == geometry) {
+            let used = compiled.remove(i);
+            compiled.push(used.clone());
+            return Ok(used.1);
+        }
+
+        let data = Arc::new(compile_geometry(geometry)?);
+
+        if self.capacity > 0 {
+            if compiled.len() == self.capacity {
+                compiled.remove(0);
+            }
+            compiled.push((geometry.to_string(), data.clone()));
+        }
+
+        Ok(data)
+    }
+}
+
+pub fn run<F>(
+    config: RunConfig,
+    progress: F,
+    cancel: CancellationToken,
+) -> Result<Progress, RunError>
+where
+    F: Fn(Progress) + Send + Sync + 'static,
+{
+    run_cached(config, &GeometryCache::new(0), progress, cancel)
+}
+
+// `run` with the geometry taken from `cache`
+pub fn run_cached<F>(
+    config: RunConfig,
+    cache: &GeometryCache,
+    progress: F,
+    cancel: CancellationToken,
+) -> Result<Progress, RunError>
+where
+    F: Fn(Progress) + Send + Sync + 'static,
+{
+    let RunConfig {
+        geometry,
+        file1,
+        file2,
+        out1,
+        out2,
+        threads,
+        options,
+    } = config;
+
+    let compiled = cache.get(&geometry)?;
+    compiled
+        .check_options(&options)
+        .map_err(|e| RunError::Geometry(vec![e]))?;
+
+    if cancel.is_cancelled() {
+        return Err(RunError::Cancelled);
+    }
+
+    let read = match file2 {
+        Some(file2) => iter_fastq2(file1, file2, 256).map(|r| r.boxed()),
+        None => iter_fastq1(file1, 256).map(|r| r.boxed()),
+    }
+    .map_err(|e| RunError::Failed(e.to_string()))?;
+
+    let reads = Arc::new(AtomicUsize::new(0));
+    let seen = reads.clone();
+    let stop = cancel.clone();
+
+    let read: BoxedReads = inspect(read, move || {
+        if stop.is_cancelled() {
+            panic!("cancelled");
+        }
+
+        let n = seen.fetch_add(1, Ordering::Relaxed) + 1;
+        if n.is_multiple_of(PROGRESS_EVERY) {
+            progress(Progress { reads: n });
+        }
+    });
+
+    // building the pipeline fails by panicking too, on an output that
+    // cannot be created or an option the geometry cannot follow
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        compiled
+            .interpret(read, out1, out2, options)
+            .run_with_threads(threads)
+    }))
+    .map_err(|payload| {
+        if cancel.is_cancelled() {
+            RunError::Cancelled
+        } else {
+            RunError::Failed(Failure::from_panic(&*payload).message)
+        }
+    })?;
+
+    Ok(Progress {
+        reads: reads.load(Ordering::Relaxed),
+    })
+}
@@ -0,0 +1,74 @@
+/*
+   The read outputs are written to temporary files beside them and renamed
+   into place once the run is done, so a run that fails or is interrupted never
+   leaves a truncated fastq where a later step would read it. The files of
+   a failed run are removed. Outputs that are not regular files, such as
+   `/dev/null` or a named pipe, are written in place, as are sharded ones.
+*/
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+// the outputs, replaced by the temporary files they are written to until
+// `commit`. uncommitted files are removed once dropped
+pub struct AtomicOutputs {
+    pub paths: Vec<String>,
+    // temporary files with the outputs they become
+    renames: Vec<(PathBuf, PathBuf)>,
+}
+
+// the temporary file of `path`, named alike so compressed outputs still are
+fn temporary(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+
+    match fs::metadata(path) {
+        Ok(meta) if !meta.is_file() => None,
+        _ => Some(path.with_file_name(format!(".seqproc-{}-{name}", process::id()))),
+    }
+}
+
+impl AtomicOutputs {
+    pub fn new(outputs: &[String]) -> Self {
+        let mut atomic = Self {
+            paths: Vec::new(),
+            renames: Vec::new(),
+        };
+
+        for out in outputs {
+            match temporary(Path::new(out)).filter(|_| !out.is_empty()) {
+                Some(tmp) => {
+                    atomic.paths.push(tmp.display().to_string());
+                    atomic.renames.push((tmp, PathBuf::from(out)));
+                }
+                None => atomic.paths.push(out.clone()),
+            }
+        }
+
+        atomic
+    }
+
+    // the outputs renamed into place, replacing any of the same name
+    pub fn commit(mut self) -> io::Result<()> {
+        while let Some((tmp, out)) = self.renames.pop() {
+            // an output the pipeline never wrote is left as it was
+            if tmp.exists() {
+                fs::rename(&tmp, &out).inspect_err(|_| {
+                    let _ = fs::remove_file(&tmp);
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for AtomicOutputs {
+    fn drop(&mut self) {
+        for (tmp, _) in &self.renames {
+            let _ = fs::remove_file(tmp);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    io::{self, BufWriter, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::Mutex,
+};
+
+use super::{qc_failures, read_name, Record};
+
+const UNMAPPED: u16 = 0x4;
+const PAIRED: u16 = 0x1;
+const MATE_UNMAPPED: u16 = 0x8;
+const FIRST: u16 = 0x40;
+const LAST: u16 = 0x80;
+const QC_FAIL: u16 = 0x200;
+
+// writes records as unaligned CRAM through `samtools`, carrying the barcode and UMI
+// of each read in its CB and UB tags
+pub struct CramWriter {
+    inner: Mutex<BufWriter<ChildStdin>>,
+    samtools: Child,
+}
+
+impl CramWriter {
+    // without a reference the CRAM is written in reference free mode
+    pub fn create<P: AsRef<Path>>(path: P, reference: Option<&Path>) -> io::Result<Self> {
+        let mut command = Command::new("samtools");
+
+        command
+            .args(["view", "--no-PG", "-C", "-o"])
+            .arg(path.as_ref());
+
+        if let Some(reference) = reference {
+            command.arg("--reference").arg(reference);
+        } else {
+            command.args(["--output-fmt-option", "no_ref=1"]);
+        }
+
+        let mut samtools = command
+            .arg("-")
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("could not run samtools: {e}")))?;
+
+        let mut stdin = BufWriter::new(samtools.stdin.take().unwrap());
+
+        stdin.write_all(b"@HD\tVN:1.6\tSO:unsorted\n")?;
+        stdin.write_all(b"@PG\tID:seqproc\tPN:seqproc\n")?;
+
+        Ok(Self {
+            inner: Mutex::new(stdin),
+            samtools,
+        })
+    }
+
+    pub fn push(&self, record: Record) -> io::Result<()> {
+        self.inner.lock().unwrap().write_all(&sam_records(&record))
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        let Self {
+            inner,
+            mut samtools,
+        } = self;
+
+        // closing stdin lets samtools finish the file
+        drop(inner.into_inner().unwrap().into_inner()?);
+
+        let status = samtools.wait()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "samtools failed writing CRAM: {status}"
+            )))
+        }
+    }
+}
+
+// one unmapped SAM line per output read of the record
+pub fn sam_records(record: &Record) -> Vec<u8> {
+    let name = read_name(&record.name);
+    let qc_fail = !qc_failures(&record.name).is_empty();
+    let n = record.seq.len();
+
+    let mut out = Vec::new();
+
+    for (i, seq) in record.seq.iter().enumerate() {
+        let mut flag = UNMAPPED;
+
+        if n > 1 {
+            flag |= PAIRED | MATE_UNMAPPED;
+            // reads between the first and last have both set
+            if i < n - 1 {
+                flag |= FIRST;
+            }
+            if i > 0 {
+                flag |= LAST;
+            }
+        }
+        if qc_fail {
+            flag |= QC_FAIL;
+        }
+
+        let qual = record.qual.get(i).map_or(&[][..], Vec::as_slice);
+
+        out.extend_from_slice(name);
+        out.extend_from_slice(format!("\t{flag}\t*\t0\t0\t*\t*\t0\t0\t").as_bytes());
+        out.extend_from_slice(or_missing(seq));
+        out.push(b'\t');
+        out.extend_from_slice(if qual.len() == seq.len() {
+            or_missing(qual)
+        } else {
+            b"*"
+        });
+
+        if !record.barcode.is_empty() {
+            out.extend_from_slice(b"\tCB:Z:");
+            out.extend_from_slice(&record.barcode);
+        }
+        if !record.umi.is_empty() {
+            out.extend_from_slice(b"\tUB:Z:");
+            out.extend_from_slice(&record.umi);
+        }
+
+        out.push(b'\n');
+    }
+
+    out
+}
+
+fn or_missing(s: &[u8]) -> &[u8] {
+    if s.is_empty() {
+        b"*"
+    } else {
+        s
+    }
+}
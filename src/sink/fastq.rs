@@ -0,0 +1,242 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    mem,
+    path::Path,
+    sync::Mutex,
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+// bytes of records compressed together, each chunk is one gzip member
+pub const GZIP_CHUNK: usize = 1 << 22;
+
+// fastq files written in step as reads arrive, the mates of a record going
+// to each file at once. when any file ends in `.gz` the files are written
+// in chunks, the worker filling a chunk compresses it while other workers
+// go on writing, and chunks are put in the files in the order they were
+// filled
+pub struct FastqWriter {
+    outs: Mutex<Option<Vec<BufWriter<File>>>>,
+    // whether each file is compressed
+    gzipped: Vec<bool>,
+    // the chunk of each file being filled and how many were filled before them
+    chunk: Option<Mutex<(usize, Vec<Vec<u8>>)>>,
+    compressed: Mutex<Compressed>,
+}
+
+// compressed chunks waiting for the chunks filled before them
+#[derive(Default)]
+struct Compressed {
+    next: usize,
+    waiting: BTreeMap<usize, Vec<Vec<u8>>>,
+}
+
+impl FastqWriter {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::create_all(&[path])
+    }
+
+    // files written in step, see `write_records`
+    pub fn create_all<P: AsRef<Path>>(paths: &[P]) -> io::Result<Self> {
+        let outs = paths
+            .iter()
+            .map(|path| File::create(path).map(BufWriter::new))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let gzipped = paths
+            .iter()
+            .map(|path| path.as_ref().extension().is_some_and(|ext| ext == "gz"))
+            .collect::<Vec<_>>();
+
+        let chunk = gzipped
+            .contains(&true)
+            .then(|| Mutex::new((0, vec![Vec::new(); paths.len()])));
+
+        Ok(Self {
+            outs: Mutex::new(Some(outs)),
+            gzipped,
+            chunk,
+            compressed: Mutex::default(),
+        })
+    }
+
+    pub fn write(&self, name: &[u8], seq: &[u8], qual: &[u8]) -> io::Result<()> {
+        self.write_records(&[(name, seq, qual)])
+    }
+
+    // one `(name, seq, qual)` record for each file
+    pub fn write_records(&self, records: &[(&[u8], &[u8], &[u8])]) -> io::Result<()> {
+        let chunk = match &self.chunk {
+            Some(chunk) => chunk,
+            None => {
+                if let Some(outs) = self.outs.lock().unwrap().as_mut() {
+                    for ((name, seq, qual), out) in records.iter().zip(outs) {
+                        out.write_all(&fastq_record(name, seq, qual))?;
+                    }
+                }
+
+                return Ok(());
+            }
+        };
+
+        let (index, full) = {
+            let mut chunk = chunk.lock().unwrap();
+            for ((name, seq, qual), bytes) in records.iter().zip(&mut chunk.1) {
+                bytes.extend_from_slice(&fastq_record(name, seq, qual));
+            }
+
+            // the chunks of every file are full once the first one is
+            if chunk.1[0].len() < GZIP_CHUNK {
+                return Ok(());
+            }
+
+            let empty = vec![Vec::new(); chunk.1.len()];
+            chunk.0 += 1;
+            (chunk.0 - 1, mem::replace(&mut chunk.1, empty))
+        };
+
+        self.put(index, self.compress(full)?)
+    }
+
+    // the chunks of the compressed files as gzip members
+    fn compress(&self, chunks: Vec<Vec<u8>>) -> io::Result<Vec<Vec<u8>>> {
+        chunks
+            .into_iter()
+            .zip(&self.gzipped)
+            .map(|(chunk, gzipped)| if *gzipped { gzip(&chunk) } else { Ok(chunk) })
+            .collect()
+    }
+
+    // write every compressed chunk whose turn it is
+    fn put(&self, index: usize, chunks: Vec<Vec<u8>>) -> io::Result<()> {
+        let mut compressed = self.compressed.lock().unwrap();
+        compressed.waiting.insert(index, chunks);
+
+        let mut outs = self.outs.lock().unwrap();
+
+        let Compressed { next, waiting } = &mut *compressed;
+
+        while let Some(chunks) = waiting.remove(next) {
+            if let Some(outs) = outs.as_mut() {
+                for (bytes, out) in chunks.iter().zip(outs.iter_mut()) {
+                    out.write_all(bytes)?;
+                }
+            }
+            *next += 1;
+        }
+
+        Ok(())
+    }
+
+    // write the last chunk and flush the files, any write after it is lost
+    pub fn finish(&self) -> io::Result<()> {
+        // a file without any reads is still a valid gzip file
+        if let Some(chunk) = &self.chunk {
+            let (index, rest) = mem::take(&mut *chunk.lock().unwrap());
+            let filled = rest.iter().any(|bytes| !bytes.is_empty());
+
+            if (filled || index == 0) && self.outs.lock().unwrap().is_some() {
+                self.put(index, self.compress(rest)?)?;
+            }
+        }
+
+        match self.outs.lock().unwrap().take() {
+            Some(outs) => outs.into_iter().try_for_each(|mut out| out.flush()),
+            None => Ok(()),
+        }
+    }
+}
+
+// one gzip member holding `data`, compressed on the calling worker
+fn gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut gzip = GzEncoder::new(Vec::with_capacity(data.len() / 3), Compression::default());
+    gzip.write_all(data)?;
+    gzip.finish()
+}
+
+impl Drop for FastqWriter {
+    // errors are reported by `finish`, a writer dropped without it, as
+    // when a run is unwinding, ends its file as best it can
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+pub fn fastq_record(name: &[u8], seq: &[u8], qual: &[u8]) -> Vec<u8> {
+    [b"@", name, b"\n", seq, b"\n+\n", qual, b"\n"].concat()
+}
+
+// a `<segment>=<file>` pair given to `--segment-out`
+pub fn segment_output(spec: &str) -> Result<(String, String), String> {
+    match spec.split_once('=') {
+        Some((segment, file)) if !segment.is_empty() && !file.is_empty() => {
+            Ok((segment.to_string(), file.to_string()))
+        }
+        _ => Err(format!("expected <segment>=<file>, found `{spec}`")),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TechPiece {
+    Segment(String),
+    Constant(Vec<u8>),
+}
+
+// a read built from segments and constant sequences, e.g. a 10x style R1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TechRead {
+    pub pieces: Vec<TechPiece>,
+    pub out: String,
+}
+
+// parse `<cb1><cb2>TTTT<umi>`, constant sequences are given as bases
+pub fn tech_read_pieces(spec: &str) -> Result<Vec<TechPiece>, String> {
+    let mut pieces = Vec::new();
+    let mut rest = spec.trim();
+
+    while !rest.is_empty() {
+        if let Some(segment) = rest.strip_prefix('<') {
+            let (name, next) = segment
+                .split_once('>')
+                .ok_or_else(|| format!("unclosed segment in `{spec}`"))?;
+
+            if name.is_empty() {
+                return Err(format!("empty segment name in `{spec}`"));
+            }
+
+            pieces.push(TechPiece::Segment(name.to_string()));
+            rest = next;
+        } else {
+            let len = rest.find('<').unwrap_or(rest.len());
+            let (constant, next) = rest.split_at(len);
+
+            if let Some(c) = constant.chars().find(|c| !"ACGTN".contains(*c)) {
+                return Err(format!(
+                    "unexpected `{c}` in `{spec}`, expected a base or <segment>"
+                ));
+            }
+
+            pieces.push(TechPiece::Constant(constant.as_bytes().to_vec()));
+            rest = next;
+        }
+    }
+
+    if pieces.is_empty() {
+        Err("a technical read needs at least one piece".to_string())
+    } else {
+        Ok(pieces)
+    }
+}
+
+// a `<file>=<pieces>` technical read given to `--tech-read`
+pub fn tech_read(spec: &str) -> Result<TechRead, String> {
+    match spec.split_once('=') {
+        Some((out, pieces)) if !out.is_empty() => Ok(TechRead {
+            pieces: tech_read_pieces(pieces)?,
+            out: out.to_string(),
+        }),
+        _ => Err(format!("expected <file>=<pieces>, found `{spec}`")),
+    }
+}
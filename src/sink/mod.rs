@@ -0,0 +1,75 @@
+pub mod atomic;
+pub mod cram;
+pub mod fastq;
+pub mod provenance;
+pub mod route;
+pub mod shard;
+pub mod tags;
+pub mod tsv;
+pub mod well;
+#[cfg(feature = "parquet")]
+pub mod table;
+
+use std::{collections::HashMap, fs, path::Path};
+
+// a processed read handed to a callback instead of being written out
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    pub name: Vec<u8>,
+    // segments labeled in the geometry, keyed by their label
+    pub segments: HashMap<String, Vec<u8>>,
+    // every barcode and every UMI segment joined in the order of the geometry
+    pub barcode: Vec<u8>,
+    pub umi: Vec<u8>,
+    // one sequence and quality string per output read
+    pub seq: Vec<Vec<u8>>,
+    pub qual: Vec<Vec<u8>>,
+}
+
+// the label given in the geometry for a segment label such as `seq1.brc`.
+// pieces without a label are given one starting with `_`
+pub fn segment_name(label: &str) -> Option<&str> {
+    let (_, name) = label.split_once('.')?;
+
+    if name.starts_with('_') || name == "*" {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+// the read name without any tags added to it
+pub fn read_name(name: &[u8]) -> &[u8] {
+    name.split(|c| c.is_ascii_whitespace())
+        .next()
+        .unwrap_or_default()
+}
+
+// the checks a read was flagged as failing, from its `QC:fail:<check>` tags
+pub fn qc_failures(name: &[u8]) -> Vec<String> {
+    name.split(|c| c.is_ascii_whitespace())
+        .filter_map(|tag| tag.strip_prefix(b"QC:fail:"))
+        .map(|check| String::from_utf8_lossy(check).into_owned())
+        .collect()
+}
+
+// the directories of the outputs, made where missing with `--mkdir`.
+// without it a missing one fails the run before any read is processed
+pub fn output_dirs(paths: &[&str], mkdir: bool) -> Result<(), String> {
+    for path in paths {
+        let dir = match Path::new(path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => dir,
+            _ => continue,
+        };
+
+        if !mkdir {
+            return Err(format!(
+                "the directory of {path} does not exist, create it or pass --mkdir"
+            ));
+        }
+
+        fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {e}", dir.display()))?;
+    }
+
+    Ok(())
+}
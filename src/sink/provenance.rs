@@ -0,0 +1,73 @@
+use std::{
+    fs, io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    checksum::{digest, Algorithm},
+    explain::quote,
+    parser::SPEC_VERSION,
+};
+
+// how an output was made, written by `--provenance` next to each fastq
+// output as `<out>.provenance.json`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    pub version: String,
+    pub spec_version: usize,
+    // sha256 of the geometry text
+    pub geometry: String,
+    // the command line the run was given
+    pub args: Vec<String>,
+    // seconds since the unix epoch
+    pub started: u64,
+    pub finished: Option<u64>,
+}
+
+impl Provenance {
+    pub fn new(geometry: &str, args: Vec<String>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            spec_version: SPEC_VERSION,
+            geometry: digest(Algorithm::Sha256, geometry.as_bytes()),
+            args,
+            started: now(),
+            finished: None,
+        }
+    }
+
+    pub fn json(&self) -> String {
+        let args = self.args.iter().map(|a| quote(a)).collect::<Vec<_>>();
+
+        format!(
+            "{{\"seqproc_version\":{},\"spec_version\":{},\"geometry_sha256\":{},\"args\":[{}],\"started\":{},\"finished\":{}}}\n",
+            quote(&self.version),
+            self.spec_version,
+            quote(&self.geometry),
+            args.join(","),
+            self.started,
+            self.finished.map_or("null".to_string(), |t| t.to_string())
+        )
+    }
+
+    // the sidecar of each output, once the run is done
+    pub fn finish(&mut self, outputs: &[String]) -> io::Result<()> {
+        self.finished = Some(now());
+
+        for out in outputs.iter().filter(|out| !out.is_empty()) {
+            fs::write(sidecar(out), self.json())?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn sidecar(out: &str) -> String {
+    format!("{out}.provenance.json")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_secs())
+}
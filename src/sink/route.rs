@@ -0,0 +1,116 @@
+/*
+   Where each kind of segment goes, `--route umi=header --route discard=keep`.
+   Segments are kept in the read unless routed elsewhere, except discards
+   which are dropped. A segment routed to the header is added to the read
+   name as `name:bases`, one routed to a sidecar is written to that fastq
+   file, the segments of a kind joined in the order of the geometry. Either
+   way it is then removed from the read, before any transformation.
+
+   A kind routed to `hash` is replaced in the read name by a short hash of
+   its segments joined, `barcode_hash:<hex>`, a key to group reads by that
+   does not give the bases away. The hash is the start of their SHA-256,
+   the same across runs and machines.
+*/
+
+use std::fmt::{self, Write as _};
+
+use sha2::{Digest, Sha256};
+
+use crate::parser::Type;
+
+// bytes of the SHA-256 kept, few enough barcodes collide for grouping
+pub const HASH_BYTES: usize = 6;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Route {
+    Keep,
+    Header,
+    Sidecar(String),
+    Hash,
+    Drop,
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Route::Keep => write!(f, "keep"),
+            Route::Header => write!(f, "header"),
+            Route::Sidecar(path) => write!(f, "sidecar:{path}"),
+            Route::Hash => write!(f, "hash"),
+            Route::Drop => write!(f, "drop"),
+        }
+    }
+}
+
+// the name a kind of segment is given on the command line
+pub fn kind_name(type_: &Type) -> &'static str {
+    match type_ {
+        Type::Barcode => "barcode",
+        Type::Umi => "umi",
+        Type::Discard => "discard",
+        Type::ReadSeq => "read",
+        Type::FixedSeq => "anchor",
+        Type::Header => "header",
+    }
+}
+
+// a `<kind>=<route>` pair given to `--route`
+pub fn route(spec: &str) -> Result<(Type, Route), String> {
+    let (kind, route) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("expected <kind>=<route>, found `{spec}`"))?;
+
+    let type_ = match kind {
+        "barcode" => Type::Barcode,
+        "umi" => Type::Umi,
+        "discard" => Type::Discard,
+        "read" => Type::ReadSeq,
+        "anchor" => Type::FixedSeq,
+        _ => {
+            return Err(format!(
+                "Unknown segment kind `{kind}`, expected barcode, umi, discard, read or anchor"
+            ))
+        }
+    };
+
+    let route = match route {
+        "keep" => Route::Keep,
+        "header" => Route::Header,
+        "hash" => Route::Hash,
+        "drop" => Route::Drop,
+        _ => match route.strip_prefix("sidecar:") {
+            Some(path) if !path.is_empty() => Route::Sidecar(path.to_string()),
+            _ => {
+                return Err(format!(
+                    "Unknown route `{route}`, expected keep, header, hash, drop or sidecar:<file>"
+                ))
+            }
+        },
+    };
+
+    Ok((type_, route))
+}
+
+// the route given last for a kind of segment, if any
+pub fn routed<'a>(routes: &'a [(Type, Route)], type_: &Type) -> Option<&'a Route> {
+    routes
+        .iter()
+        .rev()
+        .find(|(t, _)| t == type_)
+        .map(|(_, route)| route)
+}
+
+// the hash of segments routed to `hash`, in hex
+pub fn segment_hash(segments: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for segment in segments {
+        hasher.update(segment);
+    }
+
+    hasher.finalize()[..HASH_BYTES]
+        .iter()
+        .fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        })
+}
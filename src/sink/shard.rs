@@ -0,0 +1,167 @@
+use std::{io, path::Path, sync::Mutex};
+
+use super::fastq::FastqWriter;
+
+// when a shard is full, `50M` reads or `2G` bytes given as `2GB`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShardSize {
+    Reads(usize),
+    Bytes(usize),
+}
+
+pub fn shard_size(spec: &str) -> Result<ShardSize, String> {
+    let (size, bytes) = match spec.strip_suffix(['B', 'b']) {
+        Some(size) => (size, true),
+        None => (spec, false),
+    };
+
+    let (n, unit) = match size.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&size[..i], c.to_ascii_uppercase()),
+        _ => (size, ' '),
+    };
+
+    let scale = match unit {
+        ' ' => 1,
+        'K' => 1_000,
+        'M' => 1_000_000,
+        'G' => 1_000_000_000,
+        _ => return Err(format!("Unknown unit `{unit}`, expected K, M or G")),
+    };
+
+    let n = match n.parse::<usize>() {
+        Ok(n) if n > 0 => n * scale,
+        _ => {
+            return Err(format!(
+                "expected a size such as 50M or 2GB, found `{spec}`"
+            ))
+        }
+    };
+
+    Ok(if bytes {
+        ShardSize::Bytes(n)
+    } else {
+        ShardSize::Reads(n)
+    })
+}
+
+// `r1.fastq.gz` as its shard `r1.0003.fastq.gz`
+pub fn shard_path(path: &str, shard: usize) -> String {
+    let file_start = Path::new(path)
+        .parent()
+        .map_or(0, |dir| dir.as_os_str().len());
+
+    match path[file_start..].find('.') {
+        Some(dot) if file_start + dot > 0 => {
+            let (stem, ext) = path.split_at(file_start + dot);
+            format!("{stem}.{shard:04}{ext}")
+        }
+        _ => format!("{path}.{shard:04}"),
+    }
+}
+
+// how the outputs are split, by size or read by read across a fixed number of files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sharding {
+    Size(ShardSize),
+    RoundRobin(usize),
+}
+
+// output streams written in step and split into shards together, so
+// the records in a shard of one stream are the mates of those in the
+// same shard of the others
+pub struct ShardedWriter {
+    paths: Vec<String>,
+    sharding: Sharding,
+    current: Mutex<Shards>,
+}
+
+struct Shards {
+    // the shard being written when split by size
+    index: usize,
+    reads: usize,
+    bytes: usize,
+    // the writers of each open shard, one for each stream
+    writers: Vec<Vec<FastqWriter>>,
+}
+
+impl ShardedWriter {
+    pub fn create(paths: Vec<String>, sharding: Sharding) -> io::Result<Self> {
+        let open = match sharding {
+            Sharding::Size(_) => 1,
+            Sharding::RoundRobin(n) => n,
+        };
+
+        let writers = (1..=open)
+            .map(|index| Self::open(&paths, index))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            paths,
+            sharding,
+            current: Mutex::new(Shards {
+                index: 1,
+                reads: 0,
+                bytes: 0,
+                writers,
+            }),
+        })
+    }
+
+    fn open(paths: &[String], index: usize) -> io::Result<Vec<FastqWriter>> {
+        paths
+            .iter()
+            .map(|path| FastqWriter::create(shard_path(path, index)))
+            .collect()
+    }
+
+    // one `(name, seq, qual)` record for each stream
+    pub fn write(&self, records: &[(&[u8], &[u8], &[u8])]) -> io::Result<()> {
+        let mut shards = self.current.lock().unwrap();
+
+        let shard = match self.sharding {
+            Sharding::Size(size) => {
+                let full = match size {
+                    ShardSize::Reads(n) => shards.reads >= n,
+                    ShardSize::Bytes(n) => shards.bytes >= n,
+                };
+
+                if full {
+                    // the old shard is finished before the next one is started
+                    for out in shards.writers.drain(..).flatten() {
+                        out.finish()?;
+                    }
+                    shards.index += 1;
+                    shards.reads = 0;
+                    shards.bytes = 0;
+                    shards.writers = vec![Self::open(&self.paths, shards.index)?];
+                }
+
+                0
+            }
+            Sharding::RoundRobin(n) => shards.reads % n,
+        };
+
+        for ((name, seq, qual), out) in records.iter().zip(&shards.writers[shard]) {
+            out.write(name, seq, qual)?;
+        }
+
+        shards.reads += 1;
+        // the bytes of the first stream, which all streams roll over on
+        shards.bytes += records.first().map_or(0, |(name, seq, qual)| {
+            name.len() + seq.len() + qual.len() + 6
+        });
+
+        Ok(())
+    }
+
+    // finish the open shards
+    pub fn finish(&self) -> io::Result<()> {
+        let shards = self.current.lock().unwrap();
+
+        shards
+            .writers
+            .iter()
+            .flatten()
+            .try_for_each(FastqWriter::finish)
+    }
+}
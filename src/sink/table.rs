@@ -0,0 +1,141 @@
+use std::{
+    fs::File,
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use arrow::{
+    array::{ArrayRef, BooleanArray, StringArray, UInt32Array},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use parquet::{arrow::ArrowWriter, errors::Result};
+
+use super::{qc_failures, read_name, Record};
+
+const BATCH_SIZE: usize = 65536;
+
+// writes one row per record to a parquet table: the read name, each segment and
+// its length, and whether the read was flagged as failing a check
+pub struct SegmentWriter {
+    inner: Mutex<Table>,
+}
+
+struct Table {
+    // held until the first batch, the columns are taken from its segments
+    file: Option<File>,
+    writer: Option<(ArrowWriter<File>, SchemaRef)>,
+    columns: Vec<String>,
+    rows: Vec<Record>,
+}
+
+impl SegmentWriter {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            inner: Mutex::new(Table {
+                file: Some(File::create(path)?),
+                writer: None,
+                columns: Vec::new(),
+                rows: Vec::new(),
+            }),
+        })
+    }
+
+    pub fn push(&self, record: Record) -> Result<()> {
+        let mut table = self.inner.lock().unwrap();
+
+        table.rows.push(record);
+
+        if table.rows.len() >= BATCH_SIZE {
+            table.flush()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        let mut table = self.inner.into_inner().unwrap();
+
+        table.flush()?;
+
+        if let Some((writer, _)) = table.writer {
+            writer.close()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Table {
+    fn schema(columns: &[String]) -> SchemaRef {
+        let mut fields = vec![Field::new("name", DataType::Utf8, false)];
+
+        for column in columns {
+            fields.push(Field::new(column, DataType::Utf8, false));
+            fields.push(Field::new(format!("{column}_len"), DataType::UInt32, false));
+        }
+
+        fields.push(Field::new("pass", DataType::Boolean, false));
+        fields.push(Field::new("qc_fail", DataType::Utf8, true));
+
+        Arc::new(Schema::new(fields))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        if self.writer.is_none() {
+            let mut columns = self.rows[0].segments.keys().cloned().collect::<Vec<_>>();
+            columns.sort();
+
+            let schema = Self::schema(&columns);
+            let file = self.file.take().unwrap();
+
+            self.writer = Some((ArrowWriter::try_new(file, schema.clone(), None)?, schema));
+            self.columns = columns;
+        }
+
+        let rows = std::mem::take(&mut self.rows);
+        let text = |s: &[u8]| String::from_utf8_lossy(s).into_owned();
+
+        let mut arrays: Vec<ArrayRef> = vec![Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| text(read_name(&r.name))),
+        ))];
+
+        for column in &self.columns {
+            let segments = rows
+                .iter()
+                .map(|r| r.segments.get(column).map_or(&[][..], Vec::as_slice))
+                .collect::<Vec<_>>();
+
+            arrays.push(Arc::new(StringArray::from_iter_values(
+                segments.iter().map(|s| text(s)),
+            )));
+            arrays.push(Arc::new(UInt32Array::from_iter_values(
+                segments.iter().map(|s| s.len() as u32),
+            )));
+        }
+
+        let failures = rows
+            .iter()
+            .map(|r| qc_failures(&r.name))
+            .collect::<Vec<_>>();
+
+        arrays.push(Arc::new(BooleanArray::from(
+            failures.iter().map(Vec::is_empty).collect::<Vec<_>>(),
+        )));
+        arrays.push(Arc::new(StringArray::from(
+            failures
+                .iter()
+                .map(|f| (!f.is_empty()).then(|| f.join(",")))
+                .collect::<Vec<_>>(),
+        )));
+
+        let (writer, schema) = self.writer.as_mut().unwrap();
+
+        writer.write(&RecordBatch::try_new(schema.clone(), arrays)?)
+    }
+}
@@ -0,0 +1,30 @@
+/*
+   `--raw-tags` adds the barcode and UMI of each read to its header both as
+   read and as corrected, as the SAM tags of Cell Ranger: `CR:Z:` and
+   `UR:Z:` for the bases read, `CB:Z:` and `UB:Z:` for the bases after any
+   `map` or `map_with_mismatch` of their segments, such as a `--whitelist`.
+   The segments of a kind are joined in the order of the geometry, and a
+   segment without a map is the same in both. `samtools import -T` carries
+   the tags into the reads it writes. With `--raw-tags-tsv` they are written
+   to a tsv with the read name instead, to audit the corrections.
+*/
+
+// the barcode tags, then the UMI tags, each raw then corrected
+pub const BARCODE_TAGS: [&str; 2] = ["CR", "CB"];
+pub const UMI_TAGS: [&str; 2] = ["UR", "UB"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawTags {
+    // written here instead of the read header
+    pub tsv: Option<String>,
+}
+
+// the attribute of a segment keeping its bases before they are corrected
+pub fn raw_attr(label: &str) -> String {
+    format!("{label}.raw")
+}
+
+// a tag of the read header, `CB:Z:ACGT`
+pub fn sam_tag(tag: &str, bases: &[u8]) -> String {
+    format!("{tag}:Z:{}", String::from_utf8_lossy(bases))
+}
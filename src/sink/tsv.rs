@@ -0,0 +1,42 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+// named segments given to `--capture`, such as matched anchors, added to the
+// read header as `<name>:<bases>` or written to a tsv
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capture {
+    pub names: Vec<String>,
+    // written here instead of the read header
+    pub tsv: Option<String>,
+}
+
+// `<read name>\t<segment>..` a line, after a header line naming the segments
+pub struct SegmentTsv {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl SegmentTsv {
+    pub fn create<P: AsRef<Path>>(path: P, names: &[String]) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "read\t{}", names.join("\t"))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn write(&self, name: &[u8], segments: &[&[u8]]) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+
+        file.write_all(name)?;
+        for segment in segments {
+            file.write_all(b"\t")?;
+            file.write_all(segment)?;
+        }
+        file.write_all(b"\n")
+    }
+}
@@ -0,0 +1,90 @@
+/*
+   `--well-map wells.tsv` names the plate well of each read of a plate
+   based protocol from its barcodes. Each line of the map is the barcodes
+   of a well, in the order of the geometry, then the well: `ACGT\tTTAG\tA01`.
+   The well is added to the read header as `well:A01`, or written to the
+   tsv of `--well-tsv` with the read name. Reads whose barcodes are not in
+   the map are given the well `none`.
+*/
+
+use std::{collections::HashMap, fs, io};
+
+// the well of reads whose barcodes are not in the map
+pub const NO_WELL: &str = "none";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WellMap {
+    wells: HashMap<Vec<Vec<u8>>, String>,
+    // barcodes on each line
+    pub barcodes: usize,
+    // written here instead of the read header
+    pub tsv: Option<String>,
+}
+
+impl WellMap {
+    pub fn new(map: &str, tsv: Option<String>) -> Result<Self, String> {
+        let mut wells = HashMap::new();
+        let mut barcodes = None;
+
+        for (n, line) in map.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let fields = line.split('\t').map(str::trim).collect::<Vec<_>>();
+            let (well, key) = fields.split_last().unwrap();
+
+            if key.is_empty() || well.is_empty() {
+                return Err(format!(
+                    "line {} of the well map is not `<barcode>..\\t<well>`",
+                    n + 1
+                ));
+            }
+
+            if *barcodes.get_or_insert(key.len()) != key.len() {
+                return Err(format!(
+                    "line {} of the well map has {} barcodes, the lines before it {}",
+                    n + 1,
+                    key.len(),
+                    barcodes.unwrap()
+                ));
+            }
+
+            let key = key
+                .iter()
+                .map(|b| b.to_ascii_uppercase().into_bytes())
+                .collect();
+            if wells.insert(key, well.to_string()).is_some() {
+                return Err(format!(
+                    "line {} of the well map repeats the barcodes of another",
+                    n + 1
+                ));
+            }
+        }
+
+        match barcodes {
+            Some(barcodes) => Ok(Self {
+                wells,
+                barcodes,
+                tsv,
+            }),
+            None => Err("the well map has no wells".to_string()),
+        }
+    }
+
+    pub fn open(path: &str, tsv: Option<String>) -> io::Result<Self> {
+        let map = fs::read_to_string(path)?;
+
+        Self::new(&map, tsv).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // the well of a read's barcodes, in the order of the geometry
+    pub fn well(&self, barcodes: &[&[u8]]) -> &str {
+        let key = barcodes
+            .iter()
+            .map(|b| b.to_ascii_uppercase())
+            .collect::<Vec<_>>();
+
+        self.wells.get(&key).map_or(NO_WELL, String::as_str)
+    }
+}
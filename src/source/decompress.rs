@@ -0,0 +1,129 @@
+/*
+   Compressed inputs are decompressed by `gzip` rather than by antisequence,
+   which stops at the end of the first member of a file made of several,
+   such as lanes joined by `cat` or the chunked outputs of seqproc itself.
+   Each record is checked as it is decompressed and handed on through a
+   named pipe, read by antisequence in place of the file. A file that is
+   truncated or corrupt fails the run with the record and the byte of the
+   decompressed file where it broke off, and the pipeline sees the records
+   before it end cleanly instead of failing to decode.
+*/
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    thread::{self, JoinHandle},
+};
+
+use super::{fastq::FastqReader, make_pipe};
+use crate::sink::fastq::fastq_record;
+
+// whether `path` is decompressed before the pipeline reads it
+pub fn is_compressed(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext == "gz")
+}
+
+// the records of `file` written into `pipe`, checked on the way
+fn decompress(file: &str, pipe: &Path) -> io::Result<()> {
+    let mut reader = FastqReader::open(file)?;
+
+    // opening the pipe waits until the pipeline opens it for reading
+    let mut out = BufWriter::new(OpenOptions::new().write(true).open(pipe)?);
+
+    while let Some(record) = reader.next_record()? {
+        match out.write_all(&fastq_record(&record.name, &record.seq, &record.qual)) {
+            // the pipeline stopped reading before the end of the file
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            res => res?,
+        }
+    }
+
+    match out.flush() {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+        res => res?,
+    }
+
+    reader.finish()
+}
+
+// the input files, compressed ones replaced by the pipes they are
+// decompressed into. the pipes are removed once dropped
+pub struct Decompressed {
+    pub paths: Vec<String>,
+    pipes: Vec<PathBuf>,
+    threads: Vec<JoinHandle<io::Result<()>>>,
+}
+
+impl Decompressed {
+    // `crlf` tells which inputs have lines ending in `\r\n`, as the pipes
+    // the inputs may already be read through cannot be looked into
+    pub fn open(files: Vec<String>, crlf: &[bool]) -> io::Result<Self> {
+        let mut inputs = Self {
+            paths: Vec::new(),
+            pipes: Vec::new(),
+            threads: Vec::new(),
+        };
+
+        for (i, file) in files.into_iter().enumerate() {
+            if !is_compressed(&file) && !crlf[i] {
+                inputs.paths.push(file);
+                continue;
+            }
+
+            // the pipes hold decompressed records
+            let name = Path::new(&file).file_name().unwrap().to_string_lossy();
+            let name = name.strip_suffix(".gz").unwrap_or(&name);
+            let pipe = env::temp_dir().join(format!("seqproc-{}-{}-{name}", process::id(), i + 1));
+
+            make_pipe(&pipe)?;
+            inputs.paths.push(pipe.display().to_string());
+            inputs.pipes.push(pipe.clone());
+
+            inputs.threads.push(thread::spawn(move || {
+                decompress(&file, &pipe)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{file}: {e}")))
+            }));
+        }
+
+        Ok(inputs)
+    }
+
+    // the threads decompressing the inputs joined once the pipeline is done.
+    // a truncated file only closes its pipe early, which the pipeline cannot
+    // tell from its end, so the run fails here before its outputs are kept
+    pub fn finish(mut self) -> io::Result<()> {
+        join(self.threads.drain(..))
+    }
+}
+
+// the first error of `threads`, all of which are joined
+pub(super) fn join<I>(threads: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = JoinHandle<io::Result<()>>>,
+{
+    let mut failed = Ok(());
+
+    for handle in threads {
+        let res = handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("an input was not read to its end")));
+
+        if failed.is_ok() {
+            failed = res;
+        }
+    }
+
+    failed
+}
+
+impl Drop for Decompressed {
+    // a failed run may never have opened the pipes, the threads are left
+    fn drop(&mut self) {
+        for pipe in &self.pipes {
+            let _ = fs::remove_file(pipe);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    process::{Child, Command, Stdio},
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FastqRecord {
+    pub name: Vec<u8>,
+    pub seq: Vec<u8>,
+    pub qual: Vec<u8>,
+}
+
+// a fastq file read a record at a time, files ending in `.gz` are decompressed
+// by `gzip`, which reads every member of files joined by `cat`
+pub struct FastqReader {
+    input: Box<dyn BufRead + Send>,
+    gzip: Option<Child>,
+    records: usize,
+    // bytes of the records read so far, of the decompressed file
+    offset: u64,
+    // lines to read again before the input, see `next_salvaged`
+    pending: VecDeque<Vec<u8>>,
+    // the lines of the last malformed record
+    malformed: Vec<Vec<u8>>,
+}
+
+impl FastqReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        if path.extension().is_some_and(|ext| ext == "gz") {
+            let mut gzip = Command::new("gzip")
+                .arg("-dc")
+                .arg(path)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|e| io::Error::new(e.kind(), format!("could not run gzip: {e}")))?;
+
+            let mut reader = Self::new(BufReader::new(gzip.stdout.take().unwrap()));
+            reader.gzip = Some(gzip);
+
+            Ok(reader)
+        } else {
+            Ok(Self::new(BufReader::new(File::open(path)?)))
+        }
+    }
+
+    pub fn new<R: BufRead + Send + 'static>(input: R) -> Self {
+        Self {
+            input: Box::new(input),
+            gzip: None,
+            records: 0,
+            offset: 0,
+            pending: VecDeque::new(),
+            malformed: Vec::new(),
+        }
+    }
+
+    fn read_line(&mut self, line: &mut Vec<u8>) -> io::Result<usize> {
+        match self.pending.pop_front() {
+            Some(pending) => {
+                *line = pending;
+                Ok(line.len())
+            }
+            None => self.input.read_until(b'\n', line),
+        }
+    }
+
+    // the next record, `None` at the end of the file
+    pub fn next_record(&mut self) -> io::Result<Option<FastqRecord>> {
+        let mut raw: Vec<Vec<u8>> = Vec::new();
+        self.malformed.clear();
+
+        for i in 0..4 {
+            let mut line = Vec::new();
+            if self.read_line(&mut line)? == 0 {
+                return match (i, self.gzip_failed()) {
+                    (_, Some(e)) => Err(e),
+                    (0, None) => Ok(None),
+                    _ => Err(self.invalid(raw, "is truncated")),
+                };
+            }
+
+            raw.push(line);
+        }
+
+        let [name, seq, plus, qual] = [0, 1, 2, 3].map(|i| {
+            let mut line = raw[i].as_slice();
+            while let [rest @ .., b'\n' | b'\r'] = line {
+                line = rest;
+            }
+            line
+        });
+
+        let problem = match name.strip_prefix(b"@") {
+            None => "does not start with `@`",
+            Some(_) if !plus.starts_with(b"+") => "has no `+` line",
+            Some(_) if seq.len() != qual.len() => "has a sequence and quality of different lengths",
+            Some(name) => {
+                let record = FastqRecord {
+                    name: name.to_vec(),
+                    seq: seq.to_vec(),
+                    qual: qual.to_vec(),
+                };
+
+                self.records += 1;
+                self.offset += raw.iter().map(|l| l.len() as u64).sum::<u64>();
+
+                return Ok(Some(record));
+            }
+        };
+
+        Err(self.invalid(raw, problem))
+    }
+
+    // the next well formed record, each run of malformed lines before it
+    // skipped and passed to `skipped` with the error of its first record.
+    // the lines of a malformed record after its first are read again, so a
+    // record missing a line costs only itself
+    pub fn next_salvaged<F>(&mut self, mut skipped: F) -> io::Result<Option<FastqRecord>>
+    where
+        F: FnMut(io::Error),
+    {
+        let mut skipping = false;
+
+        loop {
+            match self.next_record() {
+                Ok(record) => return Ok(record),
+                // nothing was read, the file could not be
+                Err(e) if self.malformed.is_empty() => return Err(e),
+                Err(e) => {
+                    if !skipping {
+                        skipped(e);
+                        skipping = true;
+                    }
+
+                    let mut lines = std::mem::take(&mut self.malformed);
+                    self.offset += lines.remove(0).len() as u64;
+                    for line in lines.into_iter().rev() {
+                        self.pending.push_front(line);
+                    }
+                }
+            }
+        }
+    }
+
+    // records read so far
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    // fails if `gzip` could not decompress the whole file
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.gzip_failed() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    // the decompressed file ended, an error if `gzip` did not get to its end
+    fn gzip_failed(&mut self) -> Option<io::Error> {
+        let status = match self.gzip.as_mut()?.wait() {
+            Ok(status) => status,
+            Err(e) => return Some(e),
+        };
+
+        (!status.success()).then(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "gzip could not decompress past record {}, byte {} of the decompressed file, it may be truncated or corrupt",
+                    self.records, self.offset
+                ),
+            )
+        })
+    }
+
+    fn invalid(&mut self, lines: Vec<Vec<u8>>, problem: &str) -> io::Error {
+        self.malformed = lines;
+
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "record {}, at byte {}, {problem}",
+                self.records + 1,
+                self.offset
+            ),
+        )
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use super::fastq::FastqReader;
+
+// records read from the start of each input by `--sample-lengths`
+pub const SAMPLE_SIZE: usize = 10_000;
+
+// the lengths of the first records of an input file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LengthSample {
+    pub file: String,
+    // records of each length
+    pub lengths: BTreeMap<usize, usize>,
+}
+
+impl LengthSample {
+    // the lengths of the first `records` records of `path`, a shorter file is read whole
+    pub fn read(path: &str, records: usize) -> Result<Self, String> {
+        let in_file = |e| format!("{path}: {e}");
+
+        let mut reader = FastqReader::open(path).map_err(in_file)?;
+        let mut sample = Self {
+            file: path.to_string(),
+            lengths: BTreeMap::new(),
+        };
+
+        while reader.records() < records {
+            match reader.next_record().map_err(in_file)? {
+                Some(record) => *sample.lengths.entry(record.seq.len()).or_default() += 1,
+                None => break,
+            }
+        }
+
+        Ok(sample)
+    }
+
+    pub fn records(&self) -> usize {
+        self.lengths.values().sum()
+    }
+
+    // `r1.fq: 10000 reads, 28 bases` or `r1.fq: 10000 reads, 26 to 28 bases (26: 120, 28: 9880)`
+    pub fn report(&self) -> String {
+        let records = self.records();
+
+        match (self.shortest(), self.longest()) {
+            (Some(min), Some(max)) if min == max => {
+                format!("{}: {records} reads, {min} bases", self.file)
+            }
+            (Some(min), Some(max)) => format!(
+                "{}: {records} reads, {min} to {max} bases ({})",
+                self.file,
+                self.lengths
+                    .iter()
+                    .map(|(len, n)| format!("{len}: {n}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => format!("{}: no reads", self.file),
+        }
+    }
+
+    // how the sample disagrees with a read geometry needing at least `min`
+    // bases and using at most `max`, if it does
+    pub fn check(&self, min: usize, max: Option<usize>) -> Option<String> {
+        let records = self.records();
+        let short = self.lengths.range(..min).map(|(_, n)| n).sum::<usize>();
+
+        if short > 0 {
+            return Some(format!(
+                "{short} of {records} reads of {} are shorter than the {min} bases the geometry needs",
+                self.file
+            ));
+        }
+
+        match (max, self.shortest()) {
+            (Some(max), Some(shortest)) if shortest > max => Some(format!(
+                "reads of {} are at least {shortest} bases but the geometry uses only {max}, the rest are dropped",
+                self.file
+            )),
+            _ => None,
+        }
+    }
+
+    fn shortest(&self) -> Option<usize> {
+        self.lengths.keys().next().copied()
+    }
+
+    fn longest(&self) -> Option<usize> {
+        self.lengths.keys().next_back().copied()
+    }
+}
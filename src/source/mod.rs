@@ -0,0 +1,130 @@
+/*
+   Reading input files outside of antisequence, for the passes
+   made over them before the pipeline is built.
+
+   `--prescan` reads both files through once, checking that every
+   record has its four lines, that compressed files decompress to
+   the end and that both files hold as many records. Problems
+   with the input then show before the run instead of partway
+   through it.
+
+   `--sample-lengths` reads the first records of each file for the
+   lengths of its reads, warning about reads too short for the geometry.
+   The qualities of the same reads tell whether they are phred+33 or
+   phred+64.
+
+   `--geom-by` splits a pooled run into a part for each geometry, see
+   `pooled`.
+
+   With the `async-io` feature, `--async-io` reads the inputs ahead of
+   the pipeline, see `prefetch`. Inputs given as urls are streamed, see
+   `remote`. Compressed inputs, and inputs with Windows line endings, are
+   rewritten for the pipeline, see `decompress`.
+*/
+
+pub mod decompress;
+pub mod fastq;
+pub mod lengths;
+pub mod pooled;
+#[cfg(feature = "async-io")]
+pub mod prefetch;
+pub mod remote;
+pub mod salvage;
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::Path,
+    process::Command,
+    thread,
+};
+
+use fastq::FastqReader;
+use lengths::SAMPLE_SIZE;
+
+use crate::quality::{detect_offset, QualOffset};
+
+// the number of records in `path`, failing on the first malformed one
+pub fn scan(path: &str) -> Result<usize, String> {
+    let in_file = |e| format!("{path}: {e}");
+
+    let mut reader = FastqReader::open(path).map_err(in_file)?;
+    while reader.next_record().map_err(in_file)?.is_some() {}
+
+    let records = reader.records();
+    reader.finish().map_err(in_file)?;
+
+    Ok(records)
+}
+
+// both files are scanned at the same time, mates have to be as many
+// as the first reads unless `unequal` counts are expected
+pub fn prescan(file1: &str, file2: Option<&str>, unequal: bool) -> Result<usize, String> {
+    let (records1, records2) = thread::scope(|s| {
+        let records2 = file2.map(|file2| s.spawn(move || scan(file2)));
+
+        (scan(file1), records2.map(|h| h.join().unwrap()))
+    });
+
+    let records1 = records1?;
+
+    match (file2, records2.transpose()?) {
+        (Some(file2), Some(records2)) if !unequal && records1 != records2 => Err(format!(
+            "{file1} has {records1} records but {file2} has {records2}"
+        )),
+        _ => Ok(records1),
+    }
+}
+
+// whether `path` is a regular file, which can be read before the pipeline
+// reads it again. pipes and `/dev/stdin` can only be read once
+pub fn regular(path: &str) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file())
+}
+
+// whether the lines of `path` end in `\r\n`, told from its first line.
+// those of a file which is not regular are taken to end in `\n`
+pub fn crlf(path: &str) -> io::Result<bool> {
+    if !regular(path) {
+        return Ok(false);
+    }
+
+    let mut line = Vec::new();
+    BufReader::new(File::open(path)?).read_until(b'\n', &mut line)?;
+
+    Ok(line.ends_with(b"\r\n"))
+}
+
+// the quality offset of `path`, told from its first records
+pub fn quality_offset(path: &str) -> Result<QualOffset, String> {
+    let in_file = |e| format!("{path}: {e}");
+
+    let mut reader = FastqReader::open(path).map_err(in_file)?;
+    let mut quals = Vec::new();
+
+    while reader.records() < SAMPLE_SIZE {
+        match reader.next_record().map_err(in_file)? {
+            Some(record) => quals.push(record.qual),
+            None => break,
+        }
+    }
+
+    detect_offset(quals.iter().map(Vec::as_slice)).map_err(|e| format!("{path}: {e}"))
+}
+
+// a named pipe, read by antisequence in place of an input file
+pub(crate) fn make_pipe(path: &Path) -> io::Result<()> {
+    let status = Command::new("mkfifo")
+        .arg(path)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("could not run mkfifo: {e}")))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "could not create {}",
+            path.display()
+        )))
+    }
+}
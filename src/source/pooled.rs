@@ -0,0 +1,255 @@
+/*
+   Pooled runs of libraries told apart by their read names, given as
+   `--geom-by 'SAMPLEA:geomA.fgdl;SAMPLEB:geomB.fgdl'`. Each pattern is a
+   regex matched against the header line, `@` included, and a record is
+   processed by the geometry of the first pattern it matches, or by the
+   geometry of `--geom` if it matches none. Mates go where the first read
+   of the pair goes.
+
+   A pattern written `seg:<label>=<pattern>` is matched against a segment
+   of the first read instead, so that the geometry of the second read can
+   depend on a value extracted from the first: the first read is cut by
+   the geometry of `--geom`, whose read 1 the geometries of the patterns
+   are expected to share, and its segment `<label>` is matched wherever it
+   was found. With a sample index after the barcode,
+   `--geom-by 'seg:idx=^ACGTAC$:spacer.fgdl'` gives the reads of that
+   sample a geometry with the extra spacer. A first read that `--geom` does
+   not cut has no segments and matches no such pattern. The geometries
+   themselves have no conditional branches.
+
+   The inputs are split into temporary files for each geometry before the
+   run, then each part is processed in turn and its output appended to
+   the outputs given, in the order of the patterns.
+*/
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process,
+};
+
+use regex::bytes::Regex;
+
+use super::fastq::{FastqReader, FastqRecord};
+use crate::{
+    compile::CompiledData,
+    extract::{process_read, supported, Extraction},
+    sink::fastq::fastq_record,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyOn {
+    Header,
+    // the segment of the first read with this label
+    Segment(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct GeomBy {
+    // patterns with the path of their geometry and what they are matched
+    // against, in the order given
+    pub keys: Vec<(Regex, String, KeyOn)>,
+}
+
+// the `<pattern>:<geometry file>` pairs given to `--geom-by`, separated by `;`
+pub fn geom_by(spec: &str) -> Result<GeomBy, String> {
+    let keys = spec
+        .split(';')
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            // patterns may hold a `:` themselves, paths rarely do
+            let (pattern, path) = key
+                .rsplit_once(':')
+                .filter(|(pattern, path)| !pattern.is_empty() && !path.is_empty())
+                .ok_or_else(|| format!("expected <pattern>:<geometry file>, found `{key}`"))?;
+
+            let (pattern, on) = match pattern.strip_prefix("seg:") {
+                Some(segment) => {
+                    let (label, pattern) = segment
+                        .split_once('=')
+                        .filter(|(label, pattern)| !label.is_empty() && !pattern.is_empty())
+                        .ok_or_else(|| {
+                            format!("expected seg:<label>=<pattern>:<geometry file>, found `{key}`")
+                        })?;
+
+                    (pattern, KeyOn::Segment(label.to_string()))
+                }
+                None => (pattern, KeyOn::Header),
+            };
+
+            let regex =
+                Regex::new(pattern).map_err(|e| format!("Invalid pattern `{pattern}`: {e}"))?;
+
+            Ok((regex, path.to_string(), on))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    if keys.is_empty() {
+        return Err("expected at least one <pattern>:<geometry file>".to_string());
+    }
+
+    Ok(GeomBy { keys })
+}
+
+impl GeomBy {
+    // the labels segment patterns are matched against must be cut from the
+    // first read by the geometry of `--geom`
+    pub fn check(&self, compiled: &CompiledData) -> Result<(), String> {
+        let labels = self.keys.iter().filter_map(|(_, _, on)| match on {
+            KeyOn::Segment(label) => Some(label),
+            KeyOn::Header => None,
+        });
+
+        for label in labels {
+            supported(compiled)
+                .map_err(|e| format!("seg:{label}= needs read 1 cut in memory, but {e}"))?;
+
+            let cut = compiled.geometry.first().is_some_and(|read| {
+                read.iter()
+                    .any(|gm| gm.expr.0.label.as_deref() == Some(label.as_str()))
+            });
+            if !cut {
+                return Err(format!("no segment `{label}` is cut from read 1 by --geom"));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn by_segment(&self) -> bool {
+        self.keys
+            .iter()
+            .any(|(_, _, on)| matches!(on, KeyOn::Segment(_)))
+    }
+
+    // the part a read goes to by its name alone, one past the last pattern
+    // for `--geom`
+    pub fn select(&self, name: &[u8]) -> usize {
+        self.select_record(name, &Extraction::default())
+    }
+
+    // the part a read goes to by its name and the segments cut from the
+    // first read
+    pub fn select_record(&self, name: &[u8], segments: &Extraction) -> usize {
+        let header = [b"@", name].concat();
+
+        self.keys
+            .iter()
+            .position(|(regex, _, on)| match on {
+                KeyOn::Header => regex.is_match(&header),
+                KeyOn::Segment(label) => segments
+                    .get(label)
+                    .is_some_and(|segment| regex.is_match(&segment.seq)),
+            })
+            .unwrap_or(self.keys.len())
+    }
+}
+
+fn write_record<W: Write>(out: &mut W, record: &FastqRecord) -> io::Result<()> {
+    out.write_all(&fastq_record(&record.name, &record.seq, &record.qual))
+}
+
+// the inputs split by geometry, removed once dropped
+pub struct PooledFiles {
+    // the read files of each part, the last one for `--geom`
+    pub parts: Vec<(PathBuf, Option<PathBuf>)>,
+    // records in each part
+    pub records: Vec<usize>,
+}
+
+impl PooledFiles {
+    // `compiled` is the geometry of `--geom`, which cuts the segments of the
+    // first reads
+    pub fn create(
+        file1: &str,
+        file2: Option<&str>,
+        by: &GeomBy,
+        compiled: &CompiledData,
+    ) -> io::Result<Self> {
+        let dir = env::temp_dir();
+        let id = process::id();
+
+        let parts = (0..=by.keys.len())
+            .map(|i| {
+                let part = |read| dir.join(format!("seqproc-{id}-part{i}_{read}.fastq"));
+                (part(1), file2.map(|_| part(2)))
+            })
+            .collect::<Vec<_>>();
+
+        // made first so the parts are removed if splitting fails
+        let mut pooled = Self {
+            records: vec![0; parts.len()],
+            parts,
+        };
+
+        let create = |path: &PathBuf| File::create(path).map(BufWriter::new);
+        let mut out1 = pooled
+            .parts
+            .iter()
+            .map(|(part, _)| create(part))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut out2 = pooled
+            .parts
+            .iter()
+            .filter_map(|(_, part)| part.as_ref().map(create))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut r1 = FastqReader::open(file1)?;
+        let mut r2 = file2.map(FastqReader::open).transpose()?;
+
+        let unequal = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{file1} and {} hold different numbers of records",
+                    file2.unwrap()
+                ),
+            )
+        };
+
+        let by_segment = by.by_segment();
+        while let Some(rec1) = r1.next_record()? {
+            // a read that is not cut, or kept raw, has no segments
+            let segments = by_segment
+                .then(|| process_read(compiled, 1, &rec1.seq).ok())
+                .flatten()
+                .unwrap_or_default();
+            let i = by.select_record(&rec1.name, &segments);
+
+            write_record(&mut out1[i], &rec1)?;
+            if let Some(r2) = &mut r2 {
+                let rec2 = r2.next_record()?.ok_or_else(unequal)?;
+                write_record(&mut out2[i], &rec2)?;
+            }
+
+            pooled.records[i] += 1;
+        }
+
+        r1.finish()?;
+        if let Some(mut r2) = r2 {
+            if r2.next_record()?.is_some() {
+                return Err(unequal());
+            }
+            r2.finish()?;
+        }
+
+        for out in out1.iter_mut().chain(&mut out2) {
+            out.flush()?;
+        }
+
+        Ok(pooled)
+    }
+}
+
+impl Drop for PooledFiles {
+    fn drop(&mut self) {
+        for (part1, part2) in &self.parts {
+            let _ = fs::remove_file(part1);
+            if let Some(part2) = part2 {
+                let _ = fs::remove_file(part2);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+/*
+   Reading the input files ahead of the pipeline with async io, for inputs
+   on network filesystems or mounted object storage where a blocking read
+   stalls the worker waiting on it. Each file is read by a tokio task into
+   a bounded queue of chunks and handed on through a named pipe, which
+   antisequence reads in place of the file. Reading the next chunks
+   overlaps with processing the reads of the current ones, the processing
+   itself stays on the worker threads.
+*/
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+    runtime::{Builder, Runtime},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use super::make_pipe;
+
+// bytes read at a time
+pub const CHUNK: usize = 1 << 20;
+
+// chunks read ahead of the pipeline for each file
+pub const READ_AHEAD: usize = 16;
+
+// named pipes standing in for the input files, removed once dropped
+pub struct Prefetched {
+    pub paths: Vec<String>,
+    runtime: Option<Runtime>,
+    tasks: Vec<JoinHandle<io::Result<()>>>,
+}
+
+impl Prefetched {
+    pub fn start(files: &[String]) -> io::Result<Self> {
+        let runtime = Builder::new_multi_thread().worker_threads(2).build()?;
+        let id = process::id();
+
+        let mut prefetched = Self {
+            paths: Vec::new(),
+            runtime: None,
+            tasks: Vec::new(),
+        };
+
+        for (i, file) in files.iter().enumerate() {
+            // the file name is kept, compressed input is still recognized by it
+            let name = Path::new(file)
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+            let pipe = env::temp_dir().join(format!("seqproc-{id}-{}-{name}", i + 1));
+
+            // the tasks already started wait on their pipes, the runtime is not waited for
+            if let Err(e) = make_pipe(&pipe) {
+                runtime.shutdown_background();
+                return Err(e);
+            }
+
+            prefetched.paths.push(pipe.display().to_string());
+            prefetched
+                .tasks
+                .push(runtime.spawn(prefetch(file.clone(), pipe)));
+        }
+
+        prefetched.runtime = Some(runtime);
+
+        Ok(prefetched)
+    }
+
+    // the tasks joined once the pipeline is done, see `Decompressed::finish`
+    pub fn finish(mut self) -> io::Result<()> {
+        let runtime = self.runtime.take().unwrap();
+        let mut failed = Ok(());
+
+        for task in self.tasks.drain(..) {
+            let res = runtime.block_on(task).map_err(io::Error::other);
+
+            if failed.is_ok() {
+                failed = res.and_then(|res| res);
+            }
+        }
+
+        failed
+    }
+}
+
+// `file` copied into `pipe`, with up to `READ_AHEAD` chunks read before they are needed
+async fn prefetch(file: String, pipe: PathBuf) -> io::Result<()> {
+    let (tx, mut rx) = mpsc::channel::<Vec<u8>>(READ_AHEAD);
+
+    let reader = tokio::spawn(async move {
+        let mut input = File::open(&file)
+            .await
+            .map_err(|e| io::Error::new(e.kind(), format!("{file}: {e}")))?;
+
+        loop {
+            let mut chunk = Vec::with_capacity(CHUNK);
+
+            if input.read_buf(&mut chunk).await? == 0 {
+                return Ok::<_, io::Error>(());
+            }
+
+            // the pipe was closed, nothing reads the rest
+            if tx.send(chunk).await.is_err() {
+                return Ok(());
+            }
+        }
+    });
+
+    // opening the pipe waits until the pipeline opens it for reading
+    let mut out = OpenOptions::new().write(true).open(&pipe).await?;
+
+    while let Some(chunk) = rx.recv().await {
+        match out.write_all(&chunk).await {
+            // the pipeline stopped reading before the end of the file
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            res => res?,
+        }
+    }
+    out.flush().await?;
+
+    reader.await.map_err(io::Error::other)?
+}
+
+impl Drop for Prefetched {
+    // a failed run may never have opened the pipes, the tasks are left
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
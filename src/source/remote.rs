@@ -0,0 +1,252 @@
+/*
+   Input files given as `s3://`, `gs://`, `http://` or `https://` urls.
+   A remote file is read a range at a time by the command line tool of
+   its store, `aws`, `gsutil` or `curl`, and streamed through a named
+   pipe which antisequence reads in place of the file, so nothing is
+   staged on local disk. A range failing to arrive is fetched again, a
+   dropped connection partway through the file costs one range.
+*/
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{self, Command},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use super::{decompress::join, make_pipe};
+
+// bytes fetched at a time
+pub const RANGE: u64 = 64 << 20;
+
+// attempts at each request before the run fails
+pub const ATTEMPTS: u32 = 5;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Remote {
+    Http(String),
+    S3 { bucket: String, key: String },
+    Gcs(String),
+}
+
+// the remote file `path` names, if it is a url
+pub fn remote(path: &str) -> Option<Remote> {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        Some(Remote::Http(path.to_string()))
+    } else if let Some(rest) = path.strip_prefix("s3://") {
+        let (bucket, key) = rest.split_once('/')?;
+
+        Some(Remote::S3 {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    } else if path.starts_with("gs://") {
+        Some(Remote::Gcs(path.to_string()))
+    } else {
+        None
+    }
+}
+
+impl Remote {
+    // the last part of the url, compressed files are still recognized by it
+    pub fn file_name(&self) -> &str {
+        let path = match self {
+            Remote::Http(url) | Remote::Gcs(url) => url.split(['?', '#']).next().unwrap(),
+            Remote::S3 { key, .. } => key,
+        };
+
+        path.rsplit('/').next().unwrap()
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        let (mut cmd, field) = match self {
+            Remote::Http(url) => (tool("curl", ["-sSfLI", url]), "content-length:"),
+            Remote::S3 { bucket, key } => {
+                let args = ["s3api", "head-object", "--bucket", bucket, "--key", key];
+                (tool("aws", args), "\"ContentLength\":")
+            }
+            Remote::Gcs(url) => (tool("gsutil", ["stat", url]), "content-length:"),
+        };
+
+        let out = String::from_utf8_lossy(&output(&mut cmd)?).to_string();
+
+        // redirects list a length for every response, the last is the file's
+        out.lines()
+            .rev()
+            .find_map(|line| {
+                let line = line.trim().to_ascii_lowercase();
+                let value = line.strip_prefix(&field.to_ascii_lowercase())?;
+                value.trim().trim_end_matches(',').parse::<u64>().ok()
+            })
+            .ok_or_else(|| io::Error::other(format!("no size given for {}", self.file_name())))
+    }
+
+    // the bytes from `start` to `end`, both included
+    fn range(&self, start: u64, end: u64) -> io::Result<Vec<u8>> {
+        let range = format!("{start}-{end}");
+
+        let mut cmd = match self {
+            Remote::Http(url) => tool("curl", ["-sSfL", "-r", &range, url]),
+            Remote::S3 { bucket, key } => {
+                let range = format!("bytes={range}");
+                let args = ["s3api", "get-object", "--bucket", bucket, "--key", key];
+                let mut cmd = tool("aws", args);
+                cmd.args(["--range", &range, "/dev/stdout"]);
+                cmd
+            }
+            Remote::Gcs(url) => tool("gsutil", ["cat", "-r", &range, url]),
+        };
+
+        let bytes = output(&mut cmd)?;
+
+        // `aws` prints the object's metadata after its bytes
+        let len = (end - start + 1) as usize;
+        if bytes.len() < len {
+            return Err(io::Error::other(format!(
+                "{} bytes of {} arrived for {range}",
+                bytes.len(),
+                len
+            )));
+        }
+
+        Ok(bytes[..len].to_vec())
+    }
+
+    // the whole file written to `to`
+    pub fn download(&self, to: &Path) -> io::Result<()> {
+        let to = to.to_string_lossy();
+
+        let mut cmd = match self {
+            Remote::Http(url) => tool("curl", ["-sSfL", "-o", &to, url]),
+            Remote::S3 { bucket, key } => {
+                let url = format!("s3://{bucket}/{key}");
+                tool("aws", ["s3", "cp", "--quiet", &url, &to])
+            }
+            Remote::Gcs(url) => tool("gsutil", ["-q", "cp", url, &to]),
+        };
+
+        output(&mut cmd).map(|_| ())
+    }
+}
+
+fn tool<const N: usize>(name: &str, args: [&str; N]) -> Command {
+    let mut cmd = Command::new(name);
+    cmd.args(args);
+    cmd
+}
+
+// the standard output of `cmd`, which is run again if it fails
+fn output(cmd: &mut Command) -> io::Result<Vec<u8>> {
+    let name = cmd.get_program().to_string_lossy().to_string();
+
+    let mut attempt = 1;
+
+    loop {
+        let err = match cmd.output() {
+            Ok(out) if out.status.success() => return Ok(out.stdout),
+            Ok(out) => io::Error::other(format!(
+                "{name} failed: {}",
+                String::from_utf8_lossy(&out.stderr).trim()
+            )),
+            Err(e) => io::Error::new(e.kind(), format!("could not run {name}: {e}")),
+        };
+
+        if attempt == ATTEMPTS {
+            return Err(err);
+        }
+
+        // waiting longer after each failure
+        thread::sleep(Duration::from_secs(1 << attempt));
+        attempt += 1;
+    }
+}
+
+// the `size` bytes of `remote` written into `pipe` a range at a time
+fn stream(remote: Remote, size: u64, pipe: PathBuf) -> io::Result<()> {
+    // opening the pipe waits until the pipeline opens it for reading. it is
+    // opened before anything is fetched so that a failed range closes it,
+    // rather than leaving the pipeline waiting on it
+    let mut out = OpenOptions::new().write(true).open(&pipe)?;
+
+    let mut start = 0;
+    while start < size {
+        let end = (start + RANGE).min(size) - 1;
+
+        match out.write_all(&remote.range(start, end)?) {
+            // the pipeline stopped reading before the end of the file
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            res => res?,
+        }
+
+        start = end + 1;
+    }
+
+    Ok(())
+}
+
+// the input files, remote ones replaced by the pipes they are streamed
+// through. the pipes are removed once dropped
+pub struct RemoteInputs {
+    pub paths: Vec<String>,
+    pipes: Vec<PathBuf>,
+    streams: Vec<JoinHandle<io::Result<()>>>,
+}
+
+impl RemoteInputs {
+    pub fn open(files: Vec<String>) -> io::Result<Self> {
+        let mut inputs = Self {
+            paths: Vec::new(),
+            pipes: Vec::new(),
+            streams: Vec::new(),
+        };
+
+        for (i, file) in files.into_iter().enumerate() {
+            let remote = match remote(&file) {
+                Some(remote) => remote,
+                None => {
+                    inputs.paths.push(file);
+                    continue;
+                }
+            };
+
+            // a wrong url or missing credentials fail the run here, before
+            // the pipeline waits on a pipe nothing will be written to
+            let size = remote
+                .size()
+                .map_err(|e| io::Error::new(e.kind(), format!("{file}: {e}")))?;
+
+            let pipe = env::temp_dir().join(format!(
+                "seqproc-{}-{}-{}",
+                process::id(),
+                i + 1,
+                remote.file_name()
+            ));
+
+            make_pipe(&pipe)?;
+            inputs.paths.push(pipe.display().to_string());
+            inputs.pipes.push(pipe.clone());
+            inputs
+                .streams
+                .push(thread::spawn(move || stream(remote, size, pipe)));
+        }
+
+        Ok(inputs)
+    }
+
+    // the streams joined once the pipeline is done, see `Decompressed::finish`
+    pub fn finish(mut self) -> io::Result<()> {
+        join(self.streams.drain(..))
+    }
+}
+
+impl Drop for RemoteInputs {
+    // a failed run may never have opened the pipes, the streams are left
+    fn drop(&mut self) {
+        for pipe in &self.pipes {
+            let _ = fs::remove_file(pipe);
+        }
+    }
+}
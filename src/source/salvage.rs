@@ -0,0 +1,252 @@
+/*
+   Salvaging partly corrupted inputs with `--skip-bad-records N`. Malformed
+   records are skipped, each one logged, and the run fails only once more
+   than N have been. The inputs are read and checked before the pipeline
+   and handed on through named pipes, as compressed inputs are, see
+   `decompress`.
+
+   The mate of a skipped record is dropped with it so that pairs stay in
+   step: the other file is read on until its record is the mate of the one
+   after the skipped ones, mates told apart by name as in `repair`. Files
+   where both mates of a pair were skipped, or which were out of step to
+   begin with, fail the run.
+*/
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    thread::{self, JoinHandle},
+};
+
+use super::{
+    decompress::join,
+    fastq::{FastqReader, FastqRecord},
+    make_pipe,
+};
+use crate::{repair::mate_name, sink::fastq::fastq_record};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SalvageStats {
+    // records, or pairs, handed on
+    pub records: usize,
+    pub skipped: usize,
+    // well formed mates dropped with a skipped record
+    pub dropped: usize,
+}
+
+fn write_record<W: Write>(out: &mut W, record: &FastqRecord) -> io::Result<()> {
+    out.write_all(&fastq_record(&record.name, &record.seq, &record.qual))
+}
+
+// the next record of input `i`, and whether any was skipped before it
+fn next<F>(
+    reader: &mut FastqReader,
+    i: usize,
+    max_bad: usize,
+    stats: &mut SalvageStats,
+    log: &mut F,
+) -> io::Result<(Option<FastqRecord>, bool)>
+where
+    F: FnMut(usize, io::Error),
+{
+    let mut skipped = false;
+    let record = reader.next_salvaged(|e| {
+        skipped = true;
+        stats.skipped += 1;
+        log(i, e);
+    })?;
+
+    if stats.skipped > max_bad {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("more than {max_bad} malformed records"),
+        ));
+    }
+
+    Ok((record, skipped))
+}
+
+fn mates(rec1: &Option<FastqRecord>, rec2: &Option<FastqRecord>) -> bool {
+    match (rec1, rec2) {
+        (Some(rec1), Some(rec2)) => mate_name(&rec1.name) == mate_name(&rec2.name),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// the well formed records of `r1`, and `r2` in step with it, written to
+// the outputs. `log` is given each skipped record's input and error
+pub fn salvage<W: Write, F: FnMut(usize, io::Error)>(
+    r1: &mut FastqReader,
+    mut r2: Option<&mut FastqReader>,
+    out1: &mut W,
+    mut out2: Option<&mut W>,
+    max_bad: usize,
+    mut log: F,
+) -> io::Result<SalvageStats> {
+    let mut stats = SalvageStats::default();
+
+    loop {
+        let (mut rec1, mut skipped1) = next(r1, 0, max_bad, &mut stats, &mut log)?;
+
+        let r2 = match &mut r2 {
+            Some(r2) => r2,
+            None => match rec1 {
+                Some(rec1) => {
+                    write_record(out1, &rec1)?;
+                    stats.records += 1;
+                    continue;
+                }
+                None => break,
+            },
+        };
+
+        let (mut rec2, mut skipped2) = next(r2, 1, max_bad, &mut stats, &mut log)?;
+
+        // the file which skipped nothing reads on to the next mate
+        while !mates(&rec1, &rec2) {
+            match (skipped1, skipped2) {
+                (true, false) if rec2.is_some() => {
+                    let skipped;
+                    (rec2, skipped) = next(r2, 1, max_bad, &mut stats, &mut log)?;
+                    skipped2 |= skipped;
+                }
+                (false, true) if rec1.is_some() => {
+                    let skipped;
+                    (rec1, skipped) = next(r1, 0, max_bad, &mut stats, &mut log)?;
+                    skipped1 |= skipped;
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "the mates of pair {} are out of step, see --repair",
+                            stats.records + 1
+                        ),
+                    ))
+                }
+            }
+
+            stats.dropped += 1;
+        }
+
+        match (rec1, rec2) {
+            (Some(rec1), Some(rec2)) => {
+                write_record(out1, &rec1)?;
+                write_record(out2.as_mut().unwrap(), &rec2)?;
+                stats.records += 1;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(stats)
+}
+
+// the input files, replaced by the pipes their well formed records are
+// written into. the pipes are removed once dropped
+pub struct Salvaged {
+    pub paths: Vec<String>,
+    pipes: Vec<PathBuf>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+fn salvage_files(files: &[String], pipes: &[PathBuf], max_bad: usize) -> io::Result<SalvageStats> {
+    let mut readers = files
+        .iter()
+        .map(FastqReader::open)
+        .collect::<io::Result<Vec<_>>>()?;
+
+    // opening the pipes waits until the pipeline opens them for reading
+    let mut outs = pipes
+        .iter()
+        .map(|pipe| {
+            OpenOptions::new()
+                .write(true)
+                .open(pipe)
+                .map(BufWriter::new)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let log = |i: usize, e: io::Error| eprintln!("Skipped a malformed record of {}: {e}", files[i]);
+
+    let (r1, r2) = readers.split_at_mut(1);
+    let (out1, out2) = outs.split_at_mut(1);
+    let stats = salvage(
+        &mut r1[0],
+        r2.first_mut(),
+        &mut out1[0],
+        out2.first_mut(),
+        max_bad,
+        log,
+    )?;
+
+    for out in &mut outs {
+        out.flush()?;
+    }
+    for reader in readers {
+        reader.finish()?;
+    }
+
+    Ok(stats)
+}
+
+impl Salvaged {
+    pub fn open(files: Vec<String>, max_bad: usize) -> io::Result<Self> {
+        let mut salvaged = Self {
+            paths: Vec::new(),
+            pipes: Vec::new(),
+            thread: None,
+        };
+
+        for (i, file) in files.iter().enumerate() {
+            // the pipes hold decompressed records
+            let name = Path::new(file).file_name().unwrap().to_string_lossy();
+            let name = name.strip_suffix(".gz").unwrap_or(&name);
+            let pipe = env::temp_dir().join(format!(
+                "seqproc-{}-{}-salvaged-{name}",
+                process::id(),
+                i + 1
+            ));
+
+            make_pipe(&pipe)?;
+            salvaged.paths.push(pipe.display().to_string());
+            salvaged.pipes.push(pipe);
+        }
+
+        let pipes = salvaged.pipes.clone();
+        salvaged.thread = Some(thread::spawn(move || {
+            match salvage_files(&files, &pipes, max_bad) {
+                Ok(stats) => eprintln!(
+                    "Skipped {} malformed records, dropping {} mates with them",
+                    stats.skipped, stats.dropped
+                ),
+                // the pipeline stopped reading before the end of the files
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+                Err(e) => return Err(e),
+            }
+
+            Ok(())
+        }));
+
+        Ok(salvaged)
+    }
+
+    // the thread salvaging the inputs joined once the pipeline is done, see
+    // `Decompressed::finish`
+    pub fn finish(mut self) -> io::Result<()> {
+        join(self.thread.take())
+    }
+}
+
+impl Drop for Salvaged {
+    // a failed run may never have opened the pipes, the thread is left
+    fn drop(&mut self) {
+        for pipe in &self.pipes {
+            let _ = fs::remove_file(pipe);
+        }
+    }
+}
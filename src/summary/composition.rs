@@ -0,0 +1,146 @@
+/*
+   `--composition` counts the bases at each position of every named
+   segment of the reads written, to be plotted alongside the run. A
+   barcode or UMI position with nearly the same base in every read is
+   warned about, it usually means the geometry is off by a base and a
+   constant linker base is being read as part of the segment.
+*/
+
+use std::{
+    fs, io,
+    sync::{Mutex, RwLock},
+};
+
+use crate::{explain::quote, parser::Type};
+
+const BASES: [u8; 5] = *b"ACGTN";
+
+// the share of reads with the same base above which a position is warned about
+const CONSTANT: f64 = 0.95;
+
+// too few reads say nothing about a position
+const MIN_READS: u64 = 100;
+
+#[derive(Debug)]
+struct Segment {
+    name: String,
+    type_: Type,
+    // counts of A, C, G, T and anything else at each position
+    positions: Mutex<Vec<[u64; 5]>>,
+}
+
+#[derive(Debug)]
+pub struct Composition {
+    pub out: String,
+    segments: RwLock<Vec<Segment>>,
+}
+
+impl Composition {
+    pub fn new(out: String) -> Self {
+        Self {
+            out,
+            segments: RwLock::new(Vec::new()),
+        }
+    }
+
+    // a segment to count the bases of, its index is given to `insert`
+    pub fn register(&self, name: &str, type_: Type) -> usize {
+        let mut segments = self.segments.write().unwrap();
+
+        segments.push(Segment {
+            name: name.to_string(),
+            type_,
+            positions: Mutex::new(Vec::new()),
+        });
+
+        segments.len() - 1
+    }
+
+    pub fn insert(&self, segment: usize, seq: &[u8]) {
+        let segments = self.segments.read().unwrap();
+        let mut positions = segments[segment].positions.lock().unwrap();
+
+        if positions.len() < seq.len() {
+            positions.resize(seq.len(), [0; 5]);
+        }
+
+        for (counts, base) in positions.iter_mut().zip(seq) {
+            let i = BASES[..4]
+                .iter()
+                .position(|b| b.eq_ignore_ascii_case(base))
+                .unwrap_or(4);
+            counts[i] += 1;
+        }
+    }
+
+    // the counts of each position of each segment, by segment name
+    pub fn counts(&self) -> Vec<(String, Vec<[u64; 5]>)> {
+        self.segments
+            .read()
+            .unwrap()
+            .iter()
+            .map(|s| (s.name.clone(), s.positions.lock().unwrap().clone()))
+            .collect()
+    }
+
+    // barcode and UMI positions of nearly a single base, numbered from one
+    pub fn warnings(&self) -> Vec<String> {
+        let segments = self.segments.read().unwrap();
+        let mut warnings = Vec::new();
+
+        for segment in segments
+            .iter()
+            .filter(|s| matches!(s.type_, Type::Barcode | Type::Umi))
+        {
+            for (i, counts) in segment.positions.lock().unwrap().iter().enumerate() {
+                let reads = counts.iter().sum::<u64>();
+                let (base, most) = BASES.iter().zip(counts).max_by_key(|(_, n)| **n).unwrap();
+
+                if reads >= MIN_READS && *most as f64 / reads as f64 >= CONSTANT {
+                    warnings.push(format!(
+                        "position {} of {} is {} in {:.1}% of reads, the geometry may be off by a base",
+                        i + 1,
+                        segment.name,
+                        *base as char,
+                        *most as f64 / reads as f64 * 100.0
+                    ));
+                }
+            }
+        }
+
+        warnings
+    }
+
+    // `{"segments":[{"name":"umi","positions":[{"A":1,"C":0,"G":0,"T":0,"N":0},..]},..]}`
+    pub fn json(&self) -> String {
+        let segments = self
+            .counts()
+            .iter()
+            .map(|(name, positions)| {
+                let positions = positions
+                    .iter()
+                    .map(|counts| {
+                        let bases = BASES
+                            .iter()
+                            .zip(counts)
+                            .map(|(base, n)| format!("\"{}\":{n}", *base as char))
+                            .collect::<Vec<_>>();
+                        format!("{{{}}}", bases.join(","))
+                    })
+                    .collect::<Vec<_>>();
+
+                format!(
+                    "{{\"name\":{},\"positions\":[{}]}}",
+                    quote(name),
+                    positions.join(",")
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!("{{\"segments\":[{}]}}\n", segments.join(","))
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        fs::write(&self.out, self.json())
+    }
+}
@@ -0,0 +1,213 @@
+/*
+   `--index-hopping` counts the combinations of the i7 and i5 indices of
+   dual indexed reads, the barcodes labeled `i7` and `i5` in the geometry,
+   against the pairs of the sample sheet. Reads whose indices are both in
+   the sheet but not as one of its pairs have hopped: on patterned flow
+   cells free adapters swap the index of one library for another's. The
+   matrix of every i7 of the sheet against every i5 is written once the
+   run is done, reads with an index not in the sheet are only counted.
+*/
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{parser::Type, sink::segment_name};
+
+#[derive(Debug)]
+pub struct IndexHopping {
+    pub out: String,
+    // the distinct indices of the sheet, in the order first given
+    i7: Vec<Vec<u8>>,
+    i5: Vec<Vec<u8>>,
+    expected: HashSet<(usize, usize)>,
+    // reads of each i7, i5 combination, i7 by i7
+    counts: Vec<AtomicU64>,
+    // reads with an index not in the sheet
+    unknown: AtomicU64,
+}
+
+fn is_index(field: &str) -> bool {
+    !field.is_empty() && field.bytes().all(|b| b"ACGTNacgtn".contains(&b))
+}
+
+// the index of `seq` among `indices`, added if it is new
+fn position(indices: &mut Vec<Vec<u8>>, seq: &[u8]) -> usize {
+    match indices.iter().position(|i| i == seq) {
+        Some(i) => i,
+        None => {
+            indices.push(seq.to_vec());
+            indices.len() - 1
+        }
+    }
+}
+
+impl IndexHopping {
+    // `sheet` has a `<sample>,<i7>,<i5>` line for each sample, separated by
+    // commas or tabs. a header line is skipped
+    pub fn new(out: String, sheet: &str) -> Result<Self, String> {
+        let mut i7 = Vec::new();
+        let mut i5 = Vec::new();
+        let mut expected = HashSet::new();
+
+        for (n, line) in sheet.lines().enumerate() {
+            let fields = line.split([',', '\t']).map(str::trim).collect::<Vec<_>>();
+
+            if fields.iter().all(|f| f.is_empty()) {
+                continue;
+            }
+
+            match fields[..] {
+                [_, seq7, seq5, ..] if is_index(seq7) && is_index(seq5) => {
+                    let seq7 = seq7.to_ascii_uppercase();
+                    let seq5 = seq5.to_ascii_uppercase();
+                    expected.insert((
+                        position(&mut i7, seq7.as_bytes()),
+                        position(&mut i5, seq5.as_bytes()),
+                    ));
+                }
+                _ if n == 0 => {}
+                _ => {
+                    return Err(format!(
+                        "line {} of the sample sheet is not `<sample>,<i7>,<i5>`",
+                        n + 1
+                    ))
+                }
+            }
+        }
+
+        if expected.is_empty() {
+            return Err("the sample sheet has no samples".to_string());
+        }
+
+        Ok(Self {
+            out,
+            counts: (0..i7.len() * i5.len())
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            i7,
+            i5,
+            expected,
+            unknown: AtomicU64::new(0),
+        })
+    }
+
+    pub fn from_file(out: String, sheet: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(sheet)?;
+
+        Self::new(out, &text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn insert(&self, i7: &[u8], i5: &[u8]) {
+        let find = |indices: &[Vec<u8>], seq: &[u8]| {
+            indices.iter().position(|i| i.eq_ignore_ascii_case(seq))
+        };
+
+        match (find(&self.i7, i7), find(&self.i5, i5)) {
+            (Some(i), Some(j)) => {
+                self.counts[i * self.i5.len() + j].fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {
+                self.unknown.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn count(&self, i7: usize, i5: usize) -> u64 {
+        self.counts[i7 * self.i5.len() + i5].load(Ordering::Relaxed)
+    }
+
+    // reads of an unexpected combination, and reads of any combination of
+    // the sheet's indices
+    pub fn hopped(&self) -> (u64, u64) {
+        let mut hopped = 0;
+        let mut total = 0;
+
+        for i in 0..self.i7.len() {
+            for j in 0..self.i5.len() {
+                let n = self.count(i, j);
+                total += n;
+                if !self.expected.contains(&(i, j)) {
+                    hopped += n;
+                }
+            }
+        }
+
+        (hopped, total)
+    }
+
+    // the counts of `report` by their name in the `--report`
+    pub fn metrics(&self) -> Vec<(String, f64)> {
+        let (hopped, total) = self.hopped();
+
+        vec![
+            ("index_hopping.reads".to_string(), total as f64),
+            ("index_hopping.hopped".to_string(), hopped as f64),
+            (
+                "index_hopping.unknown".to_string(),
+                self.unknown.load(Ordering::Relaxed) as f64,
+            ),
+        ]
+    }
+
+    pub fn report(&self) -> String {
+        let (hopped, total) = self.hopped();
+        let rate = if total == 0 {
+            0.0
+        } else {
+            hopped as f64 / total as f64 * 100.0
+        };
+
+        format!(
+            "Index hopping: {hopped} of {total} reads ({rate:.3}%) have indices of the sample sheet in a pair it does not list, {} reads have an index not in it\n",
+            self.unknown.load(Ordering::Relaxed)
+        )
+    }
+
+    // a row for each i7 and a column for each i5, the expected combinations
+    // marked with `*`
+    pub fn write_tsv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"i7\\i5")?;
+        for i5 in &self.i5 {
+            out.write_all(b"\t")?;
+            out.write_all(i5)?;
+        }
+        out.write_all(b"\n")?;
+
+        for (i, i7) in self.i7.iter().enumerate() {
+            out.write_all(i7)?;
+            for j in 0..self.i5.len() {
+                let mark = if self.expected.contains(&(i, j)) {
+                    "*"
+                } else {
+                    ""
+                };
+                write!(out, "\t{}{mark}", self.count(i, j))?;
+            }
+            out.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(&self.out)?);
+        self.write_tsv(&mut out)?;
+        out.flush()
+    }
+}
+
+// the labels of the barcodes named `i7` and `i5`
+pub fn index_labels(segments: &[(Type, String)]) -> Option<(String, String)> {
+    let find = |name: &str| {
+        segments
+            .iter()
+            .find(|(type_, label)| *type_ == Type::Barcode && segment_name(label) == Some(name))
+            .map(|(_, label)| label.clone())
+    };
+
+    Some((find("i7")?, find("i5")?))
+}
@@ -0,0 +1,280 @@
+/*
+   Summaries of the reads passing through a run, written once it is done.
+   `--umi-counts` counts the distinct UMIs seen with each barcode, a
+   pre-alignment estimate of the complexity of each cell. Reads with more
+   than one barcode or UMI segment are keyed by their segments joined in
+   geometry order. Only the reads that pass every filter are counted.
+
+   `--estimate-duplication` estimates the fraction of PCR duplicates
+   without keeping the keys of `--dedup`. The distinct keys are counted
+   by a HyperLogLog sketch of fixed size, whose estimate is within about
+   one percent of the true count.
+
+   `--count-bases` tells where the bases of the input went: how many
+   were cut into each kind of segment of the reads written, how many of
+   those were written and how many were written in all. The rest are of
+   dropped reads, trimmed before the geometry or past its end.
+*/
+
+pub mod composition;
+pub mod hopping;
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fmt::Write as _,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, BufWriter, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::parser::Type;
+
+#[derive(Debug)]
+pub struct UmiCounts {
+    pub out: String,
+    barcodes: Mutex<HashMap<Vec<u8>, HashSet<Vec<u8>>>>,
+}
+
+impl UmiCounts {
+    pub fn new(out: String) -> Self {
+        Self {
+            out,
+            barcodes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn insert(&self, barcode: &[u8], umi: &[u8]) {
+        let mut barcodes = self.barcodes.lock().unwrap();
+
+        // most reads are of a barcode seen before
+        match barcodes.get_mut(barcode) {
+            Some(umis) => {
+                if !umis.contains(umi) {
+                    umis.insert(umi.to_vec());
+                }
+            }
+            None => {
+                barcodes.insert(barcode.to_vec(), HashSet::from([umi.to_vec()]));
+            }
+        }
+    }
+
+    // barcodes with their number of distinct UMIs, most UMIs first
+    pub fn counts(&self) -> Vec<(Vec<u8>, usize)> {
+        let barcodes = self.barcodes.lock().unwrap();
+
+        let mut counts = barcodes
+            .iter()
+            .map(|(barcode, umis)| (barcode.clone(), umis.len()))
+            .collect::<Vec<_>>();
+        counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+
+        counts
+    }
+
+    // `<barcode>\t<umis>` a line
+    pub fn write_tsv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (barcode, umis) in self.counts() {
+            out.write_all(&barcode)?;
+            writeln!(out, "\t{umis}")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(&self.out)?);
+        self.write_tsv(&mut out)?;
+        out.flush()
+    }
+}
+
+// bits of the hash picking a register, the sketch has 2^PRECISION of them
+const PRECISION: u32 = 14;
+
+#[derive(Debug)]
+pub struct DuplicationEstimate {
+    pub prefix_len: usize,
+    sketch: Mutex<Sketch>,
+}
+
+#[derive(Debug)]
+struct Sketch {
+    reads: usize,
+    // the most leading zeros seen after the register bits, plus one
+    registers: Vec<u8>,
+}
+
+impl DuplicationEstimate {
+    pub fn new(prefix_len: usize) -> Self {
+        Self {
+            prefix_len,
+            sketch: Mutex::new(Sketch {
+                reads: 0,
+                registers: vec![0; 1 << PRECISION],
+            }),
+        }
+    }
+
+    pub fn insert(&self, key: &[u8]) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let register = (hash >> (64 - PRECISION)) as usize;
+        let rank = ((hash << PRECISION).leading_zeros() + 1).min(64 - PRECISION + 1) as u8;
+
+        let mut sketch = self.sketch.lock().unwrap();
+        sketch.reads += 1;
+        sketch.registers[register] = sketch.registers[register].max(rank);
+    }
+
+    pub fn reads(&self) -> usize {
+        self.sketch.lock().unwrap().reads
+    }
+
+    pub fn distinct(&self) -> f64 {
+        let sketch = self.sketch.lock().unwrap();
+        let m = sketch.registers.len() as f64;
+
+        let sum = sketch
+            .registers
+            .iter()
+            .map(|&r| 2f64.powi(-(r as i32)))
+            .sum::<f64>();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+
+        // few keys leave registers empty, which are counted instead
+        let empty = sketch.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && empty > 0 {
+            m * (m / empty as f64).ln()
+        } else {
+            estimate
+        }
+    }
+
+    // the estimate by its name in the `--report`
+    pub fn metrics(&self) -> Vec<(String, f64)> {
+        vec![
+            ("duplication.reads".to_string(), self.reads() as f64),
+            ("duplication.rate".to_string(), self.rate()),
+        ]
+    }
+
+    // the fraction of reads repeating the key of an earlier read
+    pub fn rate(&self) -> f64 {
+        let reads = self.reads();
+
+        if reads == 0 {
+            return 0.0;
+        }
+
+        (1.0 - self.distinct() / reads as f64).max(0.0)
+    }
+}
+
+// the kinds of segments bases are counted for, in the order they are reported
+const SEGMENTS: [(Type, &str); 5] = [
+    (Type::Barcode, "barcode"),
+    (Type::Umi, "umi"),
+    (Type::FixedSeq, "anchor"),
+    (Type::Discard, "discard"),
+    (Type::ReadSeq, "read"),
+];
+
+#[derive(Debug, Default)]
+pub struct BaseCounts {
+    read: AtomicU64,
+    // bases cut into segments of each kind, and of those written
+    cut: [AtomicU64; SEGMENTS.len()],
+    kept: [AtomicU64; SEGMENTS.len()],
+    written: AtomicU64,
+}
+
+impl BaseCounts {
+    pub fn read(&self, bases: usize) {
+        self.read.fetch_add(bases as u64, Ordering::Relaxed);
+    }
+
+    // header pieces are not cut from the read and are not counted
+    pub fn cut(&self, type_: &Type, bases: usize) {
+        if let Some(i) = SEGMENTS.iter().position(|(t, _)| t == type_) {
+            self.cut[i].fetch_add(bases as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn kept(&self, type_: &Type, bases: usize) {
+        if let Some(i) = SEGMENTS.iter().position(|(t, _)| t == type_) {
+            self.kept[i].fetch_add(bases as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn written(&self, bases: usize) {
+        self.written.fetch_add(bases as u64, Ordering::Relaxed);
+    }
+
+    // the counts of `report` by their name in the `--report`
+    pub fn metrics(&self) -> Vec<(String, f64)> {
+        let load = |n: &AtomicU64| n.load(Ordering::Relaxed) as f64;
+        let mut metrics = vec![
+            ("bases.read".to_string(), load(&self.read)),
+            ("bases.written".to_string(), load(&self.written)),
+        ];
+
+        for (i, (_, name)) in SEGMENTS.iter().enumerate() {
+            metrics.push((format!("bases.{name}.cut"), load(&self.cut[i])));
+            metrics.push((format!("bases.{name}.written"), load(&self.kept[i])));
+        }
+
+        metrics
+    }
+
+    // a line for each kind of segment cut, and the rest of the bases read
+    pub fn report(&self) -> String {
+        let load = |n: &AtomicU64| n.load(Ordering::Relaxed);
+        let (read, written) = (load(&self.read), load(&self.written));
+        let mut out = String::new();
+
+        writeln!(
+            out,
+            "Bases read {read}, written {written} ({:.1}%)",
+            if read > 0 {
+                written as f64 / read as f64 * 100.0
+            } else {
+                0.0
+            }
+        )
+        .unwrap();
+
+        let mut trimmed = 0;
+        for (i, (_, name)) in SEGMENTS.iter().enumerate() {
+            let (cut, kept) = (load(&self.cut[i]), load(&self.kept[i]));
+            if cut == 0 && kept == 0 {
+                continue;
+            }
+
+            // padding writes more bases than were cut
+            trimmed += cut.saturating_sub(kept);
+            writeln!(
+                out,
+                "{name:>8} cut {cut}, written {kept}, trimmed {}",
+                cut.saturating_sub(kept)
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            out,
+            "{:>8} {}, in dropped reads or outside the segments",
+            "other",
+            read.saturating_sub(written).saturating_sub(trimmed)
+        )
+        .unwrap();
+
+        out
+    }
+}
@@ -0,0 +1,201 @@
+/*
+   Checks that the segments cut from a read are as the geometry describes
+   them: each labeled segment within the lengths of its piece, and a
+   labeled fixed sequence within the mismatches of its `hamming` of the
+   sequence. Segments whose functions change their length or bases, such
+   as `trunc` or `map`, are not checked, nor are discards, sequences that
+   may be missed with `miss=fallback` and pieces that may not be cut at
+   all, in optional groups or alternatives. Given the reads a segment was
+   cut from, as with `extract::process_record`, each segment is also
+   checked to be the bases of its range, and the ranges of a read to
+   follow one another without overlapping.
+
+   `--verify-sample <n>` checks the first n reads of a run as they are
+   cut, failing the run at the first read whose segments are not as the
+   geometry describes, a spot check of seqproc itself.
+*/
+
+use std::collections::HashMap;
+
+use crate::{
+    compile::{functions::CompiledFunction, utils::GeometryMeta, CompiledData},
+    extract::Extraction,
+    parser::{Miss, Size, Type},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bounds {
+    pub label: String,
+    pub min: usize,
+    // none for unbounded pieces
+    pub max: Option<usize>,
+    // a fixed sequence with the mismatches it allows
+    pub anchor: Option<(Vec<u8>, usize)>,
+}
+
+// the bounds of a labeled piece, none where its segment is not checked
+fn bounds(gm: &GeometryMeta) -> Option<Bounds> {
+    let piece = &gm.expr.0;
+    let label = piece.label.clone()?;
+    if piece.type_ == Type::Discard {
+        return None;
+    }
+
+    // functions keeping both the length and the bases of a segment
+    let mut mismatches = 0;
+    for (fn_, _) in &gm.stack {
+        match fn_ {
+            CompiledFunction::Hamming(n) => mismatches = *n,
+            CompiledFunction::FilterWithinDist(..) => {}
+            _ => return None,
+        }
+    }
+
+    let (min, max, anchor) = match &piece.size {
+        Size::FixedSeq(_, Miss::Fallback, _) => return None,
+        Size::FixedSeq((seq, _), _, _) => (
+            seq.len(),
+            Some(seq.len()),
+            Some((seq.as_bytes().to_vec(), mismatches)),
+        ),
+        Size::FixedLen((n, _)) => (*n, Some(*n), None),
+        Size::RangedLen(((a, b), _)) => (*a, Some(*b), None),
+        Size::UnboundedLen => (0, None, None),
+    };
+
+    Some(Bounds {
+        label,
+        min,
+        max,
+        anchor,
+    })
+}
+
+impl Bounds {
+    // what is wrong with the segment, if anything
+    pub fn check(&self, seq: &[u8]) -> Option<String> {
+        let Bounds {
+            label,
+            min,
+            max,
+            anchor,
+        } = self;
+
+        if seq.len() < *min || max.is_some_and(|max| seq.len() > max) {
+            let max = max.map_or(String::new(), |max| max.to_string());
+            return Some(format!(
+                "segment {label} is {} bases long, outside {min}-{max}",
+                seq.len()
+            ));
+        }
+
+        if let Some((fixed, mismatches)) = anchor {
+            let diff = fixed
+                .iter()
+                .zip(seq)
+                .filter(|(f, s)| !f.eq_ignore_ascii_case(s))
+                .count();
+            if diff > *mismatches {
+                return Some(format!(
+                    "segment {label} is {}, {diff} mismatches from {}",
+                    String::from_utf8_lossy(seq),
+                    String::from_utf8_lossy(fixed)
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Verifier {
+    pub bounds: Vec<Bounds>,
+}
+
+impl Verifier {
+    pub fn new(compiled: &CompiledData) -> Self {
+        let mut all = Vec::new();
+
+        for (i, geometry) in compiled.geometry.iter().enumerate() {
+            // pieces of optional groups and alternatives may not be cut at all
+            let groups = compiled
+                .optional
+                .get(i)
+                .into_iter()
+                .flatten()
+                .chain(compiled.alternatives.get(i).into_iter().flatten().flatten())
+                .collect::<Vec<_>>();
+
+            all.extend(
+                geometry
+                    .iter()
+                    .enumerate()
+                    .filter(|(n, _)| !groups.iter().any(|range| range.contains(n)))
+                    .filter_map(|(_, gm)| bounds(gm)),
+            );
+        }
+
+        Self { bounds: all }
+    }
+
+    // the labels of the segments checked
+    pub fn labels(&self) -> Vec<&str> {
+        self.bounds.iter().map(|b| b.label.as_str()).collect()
+    }
+
+    // what is wrong with the segments of a read, keyed by their label
+    pub fn check(&self, segments: &HashMap<String, Vec<u8>>) -> Vec<String> {
+        self.bounds
+            .iter()
+            .filter_map(|bounds| match segments.get(&bounds.label) {
+                Some(seq) => bounds.check(seq),
+                None => Some(format!("segment {} is missing", bounds.label)),
+            })
+            .collect()
+    }
+
+    // what is wrong with the segments extracted from `reads`
+    pub fn check_extraction(&self, reads: &[&[u8]], extraction: &Extraction) -> Vec<String> {
+        let mut issues = Vec::new();
+        let mut ends = vec![0; reads.len()];
+
+        for segment in &extraction.segments {
+            let name = segment.label.as_deref().unwrap_or("without a label");
+
+            let read = match reads.get(segment.read.wrapping_sub(1)) {
+                Some(read) => read,
+                None => {
+                    issues.push(format!("segment {name} is of read {}", segment.read));
+                    continue;
+                }
+            };
+
+            if segment.range.start < ends[segment.read - 1] {
+                issues.push(format!(
+                    "segment {name} at {:?} overlaps the segment before it",
+                    segment.range
+                ));
+            }
+            ends[segment.read - 1] = segment.range.end;
+
+            if read.get(segment.range.clone()) != Some(&segment.seq[..]) {
+                issues.push(format!(
+                    "segment {name} is not the bases of read {} at {:?}",
+                    segment.read, segment.range
+                ));
+            }
+        }
+
+        let segments = extraction
+            .segments
+            .iter()
+            .filter_map(|s| Some((s.label.clone()?, s.seq.clone())))
+            .collect();
+        if !extraction.raw {
+            issues.extend(self.check(&segments));
+        }
+
+        issues
+    }
+}
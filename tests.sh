@@ -6,20 +6,35 @@ FGDL_DIR="./tests/fgdl"
 EXPECTED_DIR="./tests/test_data/expected_out"
 DEV_NULL="/dev/null"
 
+# exec_test <name> [additional args file] [options...]
 exec_test () {
-    echo "Test $1"
+    NAME=$1
+    ADDITIONAL=$2
+    # whatever follows the additional args file is passed to seqproc as is
+    shift $(( $# < 2 ? $# : 2 ))
 
-    IN1="$IN_DIR/$1/$1""_l.fastq"
-    IN2="$IN_DIR/$1/$1""_r.fastq"
-    OUT="$OUT_DIR/$1/$1.fastq"
-    FGDL="$FGDL_DIR/$1.fgdl"
-    EXPECTED="$EXPECTED_DIR/$1.fastq"
+    echo "Test $NAME $*"
 
-    if [ -z "$2" ]
+    IN1="$IN_DIR/$NAME/$NAME""_l.fastq"
+    IN2="$IN_DIR/$NAME/$NAME""_r.fastq"
+    OUT="$OUT_DIR/$NAME/$NAME.fastq"
+    FGDL="$FGDL_DIR/$NAME.fgdl"
+    EXPECTED="$EXPECTED_DIR/$NAME.fastq"
+
+    mkdir -p "$OUT_DIR/$NAME"
+
+    # single end cases have no r2
+    INPUTS=(-1 $IN1)
+    if [ -f "$IN2" ]
+    then
+        INPUTS+=(-2 $IN2)
+    fi
+
+    if [ -z "$ADDITIONAL" ]
     then
-        cargo run -- -g $FGDL -1 $IN1 -2 $IN2 -o $OUT -t 6
+        cargo run -- -g $FGDL "${INPUTS[@]}" -o $OUT -t 6 "$@"
     else
-        cargo run -- -g $FGDL -1 $IN1 -2 $IN2 -o $OUT -t 6 -a $2
+        cargo run -- -g $FGDL "${INPUTS[@]}" -o $OUT -t 6 -a $ADDITIONAL "$@"
     fi
 
     cmp -s $OUT $EXPECTED && echo "## Test Passed ##" || echo "## Test failed ##" && diff $OUT $EXPECTED
@@ -32,4 +47,15 @@ exec_test "match"
 exec_test "revcomp"
 exec_test "map" "$IN_DIR/map/map.tsv"
 exec_test "filter" "$IN_DIR/filter/filter.txt"
-exec_test "trunc"
\ No newline at end of file
+exec_test "trunc"
+exec_test "matcher_kmer" "" --matcher kmer
+exec_test "matcher_myers" "" --matcher myers
+exec_test "matcher_window" "" --matcher kmer
+exec_test "matcher_window" "" --matcher myers
+exec_test "miss_keep_raw"
+exec_test "miss_fallback"
+exec_test "search_window"
+exec_test "repeat"
+exec_test "alt"
+exec_test "ranged_search"
+exec_test "anchored"
@@ -0,0 +1,34 @@
+use seqproc::adapters::{adapter_start, builtin, trimmed_len};
+
+#[test]
+fn builtin_sets() {
+    assert_eq!(Some(vec![b"AGATCGGAAGAGC".to_vec()]), builtin("truseq"));
+    assert!(builtin("nextera").is_some());
+    assert!(builtin("illumina").is_none());
+}
+
+#[test]
+fn adapter_at_end() {
+    let adapter = b"AGATCGGAAGAGC";
+
+    // the whole adapter, with a mismatch
+    assert_eq!(
+        Some(8),
+        adapter_start(b"ACGTACGTAGATCGGTAGAGCACAC", adapter)
+    );
+    // running off the end of the read
+    assert_eq!(Some(8), adapter_start(b"ACGTACGTAGATC", adapter));
+    // too little of it left to tell from chance
+    assert_eq!(None, adapter_start(b"ACGTACGTCCAG", adapter));
+}
+
+#[test]
+fn trim_first_adapter() {
+    let adapters = vec![b"AGATCGGAAGAGC".to_vec(), b"CTGTCTCTTATACACATCT".to_vec()];
+
+    assert_eq!(
+        4,
+        trimmed_len(b"TTTTCTGTCTCTTATACACATCTAGATCGGAAGAGC", &adapters)
+    );
+    assert_eq!(8, trimmed_len(b"TTTTTTTT", &adapters));
+}
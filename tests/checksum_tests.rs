@@ -0,0 +1,58 @@
+use std::{env, fs, io, process, thread};
+
+use seqproc::checksum::{checksum_algorithm, copy_digest, Algorithm, Checksums};
+
+const ABC_MD5: &str = "900150983cd24fb0d6963f7d28e17f72";
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+#[test]
+fn digests() {
+    assert_eq!(Ok(Algorithm::Md5), checksum_algorithm("md5"));
+    assert!(checksum_algorithm("crc32").is_err());
+
+    let mut copy = Vec::new();
+    assert_eq!(
+        ABC_MD5,
+        copy_digest(Algorithm::Md5, &b"abc"[..], &mut copy).unwrap()
+    );
+    assert_eq!(b"abc".to_vec(), copy);
+    assert_eq!(
+        ABC_SHA256,
+        copy_digest(Algorithm::Sha256, &b"abc"[..], io::sink()).unwrap()
+    );
+}
+
+#[test]
+fn hashed_through_pipes() {
+    let dir = env::temp_dir();
+    let input = dir.join(format!("seqproc-checksum-{}-in.fq", process::id()));
+    let outputs = [
+        dir.join(format!("seqproc-checksum-{}-out1.fq", process::id())),
+        dir.join(format!("seqproc-checksum-{}-out2.fq", process::id())),
+    ];
+    fs::write(&input, "abc").unwrap();
+
+    let path = |p: &std::path::PathBuf| p.display().to_string();
+    let checksums = Checksums::start(
+        Algorithm::Md5,
+        &[path(&input)],
+        &[path(&outputs[0]), path(&outputs[1])],
+    )
+    .unwrap();
+
+    // the pipeline copies its input to the first output and never opens the second
+    let (from, to) = (checksums.inputs[0].clone(), checksums.outputs[0].clone());
+    thread::spawn(move || fs::write(to, fs::read(from).unwrap()).unwrap())
+        .join()
+        .unwrap();
+
+    let digests = checksums.finish().unwrap();
+    assert_eq!(vec![(path(&input), ABC_MD5.to_string())], digests.inputs);
+    assert_eq!(ABC_MD5, digests.outputs[0].1);
+    assert_eq!("d41d8cd98f00b204e9800998ecf8427e", digests.outputs[1].1);
+    assert_eq!("abc", fs::read_to_string(&outputs[0]).unwrap());
+    assert_eq!(Algorithm::Md5, digests.algorithm);
+
+    fs::remove_file(input).unwrap();
+    outputs.iter().for_each(|p| fs::remove_file(p).unwrap());
+}
@@ -2,11 +2,31 @@ use std::{collections::HashMap, ops::Deref};
 
 use chumsky::{prelude::*, Stream};
 use seqproc::{
-    compile::{compile, definitions::compile_definitions, reads::compile_reads},
+    compile::{
+        compile, definitions::compile_definitions, diagnostics::chance_matches, diff::Difference,
+        functions::CompiledFunction, lint::lint, plan::Step, reads::compile_reads, utils::Error,
+        CompiledData,
+    },
     lexer::lexer,
-    parser::{parser, Expr},
+    parser::{parser, Expr, PreStep, Size, Type},
 };
 
+fn try_compiled(src: &str) -> Result<CompiledData, Error> {
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    compile(res.unwrap().0)
+}
+
+fn compiled(src: &str) -> CompiledData {
+    try_compiled(src).unwrap()
+}
+
 #[test]
 fn no_err() {
     let src = "1{remove(hamming(f[CAG], 1))}2{r:}";
@@ -537,3 +557,864 @@ brc = b[10-11]
 
     assert!(res.is_ok())
 }
+
+#[test]
+fn repeat_delimiter() {
+    let src = "repeat{f[ACGT]b[16]u[12]r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0);
+
+    assert_eq!(Some("ACGT".to_string()), res.unwrap().repeat);
+}
+
+#[test]
+fn fail_repeat_delimiter() {
+    let src = "repeat{b[16]u[12]r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0);
+
+    assert!(res.is_err());
+}
+
+#[test]
+fn repeated_labels() {
+    let src = "
+brc = b[8]
+1{(<brc>f[ACGTACGT])x3u<umi>[8]r:}2{r<read>:}
+ -> 1{<brc1><brc2><brc3><umi>}2{<read>}
+";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    let labels = res.geometry[0]
+        .iter()
+        .filter_map(|gm| gm.expr.0.label.clone())
+        .collect::<Vec<_>>();
+
+    assert_eq!(8, res.geometry[0].len());
+    assert_eq!(vec!["brc1", "brc2", "brc3", "umi"], labels);
+}
+
+#[test]
+fn repeated_labeled_pieces() {
+    let src = "1{(b<bc>[8]f[ACGTACGT])x2r:}2{r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    let labels = res.geometry[0]
+        .iter()
+        .filter_map(|gm| gm.expr.0.label.clone())
+        .collect::<Vec<_>>();
+
+    assert_eq!(vec!["bc1", "bc2"], labels);
+}
+
+#[test]
+fn group_label_transformation() {
+    let src = "1{cb=(b<bc1>[8]f[ACGT]b[6])r:}2{r<read>:} -> 1{<cb>}2{<read>}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    assert_eq!(
+        Some(vec![
+            vec![
+                "seq1.bc1".to_string(),
+                "seq1.cb_2".to_string(),
+                "seq1.cb_3".to_string()
+            ],
+            vec!["seq2.read".to_string()]
+        ]),
+        res.transformation
+    );
+}
+
+#[test]
+fn optional_group() {
+    let src = "1{b[4]opt(f[GGAA]b<bc>[8])u[8]r:}2{r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    assert_eq!(vec![vec![1..3], vec![]], res.optional);
+}
+
+#[test]
+fn fail_optional_group() {
+    for src in [
+        "1{opt(b[8]f[ACGT])r:}2{r:}",
+        "1{r:opt(f[ACGT]b[8])}2{r:}",
+        "1{opt(f[ACGT]b[8-10])r:}2{r:}",
+        "1{opt(f[ACGT]opt(f[TT]b[8]))r:}2{r:}",
+    ] {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        assert!(compile(res.unwrap().0).is_err(), "{src}");
+    }
+}
+
+#[test]
+fn alternatives() {
+    let src = "1{b[4]alt{f[GGAA]b<short>[8] | f[CCTT]b<long>[10]}u[8]r:}2{r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    assert_eq!(vec![vec![1..3, 3..5], vec![]], res.optional);
+    assert_eq!(vec![vec![vec![1..3, 3..5]], vec![]], res.alternatives);
+}
+
+#[test]
+fn fail_alternatives() {
+    for src in [
+        "1{alt{b[8]f[ACGT] | f[TTTT]b[8]}r:}2{r:}",
+        "1{alt{f[ACGT]b[8-10] | f[TTTT]b[8]}r:}2{r:}",
+        "1{alt{f[ACGT]opt(f[TT]b[8]) | f[TTTT]b[8]}r:}2{r:}",
+        "1{opt(f[ACGT]alt{f[TT]b[8] | f[AA]b[8]})r:}2{r:}",
+    ] {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        assert!(compile(res.unwrap().0).is_err(), "{src}");
+    }
+}
+
+#[test]
+fn passthrough_read() {
+    let src = "1{b<bc>[16]u<umi>[12]}0{} -> 1{<umi><bc>}0{}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    assert!(res.geometry[1].is_empty());
+    assert_eq!(Some(vec![]), res.transformation.map(|t| t[1].clone()));
+}
+
+#[test]
+fn fail_passthrough_pieces() {
+    let src = "1{b[16]u[12]}0{r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    assert!(compile(res.unwrap().0).is_err());
+}
+
+#[test]
+fn output_reads() {
+    for (src, n) in [
+        ("1{b<bc>[16]u<umi>[12]}2{r<read>:}", 2),
+        ("1{b<bc>[16]u<umi>[12]}2{r<read>:} -> 1{<bc><umi><read>}", 1),
+    ] {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        assert_eq!(n, compile(res.unwrap().0).unwrap().output_reads());
+    }
+}
+
+#[test]
+fn header_index() {
+    let src = "1{h<i7>[8]h<i5>[8]b<bc>[16]u<umi>[12]x:}2{r<read>:} -> 1{<bc><umi><i7>}2{<read>}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    assert_eq!(
+        vec![vec![("i7".to_string(), 8), ("i5".to_string(), 8)], vec![]],
+        res.header
+    );
+    assert_eq!(
+        Some(vec![
+            vec![
+                "seq1.bc".to_string(),
+                "seq1.umi".to_string(),
+                "name1.i7".to_string()
+            ],
+            vec!["seq2.read".to_string()]
+        ]),
+        res.transformation
+    );
+}
+
+#[test]
+fn fail_header_index() {
+    for src in [
+        "1{b[16]h<i7>[8]r:}2{r:}",
+        "1{h<a>[8]h<b>[8]h<c>[8]r:}2{r:}",
+        "1{h<i7>[8]r:}2{r:} -> 1{rev(<i7>)}2{r:}",
+        "1{h<i7>[8]r<i7>:}2{r:}",
+    ] {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        assert!(compile(res.unwrap().0).is_err(), "{src}");
+    }
+}
+
+#[test]
+fn fail_miss_policy() {
+    for src in [
+        "1{b:f[GAGT;miss=fallback]r:}2{r:}",
+        "1{b[16]opt(f[GAGT;miss=keep-raw]u[12])r:}2{r:}",
+    ] {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        assert!(compile(res.unwrap().0).is_err(), "{src}");
+    }
+}
+
+#[test]
+fn fail_search_window() {
+    for src in [
+        "1{b[16]f[CAGAGC]@20..10r:}2{r:}",
+        "1{b[16]opt(f[CAGAGC]@0..4u[12])r:}2{r:}",
+        "1{b[9-10]f[CAGAGC;miss=fallback]@0..4r:}2{r:}",
+    ] {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        assert!(compile(res.unwrap().0).is_err(), "{src}");
+    }
+}
+
+#[test]
+fn constrained_search() {
+    let src = "1{b[8-10]f[CAGAGC]u[8]f[CAGAGC]b[4-6]f[TTTT;miss=fallback]r:}2{r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    let windows = res.geometry[0]
+        .iter()
+        .filter_map(|gm| match gm.expr.0.size {
+            Size::FixedSeq(_, _, window) => Some(window),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(vec![Some((8, 10)), None, None], windows);
+}
+
+#[test]
+fn anchored_search() {
+    // the right anchor is only searched for where the segment can end, unless
+    // it falls back on a miss
+    let windows = |src: &str| {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        let res = compile(res.unwrap().0).unwrap();
+
+        res.geometry[0]
+            .iter()
+            .filter_map(|gm| match gm.expr.0.size {
+                Size::FixedSeq(_, _, window) => Some(window),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        vec![Some((0, 40)), Some((18, 22))],
+        windows("1{x[0-40]f[TTGTGG]v<guide>[18-22]f[GTTTTA]x:}2{r:}")
+    );
+    assert_eq!(
+        vec![Some((0, 40)), None],
+        windows("1{x[0-40]f[TTGTGG]v<guide>[18-22]f[GTTTTA;miss=fallback]x:}2{r:}")
+    );
+    // a definition named `v` is still a label
+    assert_eq!(
+        vec![Some((0, 40)), Some((18, 22))],
+        windows("v = x[0-40] 1{<v>f[TTGTGG]v<guide>[18-22]f[GTTTTA]x:}2{r:}")
+    );
+}
+
+#[test]
+fn anchored_needs_both_anchors() {
+    let res = compiled("1{f[TTGTGG]v<guide>[18-22]f[GTTTTA]x:}2{r:}");
+
+    // the segment is cut as a ranged read sequence with nothing left to run
+    let guide = &res.geometry[0][1];
+    assert_eq!(Type::ReadSeq, guide.expr.0.type_);
+    assert_eq!(Size::RangedLen(((18, 22), 20..25)), guide.expr.0.size);
+    assert!(guide.stack.is_empty());
+
+    for src in [
+        "1{v<guide>[18-22]f[GTTTTA]x:}2{r:}",
+        "1{b[10]v<guide>[18-22]f[GTTTTA]x:}2{r:}",
+        "1{f[TTGTGG]v<guide>[18-22]}2{r:}",
+    ] {
+        let err = try_compiled(src).unwrap_err();
+
+        assert_eq!(
+            "An anchored segment needs a fixed sequence on both sides", err.msg,
+            "{src}"
+        );
+    }
+
+    let err = try_compiled("guide = v[18-22] 1{f[TTGTGG]<guide>f[GTTTTA]x:}2{r:}").unwrap_err();
+
+    assert_eq!("An anchored segment can only be written in a read", err.msg);
+}
+
+#[test]
+fn slack_search() {
+    let src = "1{b<cb>[16;slack=3]f[TTTT]u[12]r:}2{r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    // the barcode is cut where the sequence after it is found, then trimmed
+    // to its length from the left
+    let barcode = &res.geometry[0][0];
+    assert_eq!(Size::RangedLen(((16, 19), 8..10)), barcode.expr.0.size);
+    assert_eq!(
+        vec![CompiledFunction::TruncateToLeft(16)],
+        barcode
+            .stack
+            .iter()
+            .map(|(f, _)| f.clone())
+            .collect::<Vec<_>>()
+    );
+    assert!(matches!(
+        res.geometry[0][1].expr.0.size,
+        Size::FixedSeq(_, _, Some((16, 19)))
+    ));
+
+    // with no sequence after it, a piece with slack cannot be placed
+    let src = "1{b[16;slack=3]u[12]r:}2{r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    assert!(compile(res.unwrap().0).is_err());
+}
+
+#[test]
+fn warnings() {
+    let res = compiled("1{pad_to(u[9-11], 12, A)f[CAGAGC]r:}2{r:}");
+
+    assert_eq!(1, res.warnings.len());
+    assert!(res.warnings[0].msg.contains("one base past"));
+
+    let res = compiled("1{trunc_to(b[16], 16)r:f[CAGAGC]x:}2{r:}");
+
+    assert_eq!(2, res.warnings.len());
+
+    assert!(compiled("1{pad_to(u[9-11], 11, A)f[CAGAGC]r:}2{r:}")
+        .warnings
+        .is_empty());
+
+    // labeled, then dropped by the transformation
+    let res = compiled("1{b<brc>[16]u<umi>[12]x:}2{r<read>:} -> 1{<brc>}2{<read>}");
+    assert_eq!(1, res.warnings.len());
+    assert!(res.warnings[0]
+        .msg
+        .contains("<umi> is labeled but left out of the transformation"));
+
+    // a group is used by any of its pieces, and reported as itself
+    let group = |t: &str| {
+        compiled(&format!(
+            "1{{cb=(b[8]f[ACGTACGTAC]b<bc2>[6])r:}}2{{r<read>:}} -> {t}"
+        ))
+    };
+    assert!(group("1{<bc2>}2{<read>}").warnings.is_empty());
+    assert!(group("1{<cb>}2{<read>}").warnings.is_empty());
+    let res = group("1{}2{<read>}");
+    let unused = res.warnings.iter().map(|w| &w.msg[..5]).collect::<Vec<_>>();
+    assert_eq!(vec!["<cb> ", "<bc2>"], unused);
+}
+
+#[test]
+fn inclusive_ranges() {
+    let res = compiled("1{u[9-11]f[CAGAGC]r:}2{r:}");
+
+    assert!(matches!(
+        res.geometry[0][0].expr.0.size,
+        Size::RangedLen(((9, 11), _))
+    ));
+
+    // the longest piece already has the length padded to
+    assert!(try_compiled("1{pad_to(u[9-11], 11, A)f[CAGAGC]r:}2{r:}").is_ok());
+    assert!(try_compiled("1{pad_to(u[9-11], 10, A)f[CAGAGC]r:}2{r:}").is_err());
+    assert!(try_compiled("1{trunc_to(u[9-11], 11)f[CAGAGC]r:}2{r:}").is_ok());
+    assert!(try_compiled("1{trunc_to(u[9-11], 12)f[CAGAGC]r:}2{r:}").is_err());
+}
+
+#[test]
+fn rna_fixed_seq() {
+    let src = "anchor = f[ACGU]\n1{<anchor>b[16]f[UUGA]r:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    let seqs = res.geometry[0]
+        .iter()
+        .filter_map(|gm| match &gm.expr.0.size {
+            Size::FixedSeq((seq, _), ..) => Some(seq.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(vec!["ACGT".to_string(), "TTGA".to_string()], seqs);
+}
+
+#[test]
+fn pre_steps() {
+    let res = compiled("pre{qualtrim(20) adapters(truseq)}1{b[16]r:}");
+
+    assert_eq!(
+        vec![
+            PreStep::QualTrim(20),
+            PreStep::Adapters("truseq".to_string())
+        ],
+        res.pre
+    );
+    assert_eq!(2, res.geometry[0].len());
+
+    assert!(try_compiled("pre{adapters(illumina)}1{b[16]r:}").is_err());
+    assert!(try_compiled("pre{qualtrim(94)}1{b[16]r:}").is_err());
+
+    // lengths are asserted before anything is trimmed
+    let res = compiled("pre{qualtrim(20) assert_len(2, 90:)}1{b[16]r:}2{r:}");
+
+    assert_eq!(
+        vec![
+            PreStep::AssertLen {
+                read: Some(2),
+                len: 90,
+                at_least: true
+            },
+            PreStep::QualTrim(20)
+        ],
+        res.pre
+    );
+
+    assert!(try_compiled("pre{assert_len(2, 28)}1{b[16]r:}").is_err());
+    assert!(try_compiled("pre{assert_len(28)}repeat{f[ACGT]b[16]u[12]r:}").is_ok());
+}
+
+#[test]
+fn kept_linker() {
+    let src = "1{b<bc>[16]f<linker>[CAGAGC]u<umi>[12]}2{r<read>:} -> 1{<bc><linker><umi>}2{<read>}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    assert_eq!(
+        Some(vec![
+            vec![
+                "seq1.bc".to_string(),
+                "seq1.linker".to_string(),
+                "seq1.umi".to_string()
+            ],
+            vec!["seq2.read".to_string()]
+        ]),
+        res.transformation
+    );
+}
+
+#[test]
+fn whitelisted_ranged_barcode() {
+    let src = "1{map(b[8-12], \"wl.txt\", self)u[8]r:}2{r<read>:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    assert_eq!(
+        Some(("wl.txt".to_string(), 0)),
+        res.geometry[0][0].whitelist()
+    );
+    assert_eq!(None, res.geometry[0][1].whitelist());
+
+    // without a whitelist nothing tells where the barcode ends
+    let src = "1{b[8-12]u[8]r:}2{r<read>:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    assert!(compile(res.unwrap().0).is_err());
+}
+
+#[test]
+fn whitelist_mismatches() {
+    let src = "1{map_with_mismatch(b[8-12], \"wl.txt\", self, 1)u[8]r:}2{r<read>:}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let res = compile(res.unwrap().0).unwrap();
+
+    assert_eq!(
+        Some(("wl.txt".to_string(), 1)),
+        res.geometry[0][0].whitelist()
+    );
+}
+
+#[test]
+fn read_lengths() {
+    let res = compiled("1{b[16]u[10-12]f[ACGT]opt(f[TT]b[2])}2{r:}");
+    assert_eq!((30, Some(36)), res.read_lengths(0));
+    assert_eq!((0, None), res.read_lengths(1));
+}
+
+#[test]
+fn geometry_diff() {
+    let res = compiled("brc = b[16]\n1{<brc>u[12]x:}2{r:}");
+    assert_eq!(
+        vec![vec!["b<brc>[16]", "u[12]", "x:"], vec!["r:"]],
+        res.normalize().reads
+    );
+
+    // written differently, cut the same
+    let same = compiled("1{b<brc>[16] u[12] x:} 2{r:}");
+    assert_eq!(res.normalize(), same.normalize());
+    assert!(res.diff(&same).is_empty());
+
+    let other = compiled("1{b<brc>[16]rev(u[10])x:f[ACGT]}2{r:}");
+    assert_eq!(
+        vec![
+            Difference::Piece {
+                read: 0,
+                index: 1,
+                left: Some("u[12]".to_string()),
+                right: Some("rev(u[10])".to_string()),
+            },
+            Difference::Piece {
+                read: 0,
+                index: 3,
+                left: None,
+                right: Some("f[ACGT]".to_string()),
+            },
+        ],
+        res.diff(&other)
+    );
+    assert_eq!(
+        "read 1 piece 2: u[12] -> rev(u[10])",
+        res.diff(&other)[0].to_string()
+    );
+
+    let other = compiled("pre{qualtrim(20)}1{b<brc>[16]u[12]x:}2{r:}");
+    assert_eq!(
+        vec!["pre: pre{} -> pre{qualtrim(20)}"],
+        res.diff(&other)
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn geometry_lints() {
+    let lints = |src: &str| {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        lint(&compile(res.unwrap().0).unwrap())
+            .into_iter()
+            .map(|l| (l.msg, l.fix))
+            .collect::<Vec<_>>()
+    };
+
+    assert!(lints("1{b<brc>[16]u[12]x:}2{r:}").is_empty());
+
+    assert_eq!(
+        vec![(
+            "x[4] ends read 1, nothing follows it".to_string(),
+            Some("write `x:` to discard the rest of the read".to_string())
+        )],
+        lints("1{b<brc>[16]u[12]x[4]}2{r:}")
+    );
+
+    assert_eq!(
+        vec![(
+            "barcode mapped to wl.txt has no label".to_string(),
+            Some("label it, as in `b<brc>[16]`, to refer to the corrected barcode".to_string())
+        )],
+        lints("1{map(b[16], \"wl.txt\", self)u[12]x:}2{r:}")
+    );
+
+    assert_eq!(
+        vec![(
+            "f[AT] is 2 bases, it is found by chance about every 16 bases".to_string(),
+            Some("extend it to at least 5 bases of the library around it".to_string())
+        )],
+        lints("1{b<brc>[16]f[AT]u[12]x:}2{r:}")
+    );
+}
+
+#[test]
+fn chance_anchors() {
+    assert_eq!(148.0 / 64.0, chance_matches("ACG", 150));
+    // a homopolymer is as likely as a shorter sequence
+    assert_eq!(146.0 / 64.0, chance_matches("AAAAA", 150));
+
+    let res = compiled("1{b[16]f[AT]r:}2{r:}");
+    assert_eq!(1, res.warnings.len());
+    assert!(res.warnings[0]
+        .msg
+        .contains("f[AT] is likely found by chance"));
+
+    // found by chance in a read, but not in the window after a ranged piece
+    assert_eq!(1, compiled("1{b[16]f[GTAC]r:}2{r:}").warnings.len());
+    assert!(compiled("1{b[10-12]f[GTAC]r:}2{r:}").warnings.is_empty());
+    assert!(compiled("1{b[16]f[CAGAGC]r:}2{r:}").warnings.is_empty());
+}
+
+#[test]
+fn umi_whitelist() {
+    let mut data = compiled("1{b<brc>[16]rev(u<umi>[8])x:}2{r:}");
+    data.whitelist_umi("umi", "umis.txt".to_string(), 1)
+        .unwrap();
+
+    let umi = &data.geometry[0][1];
+    assert_eq!(umi.whitelist(), Some(("umis.txt".to_string(), 1)));
+    // corrected after it is reversed
+    assert_eq!(umi.stack[0].0.to_string(), "map_with_mismatch(umis.txt, 1)");
+    assert_eq!(umi.stack[1].0.to_string(), "rev");
+
+    assert_eq!(
+        data.whitelist_umi("umi", "umis.txt".to_string(), 1),
+        Err("<umi> is already mapped in the geometry".to_string())
+    );
+    assert_eq!(
+        data.whitelist_umi("brc", "umis.txt".to_string(), 0),
+        Err("<brc> is not a UMI, barcodes are mapped in the geometry".to_string())
+    );
+    assert_eq!(
+        data.whitelist_umi("cell", "umis.txt".to_string(), 0),
+        Err("no UMI is labeled <cell>".to_string())
+    );
+
+    let mut data = compiled("1{b<brc>[16]u<umi>:}2{r:}");
+    assert!(data
+        .whitelist_umi("umi", "umis.txt".to_string(), 0)
+        .unwrap_err()
+        .starts_with("<umi> cannot be whitelisted"));
+}
+
+#[test]
+fn referenced_labels() {
+    let data = compiled("1{b<brc>[16]u<umi>[12]f<anchor>[TTTT]x:}2{r<read>:}");
+
+    assert_eq!(vec!["brc", "umi", "anchor", "read"], data.labels());
+    assert!(data
+        .check_labels(&[("--capture", "anchor"), ("--segment-out", "brc")])
+        .is_ok());
+    assert_eq!(
+        Err("--segment-out refers to <cb>, which the geometry does not label, expected one of <brc>, <umi>, <anchor>, <read>".to_string()),
+        data.check_labels(&[("--capture", "anchor"), ("--segment-out", "cb")])
+    );
+
+    let data = compiled("1{b[16]r:}2{r:}");
+    assert_eq!(
+        Err("--matcher refers to <brc>, but the geometry labels no segments".to_string()),
+        data.check_labels(&[("--matcher", "brc")])
+    );
+}
+
+#[test]
+fn read_plans() {
+    let data = compiled("1{b<brc>[16]r:f<anchor>[TTTT]u<umi>[8]}2{r<read>:}");
+
+    match &data.plan[0][..] {
+        [Step::Piece(brc), Step::Dual(read, anchor), Step::Piece(umi)] => {
+            assert_eq!("seq1.* -> seq1.brc, seq1._r", brc.cut.text);
+            // the bases skipped before the sequence are the piece before it
+            assert_eq!("seq1._r_l", read.labels.this);
+            let search = anchor.search.as_ref().unwrap();
+            assert_eq!("seq1._r & !seq1.anchor", search.unfound.text);
+            assert_eq!(
+                "seq1._r -> seq1._r_l, seq1.anchor, seq1._r_r",
+                search.split.text
+            );
+            assert_eq!("seq1._r_r -> seq1.umi, seq1._r_r_r", umi.cut.text);
+            assert_eq!("seq1.umi -> seq1.umi.v_len", umi.v_len.text);
+        }
+        steps => panic!("unexpected plan {steps:?}"),
+    }
+    match &data.plan[1][..] {
+        [Step::Piece(read)] => assert_eq!("seq2.* -> _, seq2.read", read.cut.text),
+        steps => panic!("unexpected plan {steps:?}"),
+    }
+
+    let data = compiled("1{b[16]u[10-12]f[ACGT]opt(f[TT]b[2])}2{r:}");
+
+    match &data.plan[0][..] {
+        [Step::Piece(_), Step::Dual(..), Step::Optional(group)] => {
+            assert_eq!("seq1._r_r_l", group.anchor);
+            // reads without the group are noted before they get an empty anchor
+            assert_eq!("seq1._r_r.found", group.found.text);
+            assert_eq!("!seq1._r_r.found", group.absent.text);
+            assert_eq!("seq1._r_r_r_r -> _, seq1._r_r_o", group.end_cut.text);
+            assert_eq!("seq1._r_r -> _, seq1._r_r_o", group.skip.text);
+            assert_eq!(None, group.branches);
+        }
+        steps => panic!("unexpected plan {steps:?}"),
+    }
+}
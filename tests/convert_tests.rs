@@ -0,0 +1,189 @@
+use seqproc::{
+    convert::{
+        convert_format,
+        seqspec::{parse_yaml, read_seqspec, write_seqspec, Yaml},
+        umitools::geometry,
+        Format,
+    },
+    runner::compile_geometry,
+};
+
+// a 10x 3' v3 spec as published, trimmed to the fields read
+const TENX_V3: &str = "!Assay
+seqspec_version: 0.3.0
+assay_id: 10xv3
+modalities:
+- rna
+sequence_spec:
+- !Read
+  read_id: R1.fastq.gz
+  name: Read 1
+  modality: rna
+  primer_id: r1_primer
+  min_len: 28
+  max_len: 28
+  strand: pos
+- !Read
+  read_id: R2.fastq.gz
+  name: Read 2
+  modality: rna
+  primer_id: r2_primer
+  min_len: 90
+  max_len: 90
+  strand: neg
+library_spec:
+- !Region
+  region_id: rna
+  region_type: rna
+  sequence_type: joined
+  min_len: 0
+  max_len: 500
+  regions:
+  - !Region
+    region_id: r1_primer
+    region_type: truseq_read1
+    sequence_type: fixed
+    sequence: CTACACGACGCTCTTCCGATCT
+    min_len: 22
+    max_len: 22
+    regions: null
+  - !Region
+    region_id: barcode
+    region_type: barcode
+    sequence_type: onlist
+    sequence: NNNNNNNNNNNNNNNN
+    min_len: 16
+    max_len: 16
+    onlist: !Onlist
+      file_id: 3M-february-2018.txt
+      filename: 3M-february-2018.txt # the whitelist of 10x
+    regions: null
+  - !Region
+    region_id: umi
+    region_type: umi
+    sequence_type: random
+    sequence: NNNNNNNNNNNN
+    min_len: 12
+    max_len: 12
+    regions: null
+  - !Region
+    region_id: cdna-insert
+    region_type: cdna
+    sequence_type: random
+    sequence: X
+    min_len: 1
+    max_len: 98
+    regions: null
+  - !Region
+    region_id: r2_primer
+    region_type: truseq_read2
+    sequence_type: fixed
+    sequence: AGATCGGAAGAGCACACGTCTGAACTCCAGTCAC
+    min_len: 34
+    max_len: 34
+    regions: null
+";
+
+#[test]
+fn umitools_string() {
+    let geom = geometry("CCCCCCCCCCCCCCCCNNNNNNNNNNNN", None, true).unwrap();
+
+    assert_eq!("1{b<cell_1>[16]u<umi_1>[12]r:}2{r:}\n", geom);
+    assert!(compile_geometry(&geom).is_ok());
+
+    assert_eq!(
+        "1{r[2]b<cell_1>[4]u<umi_1>[2]b<cell_2>[2]r:}\n",
+        geometry("XXCCCCNNCC", None, false).unwrap()
+    );
+    assert!(geometry("CCQ", None, false).is_err());
+}
+
+#[test]
+fn umitools_regex() {
+    let geom = geometry("(?P<cell_1>.{16})(?P<umi_1>.{12})", None, false).unwrap();
+    assert_eq!("1{b<cell_1>[16]u<umi_1>[12]r:}\n", geom);
+
+    // inDrop, a fuzzy linker between the two halves of the barcode
+    let geom = geometry(
+        "(?P<cell_1>.{8,12})(?P<discard_1>GAGTGATTGCTTGTGACGCCTT){s<=2}(?P<cell_2>.{8})(?P<umi_1>.{6})T{3}.*",
+        Some("^(?P<umi_2>.{4})"),
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        "1{b<cell_1>[8-12]remove(hamming(f<discard_1>[GAGTGATTGCTTGTGACGCCTT], 2))b<cell_2>[8]u<umi_1>[6]f[TTT]r:}2{u<umi_2>[4]r:}\n",
+        geom
+    );
+    assert!(compile_geometry(&geom).is_ok());
+}
+
+#[test]
+fn umitools_errors() {
+    for pattern in [
+        "(?P<barcode>.{16})",
+        "(?P<cell_1>.{16}",
+        "(?P<cell_1>.{8}GATC)",
+        "(?P<cell_1>.{16}){e<=1}",
+        "(?P<cell_1>[ACGT]{16})",
+        "(?P<cell_1>.{16})T+",
+    ] {
+        assert!(
+            geometry(pattern, None, false).is_err(),
+            "{pattern} is converted"
+        );
+    }
+
+    assert_eq!(Ok(Format::UmiTools), convert_format("umitools"));
+    assert!(convert_format("cutadapt").is_err());
+}
+
+#[test]
+fn yaml_blocks() {
+    let yaml = parse_yaml("a: 1\nb:\n- x\n- 'y'\nc:\n  d: [e, f]\n  g: null\n");
+
+    assert_eq!(Some("1"), yaml.str("a"));
+    assert_eq!(
+        &[Yaml::Scalar("x".to_string()), Yaml::Scalar("y".to_string())],
+        yaml.items("b")
+    );
+    assert_eq!(2, yaml.get("c").unwrap().items("d").len());
+    assert_eq!(Some(&Yaml::Null), yaml.get("c").unwrap().get("g"));
+}
+
+#[test]
+fn seqspec_read() {
+    let geom = read_seqspec(TENX_V3, "rna").unwrap();
+
+    assert_eq!(
+        "1{map(b<barcode>[16], \"3M-february-2018.txt\", self)u<umi>[12]}2{r<cdna_insert>:}\n",
+        geom
+    );
+    assert!(compile_geometry(&geom).is_ok());
+
+    assert!(read_seqspec(TENX_V3, "atac").is_err());
+}
+
+#[test]
+fn seqspec_round_trip() {
+    let compiled = compile_geometry("1{b<cb>[16]u<umi>[12]x:}2{r<cdna>:}").unwrap();
+    let spec = write_seqspec(&compiled, "tenx", "rna", 90);
+
+    let spec_yaml = parse_yaml(&spec);
+    assert_eq!(Some("0.3.0"), spec_yaml.str("seqspec_version"));
+    assert_eq!(2, spec_yaml.items("sequence_spec").len());
+
+    assert_eq!(
+        "1{b<cb>[16]u<umi>[12]x<R1_3>:}2{r<cdna>:}\n",
+        read_seqspec(&spec, "rna").unwrap()
+    );
+
+    // fixed sequences of read 2 are on the other strand of the molecule
+    let compiled = compile_geometry("1{b<cb>[8]r:}2{f<link>[AACG]r:}").unwrap();
+    let spec = write_seqspec(&compiled, "linked", "rna", 50);
+
+    assert!(spec.contains("sequence: 'CGTT'"));
+    assert_eq!(
+        "1{b<cb>[8]r<R1_2>:}2{f<link>[AACG]r<R2_2>:}\n",
+        read_seqspec(&spec, "rna").unwrap()
+    );
+}
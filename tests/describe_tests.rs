@@ -0,0 +1,62 @@
+#![cfg(feature = "cli")]
+
+use clap::{builder::ValueHint, Arg, ArgAction, Command};
+use seqproc::describe::{cwl, galaxy};
+
+fn command() -> Command {
+    Command::new("seqproc")
+        .about("General puprose sequence preprocessor")
+        .arg(
+            Arg::new("file1")
+                .long("file1")
+                .required(true)
+                .help("r1 fastq file")
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("out1")
+                .long("out1")
+                .help("r1 out fastq file")
+                .value_hint(ValueHint::AnyPath),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .help("number of threads to use")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("dedup")
+                .long("dedup")
+                .help("drop duplicates")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+#[test]
+fn cwl_descriptor() {
+    let cwl = cwl(&command());
+
+    assert!(cwl.starts_with("cwlVersion: v1.2\nclass: CommandLineTool\nbaseCommand: seqproc\n"));
+    assert!(cwl.contains("  file1:\n    type: File\n"));
+    assert!(cwl.contains(
+        "  threads:\n    type: int?\n    doc: \"number of threads to use\"\n    default: \"1\"\n"
+    ));
+    assert!(cwl.contains("  dedup:\n    type: boolean?\n"));
+    assert!(cwl.contains(
+        "outputs:\n  out1_file:\n    type: File?\n    outputBinding:\n      glob: $(inputs.out1)\n"
+    ));
+}
+
+#[test]
+fn galaxy_descriptor() {
+    let galaxy = galaxy(&command());
+
+    assert!(galaxy.contains("    --file1 '$file1'\n"));
+    assert!(galaxy.contains("    $dedup\n"));
+    assert!(galaxy.contains(
+        "<param name=\"threads\" type=\"integer\" optional=\"true\" value=\"1\" label=\"number of threads to use\"/>"
+    ));
+    assert!(galaxy.contains("<filter>out1_enabled</filter>"));
+}
@@ -0,0 +1,82 @@
+use chumsky::{prelude::*, Stream};
+use seqproc::{
+    compile::{compile, CompiledData},
+    explain::{
+        graph::{graph, graph_format, GraphFormat},
+        json, plan, text,
+    },
+    interpret::InterpretOptions,
+    lexer::lexer,
+    parser::parser,
+};
+
+fn compiled(src: &str) -> CompiledData {
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, _) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    compile(res.unwrap().0).unwrap()
+}
+
+#[test]
+fn plan_stages() {
+    let compiled = compiled("1{b<brc>[16]f[CAGAGC;miss=keep-raw]u<umi>[12]x:}2{r<read>:}");
+
+    let stages = plan(&compiled, &InterpretOptions::default());
+
+    let names = stages.iter().map(|s| s.name).collect::<Vec<_>>();
+
+    assert_eq!(
+        vec!["normalize", "cut", "match", "cut", "cut", "cut"],
+        names
+    );
+    assert_eq!(vec!["brc".to_string()], stages[1].labels);
+    assert!(stages[2].params.contains(&("miss", "keep-raw".to_string())));
+    assert!(text(&stages).starts_with("normalize    uppercase=false\n"));
+}
+
+#[test]
+fn plan_json() {
+    let compiled = compiled("1{b<brc>[16]r:}");
+
+    let stages = plan(&compiled, &InterpretOptions::default());
+
+    assert_eq!(
+        "{\"spec_version\":6,\"stages\":[\
+        {\"stage\":\"normalize\",\"params\":{\"uppercase\":\"false\"},\"labels\":[]},\
+        {\"stage\":\"cut\",\"params\":{\"read\":\"1\",\"type\":\"Barcode\",\"size\":\"[16]\"},\"labels\":[\"brc\"]},\
+        {\"stage\":\"cut\",\"params\":{\"read\":\"1\",\"type\":\"ReadSeq\",\"size\":\":\"},\"labels\":[]}\
+        ]}\n",
+        json(&stages)
+    );
+}
+
+#[test]
+fn plan_graph() {
+    let compiled = compiled("1{b<brc>[16]r:}2{r<read>:}");
+
+    let stages = plan(&compiled, &InterpretOptions::default());
+
+    assert_eq!(
+        "digraph seqproc {\n  node [shape=box];\n  s0 [label=\"normalize\\nuppercase=false\"];\n\
+        \x20 subgraph cluster1_read1 {\n    label=\"read 1\";\n\
+        \x20   s1 [label=\"cut\\ntype=Barcode\\nsize=[16]\\n[brc]\"];\n\
+        \x20   s2 [label=\"cut\\ntype=ReadSeq\\nsize=:\"];\n  }\n\
+        \x20 subgraph cluster1_read2 {\n    label=\"read 2\";\n\
+        \x20   s3 [label=\"cut\\ntype=ReadSeq\\nsize=:\\n[read]\"];\n  }\n\
+        \x20 s1 -> s2;\n  s0 -> s1;\n  s0 -> s3;\n}\n",
+        graph(&stages, GraphFormat::Dot)
+    );
+
+    let mermaid = graph(&stages, GraphFormat::Mermaid);
+    assert!(mermaid.starts_with("flowchart TD\n  s0[\"normalize<br/>uppercase=false\"]\n"));
+    assert!(mermaid.contains("  subgraph step1_read2 [read 2]\n"));
+    assert!(mermaid.ends_with("  s1 --> s2\n  s0 --> s1\n  s0 --> s3\n"));
+
+    assert_eq!(Ok(GraphFormat::Mermaid), graph_format("mermaid"));
+    assert!(graph_format("svg").is_err());
+}
@@ -0,0 +1,69 @@
+use seqproc::{
+    extract::{process_record, ExtractError},
+    presets::preset,
+    runner::compile_geometry,
+};
+
+#[test]
+fn fixed_lengths() {
+    let compiled = compile_geometry("1{b<bc>[4]u<umi>[3]x:}2{r<cdna>:}").unwrap();
+
+    let extraction = process_record(&compiled, &[b"ACGTTTGCCCC", b"GATTACA"]).unwrap();
+
+    let bc = extraction.get("bc").unwrap();
+    assert_eq!(
+        (1, 0..4, &b"ACGT"[..]),
+        (bc.read, bc.range.clone(), &bc.seq[..])
+    );
+    assert_eq!(b"TTG", &extraction.get("umi").unwrap().seq[..]);
+    assert_eq!(b"GATTACA", &extraction.get("cdna").unwrap().seq[..]);
+
+    assert!(matches!(
+        process_record(&compiled, &[b"ACG", b"GATTACA"]),
+        Err(ExtractError::Unmatched { read: 1, .. })
+    ));
+    assert_eq!(
+        Err(ExtractError::Reads {
+            expected: 2,
+            found: 1
+        }),
+        process_record(&compiled, &[b"ACGTTTGCCCC"])
+    );
+}
+
+#[test]
+fn anchored_guide() {
+    let crispr = compile_geometry(preset("crispr").unwrap().geometry).unwrap();
+
+    // a stagger, the promoter with a mismatch, the guide and the scaffold
+    let read = b"NNNNNTTGTGGAAAGGACGAAACTCCGGATTACAGATTACAGATTAGTTTTAGAGCTAGAAATAGCAAGT";
+    let extraction = process_record(&crispr, &[read]).unwrap();
+
+    let guide = extraction.get("guide").unwrap();
+    assert_eq!(b"GATTACAGATTACAGATTA", &guide.seq[..]);
+    assert_eq!(27..46, guide.range);
+
+    // no scaffold where a guide of an allowed length would end
+    let read = b"TTGTGGAAAGGACGAAACACCGGATTACAGTTTTAGAGCTAGAAATAGC";
+    assert!(matches!(
+        process_record(&crispr, &[read]),
+        Err(ExtractError::Unmatched { read: 1, .. })
+    ));
+}
+
+#[test]
+fn kept_raw() {
+    let compiled = compile_geometry("1{b<bc>[4]f[ACGT;miss=keep-raw]r:}").unwrap();
+
+    assert!(process_record(&compiled, &[b"TTTTGGGGCC"]).unwrap().raw);
+}
+
+#[test]
+fn unsupported() {
+    let compiled = compile_geometry("1{b[4]opt(f[GGAA]b<bc>[8])u[8]r:}2{r:}").unwrap();
+
+    assert!(matches!(
+        process_record(&compiled, &[b"TTTTGGAA", b"ACGT"]),
+        Err(ExtractError::Unsupported(_))
+    ));
+}
@@ -0,0 +1,46 @@
+use std::panic;
+
+use seqproc::failure::{fail, Failure, FailureKind};
+
+#[test]
+fn failure_from_panic() {
+    let payload = panic::catch_unwind(|| fail(FailureKind::Io, "r1.fastq: not found")).unwrap_err();
+
+    assert_eq!(
+        Failure::new(FailureKind::Io, "r1.fastq: not found"),
+        Failure::from_panic(&*payload)
+    );
+
+    let payload = panic::catch_unwind(|| panic!("index out of bounds")).unwrap_err();
+
+    assert_eq!(FailureKind::Internal, Failure::from_panic(&*payload).kind);
+}
+
+#[test]
+fn distinct_exit_codes() {
+    let mut codes = [
+        FailureKind::Geometry,
+        FailureKind::Io,
+        FailureKind::LowPassRate,
+        FailureKind::ReadLength,
+        FailureKind::Internal,
+    ]
+    .map(FailureKind::exit_code);
+    codes.sort();
+
+    assert!(codes.windows(2).all(|w| w[0] != w[1]));
+    assert!(!codes.contains(&0) && !codes.contains(&1));
+}
+
+#[test]
+fn failure_json() {
+    let failure = Failure::new(
+        FailureKind::LowPassRate,
+        "Only 1.0% of the last \"10000\" reads passed",
+    );
+
+    assert_eq!(
+        "{\"kind\":\"low_pass_rate\",\"exit_code\":4,\"message\":\"Only 1.0% of the last \\\"10000\\\" reads passed\"}\n",
+        failure.json()
+    );
+}
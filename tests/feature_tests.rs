@@ -0,0 +1,101 @@
+use seqproc::features::{modality_output, out_template, template_outputs, Features};
+
+fn features() -> Features {
+    let csv = "id,sequence,feature_type\nCD3,ACGTACGT,ADT\nCD4,TTGCAACC,ADT\nHTO1,GGGGAAAA,HTO\n";
+    let outputs = vec![
+        modality_output("ADT=adt_1.fq,adt_2.fq").unwrap(),
+        modality_output("HTO=hto.fq").unwrap(),
+    ];
+
+    Features::new(csv, "fb".to_string(), 1, outputs).unwrap()
+}
+
+#[test]
+fn modality_outputs() {
+    assert_eq!(
+        Ok((
+            "ADT".to_string(),
+            ["adt_1.fq".to_string(), "adt_2.fq".to_string()]
+        )),
+        modality_output("ADT=adt_1.fq,adt_2.fq")
+    );
+    assert_eq!(
+        Ok(("HTO".to_string(), ["hto.fq".to_string(), String::new()])),
+        modality_output("HTO=hto.fq")
+    );
+    // named by the template
+    assert_eq!(
+        Ok(("ADT".to_string(), [String::new(), String::new()])),
+        modality_output("ADT")
+    );
+    assert!(modality_output("ADT=").is_err());
+    assert!(modality_output("=adt.fq").is_err());
+}
+
+#[test]
+fn templated_outputs() {
+    let template = out_template("out/{sample}_S{idx}_R{read}_001.fastq.gz").unwrap();
+
+    assert_eq!(
+        Ok([
+            "out/HTO_S2_R1_001.fastq.gz".to_string(),
+            "out/HTO_S2_R2_001.fastq.gz".to_string()
+        ]),
+        template_outputs(&template, "HTO", 2, 2)
+    );
+    assert_eq!(
+        Ok(["ADT_R1.fq".to_string(), String::new()]),
+        template_outputs("{sample}_R{read}.fq", "ADT", 1, 1)
+    );
+    assert!(template_outputs("{sample}.fq", "ADT", 1, 2).is_err());
+
+    assert!(out_template("{sample}_{lane}.fq").is_err());
+    assert!(out_template("{sample.fq").is_err());
+    assert!(out_template("R{read}.fq").is_err());
+
+    // a modality needs outputs of its own or from the template
+    let outputs = vec![modality_output("ADT").unwrap()];
+    assert!(
+        Features::new("CD3,ACGT,ADT\n", "fb".to_string(), 1, outputs)
+            .unwrap_err()
+            .contains("--out-template")
+    );
+}
+
+#[test]
+fn classify_features() {
+    let features = features();
+
+    assert_eq!("CD3", features.classify(b"ACGTACGTNNNN").unwrap().name);
+    assert_eq!("CD4", features.classify(b"TTGCAACG").unwrap().name);
+    assert_eq!(1, features.classify(b"GGGGAAAA").unwrap().modality);
+    assert!(features.classify(b"CCCCCCCC").is_none());
+    assert!(features.classify(b"ACGT").is_none());
+
+    assert_eq!(1, features.count(0));
+    assert_eq!(2, features.unmatched());
+    assert_eq!(
+        "ADT: 2 reads\n  CD3: 1\n  CD4: 1\nHTO: 1 reads\n  HTO1: 1\n2 reads matching no feature\n",
+        features.report()
+    );
+}
+
+#[test]
+fn invalid_features() {
+    let outputs = || vec![modality_output("ADT=adt.fq").unwrap()];
+
+    assert!(
+        Features::new("CD3,ACGT,HTO\n", "fb".to_string(), 1, outputs())
+            .unwrap_err()
+            .contains("no --modality-out")
+    );
+    assert!(Features::new("CD3,ACGT\n", "fb".to_string(), 1, outputs()).is_err());
+    assert!(Features::new(
+        "CD3,ACGT,ADT\nCD4,ACXX,ADT\n",
+        "fb".to_string(),
+        1,
+        outputs()
+    )
+    .is_err());
+    assert!(Features::new("", "fb".to_string(), 1, outputs()).is_err());
+}
@@ -0,0 +1,330 @@
+use std::{fs, sync::Arc};
+
+use seqproc::{
+    filters::{
+        complexity::{dust_score, poly_g_len, ComplexityFilter},
+        dedup::{dedup_key, DedupConfig, DedupMode, DuplicateSet},
+        name::{name_pattern, tile, NameFilter, Tile},
+        umi::{is_homopolymer, mean_qual, UmiFailure, UmiFilter},
+        whitelist::{Whitelist, WhitelistLengths},
+        OnFail,
+    },
+    interpret::InterpretOptions,
+    monitor::metrics::Metrics,
+    report::Report,
+    run,
+    runner::{CancellationToken, RunConfig},
+    sink::qc_failures,
+};
+
+#[test]
+fn homopolymer() {
+    assert!(is_homopolymer(b"AAAAAAAA"));
+    assert!(!is_homopolymer(b"AAAAAAAC"));
+    assert!(!is_homopolymer(b""));
+}
+
+#[test]
+fn mean_quality() {
+    assert_eq!(40.0, mean_qual(b"IIII"));
+    assert_eq!(20.0, mean_qual(b"5555"));
+}
+
+#[test]
+fn umi_filter() {
+    let filter = UmiFilter {
+        homopolymer: true,
+        ambiguous: true,
+        min_mean_qual: Some(20.0),
+        flag: false,
+    };
+
+    assert_eq!(None, filter.check(b"ACGTACGT", Some(b"IIIIIIII")));
+    assert_eq!(
+        Some(UmiFailure::Homopolymer),
+        filter.check(b"TTTTTTTT", Some(b"IIIIIIII"))
+    );
+    assert_eq!(
+        Some(UmiFailure::Ambiguous),
+        filter.check(b"ACGTNCGT", Some(b"IIIIIIII"))
+    );
+    assert_eq!(
+        Some(UmiFailure::LowQuality),
+        filter.check(b"ACGTACGT", Some(b"########"))
+    );
+}
+
+#[test]
+fn inactive_umi_filter() {
+    let filter = UmiFilter::default();
+
+    assert!(!filter.is_active());
+    assert_eq!(None, filter.check(b"NNNNNNNN", Some(b"########")));
+}
+
+#[test]
+fn dedup_key_prefix() {
+    let key = dedup_key(&[b"ACGT", b"TTGG"], &[b"CCCCAAAA"], 4);
+
+    assert_eq!(b"ACGT|TTGG|CCCC|".to_vec(), key);
+}
+
+#[test]
+fn approximate_duplicates() {
+    let duplicates = DuplicateSet::new(&DedupMode::Approximate).unwrap();
+
+    assert!(duplicates.insert(b"ACGT|TTGG|CCCC|").unwrap());
+    assert!(duplicates.insert(b"ACGT|TTGA|CCCC|").unwrap());
+    assert!(!duplicates.insert(b"ACGT|TTGG|CCCC|").unwrap());
+}
+
+#[test]
+fn exact_duplicates() {
+    let path = std::env::temp_dir().join(format!("seqproc_dedup_{}", std::process::id()));
+    let duplicates = DuplicateSet::new(&DedupMode::Exact(path.clone())).unwrap();
+
+    assert!(duplicates.insert(b"ACGT|TTGG|CCCC|").unwrap());
+    assert!(duplicates.insert(b"ACGT|TTGA|CCCC|").unwrap());
+    assert!(!duplicates.insert(b"ACGT|TTGA|CCCC|").unwrap());
+
+    drop(duplicates);
+
+    assert!(!path.exists());
+}
+
+#[test]
+fn whitelist_length() {
+    let whitelist = Whitelist::from_lines("ACGTACGT\nacgtacgtac\t12\n\nTTTTTTTTTTTT\n");
+
+    assert!(whitelist.contains(b"ACGTACGTAC"));
+    assert_eq!(Some(10), whitelist.length(b"ACGTACGTACGGAAAA", 8..=12));
+    assert_eq!(Some(8), whitelist.length(b"ACGTACGTTTTTTTTT", 8..=12));
+    assert_eq!(None, whitelist.length(b"ACGTACGTACGT", 9..=9));
+    assert_eq!(None, whitelist.length(b"TTTTTTTTTTT", 8..=12));
+}
+
+#[test]
+fn whitelist_best_length() {
+    let whitelist = Whitelist::from_lines("ACGTACGT\nACGTACGTAC\nGGGGCCCCAA\n");
+
+    // a mismatch in the longer barcode still leaves the shorter one exact
+    assert_eq!(
+        Some((8, 0)),
+        whitelist.best_length(b"ACGTACGTTCGG", 8..=12, 1)
+    );
+    assert_eq!(
+        Some((10, 1)),
+        whitelist.best_length(b"GGGGCCGCAATT", 8..=12, 1)
+    );
+    assert_eq!(None, whitelist.best_length(b"GGGGCCGCTATT", 8..=12, 1));
+    assert_eq!(
+        Some((10, 2)),
+        whitelist.best_length(b"GGGGCCGCTATT", 8..=12, 2)
+    );
+
+    let lengths = WhitelistLengths::default();
+    lengths.insert(Some((8, 0)));
+    lengths.insert(Some((10, 1)));
+    lengths.insert(None);
+
+    assert_eq!(2, lengths.resolved());
+    assert_eq!(
+        "Lengths from whitelist: 2 reads, 1 corrected, 1 unresolved\n     8 1\n    10 1\n",
+        lengths.report()
+    );
+}
+
+#[test]
+fn name_filter() {
+    let lane1 = b"A00123:8:HFWKJDSX2:1:1101:1000:1000 1:N:0:ACGT";
+    let lane2 = b"A00123:8:HFWKJDSX2:2:1101:1000:1000 1:N:0:ACGT";
+
+    let filter = NameFilter::default();
+    assert!(!filter.is_active());
+    assert!(filter.keeps(lane2));
+
+    let filter = NameFilter {
+        include: Some(name_pattern("^@A00.*:1:").unwrap()),
+        exclude: None,
+        tiles: Vec::new(),
+    };
+    assert!(filter.keeps(lane1));
+    assert!(!filter.keeps(lane2));
+
+    let filter = NameFilter {
+        include: None,
+        exclude: Some(name_pattern(":1101:").unwrap()),
+        tiles: Vec::new(),
+    };
+    assert!(!filter.keeps(lane1));
+
+    assert!(name_pattern("[ACGT").is_err());
+}
+
+#[test]
+fn tile_filter() {
+    assert_eq!(
+        Ok(Tile {
+            lane: Some(2),
+            tile: 2204
+        }),
+        tile("2:2204")
+    );
+    assert_eq!(
+        Ok(Tile {
+            lane: None,
+            tile: 1101
+        }),
+        tile("1101")
+    );
+    assert!(tile("1:").is_err());
+    assert!(tile("lane1").is_err());
+
+    let filter = NameFilter {
+        tiles: vec![tile("2:2204").unwrap(), tile("1101").unwrap()],
+        ..Default::default()
+    };
+    assert!(filter.is_active());
+    assert!(!filter.keeps(b"A00123:8:HFWKJDSX2:2:2204:1000:1000 1:N:0:ACGT"));
+    assert!(filter.keeps(b"A00123:8:HFWKJDSX2:1:2204:1000:1000 1:N:0:ACGT"));
+    assert!(!filter.keeps(b"HWUSI-EAS100R:4:1101:941:1973#0/1"));
+    assert!(filter.keeps(b"SRR001666.1 length=36"));
+}
+
+#[test]
+fn poly_g_tails() {
+    assert_eq!(12, poly_g_len(b"ACGTACGTACGGGGGGGGGGGG"));
+    // one other base in every eight
+    assert_eq!(16, poly_g_len(b"ACGTACGTACGGGGGGGAGGGGGGGG"));
+    assert_eq!(0, poly_g_len(b"ACGTACGTGGGG"));
+    assert_eq!(0, poly_g_len(b""));
+}
+
+#[test]
+fn dust_scores() {
+    assert!(dust_score(b"ACGTTGCAGCTAGCATCGATCGGATCAGCTAGCTAGGACT") < 1.0);
+    assert!(dust_score(&[b'A'; 50]) > 20.0);
+    assert!(dust_score(&b"CA".repeat(25)) > 10.0);
+    assert_eq!(0.0, dust_score(b"ACG"));
+}
+
+#[test]
+fn complexity_filter() {
+    let filter = ComplexityFilter::new(true, Some(7.0));
+
+    assert_eq!((0, true), filter.check(b""));
+    assert_eq!(
+        (40, true),
+        filter.check(b"ACGTTGCAGCTAGCATCGATCGGATCAGCTAGCTAGGACTGGGGGGGGGGGG")
+    );
+    assert_eq!((0, false), filter.check(&[b'G'; 30]));
+    assert_eq!((40, false), filter.check(&b"CA".repeat(20)));
+
+    assert_eq!(
+        "Poly-G: 2 reads trimmed of 42 bases, 1 left empty and dropped\n\
+         Low complexity: 1 reads scoring above 7 dropped\n",
+        filter.report()
+    );
+}
+
+// run single end `reads` through the geometry, returning what was written
+fn run_reads(name: &str, geometry: &str, reads: &str, options: InterpretOptions) -> String {
+    let dir = std::env::temp_dir().join(format!("seqproc_{name}_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+
+    fs::write(dir.join("r1.fastq"), reads).unwrap();
+
+    let config = RunConfig {
+        geometry: geometry.to_string(),
+        file1: path("r1.fastq"),
+        file2: None,
+        out1: path("out1.fastq"),
+        out2: String::new(),
+        threads: 1,
+        options,
+    };
+    run(config, |_| (), CancellationToken::new()).unwrap();
+
+    let out = fs::read_to_string(dir.join("out1.fastq")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    out
+}
+
+const FLAGGED_GEOMETRY: &str = "1{b<cb>[4]x[2]u<umi>[4]r:}";
+
+fn umi_filter() -> UmiFilter {
+    UmiFilter {
+        homopolymer: true,
+        ambiguous: false,
+        min_mean_qual: None,
+        flag: false,
+    }
+}
+
+#[test]
+fn flagged_reads() {
+    let metrics = Arc::new(Metrics::default());
+    let options = InterpretOptions {
+        on_fail: OnFail::Flag,
+        umi_filter: umi_filter(),
+        metrics: Some(metrics.clone()),
+        ..InterpretOptions::default()
+    };
+
+    // b fails the UMI filter after its discarded bases were removed, it is
+    // written as it was read with the check it failed
+    let out = run_reads(
+        "flagged",
+        FLAGGED_GEOMETRY,
+        "@a\nACGTGGACGATTTTT\n+\nABCDEFGHIJKLMNO\n\
+         @b\nACGTGGAAAACCCCC\n+\nABCDEFGHIJKLMNO\n",
+        options,
+    );
+    assert_eq!(
+        "@a\nACGTACGATTTTT\n+\nABCDGHIJKLMNO\n\
+         @b QC:fail:umi\nACGTGGAAAACCCCC\n+\nABCDEFGHIJKLMNO\n",
+        out
+    );
+    assert_eq!(vec!["umi"], qc_failures(b"b QC:fail:umi"));
+
+    // flagged reads are kept but not counted as passed
+    let (reads, passed) = metrics.counts();
+    let report = Report {
+        reads,
+        passed,
+        ..Report::default()
+    };
+    assert_eq!(1, report.failed());
+}
+
+#[test]
+fn dedup_flagged_reads() {
+    let options = InterpretOptions {
+        on_fail: OnFail::Flag,
+        umi_filter: umi_filter(),
+        dedup: Some(DedupConfig {
+            prefix_len: 4,
+            mode: DedupMode::Approximate,
+            flag: false,
+        }),
+        ..InterpretOptions::default()
+    };
+
+    // b has no UMI to deduplicate on once flagged, c is a duplicate of a
+    let out = run_reads(
+        "dedup_flagged",
+        FLAGGED_GEOMETRY,
+        "@a\nACGTGGACGATTTTT\n+\nABCDEFGHIJKLMNO\n\
+         @b\nACGTGGAAAACCCCC\n+\nABCDEFGHIJKLMNO\n\
+         @c\nACGTGGACGATTTTT\n+\nABCDEFGHIJKLMNO\n",
+        options,
+    );
+    assert_eq!(
+        "@a\nACGTACGATTTTT\n+\nABCDGHIJKLMNO\n\
+         @b QC:fail:umi\nACGTGGAAAACCCCC\n+\nABCDEFGHIJKLMNO\n\
+         @c QC:fail:duplicate\nACGTGGACGATTTTT\n+\nABCDEFGHIJKLMNO\n",
+        out
+    );
+}
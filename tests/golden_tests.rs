@@ -0,0 +1,61 @@
+use std::fs;
+
+use seqproc::golden::{diff, Case, SHOWN_LINES};
+
+const EXPECTED: &str = "@r1\nACGT\n+\nIIII\n@r2\nGGCC\n+\nIIII\n";
+
+#[test]
+fn same_outputs() {
+    assert_eq!(None, diff("expected_r1.fastq", EXPECTED, EXPECTED));
+}
+
+#[test]
+fn differing_record() {
+    let actual = "@r1\nACGT\n+\nIIII\n@r2\nGGCA\n+\nIIII\n";
+
+    assert_eq!(
+        Some(
+            "--- expected_r1.fastq\n+++ output\n\
+            @@ record 2, sequence @@\n-GGCC\n+GGCA\n\
+            2 records expected, 2 output\n"
+                .to_string()
+        ),
+        diff("expected_r1.fastq", EXPECTED, actual)
+    );
+}
+
+#[test]
+fn missing_records() {
+    let expected = EXPECTED.repeat(2);
+    let d = diff("expected_r1.fastq", &expected, "").unwrap();
+
+    assert!(d.contains("@@ record 1, name @@\n-@r1\n"));
+    assert_eq!(SHOWN_LINES, d.matches("@@ record").count());
+    assert!(d.ends_with("and 8 more lines\n4 records expected, 0 output\n"));
+}
+
+#[test]
+fn open_case() {
+    let dir = std::env::temp_dir().join(format!("seqproc_case_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    assert!(Case::open(&dir).is_err());
+
+    fs::write(dir.join("geom.fgdl"), "1{b<bc>[4]r:}2{r:}").unwrap();
+    fs::write(dir.join("r1.fastq"), EXPECTED).unwrap();
+    fs::write(dir.join("r2.fastq"), EXPECTED).unwrap();
+    assert!(Case::open(&dir).is_err());
+
+    fs::write(dir.join("expected_r1.fastq"), EXPECTED).unwrap();
+    fs::write(dir.join("args"), "whitelist.txt\n\n").unwrap();
+    let case = Case::open(&dir).unwrap();
+
+    assert_eq!(2, case.inputs.len());
+    assert_eq!(vec![dir.join("expected_r1.fastq")], case.expected);
+    assert_eq!(
+        vec![dir.join("whitelist.txt").to_string_lossy().into_owned()],
+        case.additional_args
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,50 @@
+use seqproc::header::{casava_indices, has_indices, illumina_tile};
+
+#[test]
+fn casava_header() {
+    assert_eq!(
+        Some(vec![&b"ACGTACGT"[..]]),
+        casava_indices(b"A00123:8:H2:1:1101:1000:2000 1:N:0:ACGTACGT")
+    );
+    assert_eq!(
+        Some(vec![&b"ACGTACGT"[..], &b"TTGGCCAN"[..]]),
+        casava_indices(b"A00123:8:H2:1:1101:1000:2000 2:Y:0:ACGTACGT+TTGGCCAN")
+    );
+}
+
+#[test]
+fn not_casava_header() {
+    for name in [
+        &b"SRR001666.1 071112_SLXA-EAS1_s_7:5:1:817:345 length=36"[..],
+        b"A00123:8:H2:1:1101:1000:2000",
+        b"A00123:8:H2:1:1101:1000:2000 1:N:0:",
+        b"A00123:8:H2:1:1101:1000:2000 1:N:0:ACGT+",
+        b"A00123:8:H2:1:1101:1000:2000 1:X:0:ACGT",
+        b"A00123:8:H2:1:1101:1000:2000 1:N:0:ACGT QC:fail:duplicate",
+    ] {
+        assert_eq!(None, casava_indices(name));
+    }
+}
+
+#[test]
+fn index_lengths() {
+    let name = b"A00123:8:H2:1:1101:1000:2000 1:N:0:ACGTACGT+TTGGCC";
+
+    assert!(has_indices(name, &[8, 6]));
+    assert!(!has_indices(name, &[8]));
+    assert!(!has_indices(name, &[8, 8]));
+}
+
+#[test]
+fn illumina_tiles() {
+    assert_eq!(
+        Some((1, 1101)),
+        illumina_tile(b"A00123:8:H2:1:1101:1000:2000 1:N:0:ACGT")
+    );
+    assert_eq!(
+        Some((6, 73)),
+        illumina_tile(b"HWUSI-EAS100R:6:73:941:1973#0/1")
+    );
+    assert_eq!(None, illumina_tile(b"SRR001666.1 length=36"));
+    assert_eq!(None, illumina_tile(b"A00123:8:H2:1:1101:x:2000"));
+}
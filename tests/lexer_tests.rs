@@ -135,3 +135,27 @@ fn arguments() {
         res.unwrap().iter().map(|(tok, _)| tok).collect::<Vec<_>>()
     )
 }
+
+#[test]
+fn repeat_count() {
+    let src = "(b[8])x3";
+
+    let (res, err) = lexer().parse_recovery(src);
+
+    assert_eq!(err.len(), 0);
+
+    let result = vec![
+        &Token::Ctrl('('),
+        &Token::Barcode,
+        &Token::Ctrl('['),
+        &Token::Num(8),
+        &Token::Ctrl(']'),
+        &Token::Ctrl(')'),
+        &Token::Times(3),
+    ];
+
+    assert_eq!(
+        result,
+        res.unwrap().iter().map(|(tok, _)| tok).collect::<Vec<_>>()
+    )
+}
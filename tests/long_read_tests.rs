@@ -0,0 +1,76 @@
+use seqproc::long_read::{
+    count_anchors, orientation, revcomp, segment_name, split_concatemer, Strand,
+};
+
+#[test]
+fn reverse_complement() {
+    assert_eq!(b"ACGTT".to_vec(), revcomp(b"AACGT"));
+}
+
+#[test]
+fn forward_anchor() {
+    let anchors = vec![b"CTACACGACGCTCTTCCGATCT".to_vec()];
+    let read = b"GGGCTACACGACGCTCTTCCGATCTACGTACGTACGTACGT";
+
+    assert_eq!(1, count_anchors(read, &anchors));
+    assert_eq!(Some(Strand::Forward), orientation(read, &anchors));
+}
+
+#[test]
+fn reverse_anchor() {
+    let anchors = vec![b"CTACACGACGCTCTTCCGATCT".to_vec()];
+    let read = revcomp(b"GGGCTACACGACGCTCTTCCGATCTACGTACGTACGTACGT");
+
+    assert_eq!(0, count_anchors(&read, &anchors));
+    assert_eq!(Some(Strand::Reverse), orientation(&read, &anchors));
+}
+
+#[test]
+fn no_anchor() {
+    let anchors = vec![b"CTACACGACGCTCTTCCGATCT".to_vec()];
+
+    // left unresolved rather than taken as forward
+    assert_eq!(None, orientation(b"ACGTACGTACGT", &anchors));
+}
+
+#[test]
+fn anchor_with_errors() {
+    let anchors = vec![b"CTACACGACGCTCTTCCGATCT".to_vec()];
+    // a substitution and a deleted base in the anchor
+    let read = b"GGGCTACACGACGCTGTTCCATCTACGTACGTACGTACGT";
+
+    assert_eq!(1, count_anchors(read, &anchors));
+    assert_eq!(Some(Strand::Forward), orientation(read, &anchors));
+    assert_eq!(Some(Strand::Reverse), orientation(&revcomp(read), &anchors));
+}
+
+#[test]
+fn concatemer() {
+    let read = b"GGACGTAAAACGTCCCACGTT";
+
+    assert_eq!(vec![2..9, 9..16, 16..21], split_concatemer(read, b"ACGT"));
+    assert!(split_concatemer(b"GGGG", b"ACGT").is_empty());
+
+    // the second copy's delimiter has a substitution, the third is exact
+    let delimiter = b"AAGCAGTGGTATCAAC";
+    let read = [
+        &b"GG"[..],
+        delimiter,
+        b"TTTT",
+        b"AAGCAGTCGTATCAAC",
+        b"CCCC",
+        delimiter,
+        b"GGGG",
+    ]
+    .concat();
+    assert_eq!(
+        vec![2..22, 22..42, 42..62],
+        split_concatemer(&read, delimiter)
+    );
+}
+
+#[test]
+fn concatemer_names() {
+    assert_eq!(b"read1_2 1:N:0".to_vec(), segment_name(b"read1 1:N:0", 2));
+    assert_eq!(b"read1_0".to_vec(), segment_name(b"read1", 0));
+}
@@ -0,0 +1,86 @@
+use seqproc::matchers::{
+    backend_for, find_ignoring_case, matcher_choice, split_hit, Backend, KmerMatcher, Matcher,
+    MatcherChoice, MyersMatcher, PrefixMatcher,
+};
+
+#[test]
+fn kmer_matcher() {
+    let text = b"TTTTACGTAGCATTTT";
+
+    assert_eq!(Some(4..12), KmerMatcher.find(b"ACGTAGCA", text, 0));
+    // one mismatch in the second half, the first half seeds the hit
+    assert_eq!(Some(4..12), KmerMatcher.find(b"ACGTAGGA", text, 1));
+    assert_eq!(None, KmerMatcher.find(b"ACGTAGGA", text, 0));
+    assert_eq!(None, KmerMatcher.find(b"GGGGGGGG", text, 1));
+}
+
+#[test]
+fn myers_matcher() {
+    let text = b"TTTTACGTAGCATTTT";
+
+    assert_eq!(Some(4..12), MyersMatcher.find(b"ACGTAGCA", text, 0));
+    assert_eq!(Some(4..12), MyersMatcher.find(b"ACGTTGCA", text, 1));
+    // a base missing from the read
+    assert_eq!(Some(4..12), MyersMatcher.find(b"ACGTAAGCA", text, 1));
+    // an extra base in the read
+    assert_eq!(Some(4..12), MyersMatcher.find(b"ACGTGCA", text, 1));
+    assert_eq!(None, MyersMatcher.find(b"GGGGGGGG", text, 2));
+}
+
+#[test]
+fn prefix_matcher() {
+    assert_eq!(Some(0..4), PrefixMatcher.find(b"ACGT", b"ACGTTTTT", 0));
+    assert_eq!(Some(0..4), PrefixMatcher.find(b"ACGT", b"AGGTTTTT", 1));
+    assert_eq!(None, PrefixMatcher.find(b"ACGT", b"TACGTTTT", 1));
+    assert_eq!(None, PrefixMatcher.find(b"ACGT", b"ACG", 1));
+}
+
+#[test]
+fn split_at_hit() {
+    // the hit is cut as the read has it, mismatches and case kept
+    let [before, hit, after] = split_hit(b"TTAGgTTT", &(2..6));
+    assert_eq!((&b"TT"[..], &b"AGgT"[..], &b"TT"[..]), (before, hit, after));
+
+    // a hit with a base missing is shorter than the sequence
+    let text = b"TTACTTT";
+    let found = MyersMatcher.find(b"ACGT", text, 1).unwrap();
+    assert_eq!(b"TTACTTT".to_vec(), split_hit(text, &found).concat());
+}
+
+#[test]
+fn soft_masked_hit() {
+    let text = b"ttttACGtagcaTTTT";
+
+    // backends compare bases as they are, case is only folded around them
+    assert_eq!(None, KmerMatcher.find(b"ACGTAGCA", text, 0));
+    let hit = find_ignoring_case(&KmerMatcher, b"ACGTAGCA", text, 0).unwrap();
+    assert_eq!(4..12, hit);
+    assert_eq!(
+        Some(4..12),
+        find_ignoring_case(&MyersMatcher, b"acgtagca", text, 0)
+    );
+
+    // the read keeps its case
+    assert_eq!(b"ACGtagca", split_hit(text, &hit)[1]);
+}
+
+#[test]
+fn matcher_choices() {
+    let choices = vec![
+        matcher_choice("myers").unwrap(),
+        matcher_choice("anchor=kmer").unwrap(),
+    ];
+
+    assert_eq!(
+        MatcherChoice {
+            segment: Some("anchor".to_string()),
+            backend: Backend::Kmer
+        },
+        choices[1]
+    );
+    assert_eq!(Backend::Kmer, backend_for(&choices, Some("anchor")));
+    assert_eq!(Backend::Myers, backend_for(&choices, Some("linker")));
+    assert_eq!(Backend::Myers, backend_for(&choices, None));
+    assert_eq!(Backend::Antisequence, backend_for(&[], None));
+    assert!(matcher_choice("blast").is_err());
+}
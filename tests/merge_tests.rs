@@ -0,0 +1,55 @@
+use seqproc::{
+    long_read::complement,
+    merge::{merge_pair, MergeConfig},
+};
+
+fn config() -> MergeConfig {
+    MergeConfig {
+        min_overlap: 6,
+        max_diff: 0.2,
+        out: String::new(),
+    }
+}
+
+fn revcomp(seq: &[u8]) -> Vec<u8> {
+    seq.iter().rev().map(|n| complement(*n)).collect()
+}
+
+#[test]
+fn overlapping_pair() {
+    let insert = b"ACGTTGCAAGGCTTACCGATGCA";
+    let seq1 = &insert[..16];
+    let seq2 = revcomp(&insert[7..]);
+
+    let (seq, qual) = merge_pair(seq1, &[b'I'; 16], &seq2, &[b'I'; 16], &config()).unwrap();
+
+    assert_eq!(insert.to_vec(), seq);
+    assert_eq!(insert.len(), qual.len());
+}
+
+#[test]
+fn mismatch_takes_higher_quality() {
+    let insert = b"ACGTTGCAAGGCTTACCGATGCA";
+    let seq1 = b"ACGTTGCAAGGCTTAG";
+    let seq2 = revcomp(&insert[7..]);
+
+    let mut qual2 = [b'I'; 16];
+    qual2[16 - 1 - 8] = b'#';
+
+    let (seq, _) = merge_pair(seq1, &[b'5'; 16], &seq2, &[b'I'; 16], &config()).unwrap();
+    assert_eq!(insert.to_vec(), seq);
+
+    let (seq, _) = merge_pair(seq1, &[b'5'; 16], &seq2, &qual2, &config()).unwrap();
+    assert_eq!(b"ACGTTGCAAGGCTTAGCGATGCA".to_vec(), seq);
+}
+
+#[test]
+fn no_overlap() {
+    let seq1 = b"ACGTTGCAAGGCTTAC";
+    let seq2 = b"GGGGGGGGCCCCCCCC";
+
+    assert_eq!(
+        None,
+        merge_pair(seq1, &[b'I'; 16], seq2, &[b'I'; 16], &config())
+    );
+}
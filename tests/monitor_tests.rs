@@ -0,0 +1,123 @@
+use std::{net::UdpSocket, sync::Arc, time::Duration};
+
+use seqproc::monitor::{
+    metrics::{metrics_target, Emitter, Metrics, MetricsTarget, Sample},
+    PassRate, Timings,
+};
+
+#[test]
+fn pass_rate_window() {
+    let pass_rate = PassRate::new(0.5, 100);
+
+    // every read passes
+    for _ in 0..200 {
+        assert!(pass_rate.seen().is_ok());
+        pass_rate.passed();
+    }
+
+    // the window slides past the passing reads, failing once most of it drops
+    let failed = (0..100).position(|_| pass_rate.seen().is_err());
+
+    assert_eq!(Some(59), failed);
+}
+
+#[test]
+fn pass_rate_message() {
+    let pass_rate = PassRate::new(0.5, 10);
+
+    let err = (0..10).map(|_| pass_rate.seen()).last().unwrap();
+
+    assert_eq!(
+        Err("Only 0.0% of the last 10 reads passed, below the minimum of 50.0%, the geometry may not fit the data".to_string()),
+        err
+    );
+}
+
+#[test]
+fn stage_timings() {
+    let timings = Timings::default();
+
+    timings.add("io", Duration::from_millis(750));
+    timings.add("match", Duration::from_millis(200));
+    timings.add("io", Duration::from_millis(50));
+
+    assert_eq!(
+        "io             0.800s   80.0%\nmatch          0.200s   20.0%\n",
+        timings.report()
+    );
+}
+
+#[test]
+fn stage_reads() {
+    let timings = Timings::default();
+
+    timings.mark("io");
+    timings.mark("cut");
+    timings.mark("io");
+    timings.add("cut", Duration::from_millis(10));
+
+    assert_eq!(vec![("io", 2), ("cut", 2)], timings.reads());
+}
+
+#[test]
+fn terminal_sample() {
+    let sample = Sample {
+        seen: 200,
+        passed: 150,
+        reads_per_sec: 1000.0,
+        pass_rate: 0.75,
+        bytes_written: 4096,
+        stages: vec![("cut", 200), ("match", 150)],
+    };
+
+    assert_eq!(
+        "\x1b[H\x1b[Jseqproc  200 reads  150 passed (75.0%)  1000 reads/s  4096 bytes written\n\n\
+        stage              reads   share\n\
+        cut                  200  100.0%\n\
+        match                150   75.0%\n",
+        sample.terminal()
+    );
+}
+
+#[test]
+fn metrics_targets() {
+    assert_eq!(
+        Ok(MetricsTarget::Statsd("localhost:8125".to_string())),
+        metrics_target("statsd://localhost:8125")
+    );
+    assert_eq!(
+        Ok(MetricsTarget::Pushgateway {
+            host: "gateway:9091".to_string(),
+            path: "/metrics/job/seqproc".to_string()
+        }),
+        metrics_target("http://gateway:9091/")
+    );
+    assert!(metrics_target("udp://localhost:8125").is_err());
+}
+
+#[test]
+fn statsd_sample_on_drop() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let target = MetricsTarget::Statsd(socket.local_addr().unwrap().to_string());
+
+    let metrics = Arc::new(Metrics::default());
+    let emitter = Emitter::start(target, metrics.clone(), Vec::new(), Duration::from_secs(60));
+
+    for i in 0..4 {
+        metrics.seen();
+        if i % 2 == 0 {
+            metrics.passed();
+        }
+    }
+    drop(emitter);
+
+    let mut buf = [0; 512];
+    let n = socket.recv(&mut buf).unwrap();
+    let sample = String::from_utf8_lossy(&buf[..n]);
+
+    assert!(sample.starts_with("seqproc.reads:4|g\nseqproc.reads_passed:2|g\n"));
+    assert!(sample.contains("seqproc.pass_rate:0.5000|g"));
+}
@@ -3,7 +3,7 @@ use std::ops::Deref;
 use chumsky::{prelude::*, Stream};
 use seqproc::{
     lexer::lexer,
-    parser::{parser, Expr, Function, Size, Type},
+    parser::{parser, Expr, Function, Miss, Size, Type, SPEC_VERSION},
 };
 
 #[test]
@@ -22,7 +22,7 @@ fn definition() {
     let expected_res = (
         Expr::Definitions(vec![(
             Expr::LabeledGeomPiece(
-                Box::new(Expr::Label(("brc".to_string(), 0..3))),
+                ("brc".to_string(), 0..3),
                 Box::new((
                     Expr::GeomPiece(Type::Barcode, Size::FixedLen((10, 8..10))),
                     6..11,
@@ -254,7 +254,7 @@ fn labeled_unbounded() {
         (1, 0..1),
         vec![(
             Expr::LabeledGeomPiece(
-                Box::new(Expr::Label(("barcode".to_string(), 3..12))),
+                ("barcode".to_string(), 3..12),
                 Box::new((Expr::GeomPiece(Type::Barcode, Size::UnboundedLen), 2..13)),
             ),
             2..13,
@@ -358,7 +358,10 @@ fn fixed_seq() {
     let expected_res = Expr::Read(
         (1, 0..1),
         vec![(
-            Expr::GeomPiece(Type::FixedSeq, Size::FixedSeq(("GATCU".to_string(), 4..9))),
+            Expr::GeomPiece(
+                Type::FixedSeq,
+                Size::FixedSeq(("GATCU".to_string(), 4..9), Miss::Drop, None),
+            ),
             2..10,
         )],
     );
@@ -423,3 +426,389 @@ fn fail_map() {
     assert_eq!(0, lex_err.len());
     assert_eq!(1, parser_err.len());
 }
+
+#[test]
+fn single_read() {
+    let src = "1{b[16]u[12]f[TTTTT]r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert_eq!(1, reads.len());
+}
+
+#[test]
+fn repeat() {
+    let src = "repeat{f[ACGT]b[16]u[12]r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert!(matches!(reads.as_slice(), [Expr::Repeat(exprs)] if exprs.len() == 4));
+}
+
+#[test]
+fn repeated_group() {
+    let src = "1{(b[8]f[ACGTACGT])x3u[8]r:}2{r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert!(matches!(
+        &reads[0],
+        Expr::Read(_, exprs) if matches!(exprs[0].0, Expr::Repeated(ref group, 3) if group.len() == 2)
+    ));
+}
+
+#[test]
+fn optional_and_labeled_groups() {
+    let src = "1{cb=(b[8]f[ACGT]b[8])opt(f[GGAA]u[8])r:}2{r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    let exprs = if let Expr::Read(_, exprs) = &reads[0] {
+        exprs
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert!(matches!(
+        &exprs[0].0,
+        Expr::LabeledGeomPiece(_, group) if matches!(group.0, Expr::Group(ref pieces) if pieces.len() == 3)
+    ));
+    assert!(matches!(&exprs[1].0, Expr::Optional(pieces) if pieces.len() == 2));
+}
+
+#[test]
+fn passthrough_read() {
+    let src = "1{b<bc>[16]u<umi>[12]}0{} -> 1{<umi><bc>}0{}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert!(matches!(&reads[1], Expr::Read((0, _), exprs) if exprs.is_empty()));
+}
+
+#[test]
+fn recover_each_read() {
+    let src = "1{b[10]f[AC}2{r:->}";
+
+    let (res, _) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    assert!(res.is_some());
+    assert_eq!(2, parser_err.len());
+}
+
+#[test]
+fn miss_policies() {
+    let src = "1{b[9-10]f[GAGT;miss=fallback-fixed]u[12]f<l>[ACGT;miss=keep-raw]r:}2{r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert_eq!(
+        "1{Barcode[9-10] FixedSeq[GAGT;miss=fallback-fixed] Umi[12] l=FixedSeq[ACGT;miss=keep-raw] ReadSeq:}",
+        reads[0].to_string()
+    );
+}
+
+#[test]
+fn search_window() {
+    let src = "1{b[16]f[CAGAGC]@10..20u[12]r:}2{r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert_eq!(
+        "1{Barcode[16] FixedSeq[CAGAGC]@10..20 Umi[12] ReadSeq:}",
+        reads[0].to_string()
+    );
+}
+
+#[test]
+fn anchored_segment() {
+    let src = "1{x[0-40]f[TTGTGG]v<guide>[18-22]f[GTTTTA;miss=fallback]x:}2{r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert_eq!(
+        "1{Discard[0-40] FixedSeq[TTGTGG] anchored(guide=ReadSeq[18-22])) FixedSeq[GTTTTA;miss=fallback-fixed] Discard:}",
+        reads[0].to_string()
+    );
+}
+
+#[test]
+fn slack_segment() {
+    let src = "1{b<cb>[16;slack=3]f[TTTT]u[12]r:}2{r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert_eq!(
+        "1{slack(16(cb=Barcode[16-19])) FixedSeq[TTTT] Umi[12] ReadSeq:}",
+        reads[0].to_string()
+    );
+}
+
+// names that are not a piece type are left free for definitions
+#[test]
+fn definition_names() {
+    for name in ["v", "a", "brc", "anchored"] {
+        let src = format!("{name} = b[10] 1{{<{name}>}}2{{r:}}");
+
+        let (res, lex_err) = lexer().parse_recovery(src.as_str());
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        let (res, parser_err) =
+            parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+        assert_eq!(0, lex_err.len(), "{src}");
+        assert_eq!(0, parser_err.len(), "{src}");
+        assert!(res.is_some(), "{src}");
+    }
+}
+
+#[test]
+fn spec_version() {
+    let parse = |src: &str| {
+        let (res, _) = lexer().parse_recovery(src);
+
+        let res = res.unwrap();
+
+        let len = res.len();
+
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()))
+    };
+
+    let (res, parser_err) = parse("--spec-version 2\n1{b[16]f[CAGAGC]@10..20u[12]r:}2{r:}");
+
+    assert!(res.is_some());
+    assert_eq!(0, parser_err.len());
+
+    let (_, parser_err) = parse("--spec-version 1\n1{(b[8])x2u[12]h<i>[8]r:}0{}");
+
+    assert_eq!(3, parser_err.len());
+
+    // slack is newer than alternatives
+    let (_, parser_err) = parse("--spec-version 4\n1{b[16;slack=3]f[TTTT]r:}2{r:}");
+
+    assert_eq!(1, parser_err.len());
+    assert!(format!("{:?}", parser_err[0]).contains("slack needs spec version 5"));
+
+    let (_, parser_err) = parse("--spec-version 5\n1{b[16;slack=3]f[TTTT]r:}2{r:}");
+
+    assert_eq!(0, parser_err.len());
+
+    // anchored segments are newer than slack
+    let (_, parser_err) = parse("--spec-version 5\n1{f[TTGTGG]v[18-22]f[GTTTTA]r:}2{r:}");
+
+    assert_eq!(1, parser_err.len());
+    assert!(format!("{:?}", parser_err[0]).contains("anchored segments needs spec version 6"));
+
+    let (_, parser_err) = parse(&format!("--spec-version {}\n1{{r:}}", SPEC_VERSION + 1));
+
+    assert_eq!(1, parser_err.len());
+}
+
+#[test]
+fn pre_block() {
+    let src = "pre{qualtrim(20) adapters(truseq) adapters(\"extra.fa\") assert_len(1, 28) assert_len(90:)}1{b[16]u[12]r:}2{r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert_eq!(3, reads.len());
+    assert_eq!(
+        "pre{qualtrim(20) adapters(truseq) adapters(\"extra.fa\") assert_len(1, 28) assert_len(90:)}",
+        reads[0].to_string()
+    );
+}
+
+#[test]
+fn alternatives() {
+    let src = "1{b[4]alt{f[GGAA]b[8] | f[CCTT]b[10]}r:}2{r:}";
+
+    let (res, lex_err) = lexer().parse_recovery(src);
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (res, parser_err) =
+        parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    let reads = if let Expr::Description(_d, (r, _), _t) = res.unwrap().0 {
+        r
+    } else {
+        unreachable!()
+    };
+
+    assert_eq!(0, lex_err.len());
+    assert_eq!(0, parser_err.len());
+    assert_eq!(
+        "1{Barcode[4] alt{FixedSeq[GGAA] Barcode[8] | FixedSeq[CCTT] Barcode[10]} ReadSeq:}",
+        reads[0].to_string()
+    );
+
+    let (res, _) = lexer().parse_recovery("1{alt{f[GGAA]b[8]}r:}2{r:}");
+
+    let res = res.unwrap();
+
+    let len = res.len();
+
+    let (_, parser_err) = parser().parse_recovery(Stream::from_iter(len..len + 1, res.into_iter()));
+
+    assert!(!parser_err.is_empty());
+}
@@ -0,0 +1,170 @@
+use std::fs;
+
+use seqproc::{
+    checksum::{digest, Algorithm},
+    presets::{
+        chemistries,
+        fetch::{cached_path, fetch_whitelist, verify},
+        parse_presets, preset, read_geometry, read_geometry_of, Chemistry, PRESETS,
+    },
+    runner::compile_geometry,
+};
+
+const LAB: &str = "# the chemistries of the lab
+[tenx-v3]
+description = 10x Genomics 3' v3
+geometry = 1{b<cb>[16]u<umi>[12]x:}2{r:}
+whitelist = https://example.org/3M-february-2018.txt.gz
+whitelist_sha256 = 0123abcd
+options = --len-from-whitelist --threads 4
+
+[crispr]
+description = our own guides
+geometry = 1{x[0-8]f[CACCG]r<guide>[20]x:}
+";
+
+#[test]
+fn presets_compile() {
+    for preset in &PRESETS {
+        assert!(
+            compile_geometry(preset.geometry).is_ok(),
+            "preset {} does not compile",
+            preset.name
+        );
+    }
+}
+
+#[test]
+fn crispr_guide() {
+    let crispr = compile_geometry(preset("crispr").unwrap().geometry).unwrap();
+
+    // the guide is cut between the two anchors, and nothing else is kept
+    assert_eq!((19 + 22 + 20, None), crispr.read_lengths(0));
+    assert_eq!(
+        vec!["TTGTGGAAAGGACGAAACACCG", "GTTTTAGAGCTAGAAATAGC"],
+        crispr.anchors(0)
+    );
+}
+
+#[test]
+fn preset_geometries() {
+    assert_eq!(
+        preset("crispr").unwrap().geometry,
+        read_geometry("preset:crispr").unwrap()
+    );
+    assert!(read_geometry("preset:atac")
+        .unwrap_err()
+        .to_string()
+        .starts_with("no preset atac, expected one of crispr"));
+}
+
+#[test]
+fn preset_files() {
+    let lab = parse_presets(LAB).unwrap();
+
+    assert_eq!(
+        vec!["tenx-v3", "crispr"],
+        lab.iter().map(|c| c.name.as_str()).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Some("https://example.org/3M-february-2018.txt.gz"),
+        lab[0].whitelist.as_deref()
+    );
+    assert_eq!(Some("0123abcd"), lab[0].whitelist_sha256.as_deref());
+    assert_eq!(
+        vec!["--len-from-whitelist", "--threads", "4"],
+        lab[0].options
+    );
+
+    assert!(parse_presets("geometry = 1{r:}")
+        .unwrap_err()
+        .starts_with("line 1 is not in a [chemistry]"));
+    assert_eq!(
+        Err("chemistry empty has no geometry".to_string()),
+        parse_presets("[empty]\ndescription = nothing\n")
+    );
+    assert!(parse_presets("[x]\nwhitelists = a").is_err());
+}
+
+#[test]
+fn chemistries_of_the_user() {
+    let dir = std::env::temp_dir().join(format!("seqproc_presets_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("lab.preset"), LAB).unwrap();
+    fs::write(dir.join("notes.txt"), "not a preset").unwrap();
+
+    let file = dir.join("extra.ini");
+    fs::write(&file, "[tenx-v3]\ngeometry = 1{b[16]u[10]x:}2{r:}\n").unwrap();
+
+    let all = chemistries(Some(&dir), &[]).unwrap();
+
+    // the crispr of the lab replaces the one built in
+    assert_eq!(
+        vec!["tenx-v3", "crispr"],
+        all.iter().map(|c| c.name.as_str()).collect::<Vec<_>>()
+    );
+    assert_eq!(Some(dir.join("lab.preset")), all[1].file);
+
+    let all = chemistries(Some(&dir), &[file.display().to_string()]).unwrap();
+    assert_eq!(
+        "1{b[16]u[10]x:}2{r:}",
+        read_geometry_of("preset:tenx-v3", &all).unwrap()
+    );
+    assert!(read_geometry_of("preset:atac", &all).is_err());
+
+    // no config directory is no chemistries of the user
+    let builtin = chemistries(Some(&dir.join("missing")), &[]).unwrap();
+    assert_eq!(PRESETS.len(), builtin.len());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cached_whitelists() {
+    let dir = std::env::temp_dir().join(format!("seqproc_whitelists_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let url = "https://example.org/barcodes/3M-february-2018.txt.gz?dl=1";
+    let path = cached_path(&dir, url);
+    assert!(path
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .ends_with("-3M-february-2018.txt.gz"));
+    assert_ne!(
+        path,
+        cached_path(&dir, "https://example.org/3M-february-2018.txt.gz")
+    );
+
+    let barcodes = "AAACCCAAGAAACACT\nAAACCCAAGAAACCAT\n";
+    let sha256 = digest(Algorithm::Sha256, barcodes.as_bytes());
+
+    // a whitelist cached before is not downloaded again
+    fs::write(&path, barcodes).unwrap();
+    let mut tenx = Chemistry {
+        name: "tenx-v3".to_string(),
+        geometry: "1{map(b[16], $0, self)u[12]x:}2{r:}".to_string(),
+        whitelist: Some(url.to_string()),
+        whitelist_sha256: Some(sha256.to_uppercase()),
+        ..Chemistry::default()
+    };
+    assert_eq!(path, fetch_whitelist(&tenx, &dir).unwrap());
+
+    // a local whitelist is used in place, once its digest is checked
+    let local = dir.join("local.txt");
+    fs::write(&local, "AAACCCAAGAAACACT\n").unwrap();
+    tenx.whitelist = Some(local.display().to_string());
+    assert!(fetch_whitelist(&tenx, &dir)
+        .unwrap_err()
+        .to_string()
+        .ends_with(&format!("expected {}", sha256.to_uppercase())));
+
+    tenx.whitelist_sha256 = None;
+    assert_eq!(local, fetch_whitelist(&tenx, &dir).unwrap());
+    assert!(verify(&path, &sha256).is_ok());
+
+    tenx.whitelist = None;
+    assert!(fetch_whitelist(&tenx, &dir).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,97 @@
+use std::fs;
+
+use seqproc::{
+    preview::{sample, Preview},
+    runner::compile_geometry,
+    source::fastq::{FastqReader, FastqRecord},
+};
+
+fn record(name: &str, seq: &str) -> Vec<FastqRecord> {
+    vec![FastqRecord {
+        name: name.as_bytes().to_vec(),
+        seq: seq.as_bytes().to_vec(),
+        qual: vec![b'I'; seq.len()],
+    }]
+}
+
+#[test]
+fn preview_reads() {
+    let compiled = compile_geometry("1{b<cb>[4]hamming(f[ACGTAC], 1)u<umi>[3]r:}").unwrap();
+    let preview = Preview::new(&compiled, false);
+
+    let (shown, ok) = preview.record(&record("r1", "GGGGACGTACTTTCCAA")).unwrap();
+    assert!(ok);
+    assert_eq!(
+        "@r1
+  r1 GGGGACGTACTTTCCAA
+     bbbbffffffuuurrrr
+     cb 0-4, f 4-10, umi 10-13, r 13-17
+  matched
+",
+        shown
+    );
+
+    // the anchor has three mismatches, where one is allowed
+    let (shown, ok) = preview.record(&record("r2", "GGGGAGCAACGGGGGGG")).unwrap();
+    assert!(!ok);
+    assert!(shown.starts_with(
+        "@r2\n  r1 GGGGAGCAACGGGGGGG\n  dropped: read 1 does not match at f[ACGTAC],"
+    ));
+    assert!(shown.ends_with("closest is AGCAAC at 4, 3 mismatches where 1 are allowed\n"));
+
+    // segments are colored, fixed sequences underlined
+    let colored = Preview::new(&compiled, true)
+        .record(&record("r1", "GGGGACGTACTTTCCAA"))
+        .unwrap()
+        .0;
+    assert!(colored.contains("\x1b[34mGGGG\x1b[0m\x1b[33;4mACGTAC\x1b[0m"));
+
+    let shown = preview
+        .show(
+            &[record("r1", "GGGGACGTACTTTCCAA"), record("r2", "GGGG")],
+            10,
+        )
+        .unwrap();
+    assert!(shown.ends_with("1 of 2 reads sampled from 10 matched\n"));
+
+    // geometries the walk cannot follow are not previewed
+    let compiled = compile_geometry("1{b[4]opt(f[GGAA]b<bc>[8])u[8]r:}").unwrap();
+    assert!(Preview::new(&compiled, false)
+        .record(&record("r1", "GGGG"))
+        .is_err());
+}
+
+#[test]
+fn sampled_reads() {
+    let path = std::env::temp_dir().join(format!("seqproc_preview_{}.fastq", std::process::id()));
+    let fastq = (0..100)
+        .map(|i| format!("@read{i}\nACGT\n+\nIIII\n"))
+        .collect::<String>();
+    fs::write(&path, fastq).unwrap();
+
+    let names = |seed| {
+        let mut readers = [FastqReader::open(&path).unwrap()];
+        let (records, total) = sample(&mut readers, 5, seed).unwrap();
+        assert_eq!(100, total);
+
+        records
+            .iter()
+            .map(|r| String::from_utf8(r[0].name.clone()).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // the same seed is the same sample, kept in the order of the file
+    let sampled = names(7);
+    assert_eq!(5, sampled.len());
+    assert_eq!(sampled, names(7));
+    let mut sorted = sampled.clone();
+    sorted.sort_by_key(|n| n[4..].parse::<usize>().unwrap());
+    assert_eq!(sorted, sampled);
+    assert_ne!(sampled, names(8));
+
+    // fewer reads than asked for are all kept
+    let mut readers = [FastqReader::open(&path).unwrap()];
+    assert_eq!(100, sample(&mut readers, 500, 0).unwrap().0.len());
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,32 @@
+use seqproc::primers::PrimerPool;
+
+fn pool() -> PrimerPool {
+    PrimerPool::parse(&b">amp1 exon 2\nACGTACGT\nAA\n>amp2\nttgcaacc\n>amp3\nACGTACGTAT\n"[..])
+        .unwrap()
+}
+
+#[test]
+fn parse_fasta() {
+    assert_eq!(
+        vec![
+            ("amp1".to_string(), b"ACGTACGTAA".to_vec()),
+            ("amp2".to_string(), b"TTGCAACC".to_vec()),
+            ("amp3".to_string(), b"ACGTACGTAT".to_vec()),
+        ],
+        pool().primers
+    );
+
+    assert!(PrimerPool::parse(&b"ACGT\n>amp1\nACGT\n"[..]).is_err());
+}
+
+#[test]
+fn best_primer() {
+    let pool = pool();
+
+    assert_eq!(Some(("amp2", 8)), pool.best_match(b"TTGCAACCGGGGGGGG", 0));
+    assert_eq!(Some(("amp2", 8)), pool.best_match(b"TTGCTACCGGGGGGGG", 1));
+    assert_eq!(None, pool.best_match(b"TTGCTACCGGGGGGGG", 0));
+    assert_eq!(Some(("amp1", 10)), pool.best_match(b"ACGTACGTAAGGGGGG", 1));
+    // equally close to two primers
+    assert_eq!(None, pool.best_match(b"ACGTACGTACGGGGGG", 1));
+}
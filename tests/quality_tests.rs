@@ -0,0 +1,70 @@
+use seqproc::quality::{
+    check_lengths, detect_offset, pad, parse_qual, phred64_to_33, qual_offset, quality_trim_len,
+    QualOffset, DEFAULT_PAD_QUAL,
+};
+
+#[test]
+fn padded_quality() {
+    assert_eq!(
+        (b"AAACGT".to_vec(), Some(b"##IIII".to_vec())),
+        pad(b"ACGT", Some(b"IIII"), (2, 0), b'A', b'#')
+    );
+    assert_eq!(
+        (b"ACGTNNN".to_vec(), Some(b"IIII!!!".to_vec())),
+        pad(b"ACGT", Some(b"IIII"), (0, 3), b'N', DEFAULT_PAD_QUAL)
+    );
+    assert_eq!(
+        (b"TACGTT".to_vec(), None),
+        pad(b"ACGT", None, (1, 1), b'T', DEFAULT_PAD_QUAL)
+    );
+}
+
+#[test]
+fn length_invariant() {
+    assert!(check_lengths(b"ACGT", Some(b"IIII")).is_ok());
+    assert!(check_lengths(b"ACGT", None).is_ok());
+    assert_eq!(
+        Err("4 bases but 3 qualities".to_string()),
+        check_lengths(b"ACGT", Some(b"III"))
+    );
+}
+
+#[test]
+fn quality_char() {
+    assert_eq!(Ok(b'#'), parse_qual("#"));
+    assert!(parse_qual("").is_err());
+    assert!(parse_qual("##").is_err());
+    assert!(parse_qual(" ").is_err());
+}
+
+#[test]
+fn quality_trim() {
+    // phred 40 bases, then 2 and 35 and 2
+    assert_eq!(4, quality_trim_len(b"IIII#D##", 20));
+    assert_eq!(8, quality_trim_len(b"IIIIIIII", 20));
+    assert_eq!(0, quality_trim_len(b"####", 20));
+    // a good base on its own does not stop the trim
+    assert_eq!(2, quality_trim_len(b"II##I##", 20));
+}
+
+#[test]
+fn quality_offsets() {
+    assert_eq!(Ok(QualOffset::Auto), qual_offset("auto"));
+    assert_eq!(Ok(QualOffset::Phred64), qual_offset("64"));
+    assert!(qual_offset("solexa").is_err());
+
+    let quals = |qs: &[&'static [u8]]| detect_offset(qs.to_vec());
+    assert_eq!(Ok(QualOffset::Phred33), quals(&[b"IIII", b"#FFF"]));
+    assert_eq!(Ok(QualOffset::Phred64), quals(&[b"hhhh", b"BBBB"]));
+    assert_eq!(Ok(QualOffset::Phred33), quals(&[]));
+    // long reads, of high qualities throughout
+    assert_eq!(Ok(QualOffset::Phred33), quals(&[b"~~~~", b"@KKK"]));
+    // `@` to `J` is a valid range of either, it is refused rather than guessed
+    assert!(quals(&[b"IIII", b"@EEE"])
+        .unwrap_err()
+        .contains("give --qual-offset 33 or --qual-offset 64"));
+
+    assert_eq!(b'I', phred64_to_33(b'h'));
+    assert_eq!(b'!', phred64_to_33(b'@'));
+    assert_eq!(b'!', phred64_to_33(b'#'));
+}
@@ -0,0 +1,135 @@
+use std::{fs, io::Cursor};
+
+use seqproc::{
+    interpret::InterpretOptions,
+    repair::{mate_name, repair, verify_pairs},
+    run,
+    runner::{CancellationToken, RunConfig},
+    source::fastq::FastqReader,
+};
+
+fn reader(names: &[&str]) -> FastqReader {
+    let fastq = names
+        .iter()
+        .map(|name| format!("@{name}\nACGT\n+\nIIII\n"))
+        .collect::<String>();
+
+    FastqReader::new(Cursor::new(fastq.into_bytes()))
+}
+
+fn names(fastq: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(fastq)
+        .lines()
+        .step_by(4)
+        .map(|line| line[1..].to_string())
+        .collect()
+}
+
+#[test]
+fn mate_names() {
+    assert_eq!(b"read1", mate_name(b"read1/1"));
+    assert_eq!(b"read1", mate_name(b"read1/2"));
+    assert_eq!(b"read1", mate_name(b"read1 1:N:0:ACGT"));
+}
+
+#[test]
+fn repair_out_of_order_mates() {
+    let mut r1 = reader(&["a/1", "b/1", "c/1", "d/1"]);
+    let mut r2 = reader(&["b/2", "a/2", "d/2", "e/2"]);
+
+    let (mut out1, mut out2, mut singletons) = (Vec::new(), Vec::new(), Vec::new());
+
+    let stats = repair(
+        &mut r1,
+        &mut r2,
+        &mut out1,
+        &mut out2,
+        Some(&mut singletons),
+    )
+    .unwrap();
+
+    assert_eq!(3, stats.pairs);
+    assert_eq!(2, stats.singletons);
+    assert_eq!(vec!["b/1", "a/1", "d/1"], names(&out1));
+    assert_eq!(vec!["b/2", "a/2", "d/2"], names(&out2));
+
+    let mut singletons = names(&singletons);
+    singletons.sort();
+    assert_eq!(vec!["c/1", "e/2"], singletons);
+}
+
+#[test]
+fn truncated_record() {
+    let mut r1 = FastqReader::new(Cursor::new(b"@a\nACGT\n+\nIIII\n@b\nACGT\n".to_vec()));
+
+    assert!(r1.next_record().unwrap().is_some());
+    assert!(r1.next_record().is_err());
+}
+
+#[test]
+fn verified_pairs() {
+    let mut r1 = reader(&["a/1", "b/1 1:N:0:ACGT"]);
+    let mut r2 = reader(&["a/2", "b/2"]);
+    assert_eq!(2, verify_pairs(&mut r1, &mut r2).unwrap());
+
+    let mut r1 = reader(&["a/1", "c/1"]);
+    let mut r2 = reader(&["a/2", "b/2"]);
+    assert_eq!(
+        "the mates of pair 2 differ, c/1 and b/2",
+        verify_pairs(&mut r1, &mut r2).unwrap_err().to_string()
+    );
+
+    let mut r1 = reader(&["a/1", "b/1"]);
+    let mut r2 = reader(&["a/2"]);
+    assert_eq!(
+        "the second output ends after 1 pairs",
+        verify_pairs(&mut r1, &mut r2).unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn filtered_mates_stay_paired() {
+    let dir = std::env::temp_dir().join(format!("seqproc_mates_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+
+    // b fails in read 2 and c in read 1, each is dropped with its mate
+    fs::write(
+        dir.join("r1.fastq"),
+        "@a/1\nACGTACGT\n+\nIIIIIIII\n@b/1\nACGTACGT\n+\nIIIIIIII\n\
+         @c/1\nAC\n+\nII\n@d/1\nACGTACGT\n+\nIIIIIIII\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("r2.fastq"),
+        "@a/2\nACGTTTTT\n+\nIIIIIIII\n@b/2\nTTTTTTTT\n+\nIIIIIIII\n\
+         @c/2\nACGTTTTT\n+\nIIIIIIII\n@d/2\nACGTTTTT\n+\nIIIIIIII\n",
+    )
+    .unwrap();
+
+    let config = RunConfig {
+        geometry: "1{b[4]r:}2{f[ACGT]r:}".to_string(),
+        file1: path("r1.fastq"),
+        file2: Some(path("r2.fastq")),
+        out1: path("out1.fastq"),
+        out2: path("out2.fastq"),
+        threads: 1,
+        options: InterpretOptions::default(),
+    };
+    run(config, |_| (), CancellationToken::new()).unwrap();
+
+    let (out1, out2) = (
+        fs::read(dir.join("out1.fastq")).unwrap(),
+        fs::read(dir.join("out2.fastq")).unwrap(),
+    );
+    assert_eq!(vec!["a/1", "d/1"], names(&out1));
+    assert_eq!(vec!["a/2", "d/2"], names(&out2));
+
+    let (mut r1, mut r2) = (
+        FastqReader::new(Cursor::new(out1)),
+        FastqReader::new(Cursor::new(out2)),
+    );
+    assert_eq!(2, verify_pairs(&mut r1, &mut r2).unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
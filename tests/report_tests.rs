@@ -0,0 +1,124 @@
+use serde_json::{json, Value};
+
+use seqproc::{
+    checksum::{Algorithm, Digests},
+    report::{schema, Report, StageTiming, REPORT_VERSION},
+};
+
+#[test]
+fn report_version() {
+    let report = Report {
+        reads: 10,
+        passed: 7,
+        seconds: 1.5,
+        stages: vec![StageTiming {
+            stage: "map".to_string(),
+            seconds: 0.25,
+            reads: 9,
+        }],
+        qc: vec![("pass_rate".to_string(), 0.7)],
+        digests: None,
+    };
+
+    assert_eq!(3, report.failed());
+    assert_eq!(
+        format!("{{\"report_version\":{REPORT_VERSION},\"reads\":{{\"total\":10,\"passed\":7,\"failed\":3}},\"timings\":{{\"seconds\":1.5,\"stages\":[{{\"stage\":\"map\",\"seconds\":0.25,\"reads\":9}}]}},\"qc\":{{\"pass_rate\":0.7}}}}\n"),
+        report.json()
+    );
+
+    // a metric that is not a number is null, as JSON has no NaN
+    let report = Report {
+        qc: vec![("pass_rate".to_string(), f64::NAN)],
+        ..Report::default()
+    };
+
+    assert!(report.json().ends_with(",\"qc\":{\"pass_rate\":null}}\n"));
+
+    // the digests of `--checksum` follow
+    let report = Report {
+        digests: Some(Digests {
+            algorithm: Algorithm::Sha256,
+            inputs: vec![("r1.fq".to_string(), "ab".to_string())],
+            outputs: Vec::new(),
+        }),
+        ..Report::default()
+    };
+
+    assert!(report.json().ends_with(
+        ",\"qc\":{},\"checksum\":\"sha256\",\"inputs\":{\"r1.fq\":\"ab\"},\"outputs\":{}}\n"
+    ));
+}
+
+#[test]
+fn report_schema() {
+    let schema: Value = serde_json::from_str(&schema()).unwrap();
+
+    assert_eq!(
+        "https://json-schema.org/draft/2020-12/schema",
+        schema["$schema"]
+    );
+    assert_eq!(
+        format!("urn:seqproc:report:{REPORT_VERSION}"),
+        schema["$id"]
+    );
+    // the digests are only there with `--checksum`
+    assert_eq!(
+        json!(["report_version", "reads", "timings", "qc"]),
+        schema["required"]
+    );
+    assert_eq!(
+        json!(REPORT_VERSION),
+        schema["properties"]["report_version"]["const"]
+    );
+    assert_eq!(
+        json!(["md5", "sha256"]),
+        schema["$defs"]["Algorithm"]["enum"]
+    );
+    // fields added later do not fail parsers checking against it
+    assert_eq!(json!(true), schema["additionalProperties"]);
+}
+
+#[test]
+fn reports_follow_schema() {
+    let schema: Value = serde_json::from_str(&schema()).unwrap();
+    let validator = jsonschema::validator_for(&schema).unwrap();
+
+    let reports = [
+        Report::default(),
+        Report {
+            reads: 10,
+            passed: 7,
+            seconds: 1.5,
+            stages: vec![StageTiming {
+                stage: "map".to_string(),
+                seconds: 0.25,
+                reads: 9,
+            }],
+            qc: vec![
+                ("pass_rate".to_string(), 0.7),
+                ("duplication".to_string(), f64::NAN),
+                ("gc".to_string(), f64::INFINITY),
+            ],
+            digests: Some(Digests {
+                algorithm: Algorithm::Md5,
+                inputs: vec![("r1.fq".to_string(), "ab".to_string())],
+                outputs: vec![("out.fq".to_string(), "cd".to_string())],
+            }),
+        },
+    ];
+
+    for report in reports {
+        let json: Value = serde_json::from_str(&report.json()).unwrap();
+        assert!(validator.is_valid(&json), "{json}");
+    }
+
+    // and a report of another version, or with a metric that is not a
+    // number, does not
+    let mut json: Value = serde_json::from_str(&Report::default().json()).unwrap();
+    json["report_version"] = json!(REPORT_VERSION - 1);
+    assert!(!validator.is_valid(&json));
+
+    let mut json: Value = serde_json::from_str(&Report::default().json()).unwrap();
+    json["qc"] = json!({ "pass_rate": "high" });
+    assert!(!validator.is_valid(&json));
+}
@@ -0,0 +1,34 @@
+use seqproc::rng::Rng;
+
+#[test]
+fn same_seed_same_draws() {
+    let draws = |seed| {
+        let mut rng = Rng::for_read(seed, "subsample", b"read1");
+        (0..4).map(|_| rng.next_u64()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(draws(7), draws(7));
+    assert_ne!(draws(7), draws(8));
+}
+
+#[test]
+fn independent_streams() {
+    let mut a = Rng::for_read(0, "subsample", b"read1");
+    let mut b = Rng::for_read(0, "correction", b"read1");
+    let mut c = Rng::for_read(0, "subsample", b"read2");
+
+    let first = a.next_u64();
+    assert_ne!(first, b.next_u64());
+    assert_ne!(first, c.next_u64());
+}
+
+#[test]
+fn draws_in_range() {
+    let mut rng = Rng::new(42);
+
+    assert!((0..1000).all(|_| rng.below(10) < 10));
+
+    let hits = (0..10_000).filter(|_| rng.chance(0.25)).count();
+    assert!((2_000..3_000).contains(&hits));
+    assert!(!(0..100).any(|_| rng.chance(0.0)));
+}
@@ -0,0 +1,133 @@
+use std::{env, fs, process, sync::Arc};
+
+use seqproc::{
+    interpret::InterpretOptions,
+    merge::MergeConfig,
+    run,
+    runner::{CancellationToken, GeometryCache, RunConfig, RunError},
+    summary::UmiCounts,
+};
+
+fn config(geometry: &str) -> RunConfig {
+    RunConfig {
+        geometry: geometry.to_string(),
+        file1: "r1.fastq".to_string(),
+        file2: None,
+        out1: String::new(),
+        out2: String::new(),
+        threads: 1,
+        options: InterpretOptions::default(),
+    }
+}
+
+#[test]
+fn geometry_errors() {
+    let res = run(config("1{b[16]f[AC}"), |_| (), CancellationToken::new());
+
+    assert!(matches!(res, Err(RunError::Geometry(errs)) if !errs.is_empty()));
+
+    let res = run(
+        config("1{b<bc>[16]} -> 1{<umi>}"),
+        |_| (),
+        CancellationToken::new(),
+    );
+
+    assert!(matches!(res, Err(RunError::Geometry(errs)) if errs.len() == 1));
+
+    // options the geometry cannot follow fail before any read is processed
+    let mut counted = config("1{b[16]r:}");
+    counted.options.umi_counts = Some(Arc::new(UmiCounts::new("umis.tsv".to_string())));
+
+    assert_eq!(
+        Err(RunError::Geometry(vec![
+            "Counting UMIs needs a barcode and a UMI in the geometry".to_string()
+        ])),
+        run(counted, |_| (), CancellationToken::new())
+    );
+
+    let mut merged = config("1{b[16]r:}");
+    merged.options.merge = Some(MergeConfig {
+        min_overlap: 10,
+        max_diff: 0.2,
+        out: "merged.fastq".to_string(),
+    });
+
+    assert_eq!(
+        Err(RunError::Geometry(vec![
+            "Only pairs of output reads can be merged".to_string()
+        ])),
+        run(merged, |_| (), CancellationToken::new())
+    );
+}
+
+#[test]
+fn construction_errors() {
+    // labels the options refer to are checked against the geometry
+    let mut written = config("1{b<bc>[16]r:}");
+    written.options.segment_out = vec![("umi".to_string(), "umi.fastq".to_string())];
+
+    assert!(matches!(
+        run(written, |_| (), CancellationToken::new()),
+        Err(RunError::Geometry(errs)) if errs[0].contains("--segment-out refers to <umi>")
+    ));
+
+    // an output that cannot be created fails the run instead of panicking
+    let r1 = env::temp_dir().join(format!("seqproc-runner-{}.fastq", process::id()));
+    fs::write(
+        &r1,
+        "@read0\nACGTACGTACGTACGTAAAA\n+\nIIIIIIIIIIIIIIIIIIII\n",
+    )
+    .unwrap();
+
+    let mut unwritable = config("1{b<bc>[16]r:}");
+    unwritable.file1 = r1.display().to_string();
+    unwritable.options.segment_out = vec![(
+        "bc".to_string(),
+        "/nonexistent/seqproc/bc.fastq".to_string(),
+    )];
+
+    assert!(matches!(
+        run(unwritable, |_| (), CancellationToken::new()),
+        Err(RunError::Failed(_))
+    ));
+
+    fs::remove_file(r1).unwrap();
+}
+
+#[test]
+fn cancelled() {
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+
+    token.cancel();
+
+    assert!(cancel.is_cancelled());
+    assert_eq!(
+        Err(RunError::Cancelled),
+        run(config("1{b[16]r:}"), |_| (), cancel)
+    );
+}
+
+#[test]
+fn compiled_once() {
+    let geometry = "1{b[16]u[12]r:}2{r:}";
+    let cache = GeometryCache::new(2);
+
+    let first = cache.get(geometry).unwrap();
+    let second = cache.get(geometry).unwrap();
+
+    assert!(Arc::ptr_eq(&first, &second));
+    assert!(cache.get("1{b[16]f[AC}").is_err());
+
+    // the least recently used geometry is dropped once the cache is full
+    cache.get("1{b[16]r:}").unwrap();
+    cache.get(geometry).unwrap();
+    cache.get("1{u[12]r:}").unwrap();
+    assert!(Arc::ptr_eq(&first, &cache.get(geometry).unwrap()));
+
+    let uncached = GeometryCache::new(0);
+    assert!(!Arc::ptr_eq(
+        &uncached.get(geometry).unwrap(),
+        &uncached.get(geometry).unwrap()
+    ));
+}
@@ -0,0 +1,475 @@
+use seqproc::parser::Type;
+use seqproc::sink::{
+    atomic::AtomicOutputs,
+    cram::sam_records,
+    fastq::{segment_output, tech_read, FastqWriter, TechPiece, TechRead, GZIP_CHUNK},
+    output_dirs,
+    provenance::{sidecar, Provenance},
+    qc_failures, read_name,
+    route::{route, routed, segment_hash, Route, HASH_BYTES},
+    segment_name,
+    shard::{shard_path, shard_size, ShardSize, ShardedWriter, Sharding},
+    tags::{raw_attr, sam_tag, BARCODE_TAGS, UMI_TAGS},
+    tsv::SegmentTsv,
+    well::{WellMap, NO_WELL},
+    Record,
+};
+
+#[test]
+fn segment_names() {
+    assert_eq!(Some("brc"), segment_name("seq1.brc"));
+    assert_eq!(Some("umi2"), segment_name("seq2.umi2"));
+    assert_eq!(None, segment_name("seq1._r_l"));
+    assert_eq!(None, segment_name("seq1.*"));
+}
+
+#[test]
+fn qc_flags() {
+    let name = b"read1 1:N:0:1 QC:fail:umi_homopolymer QC:fail:duplicate";
+
+    assert_eq!(b"read1", read_name(name));
+    assert_eq!(vec!["umi_homopolymer", "duplicate"], qc_failures(name));
+    assert!(qc_failures(b"read1 1:N:0:1").is_empty());
+}
+
+#[test]
+fn cram_records() {
+    let record = Record {
+        name: b"read1 QC:fail:duplicate".to_vec(),
+        barcode: b"ACGT".to_vec(),
+        umi: b"TTGC".to_vec(),
+        seq: vec![b"ACGTTTGC".to_vec(), b"GGGA".to_vec()],
+        qual: vec![b"IIIIIIII".to_vec(), b"".to_vec()],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        "read1\t589\t*\t0\t0\t*\t*\t0\t0\tACGTTTGC\tIIIIIIII\tCB:Z:ACGT\tUB:Z:TTGC\n\
+         read1\t653\t*\t0\t0\t*\t*\t0\t0\tGGGA\t*\tCB:Z:ACGT\tUB:Z:TTGC\n",
+        String::from_utf8(sam_records(&record)).unwrap()
+    );
+
+    let record = Record {
+        name: b"read2".to_vec(),
+        seq: vec![b"ACGT".to_vec()],
+        qual: vec![b"IIII".to_vec()],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        "read2\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tIIII\n",
+        String::from_utf8(sam_records(&record)).unwrap()
+    );
+}
+
+#[test]
+fn segment_outputs() {
+    assert_eq!(
+        Ok(("cb".to_string(), "cb.fastq.gz".to_string())),
+        segment_output("cb=cb.fastq.gz")
+    );
+    assert!(segment_output("cb").is_err());
+    assert!(segment_output("=cb.fastq").is_err());
+}
+
+#[test]
+fn gzipped_segments() {
+    let path = std::env::temp_dir().join("seqproc_segment_out.fastq.gz");
+
+    {
+        let out = FastqWriter::create(&path).unwrap();
+
+        out.write(b"read1 1:N:0:1", b"ACGT", b"IIII").unwrap();
+        out.write(b"read2", b"", b"").unwrap();
+        out.finish().unwrap();
+    }
+
+    let fastq = std::process::Command::new("gzip")
+        .arg("-dc")
+        .arg(&path)
+        .output()
+        .unwrap()
+        .stdout;
+
+    assert_eq!(
+        b"@read1 1:N:0:1\nACGT\n+\nIIII\n@read2\n\n+\n\n".to_vec(),
+        fastq
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn finished_writer() {
+    let path = std::env::temp_dir().join("seqproc_finished.fastq.gz");
+
+    let out = FastqWriter::create(&path).unwrap();
+    out.write(b"read1", b"ACGT", b"IIII").unwrap();
+    out.finish().unwrap();
+    // finishing again, as a drop does, leaves the file as it was
+    out.finish().unwrap();
+    drop(out);
+
+    let fastq = std::process::Command::new("gzip")
+        .arg("-dc")
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(fastq.status.success());
+    assert_eq!(b"@read1\nACGT\n+\nIIII\n".to_vec(), fastq.stdout);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn gzipped_chunks() {
+    let path = std::env::temp_dir().join("seqproc_chunks.fastq.gz");
+    let (threads, reads) = (4, 100_000);
+
+    {
+        let out = FastqWriter::create(&path).unwrap();
+
+        // enough reads for several chunks, written from several workers
+        std::thread::scope(|s| {
+            for t in 0..threads {
+                let out = &out;
+                s.spawn(move || {
+                    for i in 0..reads {
+                        let name = format!("t{t} {i}");
+                        out.write(name.as_bytes(), b"ACGTACGTACGT", b"IIIIIIIIIIII")
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        out.finish().unwrap();
+    }
+
+    let fastq = std::process::Command::new("gzip")
+        .arg("-dc")
+        .arg(&path)
+        .output()
+        .unwrap()
+        .stdout;
+
+    assert!(fastq.len() > 2 * GZIP_CHUNK);
+
+    // every read of a worker is there, in the order it was written
+    let mut last = vec![None; threads];
+    for name in String::from_utf8(fastq).unwrap().lines().step_by(4) {
+        let (t, i) = name[2..].split_once(' ').unwrap();
+        let (t, i) = (t.parse::<usize>().unwrap(), i.parse::<usize>().unwrap());
+
+        assert_eq!(last[t].map_or(0, |l| l + 1), i);
+        last[t] = Some(i);
+    }
+    assert_eq!(vec![Some(reads - 1); threads], last);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn mates_in_step() {
+    let dir = std::env::temp_dir();
+    let paths = [
+        dir.join("seqproc_mates_r1.fastq.gz"),
+        dir.join("seqproc_mates_r2.fastq"),
+    ];
+    let (threads, reads) = (4, 50_000);
+
+    {
+        let out = FastqWriter::create_all(&paths).unwrap();
+
+        std::thread::scope(|s| {
+            for t in 0..threads {
+                let out = &out;
+                s.spawn(move || {
+                    for i in 0..reads {
+                        let name = format!("t{t} {i}");
+                        let record = (name.as_bytes(), &b"ACGTACGTACGT"[..], &b"IIIIIIIIIIII"[..]);
+                        out.write_records(&[record, record]).unwrap();
+                    }
+                });
+            }
+        });
+        out.finish().unwrap();
+    }
+
+    let r1 = std::process::Command::new("gzip")
+        .arg("-dc")
+        .arg(&paths[0])
+        .output()
+        .unwrap()
+        .stdout;
+    let r2 = std::fs::read(&paths[1]).unwrap();
+
+    // a compressed and a plain output, over several chunks, hold the mates
+    // in the same order
+    assert!(r1.len() > 2 * GZIP_CHUNK);
+    assert_eq!(r1, r2);
+
+    paths.iter().for_each(|p| std::fs::remove_file(p).unwrap());
+}
+
+#[test]
+fn tech_read_spec() {
+    assert_eq!(
+        Ok(TechRead {
+            pieces: vec![
+                TechPiece::Segment("cb1".to_string()),
+                TechPiece::Segment("cb2".to_string()),
+                TechPiece::Constant(b"TTTT".to_vec()),
+                TechPiece::Segment("umi".to_string()),
+            ],
+            out: "r3.fastq.gz".to_string(),
+        }),
+        tech_read("r3.fastq.gz=<cb1><cb2>TTTT<umi>")
+    );
+
+    for spec in [
+        "<cb1><umi>",
+        "r3.fastq=",
+        "r3.fastq=<cb1",
+        "r3.fastq=<>",
+        "r3.fastq=<cb1>tt",
+    ] {
+        assert!(tech_read(spec).is_err(), "{spec}");
+    }
+}
+
+#[test]
+fn shard_sizes() {
+    assert_eq!(Ok(ShardSize::Reads(50_000_000)), shard_size("50M"));
+    assert_eq!(Ok(ShardSize::Reads(1000)), shard_size("1000"));
+    assert_eq!(Ok(ShardSize::Bytes(2_000_000_000)), shard_size("2GB"));
+    assert!(shard_size("0").is_err());
+    assert!(shard_size("5X").is_err());
+
+    assert_eq!("out/r1.0002.fastq.gz", shard_path("out/r1.fastq.gz", 2));
+    assert_eq!("./r2.0001.fq", shard_path("./r2.fq", 1));
+    assert_eq!("r1.0003", shard_path("r1", 3));
+}
+
+#[test]
+fn paired_shards() {
+    let dir = std::env::temp_dir();
+    let path = |r: &str| {
+        dir.join(format!("seqproc_shard_{r}.fastq"))
+            .display()
+            .to_string()
+    };
+
+    {
+        let out = ShardedWriter::create(
+            vec![path("r1"), path("r2")],
+            Sharding::Size(ShardSize::Reads(2)),
+        )
+        .unwrap();
+
+        for name in [b"a", b"b", b"c"] {
+            out.write(&[(name, b"ACGT", b"IIII"), (name, b"TT", b"II")])
+                .unwrap();
+        }
+        out.finish().unwrap();
+    }
+
+    let read =
+        |r: &str, shard: usize| std::fs::read_to_string(shard_path(&path(r), shard)).unwrap();
+
+    assert_eq!("@a\nACGT\n+\nIIII\n@b\nACGT\n+\nIIII\n", read("r1", 1));
+    assert_eq!("@a\nTT\n+\nII\n@b\nTT\n+\nII\n", read("r2", 1));
+    assert_eq!("@c\nTT\n+\nII\n", read("r2", 2));
+
+    for r in ["r1", "r2"] {
+        for shard in 1..=2 {
+            std::fs::remove_file(shard_path(&path(r), shard)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn round_robin_shards() {
+    let dir = std::env::temp_dir();
+    let path = |r: &str| {
+        dir.join(format!("seqproc_split_{r}.fastq"))
+            .display()
+            .to_string()
+    };
+
+    {
+        let out =
+            ShardedWriter::create(vec![path("r1"), path("r2")], Sharding::RoundRobin(2)).unwrap();
+
+        for name in [b"a", b"b", b"c"] {
+            out.write(&[(name, b"A", b"I"), (name, b"T", b"I")])
+                .unwrap();
+        }
+        out.finish().unwrap();
+    }
+
+    let read =
+        |r: &str, shard: usize| std::fs::read_to_string(shard_path(&path(r), shard)).unwrap();
+
+    assert_eq!("@a\nA\n+\nI\n@c\nA\n+\nI\n", read("r1", 1));
+    assert_eq!("@b\nT\n+\nI\n", read("r2", 2));
+
+    for r in ["r1", "r2"] {
+        for shard in 1..=2 {
+            std::fs::remove_file(shard_path(&path(r), shard)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn captured_segments() {
+    let path = std::env::temp_dir().join("seqproc_capture.tsv");
+
+    {
+        let out = SegmentTsv::create(&path, &["tso".to_string(), "bc".to_string()]).unwrap();
+
+        out.write(b"read1", &[b"TTTCTTATATGGG", b"ACGT"]).unwrap();
+        out.write(b"read2", &[b"", b"GGCA"]).unwrap();
+    }
+
+    assert_eq!(
+        "read\ttso\tbc\nread1\tTTTCTTATATGGG\tACGT\nread2\t\tGGCA\n",
+        std::fs::read_to_string(&path).unwrap()
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn provenance_sidecar() {
+    let out = std::env::temp_dir()
+        .join(format!("seqproc-provenance-{}.fq", std::process::id()))
+        .display()
+        .to_string();
+
+    let mut provenance = Provenance::new("1{b[16]u[12]}2{r:}", vec!["seqproc".to_string()]);
+    assert_eq!(64, provenance.geometry.len());
+    assert_eq!(None, provenance.finished);
+
+    provenance.finish(&[out.clone(), String::new()]).unwrap();
+    assert!(provenance.finished.is_some());
+
+    let json = std::fs::read_to_string(sidecar(&out)).unwrap();
+    assert_eq!(provenance.json(), json);
+    assert!(json.contains(&format!("\"geometry_sha256\":\"{}\"", provenance.geometry)));
+    assert!(json.contains("\"args\":[\"seqproc\"]"));
+
+    std::fs::remove_file(sidecar(&out)).unwrap();
+}
+
+#[test]
+fn segment_routes() {
+    assert_eq!(Ok((Type::Umi, Route::Header)), route("umi=header"));
+    assert_eq!(Ok((Type::Discard, Route::Keep)), route("discard=keep"));
+    assert_eq!(
+        Ok((Type::Barcode, Route::Sidecar("bc.fq".to_string()))),
+        route("barcode=sidecar:bc.fq")
+    );
+    assert_eq!(Ok((Type::Barcode, Route::Hash)), route("barcode=hash"));
+    assert!(route("umi").is_err());
+    assert!(route("umi=sidecar:").is_err());
+    assert!(route("linker=drop").is_err());
+
+    let routes = vec![
+        (Type::Umi, Route::Header),
+        (Type::Barcode, Route::Drop),
+        (Type::Umi, Route::Keep),
+    ];
+    assert_eq!(Some(&Route::Keep), routed(&routes, &Type::Umi));
+    assert_eq!(Some(&Route::Drop), routed(&routes, &Type::Barcode));
+    assert_eq!(None, routed(&routes, &Type::Discard));
+    assert_eq!(
+        "sidecar:bc.fq",
+        Route::Sidecar("bc.fq".to_string()).to_string()
+    );
+}
+
+#[test]
+fn barcode_hash() {
+    let hash = segment_hash(&[b"ACGT", b"TTGA"]);
+
+    assert_eq!(HASH_BYTES * 2, hash.len());
+    // the start of the SHA-256 of the barcodes joined
+    assert_eq!("48557cb01261", segment_hash(&[b"ACGTTTGA"]));
+    assert_eq!(hash, segment_hash(&[b"ACGTTTGA"]));
+    assert_ne!(hash, segment_hash(&[b"ACGTTTGC"]));
+}
+
+#[test]
+fn missing_output_dirs() {
+    let dir = std::env::temp_dir().join(format!("seqproc_out_dirs_{}", std::process::id()));
+    let out = dir.join("lane1").join("r1.fastq");
+    let out = out.to_str().unwrap();
+
+    assert!(output_dirs(&[out, ""], false)
+        .unwrap_err()
+        .contains("pass --mkdir"));
+    assert!(!dir.exists());
+
+    output_dirs(&[out, ""], true).unwrap();
+    assert!(dir.join("lane1").is_dir());
+    output_dirs(&[out, "r2.fastq"], false).unwrap();
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn atomic_outputs() {
+    let dir = std::env::temp_dir();
+    let out = dir.join(format!("seqproc_atomic_{}.fastq.gz", std::process::id()));
+    let outputs = vec![
+        out.display().to_string(),
+        String::new(),
+        "/dev/null".to_string(),
+    ];
+
+    // a failed run leaves nothing behind
+    let atomic = AtomicOutputs::new(&outputs);
+    std::fs::write(&atomic.paths[0], "partial").unwrap();
+    let tmp = atomic.paths[0].clone();
+    drop(atomic);
+    assert!(!out.exists());
+    assert!(!std::path::Path::new(&tmp).exists());
+
+    let atomic = AtomicOutputs::new(&outputs);
+    assert!(atomic.paths[0].ends_with(".fastq.gz"));
+    assert_eq!(outputs[1..], atomic.paths[1..]);
+
+    std::fs::write(&atomic.paths[0], "complete").unwrap();
+    assert!(!out.exists());
+    atomic.commit().unwrap();
+    assert_eq!("complete", std::fs::read_to_string(&out).unwrap());
+
+    std::fs::remove_file(out).unwrap();
+}
+
+#[test]
+fn well_map() {
+    let wells = WellMap::new("ACGT\tTTAG\tA01\nACGT\tGGCA\tA02\n", None).unwrap();
+
+    assert_eq!(2, wells.barcodes);
+    assert_eq!("A01", wells.well(&[b"ACGT", b"TTAG"]));
+    assert_eq!("A02", wells.well(&[b"acgt", b"GGCA"]));
+    assert_eq!(NO_WELL, wells.well(&[b"ACGT", b"AAAA"]));
+
+    assert!(WellMap::new("ACGT\tTTAG\tA01\nACGT\tA02\n", None)
+        .unwrap_err()
+        .starts_with("line 2 of the well map has 1 barcodes"));
+    assert!(WellMap::new("ACGT\tA01\nACGT\tA02\n", None).is_err());
+    assert!(WellMap::new("A01\n", None).is_err());
+    assert!(WellMap::new("\n", None).is_err());
+}
+
+#[test]
+fn raw_tags() {
+    assert_eq!(["CR", "CB"], BARCODE_TAGS);
+    assert_eq!(["UR", "UB"], UMI_TAGS);
+    assert_eq!("CR:Z:ACGTTTGA", sam_tag(BARCODE_TAGS[0], b"ACGTTTGA"));
+    assert_eq!("UB:Z:", sam_tag(UMI_TAGS[1], b""));
+    assert_eq!("seq1.brc.raw", raw_attr("seq1.brc"));
+}
@@ -0,0 +1,316 @@
+use std::{
+    env, fs,
+    io::{self, Cursor},
+    path::PathBuf,
+    process::Command,
+};
+
+use seqproc::extract::process_read;
+use seqproc::quality::QualOffset;
+use seqproc::runner::compile_geometry;
+use seqproc::source::{
+    crlf,
+    decompress::Decompressed,
+    fastq::FastqReader,
+    lengths::LengthSample,
+    pooled::{geom_by, KeyOn, PooledFiles},
+    prescan, quality_offset,
+    remote::{remote, Remote, RemoteInputs},
+    salvage::{salvage, SalvageStats},
+    scan,
+};
+
+fn fastq(name: &str, records: usize) -> PathBuf {
+    let path = env::temp_dir().join(format!("seqproc-source-{}-{name}", std::process::id()));
+
+    let fastq = (0..records)
+        .map(|i| format!("@read{i}\nACGT\n+\nIIII\n"))
+        .collect::<String>();
+    fs::write(&path, fastq).unwrap();
+
+    path
+}
+
+#[test]
+fn prescan_counts() {
+    let (r1, r2, short) = (fastq("1.fq", 3), fastq("2.fq", 3), fastq("3.fq", 2));
+    let path = |p: &PathBuf| p.display().to_string();
+
+    assert_eq!(Ok(3), prescan(&path(&r1), Some(&path(&r2)), false));
+    assert!(prescan(&path(&r1), Some(&path(&short)), false).is_err());
+    assert_eq!(Ok(3), prescan(&path(&r1), Some(&path(&short)), true));
+
+    [r1, r2, short]
+        .iter()
+        .for_each(|p| fs::remove_file(p).unwrap());
+}
+
+#[test]
+fn truncated_gzip() {
+    let r1 = fastq("4.fq", 200);
+    let gz = PathBuf::from(format!("{}.gz", r1.display()));
+
+    Command::new("gzip").arg(&r1).status().unwrap();
+    assert_eq!(Ok(200), scan(&gz.display().to_string()));
+
+    // cut the compressed file short, as an interrupted upload would
+    let bytes = fs::read(&gz).unwrap();
+    fs::write(&gz, &bytes[..bytes.len() - 10]).unwrap();
+    assert!(scan(&gz.display().to_string())
+        .unwrap_err()
+        .contains("may be truncated"));
+
+    // the pipe only closes early, the run fails once the thread is joined
+    let decompressed = Decompressed::open(vec![gz.display().to_string()], &[false]).unwrap();
+    let mut reader = FastqReader::open(&decompressed.paths[0]).unwrap();
+    while let Ok(Some(_)) = reader.next_record() {}
+    assert!(decompressed.finish().is_err());
+
+    fs::remove_file(gz).unwrap();
+}
+
+#[test]
+fn multi_member_gzip() {
+    let (r1, r2) = (fastq("5.fq", 3), fastq("6.fq", 2));
+    let gz = env::temp_dir().join(format!("seqproc-source-{}-lanes.fq.gz", std::process::id()));
+
+    // lanes compressed apart and joined by `cat`
+    Command::new("gzip").arg(&r1).arg(&r2).status().unwrap();
+    let lanes = [&r1, &r2].map(|p| fs::read(format!("{}.gz", p.display())).unwrap());
+    fs::write(&gz, lanes.concat()).unwrap();
+
+    let path = gz.display().to_string();
+    assert_eq!(Ok(5), scan(&path));
+
+    // read through the pipe as the pipeline would
+    let decompressed = Decompressed::open(vec![path], &[false]).unwrap();
+    assert!(!decompressed.paths[0].ends_with(".gz"));
+
+    let mut reader = FastqReader::open(&decompressed.paths[0]).unwrap();
+    while reader.next_record().unwrap().is_some() {}
+    assert_eq!(5, reader.records());
+    decompressed.finish().unwrap();
+
+    fs::remove_file(gz).unwrap();
+    [&r1, &r2]
+        .iter()
+        .for_each(|p| fs::remove_file(format!("{}.gz", p.display())).unwrap());
+}
+
+#[test]
+fn malformed_record_offset() {
+    let r1 = fastq("7.fq", 2);
+    let mut bytes = fs::read(&r1).unwrap();
+    bytes.extend(b"read2\nACGT\n+\nIIII\n");
+    fs::write(&r1, bytes).unwrap();
+
+    // two records of 19 bytes before it
+    assert_eq!(
+        Err(format!(
+            "{}: record 3, at byte 38, does not start with `@`",
+            r1.display()
+        )),
+        scan(&r1.display().to_string())
+    );
+
+    fs::remove_file(r1).unwrap();
+}
+
+#[test]
+fn remote_urls() {
+    assert_eq!(
+        Some(Remote::S3 {
+            bucket: "runs".to_string(),
+            key: "lane1/r1.fq.gz".to_string()
+        }),
+        remote("s3://runs/lane1/r1.fq.gz")
+    );
+    assert_eq!(None, remote("s3://runs"));
+    assert_eq!(None, remote("lane1/r1.fq.gz"));
+
+    let http = remote("https://example.org/r2.fastq.gz?token=abc").unwrap();
+    assert_eq!("r2.fastq.gz", http.file_name());
+    assert_eq!("r1.fq", remote("gs://runs/r1.fq").unwrap().file_name());
+
+    // local files are read as they are
+    let inputs = RemoteInputs::open(vec!["r1.fq".to_string()]).unwrap();
+    assert_eq!(vec!["r1.fq".to_string()], inputs.paths);
+}
+
+#[test]
+fn sampled_lengths() {
+    let r1 = fastq("5.fq", 20);
+    let path = r1.display().to_string();
+
+    let sample = LengthSample::read(&path, 10).unwrap();
+    assert_eq!(10, sample.records());
+    assert_eq!(format!("{path}: 10 reads, 4 bases"), sample.report());
+
+    assert_eq!(None, sample.check(4, None));
+    assert!(sample.check(6, None).unwrap().starts_with("10 of 10 reads"));
+    assert!(sample.check(2, Some(3)).is_some());
+
+    fs::remove_file(r1).unwrap();
+}
+
+#[test]
+fn pooled_parts() {
+    let by = geom_by("^@read0:a.fgdl;read2:b.fgdl").unwrap();
+    assert_eq!("b.fgdl", by.keys[1].1);
+    assert_eq!(0, by.select(b"read0"));
+    assert_eq!(1, by.select(b"read2 1:N:0:ACGT"));
+    assert_eq!(2, by.select(b"read1"));
+
+    // patterns may hold a `:`, the path is after the last one
+    assert_eq!(
+        "^@A00.*:1:",
+        geom_by("^@A00.*:1::a.fgdl").unwrap().keys[0].0.as_str()
+    );
+    assert!(geom_by("a.fgdl").is_err());
+    assert!(geom_by("read(:a.fgdl").is_err());
+
+    // keyed on a segment of the first read, wherever it is cut
+    let compiled = compile_geometry("1{b<bc>[2-4]f[TT]b<idx>[3]x:}2{r:}").unwrap();
+    let by_seg = geom_by("seg:idx=^ACG$:spacer.fgdl").unwrap();
+    assert_eq!(KeyOn::Segment("idx".to_string()), by_seg.keys[0].2);
+    assert_eq!(Ok(()), by_seg.check(&compiled));
+
+    let select = |seq: &[u8]| {
+        let segments = process_read(&compiled, 1, seq).unwrap_or_default();
+        by_seg.select_record(b"read0", &segments)
+    };
+    assert_eq!(0, select(b"GGTTACGAA"));
+    assert_eq!(0, select(b"GGGGTTACGAA"));
+    assert_eq!(1, select(b"GGTTCCCAA"));
+    assert_eq!(1, select(b"GGGGGGGGG"));
+    assert_eq!(1, by_seg.select(b"read0"));
+
+    assert!(geom_by("seg:umi=^A:a.fgdl")
+        .unwrap()
+        .check(&compiled)
+        .is_err());
+    assert!(geom_by("seg:idx:a.fgdl").is_err());
+    assert!(geom_by("seg:=A:a.fgdl").is_err());
+
+    let (r1, r2) = (fastq("6.fq", 3), fastq("7.fq", 3));
+    let path = |p: &PathBuf| p.display().to_string();
+    let compiled = compile_geometry("1{r:}2{r:}").unwrap();
+
+    let pooled = PooledFiles::create(&path(&r1), Some(&path(&r2)), &by, &compiled).unwrap();
+    assert_eq!(vec![1, 1, 1], pooled.records);
+
+    let (part1, part2) = pooled.parts[1].clone();
+    assert_eq!(
+        "@read2\nACGT\n+\nIIII\n",
+        fs::read_to_string(&part1).unwrap()
+    );
+    assert_eq!(
+        fs::read_to_string(&part1).unwrap(),
+        fs::read_to_string(part2.as_ref().unwrap()).unwrap()
+    );
+
+    drop(pooled);
+    assert!(!part1.exists());
+
+    let short = fastq("8.fq", 2);
+    assert!(PooledFiles::create(&path(&r1), Some(&path(&short)), &by, &compiled).is_err());
+
+    [r1, r2, short]
+        .into_iter()
+        .for_each(|p| fs::remove_file(p).unwrap());
+}
+
+#[test]
+fn sampled_quality_offset() {
+    let phred64 = env::temp_dir().join(format!("seqproc-source-{}-9.fq", std::process::id()));
+    fs::write(&phred64, "@read0\nACGT\n+\nhhBh\n").unwrap();
+    let ambiguous = fastq("10.fq", 3);
+    let path = |p: &PathBuf| p.display().to_string();
+
+    assert_eq!(Ok(QualOffset::Phred64), quality_offset(&path(&phred64)));
+    assert!(quality_offset(&path(&ambiguous))
+        .unwrap_err()
+        .contains("give --qual-offset"));
+
+    [phred64, ambiguous]
+        .into_iter()
+        .for_each(|p| fs::remove_file(p).unwrap());
+}
+
+#[test]
+fn salvaged_records() {
+    let reader = |fastq: &str| FastqReader::new(Cursor::new(fastq.as_bytes().to_vec()));
+    let names = |out: &[u8]| {
+        let out = String::from_utf8(out.to_vec()).unwrap();
+        out.lines()
+            .step_by(4)
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    // the second record lost its `+` line, the lines after it are read again
+    let mut r1 = reader("@a/1\nACGT\n+\nIIII\n@b/1\nACGT\nIIII\n@c/1\nACGT\n+\nIIII\n");
+    let mut r2 = reader("@a/2\nTTTT\n+\nIIII\n@b/2\nTTTT\n+\nIIII\n@c/2\nTTTT\n+\nIIII\n");
+    let (mut out1, mut out2) = (Vec::new(), Vec::new());
+    let mut skipped = Vec::new();
+
+    let stats = salvage(
+        &mut r1,
+        Some(&mut r2),
+        &mut out1,
+        Some(&mut out2),
+        1,
+        |i, e: io::Error| skipped.push((i, e.to_string())),
+    )
+    .unwrap();
+
+    assert_eq!(
+        SalvageStats {
+            records: 2,
+            skipped: 1,
+            dropped: 1,
+        },
+        stats
+    );
+    assert_eq!(
+        vec![(0, "record 2, at byte 17, has no `+` line".to_string())],
+        skipped
+    );
+    assert_eq!(vec!["@a/1", "@c/1"], names(&out1));
+    assert_eq!(vec!["@a/2", "@c/2"], names(&out2));
+
+    // past the records allowed to be skipped
+    let mut r1 = reader("@a\nACGT\n+\nII\n@b\nACGT\n+\nIIII\n@c\nACGT\n+\nI\n");
+    let err = salvage(&mut r1, None, &mut Vec::new(), None, 1, |_, _| {}).unwrap_err();
+    assert_eq!("more than 1 malformed records", err.to_string());
+}
+
+#[test]
+fn crlf_records() {
+    let path = env::temp_dir().join(format!("seqproc-source-{}-crlf.fq", std::process::id()));
+    fs::write(
+        &path,
+        "@a\r\nACGT\r\n+\r\nIIII\r\n@b\r\nTTTT\r\n+\r\nJJJJ\r\n",
+    )
+    .unwrap();
+    let path = path.display().to_string();
+
+    assert!(crlf(&path).unwrap());
+    let lf = fastq("8.fq", 1);
+    assert!(!crlf(&lf.display().to_string()).unwrap());
+
+    // a pipe is left for the pipeline to read from its first line
+    let pipe = env::temp_dir().join(format!("seqproc-source-{}-crlf", std::process::id()));
+    Command::new("mkfifo").arg(&pipe).status().unwrap();
+    assert!(!crlf(&pipe.display().to_string()).unwrap());
+    fs::remove_file(&pipe).unwrap();
+
+    // the pipeline is handed the records with `\n` alone
+    let decompressed = Decompressed::open(vec![path.clone()], &[true]).unwrap();
+    let piped = fs::read(&decompressed.paths[0]).unwrap();
+    assert_eq!(b"@a\nACGT\n+\nIIII\n@b\nTTTT\n+\nJJJJ\n".to_vec(), piped);
+    decompressed.finish().unwrap();
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(lf).unwrap();
+}
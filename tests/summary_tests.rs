@@ -0,0 +1,146 @@
+use seqproc::{
+    parser::Type,
+    summary::{
+        composition::Composition, hopping::IndexHopping, BaseCounts, DuplicationEstimate, UmiCounts,
+    },
+};
+
+#[test]
+fn distinct_umis() {
+    let counts = UmiCounts::new("umis.tsv".to_string());
+
+    counts.insert(b"AAAA", b"ACGT");
+    counts.insert(b"AAAA", b"ACGT");
+    counts.insert(b"CCCC", b"ACGT");
+    counts.insert(b"CCCC", b"TTTT");
+    counts.insert(b"GGGG", b"GGGG");
+
+    assert_eq!(
+        vec![
+            (b"CCCC".to_vec(), 2),
+            (b"AAAA".to_vec(), 1),
+            (b"GGGG".to_vec(), 1)
+        ],
+        counts.counts()
+    );
+
+    let mut tsv = Vec::new();
+    counts.write_tsv(&mut tsv).unwrap();
+
+    assert_eq!(
+        "CCCC\t2\nAAAA\t1\nGGGG\t1\n",
+        String::from_utf8(tsv).unwrap()
+    );
+}
+
+#[test]
+fn duplication_rate() {
+    let estimate = DuplicationEstimate::new(8);
+
+    assert_eq!(0.0, estimate.rate());
+
+    // every key twice
+    for _ in 0..2 {
+        for i in 0..20_000 {
+            estimate.insert(format!("CELL|UMI{i}|ACGTACGT|").as_bytes());
+        }
+    }
+
+    assert_eq!(40_000, estimate.reads());
+    assert!((estimate.rate() - 0.5).abs() < 0.02);
+    assert_eq!(
+        vec![
+            ("duplication.reads".to_string(), 40_000.0),
+            ("duplication.rate".to_string(), estimate.rate()),
+        ],
+        estimate.metrics()
+    );
+
+    let estimate = DuplicationEstimate::new(8);
+    for key in [b"a", b"b", b"c", b"a"] {
+        estimate.insert(key);
+    }
+
+    assert!((estimate.distinct() - 3.0).abs() < 0.01);
+}
+
+#[test]
+fn base_accounting() {
+    let counts = BaseCounts::default();
+
+    // a 50 base read of which the barcode is truncated and the linker removed
+    counts.read(50);
+    counts.cut(&Type::Barcode, 16);
+    counts.kept(&Type::Barcode, 12);
+    counts.cut(&Type::Discard, 4);
+    counts.cut(&Type::ReadSeq, 30);
+    counts.kept(&Type::ReadSeq, 30);
+    counts.written(42);
+
+    assert_eq!(
+        "Bases read 50, written 42 (84.0%)\n barcode cut 16, written 12, trimmed 4\n discard cut 4, written 0, trimmed 4\n    read cut 30, written 30, trimmed 0\n   other 0, in dropped reads or outside the segments\n",
+        counts.report()
+    );
+
+    // the same counts by their name in the report
+    let metrics = counts.metrics();
+    assert_eq!(("bases.read".to_string(), 50.0), metrics[0]);
+    assert_eq!(("bases.written".to_string(), 42.0), metrics[1]);
+    assert!(metrics.contains(&("bases.barcode.cut".to_string(), 16.0)));
+    assert!(metrics.contains(&("bases.barcode.written".to_string(), 12.0)));
+}
+
+#[test]
+fn constant_umi_position() {
+    let composition = Composition::new("composition.json".to_string());
+    let (bc, umi) = (
+        composition.register("bc", Type::Barcode),
+        composition.register("umi", Type::Umi),
+    );
+
+    for i in 0..200 {
+        let bases = [b'A', b'C', b'G', b'T'];
+        composition.insert(bc, &[bases[i % 4], bases[(i / 4) % 4]]);
+        composition.insert(umi, &[bases[i % 4], b'T', b'n']);
+    }
+
+    assert_eq!(
+        vec![
+            "position 2 of umi is T in 100.0% of reads, the geometry may be off by a base",
+            "position 3 of umi is N in 100.0% of reads, the geometry may be off by a base"
+        ],
+        composition.warnings()
+    );
+    assert_eq!([50, 50, 50, 50, 0], composition.counts()[0].1[0]);
+    assert!(composition
+        .json()
+        .starts_with("{\"segments\":[{\"name\":\"bc\",\"positions\":[{\"A\":50,"));
+}
+
+#[test]
+fn index_hopping() {
+    let sheet = "sample,i7,i5\ns1,AAAA,CCCC\ns2,GGGG,TTTT\n";
+    let hopping = IndexHopping::new("hopping.tsv".to_string(), sheet).unwrap();
+
+    for _ in 0..8 {
+        hopping.insert(b"AAAA", b"CCCC");
+        hopping.insert(b"GGGG", b"TTTT");
+    }
+    hopping.insert(b"aaaa", b"TTTT");
+    hopping.insert(b"GGGG", b"CCCA");
+
+    assert_eq!(hopping.hopped(), (1, 17));
+
+    let mut tsv = Vec::new();
+    hopping.write_tsv(&mut tsv).unwrap();
+    assert_eq!(
+        String::from_utf8(tsv).unwrap(),
+        "i7\\i5\tCCCC\tTTTT\nAAAA\t8*\t1\nGGGG\t0\t8*\n"
+    );
+    assert!(hopping.report().contains("1 of 17 reads (5.882%)"));
+    assert!(hopping
+        .report()
+        .ends_with("1 reads have an index not in it\n"));
+
+    assert!(IndexHopping::new(String::new(), "sample,i7,i5\ns1,AAAA\n").is_err());
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use seqproc::{
+    extract::process_record, presets::preset, runner::compile_geometry, verify::Verifier,
+};
+
+fn segments(pairs: &[(&str, &[u8])]) -> HashMap<String, Vec<u8>> {
+    pairs
+        .iter()
+        .map(|(label, seq)| (label.to_string(), seq.to_vec()))
+        .collect()
+}
+
+#[test]
+fn checked_segments() {
+    let compiled =
+        compile_geometry("1{b<bc>[4-6]hamming(f<link>[GATC], 1)u<umi>[3]trunc(r<cdna>:, 4)}")
+            .unwrap();
+    let verifier = Verifier::new(&compiled);
+
+    // the truncated read is not checked
+    assert_eq!(vec!["bc", "link", "umi"], verifier.labels());
+
+    let good = segments(&[("bc", b"ACGTA"), ("link", b"GTTC"), ("umi", b"TTT")]);
+    assert!(verifier.check(&good).is_empty());
+
+    let bad = segments(&[("bc", b"ACG"), ("link", b"GTTA"), ("umi", b"TTT")]);
+    assert_eq!(
+        vec![
+            "segment bc is 3 bases long, outside 4-6".to_string(),
+            "segment link is GTTA, 2 mismatches from GATC".to_string(),
+        ],
+        verifier.check(&bad)
+    );
+
+    let missing = segments(&[("bc", b"ACGTA"), ("link", b"GATC")]);
+    assert_eq!(
+        vec!["segment umi is missing".to_string()],
+        verifier.check(&missing)
+    );
+}
+
+#[test]
+fn extracted_records() {
+    let crispr = compile_geometry(preset("crispr").unwrap().geometry).unwrap();
+    let verifier = Verifier::new(&crispr);
+    let guide = b"GATTACAGATTACAGATTA";
+
+    // any stagger, any guide of an allowed length
+    for stagger in 0..=40 {
+        for len in 19..=21 {
+            let read = [
+                &b"N".repeat(stagger)[..],
+                b"TTGTGGAAAGGACGAAACACCG",
+                &guide.repeat(2)[..len],
+                b"GTTTTAGAGCTAGAAATAGCAAGT",
+            ]
+            .concat();
+
+            let extraction = process_record(&crispr, &[&read]).unwrap();
+            assert_eq!(
+                Vec::<String>::new(),
+                verifier.check_extraction(&[&read], &extraction)
+            );
+            assert_eq!(len, extraction.get("guide").unwrap().seq.len());
+        }
+    }
+
+    let read = b"TTGTGGAAAGGACGAAACACCGGATTACAGATTACAGATTAGTTTTAGAGCTAGAAATAGC";
+    let mut extraction = process_record(&crispr, &[read]).unwrap();
+    let guide = extraction
+        .segments
+        .iter_mut()
+        .find(|s| s.label.as_deref() == Some("guide"))
+        .unwrap();
+    guide.range = guide.range.start - 1..guide.range.end;
+
+    assert_eq!(
+        vec![
+            "segment guide at 21..41 overlaps the segment before it".to_string(),
+            "segment guide is not the bases of read 1 at 21..41".to_string(),
+        ],
+        verifier.check_extraction(&[read], &extraction)
+    );
+}